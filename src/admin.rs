@@ -0,0 +1,1564 @@
+use crate::*;
+
+/// Owner-only configuration and moderation.
+#[near_bindgen]
+impl Corgi3D {
+    /// Admin actions with index `from_index` onwards. Indexes keep increasing;
+    /// entries older than the last `MAX_ADMIN_LOG_ENTRIES` are gone.
+    pub fn get_admin_log(&self, from_index: u64, limit: u64) -> Vec<AdminAction> {
+        let limit = view_limit(limit);
+        let oldest = self.admin_log_len.saturating_sub(MAX_ADMIN_LOG_ENTRIES);
+        let from_index = std::cmp::max(from_index, oldest);
+        (from_index..std::cmp::min(from_index.saturating_add(limit), self.admin_log_len))
+            .filter_map(|index| self.admin_log.get(&index))
+            .collect()
+    }
+
+    pub fn get_full_config(&self) -> FullConfig {
+        FullConfig {
+            config_version: CONFIG_VERSION,
+            api_version: API_VERSION,
+            owner_id: self.owner_id.clone(),
+            mint_price: U128(self.mint_price),
+            paused: self.paused,
+            pass_price: U128(PASS_PRICE),
+            house_price: U128(HOUSE_PRICE),
+            breeding_fee: U128(BREEDING_FEE),
+            keeper_bounty: U128(self.keeper_bounty),
+            max_clones_per_corgi: MAX_CLONES_PER_CORGI,
+            house_capacity: HOUSE_CAPACITY,
+            transfer_cooldown_hours: self.get_transfer_cooldown(),
+            burn_grace_hours: self.get_burn_grace_period(),
+            bond_cooldown: BOND_COOLDOWN,
+            breed_cooldown: BREED_COOLDOWN,
+            kinship_depth: self.kinship_depth,
+            fruit_sink: self.fruit_sink,
+            mint_gate: self.mint_gate.clone(),
+            premium_colors: self.premium_colors.to_vec(),
+            rarity_names: self.get_rarity_names(),
+            world_state: self.world_state.clone(),
+            world_oracle: self.world_oracle.clone(),
+            arenas: self.arenas.to_vec(),
+            vets: self.vets.to_vec(),
+            market_fee_bps: self.market_fee_bps,
+            fee_tiers: self.fee_tiers.clone(),
+            max_corgis_per_account: self.max_corgis_per_account,
+            gift_expiry_hours: self.get_gift_expiry(),
+            listing_deposit: U128(self.listing_deposit),
+            customization_fee: U128(self.customization_fee),
+            id_era: self.id_era,
+            changes: self.config_changes.to_vec(),
+        }
+    }
+
+    pub fn set_rarity_name(&mut self, rarity: Rarity, name: String) {
+        self.assert_owner();
+        self.record_config_change("rarity_names");
+        self.rarity_names.insert(&rarity, &name);
+    }
+
+    pub fn add_vet(&mut self, vet_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("vets");
+        self.vets.insert(&vet_id);
+    }
+
+    pub fn remove_vet(&mut self, vet_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("vets");
+        self.vets.remove(&vet_id);
+    }
+
+    pub fn get_vets(&self) -> Vec<AccountId> {
+        self.vets.to_vec()
+    }
+
+    pub fn add_partner(&mut self, partner_id: AccountId, quota: u64) {
+        self.assert_owner();
+        self.record_config_change("partners");
+        let minted = self.partners.get(&partner_id).map_or(0, |p| p.minted);
+        self.partners
+            .insert(&partner_id, &PartnerQuota { quota, minted });
+    }
+
+    pub fn remove_partner(&mut self, partner_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("partners");
+        self.partners.remove(&partner_id);
+    }
+
+    pub fn get_partner(&self, partner_id: AccountId) -> Option<PartnerQuota> {
+        self.partners.get(&partner_id)
+    }
+
+    /// Anti-flipping: corgis can't be transferred or listed for `hours` after a
+    /// primary mint or marketplace purchase. Zero disables the cooldown.
+    pub fn set_transfer_cooldown(&mut self, hours: u64) {
+        self.assert_owner();
+        self.record_config_change("transfer_cooldown");
+        self.transfer_cooldown = hours * 60 * 60 * 1_000_000_000;
+    }
+
+    pub fn get_transfer_cooldown(&self) -> u64 {
+        self.transfer_cooldown / (60 * 60 * 1_000_000_000)
+    }
+
+    /// Corgis can't be deleted for `hours` after a marketplace purchase, guarding
+    /// buyers against fat-finger deletes. Zero disables the grace period.
+    pub fn set_burn_grace_period(&mut self, hours: u64) {
+        self.assert_owner();
+        self.record_config_change("burn_grace_period");
+        self.burn_grace_period = hours * 60 * 60 * 1_000_000_000;
+    }
+
+    pub fn get_burn_grace_period(&self) -> u64 {
+        self.burn_grace_period / (60 * 60 * 1_000_000_000)
+    }
+
+    pub fn mark_fulfilled(&mut self, redemption_id: u64) {
+        self.assert_owner_or_admin();
+        let mut redemption = self
+            .redemptions
+            .get(redemption_id)
+            .expect("Redemption not found");
+        redemption.fulfilled = true;
+        self.redemptions.replace(redemption_id, &redemption);
+        self.log_admin_action("mark_fulfilled", redemption_id.to_string());
+    }
+
+    pub fn set_world_oracle(&mut self, oracle_id: Option<AccountId>) {
+        self.assert_owner();
+        self.record_config_change("world_oracle");
+        self.world_oracle = oracle_id;
+    }
+
+    /// Caps how many corgis of a top tier can be minted per season; once it is
+    /// reached, rolls of that tier become the next tier down. `None` uncaps it.
+    /// While pending mints have slots of the tier reserved, the cap can't go
+    /// below what's minted plus those slots.
+    pub fn set_rarity_quota(&mut self, rarity: Rarity, quota: Option<u64>) {
+        self.assert_owner();
+        if !Rarity::QUOTA_TIERS.contains(&rarity) {
+            env::panic(b"Quotas only apply to VERY RARE and ULTRA RARE");
+        }
+        let reserved = self.rarity_reservations.get(&rarity).unwrap_or(0);
+        let minted = self.season_rarity_minted.get(&rarity).unwrap_or(0);
+        if reserved > 0 && quota.is_some_and(|quota| quota < minted + reserved) {
+            env::panic(b"Quota is below the slots reserved by pending mints");
+        }
+        self.record_config_change("rarity_quotas");
+        match quota {
+            Some(quota) => self.rarity_quotas.insert(&rarity, &quota),
+            None => self.rarity_quotas.remove(&rarity),
+        };
+    }
+
+    pub fn get_rarity_quotas(&self) -> Vec<RarityQuota> {
+        Rarity::QUOTA_TIERS
+            .iter()
+            .map(|&rarity| {
+                let quota = self.rarity_quotas.get(&rarity);
+                let minted = self.season_rarity_minted.get(&rarity).unwrap_or(0);
+                let reserved = self.rarity_reservations.get(&rarity).unwrap_or(0);
+                RarityQuota {
+                    rarity,
+                    quota,
+                    minted,
+                    reserved,
+                    remaining: quota.map(|quota| quota.saturating_sub(minted + reserved)),
+                }
+            })
+            .collect()
+    }
+
+    /// Collection icon shown by wallets, as a `data:` URL; `None` removes it.
+    pub fn set_icon(&mut self, icon: Option<String>) {
+        self.assert_owner();
+        self.record_config_change("icon");
+        if icon.as_ref().is_some_and(|icon| !icon.starts_with("data:")) {
+            env::panic(b"Icon must be a data URL");
+        }
+        self.icon = icon;
+    }
+
+    /// Contract notified of every burn, fire-and-forget; `None` stops notifications.
+    pub fn set_burn_registry(&mut self, registry_id: Option<AccountId>) {
+        self.assert_owner();
+        self.record_config_change("burn_registry");
+        self.burn_registry = registry_id;
+    }
+
+    pub fn get_burn_registry(&self) -> Option<AccountId> {
+        self.burn_registry.clone()
+    }
+
+    /// Schedules a world event, replacing any previous one. Callable by the owner
+    /// or the world oracle; `None` clears it.
+    pub fn set_world_state(&mut self, world_state: Option<WorldState>) {
+        let predecessor = env::predecessor_account_id();
+        if predecessor != self.owner_id && self.world_oracle.as_ref() != Some(&predecessor) {
+            env::panic(b"Only the owner or the world oracle can set the world state");
+        }
+        if let Some(state) = &world_state {
+            if state.end_at.0 <= state.start_at.0 {
+                env::panic(b"World state must end after it starts");
+            }
+        }
+        self.world_state = world_state;
+        self.record_config_change("world_state");
+    }
+
+    pub fn add_arena(&mut self, arena_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("arenas");
+        self.arenas.insert(&arena_id);
+    }
+
+    pub fn remove_arena(&mut self, arena_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("arenas");
+        self.arenas.remove(&arena_id);
+    }
+
+    /// Whitelists a NEP-141 token contract as a listing currency.
+    pub fn add_accepted_ft(&mut self, ft_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("accepted_fts");
+        self.accepted_fts.insert(&ft_id);
+    }
+
+    /// Existing listings in the token stay purchasable; new ones are refused.
+    pub fn remove_accepted_ft(&mut self, ft_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("accepted_fts");
+        self.accepted_fts.remove(&ft_id);
+    }
+
+    pub fn get_accepted_fts(&self) -> Vec<AccountId> {
+        self.accepted_fts.to_vec()
+    }
+
+    /// Price of a public mint paid in `ft_id` through `ft_transfer_call`; `None`
+    /// stops minting with the token.
+    pub fn set_ft_mint_price(&mut self, ft_id: AccountId, price: Option<U128>) {
+        self.assert_owner();
+        self.record_config_change("ft_mint_prices");
+        match price {
+            Some(price) => {
+                if !self.accepted_fts.contains(&ft_id) {
+                    ContractError::CurrencyNotAccepted.panic();
+                }
+                self.ft_mint_prices.insert(&ft_id, &price.0);
+            }
+            None => {
+                self.ft_mint_prices.remove(&ft_id);
+            }
+        }
+    }
+
+    pub fn get_ft_mint_price(&self, ft_id: AccountId) -> Option<U128> {
+        self.ft_mint_prices.get(&ft_id).map(U128)
+    }
+
+    /// Key whose signatures `mint_with_voucher` accepts; `None` stops voucher mints.
+    pub fn set_minter_key(&mut self, public_key: Option<Base58PublicKey>) {
+        self.assert_owner();
+        self.record_config_change("minter_public_key");
+        self.minter_public_key = public_key.map(|public_key| {
+            // the first byte is the curve, 0 for ed25519
+            if public_key.0.len() != 33 || public_key.0[0] != 0 {
+                env::panic(b"Minter key must be an ed25519 key");
+            }
+            public_key.0[1..].to_vec()
+        });
+    }
+
+    /// Hands the contract to `new_owner_id`, who takes over every owner-only
+    /// method. The previous owner keeps no rights.
+    pub fn transfer_ownership(&mut self, new_owner_id: AccountId) {
+        self.assert_owner();
+        assert!(
+            env::is_valid_account_id(new_owner_id.as_bytes()),
+            "New owner's account ID is invalid."
+        );
+        self.record_config_change("owner_id");
+        self.log_admin_action("transfer_ownership", new_owner_id.clone());
+        self.owner_id = new_owner_id;
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// Fee of a public mint in NEAR, storage excluded.
+    pub fn set_mint_price(&mut self, price: U128) {
+        self.assert_owner();
+        self.record_config_change("mint_price");
+        self.mint_price = price.0;
+    }
+
+    pub fn get_mint_price(&self) -> U128 {
+        U128(self.mint_price)
+    }
+
+    /// Stops minting and trading, e.g. while a migration is pending. Transfers,
+    /// withdrawals and callbacks of calls made before the pause keep working.
+    pub fn pause(&mut self) {
+        self.assert_owner();
+        if self.paused {
+            env::panic(b"Contract is already paused");
+        }
+        self.record_config_change("paused");
+        self.paused = true;
+    }
+
+    pub fn unpause(&mut self) {
+        self.assert_owner();
+        if !self.paused {
+            env::panic(b"Contract is not paused");
+        }
+        if self.migration.is_some() {
+            env::panic(b"Records are still being migrated");
+        }
+        self.record_config_change("paused");
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Registers an endpoint `set_media` URLs may point into, e.g. `https://render.example/`.
+    pub fn add_renderer(&mut self, endpoint: String) {
+        self.assert_owner();
+        if !endpoint.starts_with("https://") {
+            env::panic(b"Renderer endpoint must use https");
+        }
+        self.record_config_change("renderers");
+        self.renderers.insert(&endpoint);
+    }
+
+    /// Media already set from the endpoint keeps being served.
+    pub fn remove_renderer(&mut self, endpoint: String) {
+        self.assert_owner();
+        self.record_config_change("renderers");
+        self.renderers.remove(&endpoint);
+    }
+
+    pub fn get_renderers(&self) -> Vec<String> {
+        self.renderers.to_vec()
+    }
+
+    /// Points a corgi's NEP-177 `media` at a render from a registered endpoint,
+    /// published with its SHA-256 as `media_hash` so clients can detect tampering.
+    pub fn set_media(&mut self, token_id: TokenId, url: String, sha256: Base64VecU8) {
+        self.assert_owner();
+        if self.corgis.get(&token_id).is_none() {
+            ContractError::CorgiNotFound.panic();
+        }
+        if !self.renderers.iter().any(|endpoint| url.starts_with(&endpoint)) {
+            env::panic(b"Media must be served by a registered renderer");
+        }
+        if sha256.0.len() != 32 {
+            env::panic(b"Media hash must be a SHA-256");
+        }
+        self.token_media.insert(&token_id, &TokenMedia { url, sha256 });
+        self.log_admin_action("set_media", token_id.to_string());
+    }
+
+    pub fn get_media(&self, token_id: TokenId) -> Option<TokenMedia> {
+        self.token_media.get(&token_id)
+    }
+
+    /// Gives `amount` of a fruit to each of `n` holders drawn at random, weighted
+    /// by their holdings or rarity. Returns the winners.
+    pub fn airdrop_fruit(
+        &mut self,
+        n: u64,
+        weighting: HolderWeighting,
+        fruit: String,
+        amount: u64,
+    ) -> Vec<AccountId> {
+        self.assert_owner_or_admin();
+        let kind = self.fruit_kind_id(&fruit);
+        let winners = self.pick_random_holders(n, weighting);
+        let mut prize = Fruit::default();
+        prize.add(kind, amount);
+        for winner in winners.iter() {
+            self.mint_fruit(winner, &prize);
+        }
+        self.log_admin_action(
+            "airdrop_fruit",
+            format!("{} {} to {} holders", amount, fruit, winners.len()),
+        );
+        winners
+    }
+
+    /// Rewrites the access sets of `grants`, given as grantors with the escrows
+    /// they gave access to, e.g. as found in `grant_access` and `revoke_access`
+    /// calls. Needed for sets created by older versions, which shared storage and
+    /// corrupted each other; listing an account with no escrows removes its set.
+    pub fn repair_access_sets(&mut self, grants: Vec<(AccountId, Vec<AccountId>)>) {
+        self.assert_owner();
+        for (grantor, escrows) in grants.iter() {
+            let grantor_hash = env::sha256(grantor.as_bytes());
+            if let Some(mut access_set) = self.account_gives_access.remove(&grantor_hash) {
+                for escrow_hash in access_set.iter() {
+                    self.unindex_access_grant(&grantor_hash, &escrow_hash);
+                }
+                // a legacy set's entries may belong to other accounts, leave them be
+                if !is_legacy_access_set(&access_set) {
+                    access_set.clear();
+                }
+            }
+            if escrows.is_empty() {
+                continue;
+            }
+            let mut access_set = UnorderedSet::new(access_set_prefix(&grantor_hash));
+            for escrow in escrows.iter() {
+                access_set.insert(&env::sha256(escrow.as_bytes()));
+                self.index_access_grant(grantor, escrow);
+            }
+            self.account_gives_access.insert(&grantor_hash, &access_set);
+        }
+        self.log_admin_action("repair_access_sets", format!("{} accounts", grants.len()));
+    }
+
+    pub fn get_arenas(&self) -> Vec<AccountId> {
+        self.arenas.to_vec()
+    }
+
+    /// Records the owners of up to `limit` more tokens into the snapshot in
+    /// progress, leaving out corgis in contract custody, starting a new snapshot when the previous one is complete.
+    /// Returns the snapshot ID; call again until `get_snapshot` reports it complete.
+    /// A snapshot in progress when a token changes owner would mix owners of
+    /// different blocks, so it is left incomplete and a new one is started instead.
+    pub fn snapshot_holders(&mut self, limit: u64) -> u64 {
+        self.assert_owner_or_admin();
+        let in_progress = self.next_snapshot_id.checked_sub(1).filter(|id| {
+            self.snapshots.get(id).is_some_and(|snapshot| {
+                !snapshot.complete && snapshot.ownership_changes == self.ownership_changes
+            })
+        });
+        let id = match in_progress {
+            Some(id) => id,
+            None => {
+                let id = self.next_snapshot_id;
+                self.next_snapshot_id += 1;
+                let mut prefix = b"snapshot".to_vec();
+                prefix.extend(id.to_le_bytes().iter());
+                self.snapshots.insert(
+                    &id,
+                    &HolderSnapshot {
+                        created_at: env::block_timestamp(),
+                        block_height: env::block_index(),
+                        token_count: self.corgi_to_account.len(),
+                        ownership_changes: self.ownership_changes,
+                        cursor: 0,
+                        complete: false,
+                        holders: Vector::new(prefix),
+                    },
+                );
+                id
+            }
+        };
+        let mut snapshot = self.snapshots.get(&id).unwrap();
+        let token_ids = self.corgi_to_account.keys_as_vector();
+        let owners = self.corgi_to_account.values_as_vector();
+        let end = std::cmp::min(snapshot.cursor.saturating_add(limit), token_ids.len());
+        for index in snapshot.cursor..end {
+            let owner = owners.get(index).unwrap();
+            if !is_custodian(&owner) {
+                snapshot.holders.push(&(token_ids.get(index).unwrap(), owner));
+            }
+        }
+        snapshot.cursor = end;
+        snapshot.complete = end >= token_ids.len();
+        self.snapshots.insert(&id, &snapshot);
+        id
+    }
+
+    /// Moves one of the owner's corgis into contract custody, e.g. to fund a
+    /// prize pool or the adoption center.
+    pub fn take_into_custody(&mut self, token_id: TokenId) {
+        self.assert_owner();
+        if self.get_token_owner(token_id) != self.owner_id {
+            env::panic(b"Only corgis held by the owner can be taken into custody");
+        }
+        let owner_id = self.owner_id.clone();
+        self.internal_transfer(token_id, owner_id.clone(), env::current_account_id());
+        emit_transfer(&owner_id, &env::current_account_id(), token_id, None, None);
+        self.log_admin_action("take_into_custody", token_id.to_string());
+    }
+
+    /// Hands a contract-held corgi to `receiver_id`. Corgis backing a kennel,
+    /// loan, fraction, auction, gift or installment plan stay put.
+    pub fn release_from_custody(&mut self, token_id: TokenId, receiver_id: AccountId) {
+        self.assert_owner();
+        if !is_custodian(&self.get_token_owner(token_id)) {
+            env::panic(b"Corgi is not in contract custody");
+        }
+        if self.kennel_treasury.get(&token_id).is_some()
+            || self.loans.get(&token_id).is_some()
+            || self.fractions.get(&token_id).is_some()
+            || self.auctions.get(&token_id).is_some()
+            || self.gifts.values().any(|gift| gift.token_id == token_id)
+            || self.installment_plans.get(&token_id).is_some()
+        {
+            env::panic(b"Corgi is held for a kennel, loan, fraction, auction, gift or installment plan");
+        }
+        self.internal_transfer(token_id, env::current_account_id(), receiver_id.clone());
+        emit_transfer(&env::current_account_id(), &receiver_id, token_id, None, None);
+        self.log_admin_action("release_from_custody", format!("{} to {}", token_id, receiver_id));
+    }
+
+    /// Corgis held by the contract itself, which holder statistics leave out.
+    pub fn get_custody_corgis(&self, from_index: u64, limit: u64) -> Vec<U64> {
+        let limit = view_limit(limit);
+        let hash = env::sha256(env::current_account_id().as_bytes());
+        match self.account_corgis.get(&hash) {
+            Some(corgi_ids) => {
+                let corgi_ids = corgi_ids.as_vector();
+                (from_index..std::cmp::min(from_index.saturating_add(limit), corgi_ids.len()))
+                    .filter_map(|index| corgi_ids.get(index))
+                    .map(U64)
+                    .collect()
+            }
+            None => vec![],
+        }
+    }
+
+    pub fn get_snapshot(&self, snapshot_id: u64) -> Option<SnapshotInfo> {
+        self.snapshots.get(&snapshot_id).map(|snapshot| SnapshotInfo {
+            id: snapshot_id,
+            created_at: snapshot.created_at,
+            block_height: snapshot.block_height,
+            token_count: snapshot.token_count,
+            complete: snapshot.complete,
+            recorded: snapshot.holders.len(),
+        })
+    }
+
+    pub fn get_snapshot_holders(
+        &self,
+        snapshot_id: u64,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(TokenId, AccountId)> {
+        let snapshot = self.snapshots.get(&snapshot_id).expect("Snapshot not found");
+        (from_index..std::cmp::min(from_index.saturating_add(limit), snapshot.holders.len()))
+            .filter_map(|index| snapshot.holders.get(index))
+            .collect()
+    }
+
+    pub fn set_mint_gate(&mut self, mint_gate: Option<MintGate>) {
+        self.assert_owner();
+        self.record_config_change("mint_gate");
+        self.mint_gate = mint_gate;
+    }
+
+    pub fn get_mint_gate(&self) -> Option<MintGate> {
+        self.mint_gate.clone()
+    }
+
+    pub fn set_keeper_bounty(&mut self, bounty: U128) {
+        self.assert_owner();
+        self.record_config_change("keeper_bounty");
+        self.keeper_bounty = bounty.0;
+    }
+
+    pub fn get_keeper_bounty(&self) -> U128 {
+        U128(self.keeper_bounty)
+    }
+
+    pub fn set_market_fee(&mut self, fee_bps: u32) {
+        self.assert_owner();
+        self.record_config_change("market_fee_bps");
+        assert_valid_fee(fee_bps);
+        self.market_fee_bps = fee_bps;
+    }
+
+    /// Deposit held while a corgi is listed, for corgis of editions without their own.
+    pub fn set_listing_deposit(&mut self, amount: U128) {
+        self.assert_owner();
+        self.record_config_change("listing_deposit");
+        self.listing_deposit = amount.0;
+    }
+
+    /// Fee for renaming a corgi or changing its quote or colors.
+    pub fn set_customization_fee(&mut self, fee: U128) {
+        self.assert_owner();
+        self.record_config_change("customization_fee");
+        self.customization_fee = fee.0;
+    }
+
+    pub fn get_customization_fee(&self) -> U128 {
+        U128(self.customization_fee)
+    }
+
+    /// Overrides the listing deposit for corgis of `edition_id`; `None` reverts to the default.
+    pub fn set_edition_listing_deposit(&mut self, edition_id: u64, amount: Option<U128>) {
+        self.assert_owner();
+        self.record_config_change("edition_listing_deposits");
+        if self.editions.get(&edition_id).is_none() {
+            env::panic(b"Edition not found");
+        }
+        match amount {
+            Some(amount) => self.edition_listing_deposits.insert(&edition_id, &amount.0),
+            None => self.edition_listing_deposits.remove(&edition_id),
+        };
+    }
+
+    /// Schedules a flash sale; its `id` is assigned whatever is given. Sales of
+    /// corgis need the contract to hold them. Ended sales are dropped here.
+    pub fn create_flash_sale(&mut self, sale: FlashSale) -> u64 {
+        self.assert_owner();
+        self.record_config_change("flash_sales");
+        assert_valid_fee(sale.discount_bps);
+        let now = env::block_timestamp();
+        if sale.ends_at.0 <= sale.starts_at.0 || sale.ends_at.0 <= now {
+            env::panic(b"Flash sale must end after it starts and in the future");
+        }
+        if sale.token_ids.iter().any(|id| !is_custodian(&self.get_token_owner(*id))) {
+            env::panic(b"Flash sales only cover corgis held by the contract");
+        }
+        self.flash_sales.retain(|running| now < running.ends_at.0);
+        if self.flash_sales.len() >= MAX_FLASH_SALES {
+            env::panic(b"Too many flash sales");
+        }
+        let id = self.next_flash_sale_id;
+        self.next_flash_sale_id += 1;
+        self.flash_sales.push(FlashSale { id, ..sale });
+        id
+    }
+
+    /// Starts a mint phase replacing any previous one, see `MintDrop`. With an
+    /// allowlist it is a presale open only to the listed accounts.
+    pub fn configure_drop(
+        &mut self,
+        start_time: U64,
+        end_time: U64,
+        price: U128,
+        per_account_limit: u64,
+        allowlist: Option<Vec<AccountId>>,
+    ) -> u64 {
+        self.assert_owner();
+        self.record_config_change("mint_drop");
+        if end_time.0 <= start_time.0 || end_time.0 <= env::block_timestamp() {
+            env::panic(b"Drop must end after it starts and in the future");
+        }
+        self.drop_allowlist.clear();
+        for account_id in allowlist.iter().flatten() {
+            self.drop_allowlist.insert(account_id);
+        }
+        let id = self.next_drop_id;
+        self.next_drop_id += 1;
+        self.mint_drop = Some(MintDrop {
+            id,
+            start_at: start_time,
+            end_at: end_time,
+            price,
+            per_account_limit,
+            allowlist_only: allowlist.is_some(),
+        });
+        id
+    }
+
+    /// Ends the mint phase, reopening public mints at the mint price.
+    pub fn end_drop(&mut self) {
+        self.assert_owner();
+        self.record_config_change("mint_drop");
+        self.mint_drop = None;
+        self.drop_allowlist.clear();
+    }
+
+    pub fn get_drop(&self) -> Option<MintDrop> {
+        self.mint_drop.clone()
+    }
+
+    /// Corgis the account minted in the current drop.
+    pub fn get_drop_mints(&self, account_id: AccountId) -> u64 {
+        self.mint_drop
+            .as_ref()
+            .and_then(|drop| self.drop_mints.get(&(drop.id, account_id)))
+            .unwrap_or(0)
+    }
+
+    pub fn cancel_flash_sale(&mut self, sale_id: u64) {
+        self.assert_owner();
+        self.record_config_change("flash_sales");
+        self.flash_sales.retain(|sale| sale.id != sale_id);
+    }
+
+    /// Share of what a buyer paid that goes to the seller when an installment is missed.
+    pub fn set_installment_forfeit(&mut self, forfeit_bps: u32) {
+        self.assert_owner();
+        self.record_config_change("installment_forfeit_bps");
+        assert_valid_fee(forfeit_bps);
+        self.installment_forfeit_bps = forfeit_bps;
+    }
+
+    /// Replaces the volume tiers; thresholds must be strictly increasing.
+    pub fn set_fee_tiers(&mut self, tiers: Vec<FeeTier>) {
+        self.assert_owner();
+        self.record_config_change("fee_tiers");
+        assert_valid_fee_tiers(&tiers);
+        self.fee_tiers = tiers;
+    }
+
+    /// Caps how many corgis an account may hold, checked whenever one receives
+    /// a corgi. `None` lifts the cap; accounts already above it keep their corgis.
+    pub fn set_max_corgis_per_account(&mut self, max: Option<u64>) {
+        self.assert_owner();
+        self.record_config_change("max_corgis_per_account");
+        self.max_corgis_per_account = max;
+    }
+
+    pub fn add_cap_exemption(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("cap_exempt");
+        self.cap_exempt.insert(&account_id);
+    }
+
+    pub fn remove_cap_exemption(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("cap_exempt");
+        self.cap_exempt.remove(&account_id);
+    }
+
+    pub fn get_cap_exemptions(&self) -> Vec<AccountId> {
+        self.cap_exempt.to_vec()
+    }
+
+    /// Generations of ancestors compared before breeding; 0 disables the check.
+    pub fn set_kinship_depth(&mut self, depth: u64) {
+        self.assert_owner();
+        self.record_config_change("kinship_depth");
+        assert_valid_kinship_depth(depth);
+        self.kinship_depth = depth;
+    }
+
+    /// Hours an unclaimed gift waits before its sender can take the corgi back.
+    /// Only applies to gifts sent afterwards.
+    pub fn set_gift_expiry(&mut self, hours: u64) {
+        self.assert_owner();
+        if hours == 0 {
+            env::panic(b"Gift expiry must be positive");
+        }
+        self.record_config_change("gift_expiry");
+        self.gift_expiry = hours * 60 * 60 * 1_000_000_000;
+    }
+
+    pub fn get_gift_expiry(&self) -> u64 {
+        self.gift_expiry / (60 * 60 * 1_000_000_000)
+    }
+
+    /// Adds a premium edition; its `minted` count starts at zero whatever is given.
+    pub fn add_edition(&mut self, edition: Edition) -> u64 {
+        self.assert_owner();
+        self.record_config_change("editions");
+        let id = self.next_edition_id;
+        self.next_edition_id += 1;
+        self.editions.insert(&id, &Edition { minted: 0, ..edition });
+        id
+    }
+
+    pub fn add_admin(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("admins");
+        self.admins.insert(&account_id);
+    }
+
+    pub fn remove_admin(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("admins");
+        self.admins.remove(&account_id);
+    }
+
+    pub fn get_admins(&self) -> Vec<AccountId> {
+        self.admins.to_vec()
+    }
+
+    pub fn add_moderator(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("moderators");
+        self.moderators.insert(&account_id);
+    }
+
+    pub fn remove_moderator(&mut self, account_id: AccountId) {
+        self.assert_owner();
+        self.record_config_change("moderators");
+        self.moderators.remove(&account_id);
+    }
+
+    pub fn get_moderators(&self) -> Vec<AccountId> {
+        self.moderators.to_vec()
+    }
+
+    /// Replaces the homepage carousel, shown in the given order.
+    pub fn set_featured(&mut self, token_ids: Vec<TokenId>) {
+        self.assert_moderator();
+        if token_ids.len() > MAX_FEATURED {
+            env::panic(b"Too many featured corgis");
+        }
+        for (i, token_id) in token_ids.iter().enumerate() {
+            if token_ids[..i].contains(token_id) {
+                env::panic(b"Duplicate featured corgi");
+            }
+            if self.corgis.get(token_id).is_none() {
+                ContractError::CorgiNotFound.panic();
+            }
+            if self.hidden_corgis.contains(token_id) {
+                env::panic(b"Hidden corgis can't be featured");
+            }
+        }
+        self.featured = token_ids;
+        self.log_admin_action("set_featured", format!("{:?}", self.featured));
+    }
+
+    /// Hides a corgi from curated views such as the carousel, which drops it.
+    pub fn hide_corgi(&mut self, token_id: TokenId) {
+        self.assert_moderator();
+        if self.corgis.get(&token_id).is_none() {
+            ContractError::CorgiNotFound.panic();
+        }
+        self.hidden_corgis.insert(&token_id);
+        self.featured.retain(|&featured| featured != token_id);
+        self.log_admin_action("hide_corgi", token_id.to_string());
+    }
+
+    /// Takes a listing off the market, forfeiting its deposit to the treasury.
+    pub fn force_delist(&mut self, token_id: TokenId) {
+        self.assert_moderator();
+        let corgi = self.corgis.get(&token_id).unwrap_or_else(|| ContractError::CorgiNotFound.panic());
+        if !corgi.selling {
+            ContractError::NotForSale.panic();
+        }
+        if let Some((_, amount)) = self.listing_deposits.remove(&token_id) {
+            self.treasury_balance += amount;
+        }
+        self.end_listing(token_id);
+        self.log_admin_action("force_delist", token_id.to_string());
+    }
+
+    pub fn unhide_corgi(&mut self, token_id: TokenId) {
+        self.assert_moderator();
+        self.hidden_corgis.remove(&token_id);
+        self.log_admin_action("unhide_corgi", token_id.to_string());
+    }
+
+    pub fn is_hidden(&self, token_id: TokenId) -> bool {
+        self.hidden_corgis.contains(&token_id)
+    }
+
+    pub fn get_treasury_balance(&self) -> U128 {
+        U128(self.treasury_balance)
+    }
+
+    /// Adds or updates a premium color that costs `cost[kind]` fruit of each kind
+    /// to use, indexed by fruit kind ID. Kinds past the end of `cost` are free.
+    pub fn set_premium_color(&mut self, color: String, cost: Vec<u64>) {
+        self.assert_owner();
+        self.record_config_change("premium_colors");
+        let fruit = self.fruit_cost(&cost);
+        self.premium_colors
+            .insert(&color.trim().to_lowercase(), &fruit);
+    }
+
+    pub fn remove_premium_color(&mut self, color: String) {
+        self.assert_owner();
+        self.record_config_change("premium_colors");
+        self.premium_colors.remove(&color.trim().to_lowercase());
+    }
+
+    pub fn get_premium_colors(&self) -> Vec<(String, Fruit)> {
+        self.premium_colors.to_vec()
+    }
+
+    pub fn set_fruit_sink(&mut self, sink: FruitSink) {
+        self.assert_owner();
+        self.record_config_change("fruit_sink");
+        self.fruit_sink = sink;
+    }
+
+    pub fn get_fruit_sink(&self) -> FruitSink {
+        self.fruit_sink
+    }
+
+    /// Rewards breeding licenses, e.g. for completed quests.
+    pub fn grant_breeding_licenses(&mut self, account_id: AccountId, count: u64) {
+        self.assert_owner();
+        self.add_breeding_licenses(&account_id, count);
+        self.log_admin_action(
+            "grant_breeding_licenses",
+            format!("{} to {}", count, account_id),
+        );
+    }
+
+    /// Adds a craftable item costing `cost[kind]` fruit of each kind, indexed by
+    /// fruit kind ID. Returns its ID.
+    pub fn add_item(
+        &mut self,
+        name: String,
+        cost: Vec<u64>,
+        breed_cooldown_reduction_hours: u64,
+        max_per_token: u32,
+    ) -> u64 {
+        self.assert_owner();
+        self.record_config_change("items");
+        let id = self.items.len();
+        let item = Item {
+            id,
+            name,
+            cost: self.fruit_cost(&cost),
+            breed_cooldown_reduction: breed_cooldown_reduction_hours * 60 * 60 * 1_000_000_000,
+            max_per_token,
+        };
+        self.items.push(&item);
+        id
+    }
+
+    /// Retunes an item; crafted items and past applications are kept.
+    pub fn set_item(
+        &mut self,
+        item_id: u64,
+        cost: Vec<u64>,
+        breed_cooldown_reduction_hours: u64,
+        max_per_token: u32,
+    ) {
+        self.assert_owner();
+        self.record_config_change("items");
+        let mut item = self.items.get(item_id).expect("Item not found");
+        item.cost = self.fruit_cost(&cost);
+        item.breed_cooldown_reduction = breed_cooldown_reduction_hours * 60 * 60 * 1_000_000_000;
+        item.max_per_token = max_per_token;
+        self.items.replace(item_id, &item);
+    }
+
+    /// Introduces a new fruit kind, e.g. for an event. Returns its ID.
+    pub fn add_fruit_kind(&mut self, name: String, effect: String) -> u64 {
+        self.assert_owner();
+        self.record_config_change("fruit_kinds");
+        self.register_fruit_kind(name, effect)
+    }
+}
+
+// Helper methods
+impl Corgi3D {
+    pub(crate) fn assert_owner(&self) {
+        if env::predecessor_account_id() != self.owner_id {
+            env::panic(b"Only the contract owner can call this method");
+        }
+    }
+
+    pub(crate) fn assert_not_paused(&self) {
+        if self.paused {
+            ContractError::ContractPaused.panic();
+        }
+    }
+
+    pub(crate) fn assert_owner_or_admin(&self) {
+        let predecessor = env::predecessor_account_id();
+        if predecessor != self.owner_id && !self.admins.contains(&predecessor) {
+            env::panic(b"Only the contract owner or an admin can call this method");
+        }
+    }
+
+    /// Fruit from a cost list indexed by fruit kind ID.
+    fn fruit_cost(&self, cost: &[u64]) -> Fruit {
+        if cost.len() as u64 > self.fruit_kinds.len() {
+            env::panic(b"Cost lists an unknown fruit kind");
+        }
+        let mut fruit = Fruit::default();
+        for (kind, &amount) in cost.iter().enumerate() {
+            fruit.add(kind as u64, amount);
+        }
+        fruit
+    }
+
+    pub(crate) fn assert_moderator(&self) {
+        let predecessor = env::predecessor_account_id();
+        if predecessor != self.owner_id && !self.moderators.contains(&predecessor) {
+            env::panic(b"Only the contract owner or a moderator can call this method");
+        }
+    }
+
+    /// Applies the settings given to `new`, with the same validation as the setters.
+    pub(crate) fn apply_init_config(&mut self, config: InitConfig) {
+        if let Some(mint_price) = config.mint_price {
+            self.mint_price = mint_price.0;
+        }
+        if let Some(fee_bps) = config.market_fee_bps {
+            assert_valid_fee(fee_bps);
+            self.market_fee_bps = fee_bps;
+        }
+        if let Some(tiers) = config.fee_tiers {
+            assert_valid_fee_tiers(&tiers);
+            self.fee_tiers = tiers;
+        }
+        if let Some(bounty) = config.keeper_bounty {
+            self.keeper_bounty = bounty.0;
+        }
+        if config.max_corgis_per_account.is_some() {
+            self.max_corgis_per_account = config.max_corgis_per_account;
+        }
+        if let Some(hours) = config.transfer_cooldown_hours {
+            self.transfer_cooldown = hours * 60 * 60 * 1_000_000_000;
+        }
+        if let Some(hours) = config.burn_grace_hours {
+            self.burn_grace_period = hours * 60 * 60 * 1_000_000_000;
+        }
+        if let Some(depth) = config.kinship_depth {
+            assert_valid_kinship_depth(depth);
+            self.kinship_depth = depth;
+        }
+        self.base_uri = config.base_uri;
+        if let Some(era) = config.id_era {
+            if era > MAX_ID_ERA {
+                env::panic(b"ID era is too large");
+            }
+            self.id_era = era;
+        }
+    }
+
+    fn record_config_change(&mut self, key: &str) {
+        self.config_changes.insert(
+            &key.to_string(),
+            &ConfigChange {
+                changed_at: env::block_timestamp(),
+                changed_by: env::predecessor_account_id(),
+            },
+        );
+        self.log_admin_action("config_change", key.to_string());
+    }
+
+    /// Appends to the admin audit log, dropping the oldest entry past the bound.
+    pub(crate) fn log_admin_action(&mut self, action: &str, details: String) {
+        let index = self.admin_log_len;
+        self.admin_log.insert(
+            &index,
+            &AdminAction {
+                index,
+                action: action.to_string(),
+                details,
+                actor: env::predecessor_account_id(),
+                timestamp: env::block_timestamp(),
+            },
+        );
+        self.admin_log_len += 1;
+        if index >= MAX_ADMIN_LOG_ENTRIES {
+            self.admin_log.remove(&(index - MAX_ADMIN_LOG_ENTRIES));
+        }
+    }
+
+    /// Draws up to `n` distinct holders for raffles, airdrops and promotional
+    /// drops, each with chances proportional to the weight of their corgis.
+    /// Corgis in contract custody don't count. To bound gas, only
+    /// `MAX_HOLDER_SCAN` tokens are looked at, a window of the ownership index
+    /// starting at a random position, so every token is equally likely to be seen.
+    pub(crate) fn pick_random_holders(&self, n: u64, weighting: HolderWeighting) -> Vec<AccountId> {
+        let token_ids = self.corgi_to_account.keys_as_vector();
+        let owners = self.corgi_to_account.values_as_vector();
+        let total = token_ids.len();
+        if total == 0 {
+            return vec![];
+        }
+        let mut rng = self.random_rng();
+        let start = rng.next_u64() % total;
+        let mut holder_index: HashMap<AccountId, usize> = HashMap::new();
+        let mut holders: Vec<(AccountId, u64)> = vec![];
+        for offset in 0..std::cmp::min(total, MAX_HOLDER_SCAN) {
+            let index = (start + offset) % total;
+            let owner = owners.get(index).unwrap();
+            if is_custodian(&owner) {
+                continue;
+            }
+            let weight = match weighting {
+                HolderWeighting::Holdings => 1,
+                HolderWeighting::Rarity => {
+                    rarity_tier(self.corgis.get(&token_ids.get(index).unwrap()).unwrap().rate)
+                }
+            };
+            match holder_index.get(&owner) {
+                Some(&i) => holders[i].1 += weight,
+                None => {
+                    holder_index.insert(owner.clone(), holders.len());
+                    holders.push((owner, weight));
+                }
+            }
+        }
+        pick_weighted(&mut rng, holders, n)
+    }
+
+    /// Pays the caller of a maintenance method the keeper bounty for each of the
+    /// `records` it processed, if that's at least `MIN_KEEPER_BATCH`. Payouts
+    /// stop at `MAX_KEEPER_PAYOUT_PER_PERIOD` per period or when the treasury
+    /// runs dry, silently, so maintenance never fails.
+    pub(crate) fn pay_keeper_bounty(&mut self, records: u64) {
+        if records < MIN_KEEPER_BATCH {
+            return;
+        }
+        let now = env::block_timestamp();
+        if now >= self.keeper_period_start + KEEPER_BOUNTY_PERIOD {
+            self.keeper_period_start = now;
+            self.keeper_paid_in_period = 0;
+        }
+        let bounty = std::cmp::min(
+            std::cmp::min(
+                self.keeper_bounty * records as u128,
+                MAX_KEEPER_PAYOUT_PER_PERIOD - self.keeper_paid_in_period,
+            ),
+            self.treasury_balance,
+        );
+        if bounty == 0 {
+            return;
+        }
+        self.treasury_balance -= bounty;
+        self.keeper_paid_in_period += bounty;
+        self.pay_out(env::predecessor_account_id(), bounty);
+    }
+}
+
+/// Draws up to `n` distinct candidates without replacement. Each draw is a
+/// single-pass weighted reservoir: candidate `i` replaces the current pick with
+/// probability `weight_i / (weight_1 + ... + weight_i)`.
+fn pick_weighted(rng: &mut ChaCha20Rng, mut candidates: Vec<(AccountId, u64)>, n: u64) -> Vec<AccountId> {
+    let mut picked = vec![];
+    while (picked.len() as u64) < n && !candidates.is_empty() {
+        let mut seen_weight = 0;
+        let mut chosen = 0;
+        for (i, (_, weight)) in candidates.iter().enumerate() {
+            seen_weight += weight;
+            if rng.next_u64() % seen_weight < *weight {
+                chosen = i;
+            }
+        }
+        picked.push(candidates.swap_remove(chosen).0);
+    }
+    picked
+}
+
+fn assert_valid_fee(fee_bps: u32) {
+    if fee_bps > 10_000 {
+        env::panic(b"Fee cannot exceed 100%");
+    }
+}
+
+fn assert_valid_fee_tiers(tiers: &[FeeTier]) {
+    for (i, tier) in tiers.iter().enumerate() {
+        if tier.discount_bps > 10_000 {
+            env::panic(b"Discount cannot exceed 100%");
+        }
+        if i > 0 && tiers[i - 1].min_volume.0 >= tier.min_volume.0 {
+            env::panic(b"Tier thresholds must be strictly increasing");
+        }
+    }
+}
+
+fn assert_valid_kinship_depth(depth: u64) {
+    if depth > MAX_KINSHIP_DEPTH {
+        env::panic(b"Kinship depth is too large");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    #[should_panic(expected = r#"Only the contract owner can call this method"#)]
+    fn set_keeper_bounty_requires_owner() {
+        testing_env!(get_context(mike(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_keeper_bounty(U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the owner or the world oracle can set the world state"#)]
+    fn set_world_state_not_oracle() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.set_world_state(None);
+    }
+
+    #[test]
+    fn full_config_tracks_changes() {
+        let mut context = get_context(robert(), 0);
+        context.block_timestamp = 42;
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_transfer_cooldown(2);
+        let config = contract.get_full_config();
+        assert_eq!(config.config_version, CONFIG_VERSION);
+        assert_eq!(config.transfer_cooldown_hours, 2);
+        assert_eq!(config.changes.len(), 1);
+        assert_eq!(config.changes[0].0, "transfer_cooldown");
+        assert_eq!(config.changes[0].1.changed_at, 42);
+        assert_eq!(config.changes[0].1.changed_by, robert());
+    }
+
+    #[test]
+    fn admin_log_records_owner_actions() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_vet(joe());
+        contract.set_keeper_bounty(U128(0));
+        let log = contract.get_admin_log(0, 10);
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].action, "config_change");
+        assert_eq!(log[0].details, "vets");
+        assert_eq!(log[1].details, "keeper_bounty");
+        assert_eq!(log[1].actor, robert());
+        assert_eq!(contract.get_admin_log(1, 10).len(), 1);
+    }
+
+    #[test]
+    fn snapshot_holders_in_batches() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b", "c"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        contract.transfer(joe(), 1, None);
+
+        let id = contract.snapshot_holders(2);
+        assert_eq!(contract.get_snapshot(id).unwrap().complete, false);
+        assert_eq!(contract.snapshot_holders(2), id);
+        let info = contract.get_snapshot(id).unwrap();
+        assert!(info.complete);
+        assert_eq!(info.recorded, 3);
+        let holders = contract.get_snapshot_holders(id, 0, 10);
+        assert!(holders.contains(&(1, joe())));
+
+        assert_eq!(contract.snapshot_holders(10), id + 1);
+    }
+
+    #[test]
+    fn snapshot_restarts_when_ownership_changes() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b", "c"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        let id = contract.snapshot_holders(2);
+        contract.transfer(joe(), 0, None);
+
+        let restarted = contract.snapshot_holders(2);
+        assert_eq!(restarted, id + 1);
+        assert!(!contract.get_snapshot(id).unwrap().complete);
+        assert_eq!(contract.snapshot_holders(2), restarted);
+        let info = contract.get_snapshot(restarted).unwrap();
+        assert!(info.complete);
+        assert_eq!(info.token_count, 3);
+        assert!(contract.get_snapshot_holders(restarted, 0, 10).contains(&(0, joe())));
+    }
+
+    #[test]
+    fn rarity_display_names() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_rarity_name(Rarity::UltraRare, "ULTRA RARO".to_string());
+        let names = contract.get_rarity_names();
+        assert_eq!(names.len(), 5);
+        assert_eq!(names[0].name, "COMMON".to_string());
+        assert_eq!(names[4].name, "ULTRA RARO".to_string());
+        assert_eq!(names[4].localization_key, "rarity.ultra_rare".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Account holds the maximum number of corgis"#)]
+    fn max_corgis_per_account() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_max_corgis_per_account(Some(1));
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        mint_revealed(&mut contract,
+            "b".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+    }
+
+    #[test]
+    fn cap_exempt_account() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_max_corgis_per_account(Some(1));
+        contract.add_cap_exemption(robert());
+        for name in &["a", "b"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        assert_eq!(contract.get_corgis_by_owner_range(robert(), 0, 10).len(), 2);
+    }
+
+    #[test]
+    fn custody_holdings_excluded_from_holder_stats() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        let votes = contract.get_held_votes(robert());
+        contract.take_into_custody(0);
+        assert_eq!(contract.get_custody_corgis(0, 10), vec![U64(0)]);
+        assert!(contract.get_held_votes(robert()) < votes);
+        assert_eq!(contract.get_held_votes(env::current_account_id()), 0);
+
+        let id = contract.snapshot_holders(10);
+        assert_eq!(contract.get_snapshot_holders(id, 0, 10), vec![(1, robert())]);
+
+        contract.release_from_custody(0, mike());
+        assert_eq!(contract.get_token_owner(0), mike());
+        assert!(contract.get_custody_corgis(0, 10).is_empty());
+    }
+
+    #[test]
+    fn new_with_config_and_admins() {
+        testing_env!(get_context(robert(), 0));
+        let contract = Corgi3D::new(
+            robert(),
+            Some(InitConfig {
+                mint_price: Some(U128(10u128.pow(24))),
+                market_fee_bps: Some(250),
+                base_uri: Some("https://corgi3d.example".to_string()),
+                ..Default::default()
+            }),
+            Some(vec![mike()]),
+        );
+        let config = contract.get_full_config();
+        assert_eq!(config.mint_price, U128(10u128.pow(24)));
+        assert_eq!(config.market_fee_bps, 250);
+        assert_eq!(config.kinship_depth, DEFAULT_KINSHIP_DEPTH);
+        assert_eq!(contract.get_admins(), vec![mike()]);
+    }
+
+    #[test]
+    fn admin_can_snapshot() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, Some(vec![mike()]));
+        testing_env!(get_context(mike(), env::storage_usage()));
+        assert_eq!(contract.snapshot_holders(10), 0);
+    }
+
+    #[test]
+    fn set_media_publishes_hash() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.add_renderer("https://render.example/".to_string());
+        let sha256 = Base64VecU8(env::sha256(b"render"));
+        contract.set_media(token_id, "https://render.example/0.glb".to_string(), sha256.clone());
+        let metadata = contract.nft_token(token_id).unwrap().metadata;
+        assert_eq!(metadata.media, Some("https://render.example/0.glb".to_string()));
+        assert_eq!(metadata.media_hash, Some(sha256));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Media must be served by a registered renderer"#)]
+    fn set_media_from_unknown_renderer() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.add_renderer("https://render.example/".to_string());
+        contract.set_media(
+            token_id,
+            "https://evil.example/0.glb".to_string(),
+            Base64VecU8(env::sha256(b"render")),
+        );
+    }
+
+    #[test]
+    fn pick_weighted_follows_weights() {
+        let candidates = vec![(robert(), 3), (mike(), 1)];
+        let mut robert_first = 0;
+        for seed in 0..2_000 {
+            let mut rng = ChaCha20Rng::seed_from_u64(seed);
+            let picked = pick_weighted(&mut rng, candidates.clone(), 1);
+            if picked == vec![robert()] {
+                robert_first += 1;
+            }
+        }
+        // expected 1500 of 2000
+        assert!(robert_first > 1_400 && robert_first < 1_600, "{}", robert_first);
+    }
+
+    #[test]
+    fn pick_weighted_without_replacement() {
+        let candidates = vec![(robert(), 1), (mike(), 1_000), (joe(), 1)];
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let mut picked = pick_weighted(&mut rng, candidates, 5);
+        picked.sort();
+        assert_eq!(picked, vec![joe(), mike(), robert()]);
+    }
+
+    #[test]
+    fn airdrop_skips_custody() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        contract.transfer(mike(), 1, None);
+        contract.take_into_custody(0);
+        let winners = contract.airdrop_fruit(2, HolderWeighting::Rarity, "apple".to_string(), 3);
+        assert_eq!(winners, vec![mike()]);
+        assert_eq!(contract.account_fruit(mike()).get(0), 3);
+    }
+
+    #[test]
+    fn featured_drops_hidden_and_burned() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b", "c"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        contract.add_moderator(mike());
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.set_featured(vec![2, 0, 1]);
+        let featured = contract.get_featured();
+        assert_eq!(featured[0].corgi.id, 2);
+        assert_eq!(featured[0].owner_id, robert());
+        contract.hide_corgi(0);
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.delete_corgi(1);
+        assert_eq!(contract.featured, vec![2]);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the contract owner or a moderator can call this method"#)]
+    fn set_featured_requires_moderator() {
+        testing_env!(get_context(mike(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_featured(vec![]);
+    }
+
+    #[test]
+    fn transferred_owner_pauses_and_prices_mints() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.transfer_ownership(mike());
+        assert_eq!(contract.get_owner(), mike());
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.set_mint_price(U128(2 * 10u128.pow(24)));
+        assert_eq!(contract.get_mint_price(), U128(2 * 10u128.pow(24)));
+        contract.pause();
+        assert!(contract.is_paused());
+        contract.unpause();
+        let result = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        assert!(result.charged.0 >= 2 * 10u128.pow(24));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Contract is paused"#)]
+    fn paused_contract_refuses_mints() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.pause();
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+    }
+
+    #[test]
+    fn drop_charges_drop_price_within_limit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.configure_drop(U64(0), U64(1_000), U128(2 * 10u128.pow(24)), 1, Some(vec![mike()]));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let treasury = contract.treasury_balance;
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        assert_eq!(contract.treasury_balance, treasury + 2 * 10u128.pow(24));
+        assert_eq!(contract.get_drop_mints(mike()), 1);
+        assert_eq!(
+            contract.simulate(SimulatedAction::Mint {
+                account_id: mike(),
+                deposit: U128(3 * 10u128.pow(24)),
+            })
+            .error,
+            Some("Account reached the drop mint limit".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Account is not on the drop allowlist"#)]
+    fn presale_refuses_unlisted_accounts() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.configure_drop(U64(0), U64(1_000), U128(10u128.pow(24)), 5, Some(vec![mike()]));
+
+        testing_env!(get_context(joe(), env::storage_usage()));
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+
+/// Views over internal indexes, compiled only with the `debug` feature.
+#[near_bindgen]
+impl Corgi3D {
+    /// Account hashes and the hashes of the escrows they gave access to.
+    pub fn debug_access_sets(
+        &self,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(Base64VecU8, Vec<Base64VecU8>)> {
+        let keys = self.account_gives_access.keys_as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), keys.len()))
+            .filter_map(|index| keys.get(index))
+            .map(|account_hash| {
+                let escrows = self
+                    .account_gives_access
+                    .get(&account_hash)
+                    .unwrap()
+                    .iter()
+                    .map(Base64VecU8)
+                    .collect();
+                (Base64VecU8(account_hash), escrows)
+            })
+            .collect()
+    }
+
+    /// Raw content of the for-sale index, which should match the `selling` flags.
+    pub fn debug_for_sale_index(&self) -> Vec<TokenId> {
+        self.selling_corgis.to_vec()
+    }
+
+    /// Escrow ledger entries of `account_ids`; the ledger itself can't be iterated.
+    pub fn debug_escrow_ledger(&self, account_ids: Vec<AccountId>) -> Vec<(AccountId, U128)> {
+        account_ids
+            .into_iter()
+            .filter_map(|account_id| {
+                self.deposits
+                    .get(&account_id)
+                    .map(|balance| (account_id, U128(balance)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn debug_views() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.grant_access(mike());
+        contract.deposit();
+        let token_id = mint_revealed(
+            &mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        )
+        .token_id
+        .0;
+        contract.sell_corgi(token_id, U128(1), None, None);
+        assert_eq!(contract.debug_access_sets(0, 10)[0].1.len(), 1);
+        assert_eq!(contract.debug_for_sale_index(), vec![token_id]);
+        assert_eq!(
+            contract.debug_escrow_ledger(vec![robert(), mike()]),
+            vec![(robert(), U128(3 * 10u128.pow(24)))]
+        );
+    }
+}
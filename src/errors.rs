@@ -0,0 +1,133 @@
+use crate::*;
+use std::fmt;
+
+/// Failures of the mint, transfer and marketplace methods. They panic as
+/// `CODE: message`, so clients can match on the code and still show the message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContractError {
+    ContractPaused,
+    CorgiNotFound,
+    InvalidName,
+    QuoteTooLong,
+    InvalidColorLength,
+    UnknownColor,
+    InvalidAccountId,
+    InvalidBatchSize,
+    MessageTooLong,
+    NotTokenOwner,
+    WrongOwner,
+    NoTransferAccess,
+    // why the corgi can't change owners right now
+    NotTransferable(&'static str),
+    NoSellPermission,
+    NoDelistPermission,
+    NoPriceUpdatePermission,
+    NotForSale,
+    CurrencyNotAccepted,
+    PricedInFt,
+    ListingNotStarted,
+    InsufficientPayment,
+    InsufficientMintFee,
+    BundleNotFound,
+    NotBundleSeller,
+    TradeNotFound,
+    NotTradeCounterparty,
+    NotTradeParty,
+}
+
+impl ContractError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ContractError::ContractPaused => "CONTRACT_PAUSED",
+            ContractError::CorgiNotFound => "CORGI_NOT_FOUND",
+            ContractError::InvalidName => "INVALID_NAME",
+            ContractError::QuoteTooLong => "QUOTE_TOO_LONG",
+            ContractError::InvalidColorLength => "INVALID_COLOR_LENGTH",
+            ContractError::UnknownColor => "UNKNOWN_COLOR",
+            ContractError::InvalidAccountId => "INVALID_ACCOUNT_ID",
+            ContractError::InvalidBatchSize => "INVALID_BATCH_SIZE",
+            ContractError::MessageTooLong => "MESSAGE_TOO_LONG",
+            ContractError::NotTokenOwner => "NOT_TOKEN_OWNER",
+            ContractError::WrongOwner => "WRONG_OWNER",
+            ContractError::NoTransferAccess => "NO_TRANSFER_ACCESS",
+            ContractError::NotTransferable(_) => "NOT_TRANSFERABLE",
+            ContractError::NoSellPermission => "NO_SELL_PERMISSION",
+            ContractError::NoDelistPermission => "NO_DELIST_PERMISSION",
+            ContractError::NoPriceUpdatePermission => "NO_PRICE_UPDATE_PERMISSION",
+            ContractError::NotForSale => "NOT_FOR_SALE",
+            ContractError::CurrencyNotAccepted => "CURRENCY_NOT_ACCEPTED",
+            ContractError::PricedInFt => "PRICED_IN_FT",
+            ContractError::ListingNotStarted => "LISTING_NOT_STARTED",
+            ContractError::InsufficientPayment => "INSUFFICIENT_PAYMENT",
+            ContractError::InsufficientMintFee => "INSUFFICIENT_MINT_FEE",
+            ContractError::BundleNotFound => "BUNDLE_NOT_FOUND",
+            ContractError::NotBundleSeller => "NOT_BUNDLE_SELLER",
+            ContractError::TradeNotFound => "TRADE_NOT_FOUND",
+            ContractError::NotTradeCounterparty => "NOT_TRADE_COUNTERPARTY",
+            ContractError::NotTradeParty => "NOT_TRADE_PARTY",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            ContractError::ContractPaused => "Contract is paused",
+            ContractError::CorgiNotFound => "Corgi not found",
+            ContractError::InvalidName => "Name must be 1 to 64 bytes",
+            ContractError::QuoteTooLong => "Quote is too long",
+            ContractError::InvalidColorLength => "Colors must be 1 to 32 bytes",
+            ContractError::UnknownColor => "Colors must be hex, a named color or a premium color",
+            ContractError::InvalidAccountId => "Invalid account ID",
+            ContractError::InvalidBatchSize => "Batch must hold 1 to 10 corgis",
+            ContractError::MessageTooLong => "Message is too long",
+            ContractError::NotTokenOwner => "Attempt to call transfer on tokens belonging to another account.",
+            ContractError::WrongOwner => "Attempt to transfer a token from a different owner.",
+            ContractError::NoTransferAccess => "Attempt to transfer a token with no access.",
+            ContractError::NotTransferable(reason) => reason,
+            ContractError::NoSellPermission => "Don't have permission to sell corgi",
+            ContractError::NoDelistPermission => "Don't have permission to delist corgi",
+            ContractError::NoPriceUpdatePermission => "Don't have permission to update the price",
+            ContractError::NotForSale => "Corgi is not for sale",
+            ContractError::CurrencyNotAccepted => "Token is not an accepted currency",
+            ContractError::PricedInFt => "Corgi is priced in a fungible token, buy it with ft_transfer_call",
+            ContractError::ListingNotStarted => "Listing has not started yet",
+            ContractError::InsufficientPayment => "Don't pay enough money to buy corgi",
+            ContractError::InsufficientMintFee => "Attached deposit must cover the mint fee",
+            ContractError::BundleNotFound => "Bundle not found",
+            ContractError::NotBundleSeller => "Only the seller can cancel a bundle",
+            ContractError::TradeNotFound => "Trade not found",
+            ContractError::NotTradeCounterparty => "Only the counterparty can accept a trade",
+            ContractError::NotTradeParty => "Only the proposer or the counterparty can cancel a trade",
+        }
+    }
+
+    pub fn panic(&self) -> ! {
+        env::panic(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for ContractError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+/// Panics unless `account_id` is a valid NEAR account ID.
+pub(crate) fn assert_valid_account_id(account_id: &str) {
+    if !env::is_valid_account_id(account_id.as_bytes()) {
+        ContractError::InvalidAccountId.panic();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_format_as_code_and_message() {
+        assert_eq!(ContractError::NotForSale.to_string(), "NOT_FOR_SALE: Corgi is not for sale");
+        assert_eq!(
+            ContractError::NotTransferable("Corgi is staked").to_string(),
+            "NOT_TRANSFERABLE: Corgi is staked"
+        );
+    }
+}
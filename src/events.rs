@@ -0,0 +1,126 @@
+use crate::*;
+
+/// Logs a NEP-297 event for this contract.
+pub(crate) fn emit_event(event: &str, data: serde_json::Value) {
+    emit_standard_event("nep171", event, data)
+}
+
+pub(crate) fn emit_standard_event(standard: &str, event: &str, data: serde_json::Value) {
+    let log = json!({
+        "standard": standard,
+        "version": "1.0.0",
+        "event": event,
+        "data": [data],
+    });
+    env::log(format!("EVENT_JSON:{}", log).as_bytes());
+}
+
+/// `memo` records where the mint came from, e.g. `public` or `partner:<account>`.
+pub(crate) fn emit_mint(owner_id: &str, token_id: TokenId, memo: &str) {
+    emit_mint_batch(owner_id, &[token_id], memo)
+}
+
+/// One `nft_mint` event for several corgis minted to the same owner.
+pub(crate) fn emit_mint_batch(owner_id: &str, token_ids: &[TokenId], memo: &str) {
+    emit_event(
+        "nft_mint",
+        json!({
+            "owner_id": owner_id,
+            "token_ids": token_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+            "memo": memo,
+        }),
+    );
+}
+
+/// `memo` is passed through from the caller and never stored.
+pub(crate) fn emit_transfer(
+    old_owner_id: &str,
+    new_owner_id: &str,
+    token_id: TokenId,
+    authorized_id: Option<&str>,
+    memo: Option<String>,
+) {
+    emit_transfer_batch(old_owner_id, new_owner_id, &[token_id], authorized_id, memo)
+}
+
+/// One `nft_transfer` event for several corgis moving between the same owners.
+pub(crate) fn emit_transfer_batch(
+    old_owner_id: &str,
+    new_owner_id: &str,
+    token_ids: &[TokenId],
+    authorized_id: Option<&str>,
+    memo: Option<String>,
+) {
+    emit_event(
+        "nft_transfer",
+        json!({
+            "authorized_id": authorized_id,
+            "old_owner_id": old_owner_id,
+            "new_owner_id": new_owner_id,
+            "token_ids": token_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+            "memo": memo,
+        }),
+    );
+}
+
+pub(crate) fn emit_burn(owner_id: &str, token_id: TokenId) {
+    emit_event(
+        "nft_burn",
+        json!({
+            "owner_id": owner_id,
+            "token_ids": [token_id.to_string()],
+        }),
+    );
+}
+
+/// Tells indexers to refresh the cached metadata of the corgi; `memo` names
+/// what changed.
+pub(crate) fn emit_metadata_update(token_id: TokenId, memo: &str) {
+    emit_event(
+        "nft_metadata_update",
+        json!({
+            "token_ids": [token_id.to_string()],
+            "memo": memo,
+        }),
+    );
+}
+
+pub(crate) fn emit_sale_listed(
+    owner_id: &str,
+    token_id: TokenId,
+    price: U128,
+    currency: &Currency,
+    memo: Option<String>,
+) {
+    emit_event(
+        "corgi_sale_listed",
+        json!({
+            "owner_id": owner_id,
+            "token_ids": [token_id.to_string()],
+            "price": price,
+            "currency": currency,
+            "memo": memo,
+        }),
+    );
+}
+
+/// Logged alongside the `nft_transfer` of every marketplace sale, with the
+/// price the buyer paid.
+pub(crate) fn emit_sold(
+    seller_id: &str,
+    buyer_id: &str,
+    token_id: TokenId,
+    price: U128,
+    currency: &Currency,
+) {
+    emit_event(
+        "corgi_sold",
+        json!({
+            "seller_id": seller_id,
+            "buyer_id": buyer_id,
+            "token_ids": [token_id.to_string()],
+            "price": price,
+            "currency": currency,
+        }),
+    );
+}
@@ -0,0 +1,2890 @@
+use crate::*;
+
+/// Fruit, mazes, battles, kennels, houses and seasons.
+#[near_bindgen]
+impl Corgi3D {
+    /// Pairs two corgis of the caller as companions, which adds
+    /// `BOND_POWER_BONUS` to their battle power and `BOND_STAKE_WEIGHT_BONUS` to
+    /// their staking weight while the bond lasts.
+    pub fn bond_corgis(&mut self, token_a: TokenId, token_b: TokenId) {
+        if token_a == token_b {
+            env::panic(b"A corgi cannot bond with itself");
+        }
+        let predecessor = env::predecessor_account_id();
+        let now = env::block_timestamp();
+        let mut corgi_a = self.get_corgi(token_a);
+        let mut corgi_b = self.get_corgi(token_b);
+        for corgi in &[&corgi_a, &corgi_b] {
+            if self.get_token_owner(corgi.id) != predecessor {
+                env::panic(b"Both corgis must belong to the caller");
+            }
+            if corgi.bonded_with.is_some() {
+                env::panic(b"Corgi is already bonded");
+            }
+            assert_bond_cooldown(corgi, now);
+        }
+        corgi_a.bonded_with = Some(token_b);
+        corgi_a.bond_changed_at = now;
+        corgi_b.bonded_with = Some(token_a);
+        corgi_b.bond_changed_at = now;
+        self.corgis.insert(&token_a, &corgi_a);
+        self.corgis.insert(&token_b, &corgi_b);
+        self.refresh_stake_weight(token_a);
+        self.refresh_stake_weight(token_b);
+    }
+
+    pub fn unbond_corgi(&mut self, token_id: TokenId) {
+        let now = env::block_timestamp();
+        let corgi = self.get_corgi(token_id);
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can unbond a corgi");
+        }
+        if corgi.bonded_with.is_none() {
+            env::panic(b"Corgi is not bonded");
+        }
+        assert_bond_cooldown(&corgi, now);
+        self.break_bond(corgi, now);
+    }
+
+    /// Sends the caller's corgi to train `skill`. The session lasts
+    /// `TRAINING_DURATION`, less any progress kept from cancelled sessions, and
+    /// the corgi can't be transferred or sent to battle meanwhile.
+    pub fn start_training(&mut self, token_id: TokenId, skill: Skill) -> Training {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can train a corgi");
+        }
+        if self.trainings.get(&token_id).is_some() {
+            env::panic(b"Corgi is already in training");
+        }
+        let corgi = self.get_corgi(token_id);
+        if corgi.skills[skill as usize] >= skill.cap() {
+            env::panic(b"Skill is already at its cap");
+        }
+        let now = env::block_timestamp();
+        let remaining_bps = 10_000 - corgi.skill_progress[skill as usize];
+        let training = Training {
+            skill,
+            started_at: U64(now),
+            ends_at: U64(now + TRAINING_DURATION / 10_000 * remaining_bps),
+        };
+        self.trainings.insert(&token_id, &training);
+        training
+    }
+
+    /// Finishes a session whose timer has run out, raising the trained skill by one.
+    pub fn complete_training(&mut self, token_id: TokenId) -> u64 {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can complete a training");
+        }
+        let training = self.trainings.get(&token_id).expect("Corgi is not in training");
+        if env::block_timestamp() < training.ends_at.0 {
+            env::panic(b"Training is not finished yet");
+        }
+        self.trainings.remove(&token_id);
+        let skill = training.skill as usize;
+        let mut corgi = self.get_corgi(token_id);
+        corgi.skills[skill] += 1;
+        corgi.skill_progress[skill] = 0;
+        self.corgis.insert(&token_id, &corgi);
+        emit_event(
+            "corgi_trained",
+            json!({
+                "token_id": token_id.to_string(),
+                "skill": training.skill,
+                "level": corgi.skills[skill],
+            }),
+        );
+        corgi.skills[skill]
+    }
+
+    /// Stops a session early. Half of the progress made in it is kept towards
+    /// the next session of the same skill.
+    pub fn cancel_training(&mut self, token_id: TokenId) {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can cancel a training");
+        }
+        let training = self.trainings.remove(&token_id).expect("Corgi is not in training");
+        let skill = training.skill as usize;
+        let mut corgi = self.get_corgi(token_id);
+        let elapsed = env::block_timestamp() - training.started_at.0;
+        let progress_bps = elapsed as u128 * 10_000 / TRAINING_DURATION as u128;
+        corgi.skill_progress[skill] =
+            std::cmp::min(corgi.skill_progress[skill] + progress_bps as u64 / 2, 9_999);
+        self.corgis.insert(&token_id, &corgi);
+    }
+
+    pub fn get_training(&self, token_id: TokenId) -> Option<Training> {
+        self.trainings.get(&token_id)
+    }
+
+    /// The world event in effect right now, if any.
+    pub fn get_world_state(&self) -> Option<WorldState> {
+        let now = env::block_timestamp();
+        self.world_state
+            .clone()
+            .filter(|state| state.start_at.0 <= now && now < state.end_at.0)
+    }
+
+    /// Crafts a house for `HOUSE_PRICE` plus `HOUSE_FRUIT_PER_KIND` of every fruit.
+    #[payable]
+    pub fn build_house(&mut self) -> u64 {
+        if env::attached_deposit() != HOUSE_PRICE {
+            env::panic(b"Each new house cost 2 NEAR");
+        }
+        let predecessor = env::predecessor_account_id();
+        self.spend_fruit(
+            &predecessor,
+            &Fruit::of_each(DEFAULT_FRUIT_NAMES.len() as u64, HOUSE_FRUIT_PER_KIND),
+        );
+        self.treasury_balance += HOUSE_PRICE;
+        let id = self.next_house_id;
+        self.next_house_id += 1;
+        self.houses.insert(
+            &id,
+            &House {
+                id,
+                owner: predecessor,
+                capacity: HOUSE_CAPACITY,
+                residents: vec![],
+            },
+        );
+        id
+    }
+
+    /// Transfers a house. Its residents belong to the previous owner, so they move out.
+    pub fn transfer_house(&mut self, house_id: u64, receiver_id: AccountId) {
+        assert!(
+            env::is_valid_account_id(receiver_id.as_bytes()),
+            "Receiver's account ID is invalid."
+        );
+        let mut house = self.houses.get(&house_id).expect("House not found");
+        if house.owner != env::predecessor_account_id() {
+            env::panic(b"Only the owner can transfer a house");
+        }
+        for resident in house.residents.drain(..) {
+            self.corgi_house.remove(&resident);
+        }
+        house.owner = receiver_id;
+        self.houses.insert(&house_id, &house);
+    }
+
+    pub fn move_into_house(&mut self, token_id: TokenId, house_id: u64) {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can move a corgi");
+        }
+        let mut house = self.houses.get(&house_id).expect("House not found");
+        if house.owner != predecessor {
+            env::panic(b"Corgis can only move into their owner's house");
+        }
+        if house.residents.len() as u64 >= house.capacity {
+            env::panic(b"House is full");
+        }
+        self.leave_house(token_id);
+        house.residents.push(token_id);
+        self.houses.insert(&house_id, &house);
+        self.corgi_house.insert(&token_id, &house_id);
+    }
+
+    pub fn move_out_of_house(&mut self, token_id: TokenId) {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can move a corgi");
+        }
+        self.leave_house(token_id);
+    }
+
+    pub fn get_house(&self, house_id: u64) -> Option<House> {
+        self.houses.get(&house_id)
+    }
+
+    pub fn get_corgi_house(&self, token_id: TokenId) -> Option<u64> {
+        self.corgi_house.get(&token_id)
+    }
+
+    /// Registers a team of the caller's corgis for tournaments. Members must not be
+    /// listed, rented out, in training or already in another team.
+    pub fn form_team(&mut self, token_ids: Vec<TokenId>, name: String) -> u64 {
+        if token_ids.len() < MIN_TEAM_SIZE || token_ids.len() > MAX_TEAM_SIZE {
+            env::panic(format!("A team has {} to {} corgis", MIN_TEAM_SIZE, MAX_TEAM_SIZE).as_bytes());
+        }
+        if name.is_empty() || name.len() > MAX_TEAM_NAME_LENGTH {
+            env::panic(b"Invalid team name");
+        }
+        let predecessor = env::predecessor_account_id();
+        for (i, &token_id) in token_ids.iter().enumerate() {
+            if token_ids[..i].contains(&token_id) {
+                env::panic(b"Duplicate team member");
+            }
+            if self.get_token_owner(token_id) != predecessor {
+                env::panic(b"Only the owner can add a corgi to a team");
+            }
+            self.assert_free_for_team(token_id);
+        }
+        let id = self.next_team_id;
+        self.next_team_id += 1;
+        for token_id in token_ids.iter() {
+            self.corgi_team.insert(token_id, &id);
+        }
+        self.teams.insert(
+            &id,
+            &Team {
+                id,
+                owner: predecessor,
+                name,
+                members: token_ids,
+            },
+        );
+        id
+    }
+
+    pub fn disband_team(&mut self, team_id: u64) {
+        let team = self.teams.get(&team_id).expect("Team not found");
+        if team.owner != env::predecessor_account_id() {
+            env::panic(b"Only the owner can disband a team");
+        }
+        self.remove_team(team);
+    }
+
+    pub fn get_team(&self, team_id: u64) -> Option<Team> {
+        self.teams.get(&team_id)
+    }
+
+    pub fn get_corgi_team(&self, token_id: TokenId) -> Option<u64> {
+        self.corgi_team.get(&token_id)
+    }
+
+    /// Opens a bracket for `max_teams` teams, a power of two. Entry fees make up the prize pool.
+    pub fn create_tournament(&mut self, max_teams: u64, entry_fee: U128) -> u64 {
+        self.assert_owner_or_admin();
+        if !(2..=MAX_TOURNAMENT_TEAMS).contains(&max_teams) || !max_teams.is_power_of_two() {
+            env::panic(b"Tournament size must be a power of two up to 64");
+        }
+        let id = self.next_tournament_id;
+        self.next_tournament_id += 1;
+        self.tournaments.insert(
+            &id,
+            &Tournament {
+                id,
+                max_teams,
+                entry_fee,
+                prize_pool: U128(0),
+                entrants: vec![],
+                rounds: vec![],
+                winner: None,
+            },
+        );
+        id
+    }
+
+    /// Enters a team into a tournament; the attached deposit must equal the entry fee.
+    #[payable]
+    pub fn join_tournament(&mut self, tournament_id: u64, team_id: u64) {
+        let mut tournament = self
+            .tournaments
+            .get(&tournament_id)
+            .expect("Tournament not found");
+        let team = self.teams.get(&team_id).expect("Team not found");
+        if team.owner != env::predecessor_account_id() {
+            env::panic(b"Only the owner can enter a team");
+        }
+        if tournament.entrants.len() as u64 >= tournament.max_teams {
+            env::panic(b"Tournament is full");
+        }
+        if tournament.entrants.iter().any(|(entrant, _)| *entrant == team_id) {
+            env::panic(b"Team already entered");
+        }
+        if env::attached_deposit() != tournament.entry_fee.0 {
+            env::panic(b"Attached deposit must equal the entry fee");
+        }
+        tournament.prize_pool = U128(tournament.prize_pool.0 + tournament.entry_fee.0);
+        tournament.entrants.push((team_id, team.owner));
+        self.tournaments.insert(&tournament_id, &tournament);
+    }
+
+    /// Plays out a full bracket round by round and escrows the prizes. Anyone can
+    /// call it once the tournament is full. Teams dissolved since joining forfeit
+    /// their matches, and prizes of dissolved teams go to the treasury.
+    pub fn run_tournament(&mut self, tournament_id: u64) -> Tournament {
+        let mut tournament = self
+            .tournaments
+            .get(&tournament_id)
+            .expect("Tournament not found");
+        if tournament.winner.is_some() {
+            env::panic(b"Tournament already finished");
+        }
+        if (tournament.entrants.len() as u64) < tournament.max_teams {
+            env::panic(b"Tournament is not full yet");
+        }
+        let mut rng = self.random_rng();
+        let mut bracket = tournament
+            .entrants
+            .iter()
+            .map(|(team_id, _)| *team_id)
+            .collect::<Vec<_>>();
+        let mut runner_up = None;
+        while bracket.len() > 1 {
+            let mut advancing = vec![];
+            for pair in bracket.chunks(2) {
+                let power_a = self.team_power(pair[0]).map(|power| power + rng.next_u64() % 50);
+                let power_b = self.team_power(pair[1]).map(|power| power + rng.next_u64() % 50);
+                let (winner, loser) = if power_b > power_a {
+                    (pair[1], pair[0])
+                } else {
+                    (pair[0], pair[1])
+                };
+                advancing.push(winner);
+                runner_up = Some(loser);
+            }
+            tournament.rounds.push(advancing.clone());
+            bracket = advancing;
+        }
+        let winner = bracket[0];
+        tournament.winner = Some(winner);
+        let mut paid = 0;
+        for (team_id, bps) in [Some(winner), runner_up].iter().zip(TOURNAMENT_PRIZE_BPS.iter()) {
+            let team = team_id.and_then(|team_id| self.teams.get(&team_id));
+            if let Some(team) = team {
+                let prize = tournament.prize_pool.0 * bps / 10_000;
+                self.credit_deposit(&team.owner, prize);
+                paid += prize;
+            }
+        }
+        self.treasury_balance += tournament.prize_pool.0 - paid;
+        self.tournaments.insert(&tournament_id, &tournament);
+        env::log(format!("Tournament {} won by team {}", tournament_id, winner).as_bytes());
+        tournament
+    }
+
+    pub fn get_tournament(&self, tournament_id: u64) -> Option<Tournament> {
+        self.tournaments.get(&tournament_id)
+    }
+
+    /// Challenges an NFT on a trusted partner arena. The attached deposit is the
+    /// wager; the arena matches it from its escrow balance and the winner of the
+    /// battle receives both wagers in escrow.
+    #[payable]
+    pub fn challenge_arena(
+        &mut self,
+        token_id: TokenId,
+        arena_id: AccountId,
+        opponent_token_id: String,
+    ) -> Promise {
+        if !self.arenas.contains(&arena_id) {
+            env::panic(b"Arena is not trusted");
+        }
+        let predecessor = env::predecessor_account_id();
+        let is_renter = self
+            .active_rental(token_id)
+            .is_some_and(|rental| rental.renter == predecessor);
+        if self.get_token_owner(token_id) != predecessor && !is_renter {
+            env::panic(b"Only the owner or renter can send a corgi to battle");
+        }
+        if self.trainings.get(&token_id).is_some() {
+            env::panic(b"Corgi is in training");
+        }
+        let mut corgi = self.get_corgi(token_id);
+        apply_hunger(&mut corgi, env::block_timestamp());
+        let wager = env::attached_deposit();
+        self.debit_deposit(&arena_id, wager);
+        let battle_id = self.next_battle_id;
+        self.next_battle_id += 1;
+        self.battles.insert(
+            &battle_id,
+            &Battle {
+                challenger: predecessor.clone(),
+                token_id,
+                arena: arena_id.clone(),
+                opponent_token_id: opponent_token_id.clone(),
+                wager: U128(wager),
+            },
+        );
+        let stats = BattleStats {
+            token_id: U64(token_id),
+            owner: predecessor,
+            power: std::cmp::min(
+                rarity_tier(corgi.rate) * 20
+                    + self.corgi_house.get(&token_id).map_or(0, |_| HOUSE_POWER_BONUS)
+                    + corgi.bonded_with.map_or(0, |_| BOND_POWER_BONUS)
+                    + self.get_world_state().map_or(0, |state| state.battle_power_bonus),
+                100,
+            ),
+            energy: corgi.energy,
+            size: std::cmp::min(corgi_sausage(&corgi) * 100 / MAX_SAUSAGE, 100),
+        };
+        battle_interface::battle(stats, opponent_token_id, &arena_id, 0, BATTLE_GAS).then(
+            ext_self::on_battle_resolved(
+                battle_id,
+                &env::current_account_id(),
+                0,
+                BATTLE_CALLBACK_GAS,
+            ),
+        )
+    }
+
+    /// Settles a battle with the arena's response. If the arena failed or
+    /// returned garbage, both wagers are refunded. Returns whether the challenger won.
+    #[private]
+    pub fn on_battle_resolved(&mut self, battle_id: u64) -> bool {
+        let battle = self.battles.remove(&battle_id).expect("Battle not found");
+        let outcome = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                serde_json::from_slice::<BattleOutcome>(&result).ok()
+            }
+            _ => None,
+        };
+        match outcome {
+            Some(outcome) => {
+                let winner = if outcome.challenger_won {
+                    &battle.challenger
+                } else {
+                    &battle.arena
+                };
+                let winner = winner.clone();
+                self.credit_deposit(&winner, battle.wager.0 * 2);
+                if let Some(mut corgi) = self.corgis.get(&battle.token_id) {
+                    corgi.battles_fought += 1;
+                    if outcome.challenger_won {
+                        corgi.battles_won += 1;
+                    }
+                    self.unlock_hidden_traits(&mut corgi);
+                    self.corgis.insert(&battle.token_id, &corgi);
+                }
+                env::log(format!("Battle {} won by {}", battle_id, winner).as_bytes());
+                if outcome.challenger_won {
+                    self.add_season_points(&winner, BATTLE_WIN_POINTS);
+                }
+                outcome.challenger_won
+            }
+            None => {
+                self.credit_deposit(&battle.challenger, battle.wager.0);
+                self.credit_deposit(&battle.arena, battle.wager.0);
+                false
+            }
+        }
+    }
+
+    pub fn get_battle(&self, battle_id: u64) -> Option<Battle> {
+        self.battles.get(&battle_id)
+    }
+
+    /// Challenges `opponent` to a battle against the caller's `my_corgi`. The
+    /// attached deposit is the wager, which the opponent must match to accept.
+    #[payable]
+    pub fn challenge(&mut self, opponent: AccountId, my_corgi: TokenId) -> Challenge {
+        let challenger = env::predecessor_account_id();
+        if self.get_token_owner(my_corgi) != challenger {
+            env::panic(b"Only the owner can send a corgi to battle");
+        }
+        if opponent == challenger {
+            env::panic(b"Can't challenge yourself");
+        }
+        if self.trainings.get(&my_corgi).is_some() {
+            env::panic(b"Corgi is in training");
+        }
+        let id = self.next_challenge_id;
+        self.next_challenge_id += 1;
+        let challenge = Challenge {
+            id,
+            challenger,
+            token_id: my_corgi,
+            opponent,
+            wager: U128(env::attached_deposit()),
+            created_at: U64(env::block_timestamp()),
+        };
+        self.challenges.insert(&id, &challenge);
+        challenge
+    }
+
+    /// Withdraws an open challenge and refunds its wager.
+    pub fn cancel_challenge(&mut self, challenge_id: u64) {
+        let challenge = self.challenges.get(&challenge_id).expect("Challenge not found");
+        if challenge.challenger != env::predecessor_account_id() {
+            env::panic(b"Only the challenger can cancel a challenge");
+        }
+        self.challenges.remove(&challenge_id);
+        if challenge.wager.0 > 0 {
+            self.pay_out(challenge.challenger, challenge.wager.0);
+        }
+    }
+
+    /// Accepts a challenge with the caller's `my_corgi`, matching the wager, and
+    /// fights it out right away. Each side scores its rarity, size and a random
+    /// roll; the higher score takes both wagers, the challenger on a tie.
+    #[payable]
+    pub fn accept_challenge(&mut self, challenge_id: u64, my_corgi: TokenId) -> DuelRecord {
+        let challenge = self.challenges.get(&challenge_id).expect("Challenge not found");
+        let opponent = env::predecessor_account_id();
+        if challenge.opponent != opponent {
+            env::panic(b"Challenge is addressed to another account");
+        }
+        if self.get_token_owner(my_corgi) != opponent {
+            env::panic(b"Only the owner can send a corgi to battle");
+        }
+        if self.trainings.get(&my_corgi).is_some() {
+            env::panic(b"Corgi is in training");
+        }
+        if env::attached_deposit() != challenge.wager.0 {
+            env::panic(b"Attached deposit must match the wager");
+        }
+        if self.corgi_to_account.get(&challenge.token_id) != Some(challenge.challenger.clone()) {
+            env::panic(b"Challenger's corgi is no longer available");
+        }
+        self.challenges.remove(&challenge_id);
+        let pot = challenge.wager.0 * 2;
+        let mut challenger_corgi = self.get_corgi(challenge.token_id);
+        let mut opponent_corgi = self.get_corgi(my_corgi);
+        let (roll_a, roll_b) = self.random_num();
+        let challenger_won =
+            duel_score(&challenger_corgi, roll_a / 2) >= duel_score(&opponent_corgi, roll_b);
+        let (winner, winner_token_id) = if challenger_won {
+            (challenge.challenger.clone(), challenge.token_id)
+        } else {
+            (opponent, my_corgi)
+        };
+        self.record_battle(&mut challenger_corgi, challenger_won);
+        self.record_battle(&mut opponent_corgi, !challenger_won);
+        if pot > 0 {
+            self.pay_out(winner.clone(), pot);
+        }
+        self.add_season_points(&winner, BATTLE_WIN_POINTS);
+        let record = DuelRecord {
+            challenge_id,
+            challenger_token_id: challenge.token_id,
+            opponent_token_id: my_corgi,
+            winner_token_id,
+            pot: U128(pot),
+            fought_at: U64(env::block_timestamp()),
+        };
+        self.record_duel(challenge.token_id, &record);
+        self.record_duel(my_corgi, &record);
+        env::log(format!("Challenge {} won by {}", challenge_id, winner).as_bytes());
+        record
+    }
+
+    /// Open challenges addressed to `account_id`.
+    pub fn get_open_challenges(&self, account_id: AccountId) -> Vec<Challenge> {
+        self.challenges
+            .values()
+            .filter(|challenge| challenge.opponent == account_id)
+            .collect()
+    }
+
+    /// Latest PvP battles of a corgi, oldest first.
+    pub fn get_battle_history(&self, token_id: TokenId) -> Vec<DuelRecord> {
+        self.duel_history.get(&token_id).unwrap_or_default()
+    }
+
+    /// Stakes the caller's corgi. It stays theirs but can't be transferred, sold
+    /// or deleted until unstaked, and earns its rarity tier in shares of each
+    /// epoch's release from the staking reward pool.
+    pub fn stake_corgi(&mut self, token_id: TokenId) {
+        let predecessor = env::predecessor_account_id();
+        let corgi = self.get_corgi(token_id);
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can stake a corgi");
+        }
+        if corgi.selling {
+            env::panic(b"Cancel the sale before staking the corgi");
+        }
+        self.assert_transferable(token_id);
+        let mut staked = self.account_stakes.get(&predecessor).unwrap_or_default();
+        if staked.len() >= MAX_STAKED_CORGIS {
+            env::panic(b"Account has staked the maximum number of corgis");
+        }
+        self.release_staking_rewards();
+        let weight = stake_weight(&corgi);
+        self.stakes.insert(
+            &token_id,
+            &Stake {
+                owner: predecessor.clone(),
+                staked_at: env::block_timestamp(),
+                weight,
+                reward_debt: weight as u128 * self.reward_per_weight / STAKING_REWARD_PRECISION,
+            },
+        );
+        staked.push(token_id);
+        self.account_stakes.insert(&predecessor, &staked);
+        self.total_stake_weight += weight;
+    }
+
+    /// Unstakes the caller's corgi, keeping its earned rewards for `claim_rewards`.
+    pub fn unstake_corgi(&mut self, token_id: TokenId) {
+        let predecessor = env::predecessor_account_id();
+        let stake = self.stakes.get(&token_id).expect("Corgi is not staked");
+        if stake.owner != predecessor {
+            env::panic(b"Only the staker can unstake a corgi");
+        }
+        self.release_staking_rewards();
+        let reward = self.staking_rewards.get(&predecessor).unwrap_or(0)
+            + pending_stake_reward(&stake, self.reward_per_weight);
+        self.staking_rewards.insert(&predecessor, &reward);
+        self.stakes.remove(&token_id);
+        let mut staked = self.account_stakes.get(&predecessor).unwrap();
+        staked.retain(|&staked_id| staked_id != token_id);
+        if staked.is_empty() {
+            self.account_stakes.remove(&predecessor);
+        } else {
+            self.account_stakes.insert(&predecessor, &staked);
+        }
+        self.total_stake_weight -= stake.weight;
+    }
+
+    /// Pays out everything the caller's corgis earned, staked or unstaked since.
+    pub fn claim_rewards(&mut self) -> Promise {
+        let predecessor = env::predecessor_account_id();
+        self.release_staking_rewards();
+        let mut reward = self.staking_rewards.remove(&predecessor).unwrap_or(0);
+        for token_id in self.account_stakes.get(&predecessor).unwrap_or_default() {
+            let mut stake = self.stakes.get(&token_id).unwrap();
+            reward += pending_stake_reward(&stake, self.reward_per_weight);
+            stake.reward_debt =
+                stake.weight as u128 * self.reward_per_weight / STAKING_REWARD_PRECISION;
+            self.stakes.insert(&token_id, &stake);
+        }
+        if reward == 0 {
+            env::panic(b"No staking rewards to claim");
+        }
+        self.pay_out(predecessor, reward)
+    }
+
+    /// Moves `amount` of the treasury into the staking reward pool.
+    pub fn fund_staking_rewards(&mut self, amount: U128) {
+        self.assert_owner();
+        if self.treasury_balance < amount.0 {
+            env::panic(b"Treasury can't fund the staking rewards");
+        }
+        self.treasury_balance -= amount.0;
+        self.staking_reward_pool += amount.0;
+        self.log_admin_action("fund_staking_rewards", amount.0.to_string());
+    }
+
+    pub fn get_staked_corgis(&self, account_id: AccountId) -> Vec<StakedCorgi> {
+        let (reward_per_weight, _) = self.staking_rewards_now();
+        self.account_stakes
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|token_id| {
+                let stake = self.stakes.get(&token_id).unwrap();
+                StakedCorgi {
+                    token_id,
+                    staked_at: U64(stake.staked_at),
+                    weight: stake.weight,
+                    pending_reward: U128(pending_stake_reward(&stake, reward_per_weight)),
+                }
+            })
+            .collect()
+    }
+
+    /// Rewards `claim_rewards` would pay the account now.
+    pub fn get_staking_rewards(&self, account_id: AccountId) -> U128 {
+        let staked: Balance = self
+            .get_staked_corgis(account_id.clone())
+            .iter()
+            .map(|staked| staked.pending_reward.0)
+            .sum();
+        U128(staked + self.staking_rewards.get(&account_id).unwrap_or(0))
+    }
+
+    pub fn get_staking_reward_pool(&self) -> U128 {
+        U128(self.staking_rewards_now().1)
+    }
+
+    /// Sets the caller's nickname (unique, case-insensitive) and optional avatar corgi.
+    pub fn set_profile(&mut self, nickname: String, avatar_token_id: Option<TokenId>) {
+        let predecessor = env::predecessor_account_id();
+        if nickname.len() < MIN_NICKNAME_LENGTH
+            || nickname.len() > MAX_NICKNAME_LENGTH
+            || !nickname
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            env::panic(b"Nickname must be 3-32 letters, digits, '_' or '-'");
+        }
+        if let Some(token_id) = avatar_token_id {
+            if self.corgi_to_account.get(&token_id) != Some(predecessor.clone()) {
+                env::panic(b"Avatar must be a corgi owned by the caller");
+            }
+        }
+        let key = nickname.to_lowercase();
+        match self.nicknames.get(&key) {
+            Some(holder) if holder != predecessor => env::panic(b"Nickname is already taken"),
+            _ => {}
+        }
+        if let Some(old) = self.profiles.get(&predecessor) {
+            self.nicknames.remove(&old.nickname.to_lowercase());
+        }
+        self.nicknames.insert(&key, &predecessor);
+        self.profiles.insert(
+            &predecessor,
+            &Profile {
+                nickname,
+                avatar_token_id,
+            },
+        );
+    }
+
+    /// The avatar is omitted once the account no longer owns that corgi.
+    pub fn get_profile(&self, account_id: AccountId) -> Option<Profile> {
+        self.profiles.get(&account_id).map(|mut profile| {
+            if let Some(token_id) = profile.avatar_token_id {
+                if self.corgi_to_account.get(&token_id) != Some(account_id.clone()) {
+                    profile.avatar_token_id = None;
+                }
+            }
+            profile
+        })
+    }
+
+    pub fn get_account_by_nickname(&self, nickname: String) -> Option<AccountId> {
+        self.nicknames.get(&nickname.to_lowercase())
+    }
+
+    /// Answers a game server's login challenge: the caller stores the hex
+    /// SHA-256 of the nonce it was handed, proving control of the account.
+    pub fn register_login_nonce(&mut self, nonce_hash: String) {
+        if nonce_hash.len() != 64 {
+            env::panic(b"Nonce hash must be a hex SHA-256");
+        }
+        let predecessor = env::predecessor_account_id();
+        self.login_nonces.insert(
+            &predecessor,
+            &LoginNonce {
+                nonce_hash: nonce_hash.to_lowercase(),
+                registered_at: U64(env::block_timestamp()),
+            },
+        );
+        self.record_activity(&predecessor);
+    }
+
+    /// Whether `account_id` holds `token_id`, with its latest login nonce and
+    /// activity for the server to check against its challenge.
+    pub fn verify_holding(&self, account_id: AccountId, token_id: TokenId) -> HoldingProof {
+        HoldingProof {
+            holds: self.corgi_to_account.get(&token_id) == Some(account_id.clone()),
+            token_id,
+            nonce: self.login_nonces.get(&account_id),
+            last_active_at: self.last_active.get(&account_id).map(U64),
+            account_id,
+        }
+    }
+
+    pub fn get_login_state(&self, account_id: AccountId) -> LoginState {
+        let hash = env::sha256(account_id.as_bytes());
+        let token_ids = match self.account_corgis.get(&hash) {
+            Some(corgi_ids) => corgi_ids.iter().take(MAX_LOGIN_HOLDINGS as usize).collect(),
+            None => vec![],
+        };
+        LoginState {
+            token_ids,
+            nonce: self.login_nonces.get(&account_id),
+            last_active_at: self.last_active.get(&account_id).map(U64),
+            account_id,
+        }
+    }
+
+    pub fn create_kennel(&mut self, name: String) -> u64 {
+        let predecessor = env::predecessor_account_id();
+        if name.is_empty() || name.len() > MAX_NICKNAME_LENGTH {
+            env::panic(b"Kennel name must be 1-32 characters");
+        }
+        let id = self.next_kennel_id;
+        self.next_kennel_id += 1;
+        self.kennels.insert(
+            &id,
+            &Kennel {
+                id,
+                name,
+                leader: predecessor.clone(),
+                members: 0,
+                showcase: vec![],
+                corgi_count: 0,
+                total_sausage: 0,
+                treasury_corgis: vec![],
+                total_shares: 0,
+                shareholders: vec![],
+            },
+        );
+        self.add_kennel_member(id, predecessor);
+        id
+    }
+
+    pub fn join_kennel(&mut self, kennel_id: u64) {
+        if self.kennels.get(&kennel_id).is_none() {
+            env::panic(b"Kennel not found");
+        }
+        self.add_kennel_member(kennel_id, env::predecessor_account_id());
+    }
+
+    /// The leader can only leave as the last member, which closes the kennel.
+    pub fn leave_kennel(&mut self) {
+        let predecessor = env::predecessor_account_id();
+        let kennel = self.kennel_of(&predecessor).expect("Not a kennel member");
+        if kennel.leader == predecessor && kennel.members > 1 {
+            env::panic(b"The leader must be the last member to leave");
+        }
+        if kennel.members == 1 && !kennel.treasury_corgis.is_empty() {
+            env::panic(b"Sell the kennel treasury before closing the kennel");
+        }
+        let shares = kennel_shares_of(&kennel, &predecessor);
+        if shares > 0 {
+            let price = self.kennel_treasury_value(&kennel) * shares as u128
+                / kennel.total_shares as u128;
+            self.share_buyouts.insert(
+                &predecessor,
+                &ShareBuyout {
+                    kennel_id: kennel.id,
+                    seller: predecessor.clone(),
+                    shares,
+                    price: U128(price),
+                },
+            );
+        }
+        self.remove_kennel_member(&predecessor);
+    }
+
+    /// Moves the caller's corgi into the kennel treasury for shares weighted by rarity.
+    pub fn contribute_to_kennel(&mut self, token_id: TokenId) -> u64 {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can contribute a corgi");
+        }
+        let kennel_id = self.kennel_of(&predecessor).expect("Not a kennel member").id;
+        let shares = rarity_tier(self.get_corgi(token_id).rate)
+            * SHARES_PER_TIER;
+        self.internal_transfer(token_id, predecessor.clone(), env::current_account_id());
+        let mut kennel = self.kennels.get(&kennel_id).unwrap();
+        kennel.treasury_corgis.push(token_id);
+        kennel.total_shares += shares;
+        match kennel
+            .shareholders
+            .iter_mut()
+            .find(|(holder, _)| *holder == predecessor)
+        {
+            Some((_, held)) => *held += shares,
+            None => kennel.shareholders.push((predecessor, shares)),
+        }
+        self.kennels.insert(&kennel_id, &kennel);
+        self.kennel_treasury.insert(&token_id, &kennel_id);
+        shares
+    }
+
+    /// Lists a treasury corgi on the marketplace; proceeds are split by shares.
+    pub fn list_kennel_corgi(&mut self, token_id: TokenId, price: U128) {
+        let kennel_id = self
+            .kennel_treasury
+            .get(&token_id)
+            .expect("Corgi is not in a kennel treasury");
+        let kennel = self.kennels.get(&kennel_id).unwrap();
+        if kennel.leader != env::predecessor_account_id() {
+            env::panic(b"Only the kennel leader can list treasury corgis");
+        }
+        let mut corgi = self.get_corgi(token_id);
+        corgi.selling = true;
+        corgi.selling_price = price;
+        self.corgis.insert(&token_id, &corgi);
+        self.selling_corgis.insert(&token_id);
+        self.listing_currencies.remove(&token_id);
+    }
+
+    /// Buys the shares a departed member left behind, paying them the buyout price.
+    #[payable]
+    pub fn complete_buyout(&mut self, seller: AccountId) -> Promise {
+        let buyout = self.share_buyouts.get(&seller).expect("Buyout not found");
+        let buyer = env::predecessor_account_id();
+        if self.account_kennel.get(&buyer) != Some(buyout.kennel_id) {
+            env::panic(b"Only kennel members can buy out shares");
+        }
+        if env::attached_deposit() != buyout.price.0 {
+            env::panic(b"Attached deposit must equal the buyout price");
+        }
+        let mut kennel = self.kennels.get(&buyout.kennel_id).unwrap();
+        kennel.shareholders.retain(|(holder, _)| *holder != seller);
+        match kennel.shareholders.iter_mut().find(|(holder, _)| *holder == buyer) {
+            Some((_, held)) => *held += buyout.shares,
+            None => kennel.shareholders.push((buyer, buyout.shares)),
+        }
+        self.kennels.insert(&buyout.kennel_id, &kennel);
+        self.share_buyouts.remove(&seller);
+        self.pay_out(seller, buyout.price.0)
+    }
+
+    pub fn get_share_buyouts(&self, from_index: u64, limit: u64) -> Vec<ShareBuyout> {
+        let limit = view_limit(limit);
+        let values = self.share_buyouts.values_as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), values.len()))
+            .filter_map(|index| values.get(index))
+            .collect()
+    }
+
+    pub fn kick_kennel_member(&mut self, member: AccountId) {
+        let predecessor = env::predecessor_account_id();
+        let kennel = self.kennel_of(&predecessor).expect("Not a kennel member");
+        if kennel.leader != predecessor || member == predecessor {
+            env::panic(b"Only the leader can remove other members");
+        }
+        if self.account_kennel.get(&member) != Some(kennel.id) {
+            env::panic(b"Account is not a member of this kennel");
+        }
+        self.remove_kennel_member(&member);
+    }
+
+    pub fn add_to_showcase(&mut self, token_id: TokenId) {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can showcase a corgi");
+        }
+        let mut kennel = self.kennel_of(&predecessor).expect("Not a kennel member");
+        if kennel.showcase.contains(&token_id) {
+            return;
+        }
+        if kennel.showcase.len() >= MAX_KENNEL_SHOWCASE {
+            env::panic(b"Kennel showcase is full");
+        }
+        kennel.showcase.push(token_id);
+        self.kennels.insert(&kennel.id, &kennel);
+    }
+
+    pub fn remove_from_showcase(&mut self, token_id: TokenId) {
+        let predecessor = env::predecessor_account_id();
+        let mut kennel = self.kennel_of(&predecessor).expect("Not a kennel member");
+        if self.corgi_to_account.get(&token_id) != Some(predecessor.clone())
+            && kennel.leader != predecessor
+        {
+            env::panic(b"Only the owner or leader can remove a showcased corgi");
+        }
+        kennel.showcase.retain(|&id| id != token_id);
+        self.kennels.insert(&kennel.id, &kennel);
+    }
+
+    pub fn get_kennel(&self, kennel_id: u64) -> Option<Kennel> {
+        self.kennels.get(&kennel_id)
+    }
+
+    pub fn get_account_kennel(&self, account_id: AccountId) -> Option<u64> {
+        self.account_kennel.get(&account_id)
+    }
+
+    /// Kennels ranked by corgi count, then total sausage.
+    pub fn get_kennel_leaderboard(&self, limit: u64) -> Vec<Kennel> {
+        let limit = view_limit(limit);
+        let mut kennels = self.kennels.values().collect::<Vec<_>>();
+        kennels.sort_by(|a, b| {
+            (b.corgi_count, b.total_sausage).cmp(&(a.corgi_count, a.total_sausage))
+        });
+        kennels.truncate(limit as usize);
+        kennels
+    }
+
+    /// Starts a season running until `end_at` (ns), funding its prize pool from the treasury.
+    pub fn start_season(&mut self, end_at: U64, prize_pool: U128) -> u64 {
+        self.assert_owner();
+        if self.current_season.is_some() {
+            env::panic(b"A season is already running");
+        }
+        if !self.season_scores.is_empty() {
+            env::panic(b"Scores of the previous season are not cleared yet");
+        }
+        let now = env::block_timestamp();
+        if end_at.0 <= now {
+            env::panic(b"Season must end in the future");
+        }
+        if self.treasury_balance < prize_pool.0 {
+            env::panic(b"Treasury can't fund the prize pool");
+        }
+        self.treasury_balance -= prize_pool.0;
+        for rarity in Rarity::QUOTA_TIERS.iter() {
+            self.season_rarity_minted.remove(rarity);
+        }
+        let id = self.seasons.len();
+        self.seasons.insert(
+            &id,
+            &Season {
+                id,
+                start_at: now,
+                end_at: end_at.0,
+                prize_pool,
+                winners: vec![],
+            },
+        );
+        self.current_season = Some(id);
+        self.log_admin_action(
+            "start_season",
+            format!("season {} with prize pool {}", id, prize_pool.0),
+        );
+        id
+    }
+
+    /// Snapshots the top accounts of an ended season, escrows their prizes and
+    /// resets the leaderboard. Anyone can call it once the season is over; the
+    /// season's scores are then cleared with `clear_season_scores`.
+    pub fn close_season(&mut self) -> Season {
+        let id = self.current_season.expect("No season is running");
+        let mut season = self.seasons.get(&id).unwrap();
+        if env::block_timestamp() < season.end_at {
+            env::panic(b"Season has not ended yet");
+        }
+        let mut paid = 0;
+        let leaders = std::mem::take(&mut self.season_leaders);
+        for ((account, _), bps) in leaders.into_iter().zip(SEASON_PRIZE_BPS.iter()) {
+            let prize = season.prize_pool.0 * bps / 10_000;
+            let unclaimed = self.season_prizes.get(&account).unwrap_or(0);
+            self.season_prizes.insert(&account, &(unclaimed + prize));
+            season.winners.push((account, U128(prize)));
+            paid += prize;
+        }
+        self.treasury_balance += season.prize_pool.0 - paid;
+        self.seasons.insert(&id, &season);
+        self.current_season = None;
+        season
+    }
+
+    /// Removes up to `limit` scores of the closed season, paying the keeper
+    /// bounty for them. Returns how many are left.
+    pub fn clear_season_scores(&mut self, limit: u64) -> u64 {
+        if self.current_season.is_some() {
+            env::panic(b"A season is still running");
+        }
+        let mut cleared = 0;
+        while cleared < limit {
+            let keys = self.season_scores.keys_as_vector();
+            // the last key is the cheapest to remove
+            let account = match keys.len().checked_sub(1).and_then(|last| keys.get(last)) {
+                Some(account) => account,
+                None => break,
+            };
+            self.season_scores.remove(&account);
+            cleared += 1;
+        }
+        self.pay_keeper_bounty(cleared);
+        self.season_scores.len()
+    }
+
+    /// Starts a cross-promotion with a partner collection. Returns the campaign ID.
+    pub fn add_campaign(&mut self, partner_contract: AccountId, accessory: String, ends_at: U64) -> u64 {
+        self.assert_owner();
+        if ends_at.0 <= env::block_timestamp() {
+            env::panic(b"Campaign must end in the future");
+        }
+        let id = self.next_campaign_id;
+        self.next_campaign_id += 1;
+        self.campaigns.insert(
+            &id,
+            &Campaign {
+                partner_contract: partner_contract.clone(),
+                accessory,
+                ends_at,
+            },
+        );
+        self.log_admin_action("add_campaign", format!("campaign {} with {}", id, partner_contract));
+        id
+    }
+
+    pub fn end_campaign(&mut self, campaign_id: u64) {
+        self.assert_owner();
+        self.campaigns.remove(&campaign_id).expect("Campaign not found");
+        self.log_admin_action("end_campaign", format!("campaign {}", campaign_id));
+    }
+
+    pub fn get_campaigns(&self) -> Vec<(u64, Campaign)> {
+        self.campaigns.to_vec()
+    }
+
+    /// Claims a campaign's accessory for the caller's corgi by proving it holds
+    /// `partner_token_id`; ownership is checked with the partner contract in
+    /// `on_campaign_verified`. Each partner token can claim once per campaign.
+    pub fn claim_campaign_accessory(
+        &mut self,
+        campaign_id: u64,
+        token_id: TokenId,
+        partner_token_id: String,
+    ) -> Promise {
+        let campaign = self.campaigns.get(&campaign_id).expect("Campaign not found");
+        if env::block_timestamp() >= campaign.ends_at.0 {
+            env::panic(b"Campaign has ended");
+        }
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can claim for a corgi");
+        }
+        let claim = (campaign_id, partner_token_id.clone());
+        if self.campaign_claims.get(&claim).is_some() {
+            env::panic(b"Partner token already claimed in this campaign");
+        }
+        // reserved now so concurrent claims fail, released if verification fails
+        self.campaign_claims.insert(&claim, &token_id);
+        ext_partner_nft::nft_token(
+            partner_token_id.clone(),
+            &campaign.partner_contract,
+            0,
+            PARTNER_VIEW_GAS,
+        )
+        .then(ext_self::on_campaign_verified(
+            campaign_id,
+            token_id,
+            partner_token_id,
+            predecessor,
+            &env::current_account_id(),
+            0,
+            CAMPAIGN_CALLBACK_GAS,
+        ))
+    }
+
+    /// Grants the accessory if the partner reports `claimer` as the holder and
+    /// the corgi is still theirs. Returns whether it was granted.
+    #[private]
+    pub fn on_campaign_verified(
+        &mut self,
+        campaign_id: u64,
+        token_id: TokenId,
+        partner_token_id: String,
+        claimer: AccountId,
+    ) -> bool {
+        let holder = match env::promise_result(0) {
+            PromiseResult::Successful(result) => {
+                serde_json::from_slice::<Option<PartnerToken>>(&result)
+                    .ok()
+                    .flatten()
+                    .map(|token| token.owner_id)
+            }
+            _ => None,
+        };
+        let campaign = self.campaigns.get(&campaign_id);
+        let corgi = self.corgis.get(&token_id);
+        let granted = match (&campaign, &corgi) {
+            (Some(campaign), Some(corgi)) => {
+                holder.as_ref() == Some(&claimer)
+                    && self.corgi_to_account.get(&token_id).as_ref() == Some(&claimer)
+                    && !corgi.accessories.contains(&campaign.accessory)
+                    && corgi.accessories.len() < MAX_ACCESSORIES
+            }
+            _ => false,
+        };
+        if !granted {
+            self.campaign_claims.remove(&(campaign_id, partner_token_id));
+            return false;
+        }
+        let (campaign, mut corgi) = (campaign.unwrap(), corgi.unwrap());
+        corgi.accessories.push(campaign.accessory.clone());
+        self.corgis.insert(&token_id, &corgi);
+        emit_event(
+            "corgi_accessory_claimed",
+            json!({
+                "token_id": token_id.to_string(),
+                "campaign_id": campaign_id,
+                "accessory": campaign.accessory,
+            }),
+        );
+        true
+    }
+
+    pub fn claim_season_prize(&mut self) -> Promise {
+        let predecessor = env::predecessor_account_id();
+        let prize = self
+            .season_prizes
+            .remove(&predecessor)
+            .expect("No season prize to claim");
+        self.pay_out(predecessor, prize)
+    }
+
+    pub fn get_season(&self, season_id: u64) -> Option<Season> {
+        self.seasons.get(&season_id)
+    }
+
+    pub fn get_current_season(&self) -> Option<u64> {
+        self.current_season
+    }
+
+    /// Top scores of the running season, highest first; only the top
+    /// `MAX_LEADERBOARD_SIZE` are ranked.
+    pub fn get_season_leaderboard(&self, from_index: u64, limit: u64) -> Vec<(AccountId, u64)> {
+        let limit = view_limit(limit);
+        self.season_leaders
+            .iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_season_prize(&self, account_id: AccountId) -> U128 {
+        U128(self.season_prizes.get(&account_id).unwrap_or(0))
+    }
+
+    pub fn new_maze_game(&mut self) -> MazeGame {
+        let predecessor = env::predecessor_account_id();
+        let mut fruit = HashSet::new();
+        let mut rng = self.random_rng();
+        let total = 10 + rng.next_u32() % 10;
+        let total = self
+            .get_world_state()
+            .map_or(total, |state| (total as u64 * state.fruit_bps / 10_000) as u32);
+        for _ in 0..total {
+            let kind = rng.next_u64() % self.fruit_kinds.len();
+            let x = (rng.next_u32() % 10) as u64;
+            let y = (rng.next_u32() % 10) as u64;
+            fruit.insert(MazeFruit { kind, x, y });
+        }
+        let game = MazeGame {
+            fruit: Vec::from_iter(fruit),
+        };
+        self.account_maze_game.insert(&predecessor, &game);
+        game
+    }
+
+    pub fn finish_maze_game(&mut self, eat: Vec<MazeFruit>) {
+        let predecessor = env::predecessor_account_id();
+        let game = self.account_maze_game.get(&predecessor).unwrap();
+        let mut fruit: HashSet<_> = HashSet::from_iter(game.fruit);
+        let mut eaten_fruit = Fruit::default();
+        let mut eaten = 0;
+        for e in eat {
+            if fruit.remove(&e) {
+                eaten_fruit.add(e.kind, 1);
+                eaten += 1;
+            }
+        }
+        self.mint_fruit(&predecessor, &eaten_fruit);
+        self.add_season_points(&predecessor, eaten);
+        self.account_maze_game.remove(&predecessor);
+    }
+
+    /// Processes time-based effects for up to `limit` corgis starting at index `from`
+    /// of the corgi list: hunger, ended auctions, expired rentals and a checkpoint
+    /// of staking rewards. Returns the index to resume from, which is also stored
+    /// so keepers can run it incrementally. Callers are paid the keeper bounty per
+    /// processed record.
+    pub fn tick(&mut self, from: u64, limit: u64) -> u64 {
+        let ids = self.corgis.keys_as_vector();
+        let len = ids.len();
+        let end = std::cmp::min(from.saturating_add(limit), len);
+        let batch: Vec<TokenId> = (from..end).filter_map(|index| ids.get(index)).collect();
+        let now = env::block_timestamp();
+        self.release_staking_rewards();
+        let mut processed = 0;
+        for id in batch {
+            let mut corgi = self.corgis.get(&id).unwrap();
+            if apply_hunger(&mut corgi, now) {
+                self.corgis.insert(&id, &corgi);
+                processed += 1;
+            }
+            if self.auctions.get(&id).is_some_and(|auction| auction.ends_at <= now) {
+                self.settle_auction(id);
+                processed += 1;
+            }
+            if self.rentals.get(&id).is_some_and(|rental| rental.ends_at.0 <= now) {
+                self.remove_rental(id);
+                processed += 1;
+            }
+            if let Some(mut stake) = self.stakes.get(&id) {
+                let reward = pending_stake_reward(&stake, self.reward_per_weight);
+                if reward > 0 {
+                    let settled = self.staking_rewards.get(&stake.owner).unwrap_or(0) + reward;
+                    self.staking_rewards.insert(&stake.owner, &settled);
+                    stake.reward_debt =
+                        stake.weight as u128 * self.reward_per_weight / STAKING_REWARD_PRECISION;
+                    self.stakes.insert(&id, &stake);
+                    processed += 1;
+                }
+            }
+        }
+        self.tick_cursor = if end >= len { 0 } else { end };
+        self.pay_keeper_bounty(processed);
+        self.tick_cursor
+    }
+
+    pub fn get_tick_cursor(&self) -> u64 {
+        self.tick_cursor
+    }
+
+    pub fn get_fruit_sink_totals(&self) -> FruitSinkTotals {
+        self.fruit_sink_totals.clone()
+    }
+
+    pub fn get_stakers_fruit_pool(&self) -> Fruit {
+        self.stakers_fruit_pool.clone()
+    }
+
+    pub fn account_fruit(&self, account_id: AccountId) -> Fruit {
+        self.account_fruit.get(&account_id).unwrap_or_default()
+    }
+
+    pub fn get_fruit_kinds(&self) -> Vec<FruitKind> {
+        self.fruit_kinds.to_vec()
+    }
+
+    /// Collects the fruit the caller's corgis grew since their last harvest: each
+    /// yields its rarity tier in fruit of a random kind per `HARVEST_INTERVAL`,
+    /// for up to `MAX_HARVEST_PERIODS`. A corgi's first harvest, including after
+    /// changing hands, only starts its clock. Looks at the first
+    /// `MAX_HARVEST_CORGIS` corgis of the account.
+    pub fn harvest_fruit(&mut self) -> Fruit {
+        let predecessor = env::predecessor_account_id();
+        let token_ids: Vec<TokenId> =
+            match self.account_corgis.get(&env::sha256(predecessor.as_bytes())) {
+                Some(token_ids) => {
+                    token_ids.iter().take(MAX_HARVEST_CORGIS as usize).collect()
+                }
+                None => vec![],
+            };
+        let now = env::block_timestamp();
+        let mut rng = self.random_rng();
+        let mut harvested = Fruit::default();
+        for token_id in token_ids {
+            let harvested_at = match self.last_harvested.get(&token_id) {
+                Some(harvested_at) => harvested_at,
+                None => {
+                    self.last_harvested.insert(&token_id, &now);
+                    continue;
+                }
+            };
+            let periods = now.saturating_sub(harvested_at) / HARVEST_INTERVAL;
+            if periods == 0 {
+                continue;
+            }
+            let kind = rng.next_u64() % self.fruit_kinds.len();
+            let tier = rarity_tier(self.get_corgi(token_id).rate);
+            harvested.add(kind, std::cmp::min(periods, MAX_HARVEST_PERIODS) * tier);
+            // the part of an interval not harvested yet carries over
+            let harvested_at = if periods > MAX_HARVEST_PERIODS {
+                now
+            } else {
+                harvested_at + periods * HARVEST_INTERVAL
+            };
+            self.last_harvested.insert(&token_id, &harvested_at);
+        }
+        self.mint_fruit(&predecessor, &harvested);
+        harvested
+    }
+
+    /// Feeds the caller's corgi one fruit of kind `fruit_index`, which goes to the
+    /// fruit sink, lengthening its sausage up to `MAX_SAUSAGE` and restoring energy.
+    pub fn feed_corgi(&mut self, token_id: TokenId, fruit_index: u64) -> Corgi {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can feed a corgi");
+        }
+        if fruit_index >= self.fruit_kinds.len() {
+            env::panic(b"Unknown fruit kind");
+        }
+        let mut cost = Fruit::default();
+        cost.add(fruit_index, 1);
+        self.spend_fruit(&predecessor, &cost);
+        let mut corgi = self.get_corgi(token_id);
+        self.update_trait_counts(&corgi, false);
+        self.update_kennel_stats(&predecessor, token_id, false);
+        apply_hunger(&mut corgi, env::block_timestamp());
+        corgi.energy = std::cmp::min(corgi.energy + FEED_ENERGY_GAIN, MAX_ENERGY);
+        corgi.sausage =
+            std::cmp::min(corgi_sausage(&corgi) + FEED_SAUSAGE_GAIN, MAX_SAUSAGE).to_string();
+        self.update_trait_counts(&corgi, true);
+        self.corgis.insert(&token_id, &corgi);
+        self.update_kennel_stats(&predecessor, token_id, true);
+        corgi
+    }
+
+    /// Fruit held by `account_id` per kind name, every registered kind included.
+    pub fn get_fruit_balance(&self, account_id: AccountId) -> Vec<(String, u64)> {
+        let fruit = self.account_fruit(account_id);
+        self.fruit_kinds
+            .iter()
+            .map(|kind| (kind.name, fruit.get(kind.id)))
+            .collect()
+    }
+
+    /// Crafts `count` of an item, paying its fruit cost to the fruit sink.
+    pub fn craft_item(&mut self, item_id: u64, count: u64) -> u64 {
+        let item = self.items.get(item_id).expect("Item not found");
+        let predecessor = env::predecessor_account_id();
+        let mut cost = Fruit::default();
+        for (&kind, &amount) in item.cost.count.iter() {
+            cost.add(kind, amount * count);
+        }
+        self.spend_fruit(&predecessor, &cost);
+        let key = (predecessor, item_id);
+        let balance = self.item_balances.get(&key).unwrap_or(0) + count;
+        self.item_balances.insert(&key, &balance);
+        balance
+    }
+
+    /// Consumes one item on a corgi, taking its breeding cooldown down by the
+    /// item's reduction, up to the item's limit per corgi.
+    pub fn apply_item(&mut self, token_id: TokenId, item_id: u64) {
+        let item = self.items.get(item_id).expect("Item not found");
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can apply items to a corgi");
+        }
+        let now = env::block_timestamp();
+        let bred_at = match self.last_bred_at.get(&token_id) {
+            Some(bred_at) if now < bred_at + BREED_COOLDOWN => bred_at,
+            _ => env::panic(b"Corgi is not in its breeding cooldown"),
+        };
+        let applications = self.item_applications.get(&(token_id, item_id)).unwrap_or(0);
+        if applications >= item.max_per_token {
+            env::panic(b"Item was applied to this corgi too many times");
+        }
+        let key = (predecessor, item_id);
+        let balance = self.item_balances.get(&key).unwrap_or(0);
+        if balance == 0 {
+            env::panic(b"No item left to apply");
+        }
+        self.item_balances.insert(&key, &(balance - 1));
+        self.item_applications.insert(&(token_id, item_id), &(applications + 1));
+        self.last_bred_at.insert(
+            &token_id,
+            &bred_at.saturating_sub(item.breed_cooldown_reduction),
+        );
+    }
+
+    pub fn get_items(&self) -> Vec<Item> {
+        self.items.to_vec()
+    }
+
+    pub fn get_item_balance(&self, account_id: AccountId, item_id: u64) -> u64 {
+        self.item_balances.get(&(account_id, item_id)).unwrap_or(0)
+    }
+
+    pub fn get_item_applications(&self, token_id: TokenId, item_id: u64) -> u32 {
+        self.item_applications.get(&(token_id, item_id)).unwrap_or(0)
+    }
+
+    /// Lifetime minted, burned and transferred amounts of every fruit kind.
+    pub fn get_fruit_stats(&self) -> Vec<FruitStats> {
+        self.fruit_kinds
+            .iter()
+            .map(|kind| {
+                let minted = self.fruit_minted.get(kind.id);
+                let burned = self.fruit_sink_totals.burned.get(kind.id);
+                FruitStats {
+                    kind: kind.id,
+                    name: kind.name,
+                    minted,
+                    burned,
+                    transferred: self.fruit_transferred.get(kind.id),
+                    held: minted.saturating_sub(burned),
+                }
+            })
+            .collect()
+    }
+}
+
+#[near_bindgen]
+impl NEP245 for Corgi3D {
+    #[payable]
+    fn mt_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        amount: U128,
+        memo: Option<String>,
+    ) {
+        self.mt_batch_transfer(receiver_id, vec![token_id], vec![amount], memo)
+    }
+
+    #[payable]
+    fn mt_batch_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<String>,
+        amounts: Vec<U128>,
+        memo: Option<String>,
+    ) {
+        if env::attached_deposit() != 1 {
+            env::panic(b"Requires attached deposit of exactly 1 yoctoNEAR");
+        }
+        assert!(
+            env::is_valid_account_id(receiver_id.as_bytes()),
+            "Receiver's account ID is invalid."
+        );
+        if token_ids.len() != amounts.len() {
+            env::panic(b"token_ids and amounts must have the same length");
+        }
+        let sender_id = env::predecessor_account_id();
+        if sender_id == receiver_id {
+            env::panic(b"Cannot transfer fruit to yourself");
+        }
+        let mut sent = Fruit::default();
+        for (token_id, amount) in token_ids.iter().zip(amounts.iter()) {
+            if amount.0 == 0 || amount.0 > u64::MAX as u128 {
+                env::panic(b"Invalid fruit amount");
+            }
+            sent.add(self.fruit_kind_id(token_id), amount.0 as u64);
+        }
+        let mut sender_fruit = self.account_fruit(sender_id.clone());
+        let mut receiver_fruit = self.account_fruit(receiver_id.clone());
+        sender_fruit.sub_all(&sent);
+        receiver_fruit.add_all(&sent);
+        self.fruit_transferred.add_all(&sent);
+        self.account_fruit.insert(&sender_id, &sender_fruit);
+        self.account_fruit.insert(&receiver_id, &receiver_fruit);
+        emit_standard_event(
+            "nep245",
+            "mt_transfer",
+            json!({
+                "old_owner_id": sender_id,
+                "new_owner_id": receiver_id,
+                "token_ids": token_ids,
+                "amounts": amounts,
+                "memo": memo,
+            }),
+        );
+    }
+
+    fn mt_balance_of(&self, account_id: AccountId, token_id: String) -> U128 {
+        U128(self.account_fruit(account_id).get(self.fruit_kind_id(&token_id)) as u128)
+    }
+
+    fn mt_batch_balance_of(&self, account_id: AccountId, token_ids: Vec<String>) -> Vec<U128> {
+        let fruit = self.account_fruit(account_id);
+        token_ids
+            .iter()
+            .map(|token_id| U128(fruit.get(self.fruit_kind_id(token_id)) as u128))
+            .collect()
+    }
+}
+
+// Helper methods
+impl Corgi3D {
+    /// Reveals the hidden traits whose conditions `corgi` now meets, emitting an
+    /// event for each. The caller saves the corgi.
+    fn record_battle(&mut self, corgi: &mut Corgi, won: bool) {
+        corgi.battles_fought += 1;
+        if won {
+            corgi.battles_won += 1;
+        }
+        self.unlock_hidden_traits(corgi);
+        self.corgis.insert(&corgi.id, corgi);
+    }
+
+    fn record_duel(&mut self, token_id: TokenId, record: &DuelRecord) {
+        let mut history = self.duel_history.get(&token_id).unwrap_or_default();
+        history.push(record.clone());
+        if history.len() > MAX_DUEL_HISTORY {
+            history.remove(0);
+        }
+        self.duel_history.insert(&token_id, &history);
+    }
+
+    pub(crate) fn unlock_hidden_traits(&mut self, corgi: &mut Corgi) {
+        let conditions = [
+            corgi_level(corgi) >= AURA_UNLOCK_LEVEL,
+            corgi.times_bred > 0,
+            corgi.battles_fought >= SCAR_UNLOCK_BATTLES,
+        ];
+        for (slot, &met) in conditions.iter().enumerate() {
+            let bit = 1u8 << slot;
+            if met && corgi.unlocked_traits & bit == 0 {
+                corgi.unlocked_traits |= bit;
+                emit_event(
+                    "corgi_trait_unlocked",
+                    json!({
+                        "token_id": corgi.id.to_string(),
+                        "trait": HIDDEN_TRAITS[slot].0,
+                        "value": hidden_trait_value(corgi.id, slot),
+                    }),
+                );
+            }
+        }
+    }
+
+    /// Burns the fruit required by any premium colors among `colors`.
+    pub(crate) fn charge_premium_colors(&mut self, account: &AccountId, colors: &[&String]) {
+        let mut cost = Fruit::default();
+        for color in colors {
+            if let Some(color_cost) = self.premium_colors.get(&color.trim().to_lowercase()) {
+                cost.add_all(&color_cost);
+            }
+        }
+        if !cost.is_empty() {
+            self.spend_fruit(account, &cost);
+        }
+    }
+
+    /// Maps a NEP-245 token ID (fruit name) to its fruit kind ID.
+    pub(crate) fn fruit_kind_id(&self, token_id: &str) -> u64 {
+        match self.fruit_kind_ids.get(&token_id.to_string()) {
+            Some(kind) => kind,
+            None => env::panic(b"Unknown fruit token ID"),
+        }
+    }
+
+    pub(crate) fn register_fruit_kind(&mut self, name: String, effect: String) -> u64 {
+        if name.is_empty()
+            || name.len() > MAX_FRUIT_NAME_LENGTH
+            || !name.chars().all(|c| c.is_ascii_lowercase() || c == '-')
+        {
+            env::panic(b"Fruit names must be lowercase letters and dashes");
+        }
+        if self.fruit_kind_ids.get(&name).is_some() {
+            env::panic(b"Fruit kind already exists");
+        }
+        let id = self.fruit_kinds.len();
+        self.fruit_kind_ids.insert(&name, &id);
+        self.fruit_kinds.push(&FruitKind { id, name, effect });
+        id
+    }
+
+    /// Creates new fruit for `account`, counted in the fruit stats.
+    pub(crate) fn mint_fruit(&mut self, account: &AccountId, fruit: &Fruit) {
+        let mut account_fruit = self.account_fruit(account.clone());
+        account_fruit.add_all(fruit);
+        self.account_fruit.insert(account, &account_fruit);
+        self.fruit_minted.add_all(fruit);
+    }
+
+    /// `reward_per_weight` and the reward pool once every epoch elapsed since the
+    /// last release has been released. Epochs without stakers release nothing.
+    pub(crate) fn staking_rewards_now(&self) -> (u128, Balance) {
+        let epoch = env::block_timestamp() / STAKING_EPOCH;
+        let mut pool = self.staking_reward_pool;
+        if self.total_stake_weight == 0 || epoch <= self.staking_epoch {
+            return (self.reward_per_weight, pool);
+        }
+        let epochs = std::cmp::min(epoch - self.staking_epoch, MAX_STAKING_EPOCHS_PER_UPDATE);
+        let mut released = 0;
+        for _ in 0..epochs {
+            let release = pool * STAKING_RELEASE_BPS / 10_000;
+            pool -= release;
+            released += release;
+        }
+        let weight = self.total_stake_weight as u128;
+        let increase = released * STAKING_REWARD_PRECISION / weight;
+        // rounding dust stays in the pool
+        pool += released - increase * weight / STAKING_REWARD_PRECISION;
+        (self.reward_per_weight + increase, pool)
+    }
+
+    fn release_staking_rewards(&mut self) {
+        let (reward_per_weight, pool) = self.staking_rewards_now();
+        self.reward_per_weight = reward_per_weight;
+        self.staking_reward_pool = pool;
+        self.staking_epoch = env::block_timestamp() / STAKING_EPOCH;
+    }
+
+    /// Charges a fruit fee from `account` and routes it to the configured sink.
+    fn spend_fruit(&mut self, account: &AccountId, cost: &Fruit) {
+        let mut fruit = self.account_fruit(account.clone());
+        fruit.sub_all(cost);
+        self.account_fruit.insert(account, &fruit);
+
+        let totals = match self.fruit_sink {
+            FruitSink::Burn => &mut self.fruit_sink_totals.burned,
+            FruitSink::Treasury => &mut self.fruit_sink_totals.treasury,
+            FruitSink::Stakers => &mut self.fruit_sink_totals.stakers,
+        };
+        totals.add_all(cost);
+        match self.fruit_sink {
+            FruitSink::Burn => {}
+            FruitSink::Treasury => {
+                let treasury = env::current_account_id();
+                let mut treasury_fruit = self.account_fruit(treasury.clone());
+                treasury_fruit.add_all(cost);
+                self.account_fruit.insert(&treasury, &treasury_fruit);
+            }
+            FruitSink::Stakers => self.stakers_fruit_pool.add_all(cost),
+        }
+    }
+
+    /// Adds to the account's score while a season is running.
+    fn add_season_points(&mut self, account: &AccountId, points: u64) {
+        let season_id = match self.current_season {
+            Some(season_id) => season_id,
+            None => return,
+        };
+        if points == 0 || env::block_timestamp() >= self.seasons.get(&season_id).unwrap().end_at {
+            return;
+        }
+        let score = self.season_scores.get(account).unwrap_or(0) + points;
+        self.season_scores.insert(account, &score);
+        // scores only grow, so an account only ever moves up
+        self.season_leaders.retain(|(leader, _)| leader != account);
+        let position = self
+            .season_leaders
+            .iter()
+            .position(|(_, leader_score)| *leader_score < score)
+            .unwrap_or(self.season_leaders.len());
+        if position < MAX_LEADERBOARD_SIZE {
+            self.season_leaders.insert(position, (account.clone(), score));
+            self.season_leaders.truncate(MAX_LEADERBOARD_SIZE);
+        }
+    }
+
+    pub(crate) fn leave_house(&mut self, token_id: TokenId) {
+        if let Some(house_id) = self.corgi_house.remove(&token_id) {
+            let mut house = self.houses.get(&house_id).unwrap();
+            house.residents.retain(|&resident| resident != token_id);
+            self.houses.insert(&house_id, &house);
+        }
+    }
+
+    pub(crate) fn record_activity(&mut self, account: &AccountId) {
+        self.last_active.insert(account, &env::block_timestamp());
+    }
+
+    /// Disbands the team `token_id` belongs to, if any.
+    pub(crate) fn dissolve_team_of(&mut self, token_id: TokenId) {
+        if let Some(team) = self
+            .corgi_team
+            .get(&token_id)
+            .and_then(|team_id| self.teams.get(&team_id))
+        {
+            self.remove_team(team);
+        }
+    }
+
+    fn remove_team(&mut self, team: Team) {
+        for token_id in team.members.iter() {
+            self.corgi_team.remove(token_id);
+        }
+        self.teams.remove(&team.id);
+    }
+
+    fn assert_free_for_team(&self, token_id: TokenId) {
+        if self.corgi_team.get(&token_id).is_some() {
+            env::panic(b"Corgi is already in a team");
+        }
+        if self.selling_corgis.contains(&token_id) || self.auctions.get(&token_id).is_some() {
+            env::panic(b"Corgi is listed for sale");
+        }
+        if self.active_rental(token_id).is_some() {
+            env::panic(b"Corgi is rented out");
+        }
+        if self.trainings.get(&token_id).is_some() {
+            env::panic(b"Corgi is in training");
+        }
+    }
+
+    /// Summed battle power of a team's members, `None` once the team is dissolved.
+    fn team_power(&self, team_id: u64) -> Option<u64> {
+        let team = self.teams.get(&team_id)?;
+        let now = env::block_timestamp();
+        Some(
+            team.members
+                .iter()
+                .map(|token_id| {
+                    let mut corgi = self.corgis.get(token_id).unwrap();
+                    apply_hunger(&mut corgi, now);
+                    rarity_tier(corgi.rate) * 20
+                        + corgi.skills.iter().sum::<u64>()
+                        + corgi.energy / 10
+                        + self.corgi_house.get(token_id).map_or(0, |_| HOUSE_POWER_BONUS)
+                        + corgi.bonded_with.map_or(0, |_| BOND_POWER_BONUS)
+                })
+                .sum(),
+        )
+    }
+
+    fn kennel_of(&self, account: &AccountId) -> Option<Kennel> {
+        self.account_kennel
+            .get(account)
+            .and_then(|kennel_id| self.kennels.get(&kennel_id))
+    }
+
+    fn add_kennel_member(&mut self, kennel_id: u64, member: AccountId) {
+        if self.account_kennel.get(&member).is_some() {
+            env::panic(b"Account already belongs to a kennel");
+        }
+        let mut kennel = self.kennels.get(&kennel_id).unwrap();
+        kennel.members += 1;
+        let account_hash = env::sha256(member.as_bytes());
+        if let Some(corgi_ids) = self.account_corgis.get(&account_hash) {
+            for id in corgi_ids.iter() {
+                kennel.corgi_count += 1;
+                kennel.total_sausage += corgi_sausage(&self.corgis.get(&id).unwrap());
+            }
+        }
+        self.kennels.insert(&kennel_id, &kennel);
+        self.account_kennel.insert(&member, &kennel_id);
+    }
+
+    /// Removes the member with their corgis' stats; closes the kennel when empty.
+    fn remove_kennel_member(&mut self, member: &AccountId) {
+        let kennel_id = self.account_kennel.remove(member).unwrap();
+        let mut kennel = self.kennels.get(&kennel_id).unwrap();
+        kennel.members -= 1;
+        if kennel.members == 0 {
+            self.kennels.remove(&kennel_id);
+            return;
+        }
+        let account_hash = env::sha256(member.as_bytes());
+        if let Some(corgi_ids) = self.account_corgis.get(&account_hash) {
+            for id in corgi_ids.iter() {
+                kennel.corgi_count -= 1;
+                kennel.total_sausage -= corgi_sausage(&self.corgis.get(&id).unwrap());
+                kennel.showcase.retain(|&showcased| showcased != id);
+            }
+        }
+        self.kennels.insert(&kennel_id, &kennel);
+    }
+
+    /// Reference value of a kennel treasury: each corgi at its tier's weekly
+    /// TWAP, or the mint price when the tier has no sales yet.
+    fn kennel_treasury_value(&self, kennel: &Kennel) -> Balance {
+        kennel
+            .treasury_corgis
+            .iter()
+            .map(|id| {
+                let rarity = self.get_corgi(*id).rate;
+                self.get_twap(rarity, LOAN_TWAP_WINDOW_SEC)
+                    .map_or(self.mint_price, |twap| twap.0)
+            })
+            .sum()
+    }
+
+    /// Splits sale proceeds between shareholders' escrow balances; rounding dust
+    /// goes to the treasury.
+    pub(crate) fn distribute_kennel_proceeds(&mut self, kennel_id: u64, proceeds: Balance) {
+        let kennel = self.kennels.get(&kennel_id).unwrap();
+        let mut paid = 0;
+        for (holder, shares) in kennel.shareholders.iter() {
+            let amount = proceeds * *shares as u128 / kennel.total_shares as u128;
+            self.credit_deposit(holder, amount);
+            paid += amount;
+        }
+        self.treasury_balance += proceeds - paid;
+    }
+
+    /// Keeps kennel aggregates in sync whenever a corgi enters or leaves an
+    /// account, or its sausage changes, taking it out and back in around the change.
+    pub(crate) fn update_kennel_stats(&mut self, account: &AccountId, id: TokenId, added: bool) {
+        if let Some(mut kennel) = self.kennel_of(account) {
+            let sausage = corgi_sausage(&self.corgis.get(&id).unwrap());
+            if added {
+                kennel.corgi_count += 1;
+                kennel.total_sausage += sausage;
+            } else {
+                kennel.corgi_count -= 1;
+                kennel.total_sausage -= sausage;
+            }
+            self.kennels.insert(&kennel.id, &kennel);
+        }
+    }
+
+    /// Takes a corgi leaving `account` out of its kennel's showcase.
+    pub(crate) fn leave_showcase(&mut self, account: &AccountId, id: TokenId) {
+        if let Some(mut kennel) = self.kennel_of(account) {
+            if kennel.showcase.contains(&id) {
+                kennel.showcase.retain(|&showcased| showcased != id);
+                self.kennels.insert(&kennel.id, &kennel);
+            }
+        }
+    }
+
+    /// Clears the bond of `corgi` and its companion.
+    pub(crate) fn break_bond(&mut self, mut corgi: Corgi, now: u64) {
+        if let Some(companion_id) = corgi.bonded_with.take() {
+            corgi.bond_changed_at = now;
+            self.corgis.insert(&corgi.id, &corgi);
+            self.refresh_stake_weight(corgi.id);
+            if let Some(mut companion) = self.corgis.get(&companion_id) {
+                companion.bonded_with = None;
+                companion.bond_changed_at = now;
+                self.corgis.insert(&companion_id, &companion);
+                self.refresh_stake_weight(companion_id);
+            }
+        }
+    }
+
+    /// Re-weighs the stake of `token_id`, if it is staked, after its bond
+    /// changed. What it earned at the old weight is settled first.
+    fn refresh_stake_weight(&mut self, token_id: TokenId) {
+        let (mut stake, corgi) = match (self.stakes.get(&token_id), self.corgis.get(&token_id)) {
+            (Some(stake), Some(corgi)) => (stake, corgi),
+            _ => return,
+        };
+        let weight = stake_weight(&corgi);
+        if weight == stake.weight {
+            return;
+        }
+        self.release_staking_rewards();
+        let reward = self.staking_rewards.get(&stake.owner).unwrap_or(0)
+            + pending_stake_reward(&stake, self.reward_per_weight);
+        self.staking_rewards.insert(&stake.owner, &reward);
+        self.total_stake_weight = self.total_stake_weight - stake.weight + weight;
+        stake.weight = weight;
+        stake.reward_debt = weight as u128 * self.reward_per_weight / STAKING_REWARD_PRECISION;
+        self.stakes.insert(&token_id, &stake);
+    }
+}
+
+/// Staking weight of `corgi`: its rarity tier, plus a bonus while it is bonded.
+fn stake_weight(corgi: &Corgi) -> u64 {
+    rarity_tier(corgi.rate) + corgi.bonded_with.map_or(0, |_| BOND_STAKE_WEIGHT_BONUS)
+}
+
+/// What `stake` earned beyond its debt at `reward_per_weight`.
+fn pending_stake_reward(stake: &Stake, reward_per_weight: u128) -> Balance {
+    (stake.weight as u128 * reward_per_weight / STAKING_REWARD_PRECISION)
+        .saturating_sub(stake.reward_debt)
+}
+
+/// Drains energy for every full hunger interval elapsed since the last update.
+/// Returns whether the corgi changed.
+fn apply_hunger(corgi: &mut Corgi, now: u64) -> bool {
+    let periods = now.saturating_sub(corgi.energy_updated_at) / HUNGER_INTERVAL;
+    if periods == 0 {
+        return false;
+    }
+    let was_starving = corgi.energy == 0;
+    corgi.energy = corgi
+        .energy
+        .saturating_sub(periods.saturating_mul(HUNGER_ENERGY_LOSS));
+    corgi.energy_updated_at += periods * HUNGER_INTERVAL;
+    if corgi.energy == 0 && !was_starving {
+        env::log(format!("Corgi {} is starving", corgi.id).as_bytes());
+    }
+    true
+}
+
+fn kennel_shares_of(kennel: &Kennel, account: &AccountId) -> u64 {
+    kennel
+        .shareholders
+        .iter()
+        .find(|(holder, _)| holder == account)
+        .map_or(0, |(_, shares)| *shares)
+}
+
+fn corgi_sausage(corgi: &Corgi) -> u64 {
+    corgi.sausage.parse().unwrap_or(0)
+}
+
+/// PvP score: up to 100 for rarity, 50 for size, plus the random `roll`.
+fn duel_score(corgi: &Corgi, roll: u32) -> u64 {
+    rarity_tier(corgi.rate) * 20
+        + std::cmp::min(corgi_sausage(corgi) * 50 / MAX_SAUSAGE, 50)
+        + roll as u64
+}
+
+fn assert_bond_cooldown(corgi: &Corgi, now: u64) {
+    if corgi.bond_changed_at != 0 && now < corgi.bond_changed_at + BOND_COOLDOWN {
+        env::panic(b"Bond cooldown has not passed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn test_maze_game() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+
+        let game = contract.new_maze_game();
+        let mut count = Fruit::default();
+        for f in HashSet::<_>::from_iter(game.fruit.clone()) {
+            count.add(f.kind, 1);
+        }
+        contract.finish_maze_game(game.fruit);
+        let account_fruit = contract.account_fruit(robert());
+        assert_eq!(account_fruit.count, count.count);
+    }
+
+    #[test]
+    fn harvest_and_feed() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert!(contract.harvest_fruit().is_empty());
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 30 * HARVEST_INTERVAL;
+        testing_env!(context);
+        let harvested = contract.harvest_fruit();
+        let tier = rarity_tier(contract.get_corgi(token_id).rate);
+        let (&kind, &amount) = harvested.count.iter().next().unwrap();
+        assert_eq!(amount, MAX_HARVEST_PERIODS * tier);
+        assert!(contract.harvest_fruit().is_empty());
+        let balance = contract.get_fruit_balance(robert());
+        assert_eq!(balance.len(), DEFAULT_FRUIT_NAMES.len());
+        assert_eq!(balance[kind as usize].1, amount);
+
+        let kennel_id = contract.create_kennel("pups".to_string());
+        contract.add_to_showcase(token_id);
+        let sausage = corgi_sausage(&contract.get_corgi(token_id));
+        let corgi = contract.feed_corgi(token_id, kind);
+        assert_eq!(
+            corgi_sausage(&corgi),
+            std::cmp::min(sausage + FEED_SAUSAGE_GAIN, MAX_SAUSAGE)
+        );
+        assert_eq!(contract.account_fruit(robert()).get(kind), amount - 1);
+        let kennel = contract.get_kennel(kennel_id).unwrap();
+        assert_eq!(kennel.total_sausage, corgi_sausage(&corgi));
+        assert_eq!(kennel.showcase, vec![token_id]);
+    }
+
+    #[test]
+    fn tick_applies_hunger() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 3 * HUNGER_INTERVAL;
+        testing_env!(context);
+        assert_eq!(contract.tick(0, 1), 1);
+        assert_eq!(contract.get_corgi(0).energy, MAX_ENERGY - 3 * HUNGER_ENERGY_LOSS);
+        assert_eq!(contract.get_corgi(1).energy, MAX_ENERGY);
+        assert_eq!(contract.tick(contract.get_tick_cursor(), 10), 0);
+        assert_eq!(contract.get_corgi(1).energy, MAX_ENERGY - 3 * HUNGER_ENERGY_LOSS);
+    }
+
+    #[test]
+    fn tick_settles_ended_auctions_and_rentals() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        contract.start_auction(0, 1, BidIncrement::Bps(1_000), None, None);
+        contract.rent_out(1, mike(), 1);
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.block_timestamp = 60 * 60 * 1_000_000_000;
+        testing_env!(context);
+        contract.tick(0, 10);
+        assert!(contract.get_auction(0).is_none());
+        assert_eq!(contract.get_token_owner(0), robert());
+        assert!(contract.rentals.get(&1).is_none());
+    }
+
+    #[test]
+    fn tick_pays_keeper_bounty() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        create_corgis(&mut contract, MIN_KEEPER_BATCH as usize);
+        let treasury = contract.get_treasury_balance().0;
+
+        // nothing to do yet, so no bounty
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.tick(0, 10);
+        assert_eq!(contract.get_treasury_balance().0, treasury);
+
+        // too small a batch
+        let mut context = get_context(mike(), env::storage_usage());
+        context.block_timestamp = HUNGER_INTERVAL;
+        testing_env!(context);
+        contract.tick(0, MIN_KEEPER_BATCH - 1);
+        assert_eq!(contract.get_treasury_balance().0, treasury);
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.block_timestamp = 2 * HUNGER_INTERVAL;
+        testing_env!(context);
+        contract.tick(0, 10);
+        let treasury = treasury - MIN_KEEPER_BATCH as u128 * DEFAULT_KEEPER_BOUNTY;
+        assert_eq!(contract.get_treasury_balance().0, treasury);
+
+        // capped per period
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.set_keeper_bounty(U128(MAX_KEEPER_PAYOUT_PER_PERIOD));
+        let mut context = get_context(mike(), env::storage_usage());
+        context.block_timestamp = 3 * HUNGER_INTERVAL;
+        testing_env!(context);
+        contract.tick(0, 10);
+        assert_eq!(
+            contract.get_treasury_balance().0,
+            treasury - MAX_KEEPER_PAYOUT_PER_PERIOD
+        );
+    }
+
+    #[test]
+    fn premium_color_burns_fruit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_premium_color("#FFD700".to_string(), vec![0, 0, 1, 0, 2, 0, 0]);
+        let mut fruit = Fruit::default();
+        fruit.add(BANANA, 1);
+        fruit.add(LEMON, 3);
+        contract.account_fruit.insert(&robert(), &fruit);
+
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "#ffd700".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        let fruit = contract.account_fruit(robert());
+        assert_eq!(fruit.get(BANANA), 0);
+        assert_eq!(fruit.get(LEMON), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not enough fruit"#)]
+    fn premium_color_without_fruit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_premium_color("gold".to_string(), vec![1, 0, 0, 0, 0, 0, 0]);
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "gold".to_string(),
+            "haha".to_string(),
+        );
+    }
+
+    #[test]
+    fn fruit_fee_routed_to_treasury() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_premium_color("gold".to_string(), vec![2, 0, 0, 0, 0, 0, 0]);
+        contract.set_fruit_sink(FruitSink::Treasury);
+        let mut fruit = Fruit::default();
+        fruit.add(APPLE, 2);
+        contract.account_fruit.insert(&robert(), &fruit);
+
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "gold".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        assert_eq!(contract.get_fruit_sink_totals().treasury.get(APPLE), 2);
+        assert_eq!(contract.get_fruit_sink_totals().burned.get(APPLE), 0);
+        assert_eq!(contract.account_fruit(env::current_account_id()).get(APPLE), 2);
+    }
+
+    #[test]
+    fn challenge_trusted_arena() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_arena(joe());
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.deposit();
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.challenge_arena(token_id, joe(), "dragon-7".to_string());
+        let battle = contract.get_battle(0).unwrap();
+        assert_eq!(battle.challenger, robert());
+        assert_eq!(battle.wager.0, 3 * 10u128.pow(24));
+        assert_eq!(contract.get_deposit(joe()).0, 0);
+    }
+
+    #[test]
+    fn accepted_challenge_is_fought() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let robert_token = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let challenge = contract.challenge(mike(), robert_token);
+        assert_eq!(challenge.wager.0, 3 * 10u128.pow(24));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let mike_token = mint_revealed(&mut contract,
+            "b".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert_eq!(contract.get_open_challenges(mike()).len(), 1);
+        let record = contract.accept_challenge(challenge.id, mike_token);
+        assert!(record.winner_token_id == robert_token || record.winner_token_id == mike_token);
+        assert_eq!(record.pot.0, 6 * 10u128.pow(24));
+        assert!(contract.get_open_challenges(mike()).is_empty());
+        for &token_id in &[robert_token, mike_token] {
+            let corgi = contract.get_corgi(token_id);
+            assert_eq!(corgi.battles_fought, 1);
+            assert_eq!(corgi.battles_won, (record.winner_token_id == token_id) as u64);
+            assert_eq!(contract.get_battle_history(token_id).len(), 1);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attached deposit must match the wager"#)]
+    fn accept_challenge_requires_matching_wager() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let robert_token = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let challenge = contract.challenge(mike(), robert_token);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let mike_token = mint_revealed(&mut contract,
+            "b".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.accept_challenge(challenge.id, mike_token);
+    }
+
+    #[test]
+    fn staking_rewards_released_per_epoch() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.stake_corgi(token_id);
+        contract.fund_staking_rewards(U128(10u128.pow(24)));
+        assert_eq!(contract.get_staking_rewards(robert()), U128(0));
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = STAKING_EPOCH;
+        testing_env!(context);
+        let reward = contract.get_staking_rewards(robert()).0;
+        assert!(10u128.pow(23) - reward < 10);
+        contract.unstake_corgi(token_id);
+        assert!(contract.get_staked_corgis(robert()).is_empty());
+        assert_eq!(contract.get_staking_rewards(robert()).0, reward);
+        contract.claim_rewards();
+        assert_eq!(contract.get_staking_rewards(robert()), U128(0));
+        assert_eq!(contract.get_staking_reward_pool().0, 10u128.pow(24) - reward);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi is staked"#)]
+    fn staked_corgi_cannot_be_listed() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.stake_corgi(token_id);
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Arena is not trusted"#)]
+    fn challenge_untrusted_arena() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.challenge_arena(token_id, joe(), "dragon-7".to_string());
+    }
+
+    #[test]
+    fn season_close_escrows_prizes() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        contract.start_season(U64(100), U128(10u128.pow(24)));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let game = contract.new_maze_game();
+        let eaten = game.fruit.len() as u64;
+        contract.finish_maze_game(game.fruit);
+        assert_eq!(contract.get_season_leaderboard(0, 3), vec![(mike(), eaten)]);
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.block_timestamp = 100;
+        testing_env!(context);
+        let season = contract.close_season();
+        assert_eq!(season.winners, vec![(mike(), U128(5 * 10u128.pow(23)))]);
+        assert_eq!(contract.get_season_prize(mike()).0, 5 * 10u128.pow(23));
+        assert_eq!(contract.get_treasury_balance().0, 5 * 10u128.pow(23));
+        assert!(contract.get_season_leaderboard(0, 3).is_empty());
+        assert_eq!(contract.get_current_season(), None);
+        assert_eq!(contract.clear_season_scores(10), 0);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 100;
+        testing_env!(context);
+        contract.start_season(U64(200), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Scores of the previous season are not cleared yet"#)]
+    fn season_starts_after_scores_are_cleared() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.start_season(U64(100), U128(0));
+        let game = contract.new_maze_game();
+        contract.finish_maze_game(game.fruit);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 100;
+        testing_env!(context);
+        contract.close_season();
+        contract.start_season(U64(200), U128(0));
+    }
+
+    #[test]
+    fn build_house_and_move_in() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.account_fruit.insert(
+            &robert(),
+            &Fruit::of_each(DEFAULT_FRUIT_NAMES.len() as u64, HOUSE_FRUIT_PER_KIND),
+        );
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = HOUSE_PRICE;
+        testing_env!(context);
+        let house_id = contract.build_house();
+        assert!(contract.account_fruit(robert()).is_empty());
+
+        contract.move_into_house(token_id, house_id);
+        assert_eq!(contract.get_corgi_house(token_id), Some(house_id));
+        assert_eq!(contract.get_house(house_id).unwrap().residents, vec![token_id]);
+
+        contract.transfer(mike(), token_id, None);
+        assert_eq!(contract.get_corgi_house(token_id), None);
+        assert!(contract.get_house(house_id).unwrap().residents.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not enough fruit"#)]
+    fn build_house_without_fruit() {
+        let mut context = get_context(robert(), 0);
+        context.attached_deposit = HOUSE_PRICE;
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.build_house();
+    }
+
+    #[test]
+    fn world_state_boosts_maze_fruit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_world_oracle(Some(joe()));
+
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.set_world_state(Some(WorldState {
+            name: "Lemon Season".to_string(),
+            start_at: U64(0),
+            end_at: U64(100),
+            rarity_boost: 0,
+            fruit_bps: 100_000,
+            battle_power_bonus: 0,
+        }));
+        assert_eq!(contract.get_world_state().unwrap().name, "Lemon Season");
+        // ten times the usual 10..20 fruit, deduplicated over a 10x10 maze
+        assert!(contract.new_maze_game().fruit.len() > 20);
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.block_timestamp = 100;
+        testing_env!(context);
+        assert!(contract.get_world_state().is_none());
+    }
+
+    #[test]
+    fn mt_transfer_fruit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mut fruit = Fruit::default();
+        fruit.add(LIME, 5);
+        fruit.add(ORANGE, 1);
+        contract.account_fruit.insert(&robert(), &fruit);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.mt_batch_transfer(
+            mike(),
+            vec!["lime".to_string(), "orange".to_string()],
+            vec![U128(2), U128(1)],
+            None,
+        );
+        assert_eq!(contract.mt_balance_of(robert(), "lime".to_string()), U128(3));
+        assert_eq!(
+            contract.mt_batch_balance_of(mike(), vec!["lime".to_string(), "orange".to_string()]),
+            vec![U128(2), U128(1)]
+        );
+    }
+
+    #[test]
+    fn bond_and_break_on_transfer() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        contract.bond_corgis(0, 1);
+        assert_eq!(contract.get_corgi(0).bonded_with, Some(1));
+        assert_eq!(contract.get_corgi(1).bonded_with, Some(0));
+
+        contract.transfer(joe(), 1, None);
+        assert_eq!(contract.get_corgi(0).bonded_with, None);
+        assert_eq!(contract.get_corgi(1).bonded_with, None);
+    }
+
+    #[test]
+    fn bond_raises_stake_weight() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        contract.stake_corgi(0);
+        let tier = rarity_tier(contract.get_corgi(0).rate);
+        assert_eq!(contract.total_stake_weight, tier);
+
+        contract.bond_corgis(0, 1);
+        assert_eq!(contract.stakes.get(&0).unwrap().weight, tier + BOND_STAKE_WEIGHT_BONUS);
+        assert_eq!(contract.total_stake_weight, tier + BOND_STAKE_WEIGHT_BONUS);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = BOND_COOLDOWN;
+        testing_env!(context);
+        contract.unbond_corgi(1);
+        assert_eq!(contract.total_stake_weight, tier);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Bond cooldown has not passed"#)]
+    fn unbond_within_cooldown() {
+        let mut context = get_context(robert(), 0);
+        context.block_timestamp = 1;
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        contract.bond_corgis(0, 1);
+        contract.unbond_corgi(0);
+    }
+
+    #[test]
+    fn profile_with_avatar() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.set_profile("Rob".to_string(), Some(token_id));
+        assert_eq!(
+            contract.get_profile(robert()),
+            Some(Profile {
+                nickname: "Rob".to_string(),
+                avatar_token_id: Some(token_id),
+            })
+        );
+        assert_eq!(contract.get_account_by_nickname("rob".to_string()), Some(robert()));
+
+        contract.transfer(joe(), token_id, None);
+        assert_eq!(contract.get_profile(robert()).unwrap().avatar_token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Nickname is already taken"#)]
+    fn profile_nickname_unique() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_profile("corgifan".to_string(), None);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.set_profile("CorgiFan".to_string(), None);
+    }
+
+    #[test]
+    fn kennel_stats_follow_transfers() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let sausage = corgi_sausage(&contract.get_corgi(token_id));
+        let kennel_id = contract.create_kennel("pups".to_string());
+        contract.add_to_showcase(token_id);
+        let kennel = contract.get_kennel(kennel_id).unwrap();
+        assert_eq!(kennel.corgi_count, 1);
+        assert_eq!(kennel.total_sausage, sausage);
+        assert_eq!(kennel.showcase, vec![token_id]);
+
+        contract.transfer(joe(), token_id, None);
+        let kennel = contract.get_kennel(kennel_id).unwrap();
+        assert_eq!(kennel.corgi_count, 0);
+        assert_eq!(kennel.showcase.len(), 0);
+
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.join_kennel(kennel_id);
+        let kennel = contract.get_kennel(kennel_id).unwrap();
+        assert_eq!(kennel.members, 2);
+        assert_eq!(kennel.corgi_count, 1);
+        assert_eq!(contract.get_kennel_leaderboard(10)[0].id, kennel_id);
+    }
+
+    #[test]
+    fn kennel_treasury_sale_splits_proceeds() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let kennel_id = contract.create_kennel("pups".to_string());
+        let robert_shares = contract.contribute_to_kennel(token_id);
+
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.join_kennel(kennel_id);
+        let joe_token = mint_revealed(&mut contract,
+            "b".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let joe_shares = contract.contribute_to_kennel(joe_token);
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        let price = 10u128.pow(24);
+        contract.list_kennel_corgi(token_id, U128(price));
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = price;
+        testing_env!(context);
+        contract.buy_corgi(token_id, None);
+
+        // the creator royalty comes off the top
+        let proceeds = price - price * DEFAULT_ROYALTY_BPS as u128 / 10_000;
+        let total = (robert_shares + joe_shares) as u128;
+        assert_eq!(
+            contract.get_deposit(robert()).0,
+            proceeds * robert_shares as u128 / total
+        );
+        assert_eq!(contract.get_deposit(joe()).0, proceeds * joe_shares as u128 / total);
+        assert_eq!(
+            contract.get_kennel(kennel_id).unwrap().treasury_corgis,
+            vec![joe_token]
+        );
+    }
+
+    #[test]
+    fn add_fruit_kind_for_event() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let kind = contract.add_fruit_kind("pumpkin".to_string(), "energy:+20".to_string());
+        assert_eq!(kind, DEFAULT_FRUIT_NAMES.len() as u64);
+        assert_eq!(contract.get_fruit_kinds()[kind as usize].name, "pumpkin");
+
+        let mut fruit = Fruit::default();
+        fruit.add(kind, 4);
+        contract.account_fruit.insert(&robert(), &fruit);
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.mt_transfer(mike(), "pumpkin".to_string(), U128(3), None);
+        assert_eq!(contract.mt_balance_of(mike(), "pumpkin".to_string()), U128(3));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Fruit kind already exists"#)]
+    fn add_duplicate_fruit_kind() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_fruit_kind("apple".to_string(), "".to_string());
+    }
+
+    #[test]
+    fn hidden_traits_unlock_with_progress() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert!(contract.get_hidden_traits(token_id).iter().all(|t| t.value.is_none()));
+
+        let mut corgi = contract.get_corgi(token_id);
+        corgi.battles_fought = SCAR_UNLOCK_BATTLES;
+        corgi.battles_won = (AURA_UNLOCK_LEVEL - 1) * WINS_PER_LEVEL;
+        contract.unlock_hidden_traits(&mut corgi);
+        contract.corgis.insert(&token_id, &corgi);
+        assert_eq!(contract.get_corgi_level(token_id), Some(AURA_UNLOCK_LEVEL));
+        let traits = contract.get_hidden_traits(token_id);
+        assert!(traits[0].value.is_some());
+        assert!(traits[1].value.is_none());
+        assert!(traits[2].value.is_some());
+    }
+
+    #[test]
+    fn training_raises_skill() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.start_training(token_id, Skill::Agility);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = TRAINING_DURATION / 2;
+        testing_env!(context);
+        contract.cancel_training(token_id);
+        assert_eq!(contract.get_corgi(token_id).skill_progress[Skill::Agility as usize], 2_500);
+
+        let training = contract.start_training(token_id, Skill::Agility);
+        assert_eq!(training.ends_at.0, TRAINING_DURATION / 2 + TRAINING_DURATION * 3 / 4);
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = training.ends_at.0;
+        testing_env!(context);
+        assert_eq!(contract.complete_training(token_id), 1);
+        assert!(contract.get_training(token_id).is_none());
+        assert_eq!(contract.get_corgi(token_id).skill_progress[Skill::Agility as usize], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Training is not finished yet"#)]
+    fn complete_training_early() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.start_training(token_id, Skill::Stamina);
+        contract.complete_training(token_id);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Partner token already claimed in this campaign"#)]
+    fn campaign_claim_once_per_partner_token() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let campaign_id = contract.add_campaign("partner.testnet".to_string(), "party hat".to_string(), U64(1));
+        assert_eq!(contract.get_campaigns()[0].1.accessory, "party hat");
+        contract.claim_campaign_accessory(campaign_id, token_id, "7".to_string());
+        contract.claim_campaign_accessory(campaign_id, token_id, "7".to_string());
+    }
+
+    fn create_corgis(contract: &mut Corgi3D, count: usize) -> Vec<TokenId> {
+        (0..count)
+            .map(|_| {
+                mint_revealed(contract,
+                    "a".to_string(),
+                    "blue".to_string(),
+                    "green".to_string(),
+                    "haha".to_string(),
+                ).token_id.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn transfer_dissolves_team() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_ids = create_corgis(&mut contract, 2);
+        let team_id = contract.form_team(token_ids.clone(), "pack".to_string());
+        assert_eq!(contract.get_corgi_team(token_ids[1]), Some(team_id));
+
+        contract.transfer(mike(), token_ids[0], None);
+        assert!(contract.get_team(team_id).is_none());
+        assert_eq!(contract.get_corgi_team(token_ids[1]), None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the owner can add a corgi to a team"#)]
+    fn form_team_with_foreign_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_ids = create_corgis(&mut contract, 2);
+        contract.transfer(mike(), token_ids[1], None);
+        contract.form_team(token_ids, "pack".to_string());
+    }
+
+    #[test]
+    fn tournament_pays_winner_and_runner_up() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let entry_fee = env::attached_deposit();
+        let tournament_id = contract.create_tournament(2, U128(entry_fee));
+        let token_ids = create_corgis(&mut contract, 2);
+        let team_a = contract.form_team(token_ids, "a".to_string());
+        contract.join_tournament(tournament_id, team_a);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let token_ids = create_corgis(&mut contract, 2);
+        let team_b = contract.form_team(token_ids, "b".to_string());
+        contract.join_tournament(tournament_id, team_b);
+
+        let tournament = contract.run_tournament(tournament_id);
+        assert_eq!(tournament.prize_pool.0, entry_fee * 2);
+        let (winner, runner_up) = if tournament.winner == Some(team_a) {
+            (robert(), mike())
+        } else {
+            (mike(), robert())
+        };
+        assert_eq!(contract.get_deposit(winner).0, entry_fee * 2 * 7 / 10);
+        assert_eq!(contract.get_deposit(runner_up).0, entry_fee * 2 * 3 / 10);
+    }
+
+    #[test]
+    fn fruit_stats_track_flows() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let game = contract.new_maze_game();
+        let kind = game.fruit[0].kind;
+        let eaten = game.fruit.iter().filter(|fruit| fruit.kind == kind).count() as u64;
+        contract.finish_maze_game(game.fruit.clone());
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        let name = contract.get_fruit_kinds()[kind as usize].name.clone();
+        contract.mt_transfer(mike(), name, U128(1), None);
+
+        let stats = &contract.get_fruit_stats()[kind as usize];
+        assert_eq!(stats.minted, eaten);
+        assert_eq!(stats.burned, 0);
+        assert_eq!(stats.transferred, 1);
+        assert_eq!(stats.held, eaten);
+    }
+
+    #[test]
+    fn vitamin_shortens_breed_cooldown() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.account_fruit.insert(&robert(), &Fruit::of_each(7, 2));
+        assert_eq!(contract.craft_item(0, 2), 2);
+        assert!(contract.account_fruit(robert()).is_empty());
+
+        contract.last_bred_at.insert(&token_id, &BREED_COOLDOWN);
+        contract.apply_item(token_id, 0);
+        let reduction = VITAMIN_COOLDOWN_REDUCTION_HOURS * 60 * 60 * 1_000_000_000;
+        assert_eq!(contract.last_bred_at.get(&token_id), Some(BREED_COOLDOWN - reduction));
+        assert_eq!(contract.get_item_balance(robert(), 0), 1);
+        assert_eq!(contract.get_item_applications(token_id, 0), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Item was applied to this corgi too many times"#)]
+    fn item_application_limit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let item_id = contract.add_item("tonic".to_string(), vec![1], 1, 1);
+        contract.account_fruit.insert(&robert(), &Fruit::of_each(1, 2));
+        contract.craft_item(item_id, 2);
+        contract.last_bred_at.insert(&token_id, &BREED_COOLDOWN);
+        contract.apply_item(token_id, item_id);
+        contract.apply_item(token_id, item_id);
+    }
+
+    #[test]
+    fn login_challenge_and_holdings() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 42;
+        testing_env!(context);
+        let nonce_hash = to_hex(&env::sha256(b"server-nonce"));
+        contract.register_login_nonce(nonce_hash.clone());
+
+        let proof = contract.verify_holding(robert(), token_id);
+        assert!(proof.holds);
+        assert_eq!(proof.nonce.unwrap().nonce_hash, nonce_hash);
+        assert_eq!(proof.last_active_at, Some(U64(42)));
+        assert!(!contract.verify_holding(mike(), token_id).holds);
+
+        let state = contract.get_login_state(robert());
+        assert_eq!(state.token_ids, vec![token_id]);
+        assert_eq!(state.last_active_at, Some(U64(42)));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::*;
+
+/// Voting weight of corgi holders and its delegation, for the DAO to count votes.
+#[near_bindgen]
+impl Corgi3D {
+    /// Delegates the weight of every corgi the caller holds, now and later, to
+    /// `to`. Delegating to yourself takes the votes back.
+    pub fn delegate_votes(&mut self, to: AccountId) {
+        assert!(
+            env::is_valid_account_id(to.as_bytes()),
+            "Delegate's account ID is invalid."
+        );
+        let predecessor = env::predecessor_account_id();
+        let held = self.held_votes.get(&predecessor).unwrap_or(0);
+        let previous = self.get_delegate(predecessor.clone());
+        self.move_votes(&previous, &to, held);
+        if to == predecessor {
+            self.delegates.remove(&predecessor);
+        } else {
+            self.delegates.insert(&predecessor, &to);
+        }
+    }
+
+    pub fn get_delegate(&self, account_id: AccountId) -> AccountId {
+        self.delegates.get(&account_id).unwrap_or(account_id)
+    }
+
+    /// Weight the account can vote with: its own unless delegated away, plus
+    /// everything delegated to it.
+    pub fn get_votes(&self, account_id: AccountId) -> u64 {
+        self.votes.get(&account_id).unwrap_or(0)
+    }
+
+    pub fn get_held_votes(&self, account_id: AccountId) -> u64 {
+        self.held_votes.get(&account_id).unwrap_or(0)
+    }
+}
+
+// Helper methods
+impl Corgi3D {
+    /// Called whenever `account` gains or loses corgi `id`.
+    pub(crate) fn update_held_votes(&mut self, account: &AccountId, id: TokenId, added: bool) {
+        // corgis in contract custody carry no votes
+        if is_custodian(account) {
+            return;
+        }
+        let weight = rarity_tier(self.corgis.get(&id).unwrap().rate);
+        let held = self.held_votes.get(account).unwrap_or(0);
+        let delegate = self.get_delegate(account.clone());
+        let votes = self.get_votes(delegate.clone());
+        if added {
+            self.held_votes.insert(account, &(held + weight));
+            self.votes.insert(&delegate, &(votes + weight));
+        } else {
+            self.held_votes.insert(account, &(held - weight));
+            self.votes.insert(&delegate, &(votes - weight));
+        }
+    }
+
+    fn move_votes(&mut self, from: &AccountId, to: &AccountId, weight: u64) {
+        if weight == 0 || from == to {
+            return;
+        }
+        let from_votes = self.get_votes(from.clone()) - weight;
+        let to_votes = self.get_votes(to.clone()) + weight;
+        self.votes.insert(from, &from_votes);
+        self.votes.insert(to, &to_votes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn delegated_votes_follow_transfers() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(
+            &mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        )
+        .token_id
+        .0;
+        let weight = rarity_tier(contract.get_corgi(token_id).rate);
+        assert_eq!(contract.get_votes(robert()), weight);
+
+        contract.delegate_votes(joe());
+        assert_eq!(contract.get_votes(robert()), 0);
+        assert_eq!(contract.get_votes(joe()), weight);
+
+        contract.transfer(mike(), token_id, None);
+        assert_eq!(contract.get_votes(joe()), 0);
+        assert_eq!(contract.get_votes(mike()), weight);
+        assert_eq!(contract.get_held_votes(robert()), 0);
+
+        contract.delegate_votes(robert());
+        assert_eq!(contract.get_delegate(robert()), robert());
+    }
+}
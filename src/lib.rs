@@ -1,12 +1,42 @@
+// `ext_contract` adds the receiver, deposit and gas to the arguments of every callback.
+#![allow(clippy::too_many_arguments)]
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::collections::LookupSet;
+use near_sdk::collections::TreeMap;
 use near_sdk::collections::UnorderedMap;
 use near_sdk::collections::UnorderedSet;
-use near_sdk::json_types::U128;
+use near_sdk::collections::Vector;
+use near_sdk::json_types::{Base58PublicKey, Base64VecU8, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, near_bindgen, AccountId, Promise};
+use near_sdk::{
+    env, ext_contract, near_bindgen, AccountId, Balance, Gas, Promise, PromiseOrValue, PromiseResult,
+};
 use rand_chacha::ChaCha20Rng;
 use rand_core::{RngCore, SeedableRng};
-use std::{collections::HashSet, iter::FromIterator};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    iter::FromIterator,
+};
+
+mod admin;
+#[cfg(feature = "debug")]
+mod debug;
+mod errors;
+mod events;
+mod gameplay;
+mod governance;
+mod marketplace;
+mod migration;
+mod nep4;
+mod storage;
+#[cfg(feature = "testnet")]
+mod testnet;
+mod views;
+
+use errors::*;
+use events::*;
 
 #[global_allocator]
 static ALLOC: near_sdk::wee_alloc::WeeAlloc = near_sdk::wee_alloc::WeeAlloc::INIT;
@@ -27,13 +57,20 @@ pub trait NEP171 {
     // Transfer the given `tokenId` to the given `accountId`. Account `accountId` becomes the new owner.
     // Requirements:
     // * The caller of the function (`predecessor_id`) should have access to the token.
-    fn transfer_from(&mut self, owner_id: AccountId, new_owner_id: AccountId, token_id: TokenId);
+    // `memo` is only included in the emitted transfer event.
+    fn transfer_from(
+        &mut self,
+        owner_id: AccountId,
+        new_owner_id: AccountId,
+        token_id: TokenId,
+        memo: Option<String>,
+    );
 
     // Transfer the given `tokenId` to the given `accountId`. Account `accountId` becomes the new owner.
     // Requirements:
     // * The caller of the function (`predecessor_id`) should be the owner of the token. Callers who have
     // escrow access should use transfer_from.
-    fn transfer(&mut self, new_owner_id: AccountId, token_id: TokenId);
+    fn transfer(&mut self, new_owner_id: AccountId, token_id: TokenId, memo: Option<String>);
 
     // Returns `true` or `false` based on caller of the function (`predecessor_id) having access to the token
     fn check_access(&self, account_id: AccountId) -> bool;
@@ -41,27 +78,202 @@ pub trait NEP171 {
     // Get an individual owner by given `tokenId`.
     fn get_token_owner(&self, token_id: TokenId) -> String;
 
-    // follow nep 171 get token
-    fn nft_token(&self,token_id: TokenId)-> Corgi;
+    // NEP-177 contract metadata wallets and explorers show for the collection
+    fn nft_metadata(&self) -> NFTContractMetadata;
 
-    fn nft_transfer(&mut self,
-        new_owner_id: AccountId,
-        token_id: TokenId,
-        message: String,
+    // follow nep 171 get token, in the standard JsonToken shape wallets understand
+    fn nft_token(&self, token_id: TokenId) -> Option<JsonToken>;
+
+    // NEP-171 transfer. Requires exactly 1 yoctoNEAR attached. Callers other than the
+    // owner need escrow access or an approval, whose ID must match `approval_id` if given.
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
     );
 
-    fn nft_total_supply(&self) -> String;
+    // NEP-171 transfer that then calls `nft_on_transfer` on `receiver_id`. The corgi is
+    // returned to its previous owner in `nft_resolve_transfer` if the receiver asks for
+    // it back or the call fails.
+    fn nft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
+
+    // NEP-181 enumeration. Pages start at `from_index` (0 if omitted) and hold up to
+    // `limit` tokens, `DEFAULT_ENUMERATION_LIMIT` if omitted and at most `MAX_VIEW_LIMIT`.
+    fn nft_total_supply(&self) -> U128;
 
-    fn nft_tokens(&self, from_index: u64, limit: u64)-> Vec<Corgi>;
+    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken>;
 
-    fn nft_supply_for_owner(&self, account_id: AccountId) -> String;
+    fn nft_supply_for_owner(&self, account_id: AccountId) -> U128;
 
     fn nft_tokens_for_owner(
-        &self, 
+        &self,
         account_id: AccountId,
-        from_index: u64, 
-        limit: u64
-    )-> Vec<Corgi>;
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken>;
+}
+
+/// Fruit balances exposed through the NEP-245 multi-token standard:
+/// https://github.com/near/NEPs/blob/master/neps/nep-0245.md
+/// Token IDs are the fruit names, e.g. `"apple"`.
+pub trait NEP245 {
+    // Transfer `amount` fruit of kind `token_id` from the caller to `receiver_id`.
+    // Requires exactly 1 yoctoNEAR attached.
+    fn mt_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        amount: U128,
+        memo: Option<String>,
+    );
+
+    fn mt_batch_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_ids: Vec<String>,
+        amounts: Vec<U128>,
+        memo: Option<String>,
+    );
+
+    fn mt_balance_of(&self, account_id: AccountId, token_id: String) -> U128;
+
+    fn mt_batch_balance_of(&self, account_id: AccountId, token_ids: Vec<String>) -> Vec<U128>;
+}
+
+/// NEP-145 storage management over the escrow balance of `deposit`/`withdraw`:
+/// https://github.com/near/NEPs/blob/master/neps/nep-0145.md
+/// The balance pays for storage that mints don't cover with their attached
+/// deposit, and for transfer messages.
+pub trait NEP145 {
+    // Adds the attached deposit to the storage balance of `account_id`, the caller if
+    // omitted. With `registration_only` it is refunded, as no minimum balance is needed.
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance;
+
+    // Withdraws `amount`, everything if omitted. Requires exactly 1 yoctoNEAR attached.
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance;
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance>;
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds;
+}
+
+/// NEP-199 payouts, so external marketplaces pay creator royalties:
+/// https://github.com/near/NEPs/blob/master/neps/nep-0199.md
+/// The royalty goes to the corgi's creator unless the creator is selling it.
+pub trait NEP199 {
+    // How a sale of `token_id` for `balance` is split, in at most `max_len_payout` entries.
+    fn nft_payout(&self, token_id: String, balance: U128, max_len_payout: u32) -> Payout;
+
+    // `nft_transfer` that returns the split of `balance` as it stood before the transfer.
+    fn nft_transfer_payout(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: u32,
+    ) -> Payout;
+}
+
+/// Implemented by partner arena contracts so our corgis can battle their NFTs.
+/// The arena receives the challenger's normalized stats and decides the outcome.
+#[ext_contract(battle_interface)]
+pub trait BattleInterface {
+    fn battle(&mut self, challenger: BattleStats, opponent_token_id: String) -> BattleOutcome;
+}
+
+/// Implemented by marketplaces that list a corgi once they are approved for it
+/// (NEP-178 approval receiver).
+#[ext_contract(ext_approval_receiver)]
+pub trait ApprovalReceiver {
+    fn nft_on_approve(&mut self, token_id: String, owner_id: AccountId, approval_id: U64, msg: String);
+}
+
+/// NEP-141 fungible tokens accepted as listing currencies.
+#[ext_contract(ext_ft)]
+pub trait FungibleToken {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+/// NEP-171 view of partner collections, used to verify campaign claims.
+#[ext_contract(ext_partner_nft)]
+pub trait PartnerNft {
+    fn nft_token(&self, token_id: String) -> Option<PartnerToken>;
+}
+
+/// Registries told about burns, e.g. a global burn tracker or the renderer cache.
+#[ext_contract(ext_burn_registry)]
+pub trait BurnRegistry {
+    fn on_nft_burned(&mut self, token_id: String, owner_id: AccountId);
+}
+
+/// Implemented by contracts accepting corgis through `nft_transfer_call`. Returns
+/// `true` if the corgi should be returned to `previous_owner_id`.
+#[ext_contract(ext_nft_receiver)]
+pub trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> bool;
+}
+
+#[ext_contract(ext_self)]
+pub trait SelfCallbacks {
+    fn on_battle_resolved(&mut self, battle_id: u64) -> bool;
+    fn resolve_breeding(&mut self, breeding_id: u64) -> Option<PendingMint>;
+    fn on_listing_resolved(&mut self, token_id: TokenId, account_id: AccountId, approval_id: u64) -> bool;
+    fn on_payout_resolved(&mut self, account_id: AccountId, amount: U128) -> bool;
+    fn on_ft_payout_resolved(&mut self, account_id: AccountId, ft_id: AccountId, amount: U128) -> bool;
+    fn on_ft_sale_resolved(
+        &mut self,
+        token_id: TokenId,
+        seller: AccountId,
+        buyer: AccountId,
+        ft_id: AccountId,
+        price: U128,
+        proceeds: U128,
+        royalty: U128,
+    ) -> bool;
+    fn on_purchase_complete(
+        &mut self,
+        token_id: TokenId,
+        seller: AccountId,
+        buyer: AccountId,
+        price: U128,
+        proceeds: U128,
+        royalty: U128,
+    ) -> bool;
+    fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: String,
+        approved_account_ids: HashMap<AccountId, u64>,
+    ) -> bool;
+    fn on_campaign_verified(
+        &mut self,
+        campaign_id: u64,
+        token_id: TokenId,
+        partner_token_id: String,
+        claimer: AccountId,
+    ) -> bool;
 }
 
 /// The token ID type is also defined in the NEP
@@ -72,31 +284,453 @@ pub type AccountIdHash = Vec<u8>;
 // A Corgi
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
 pub struct Corgi {
+    #[serde(serialize_with = "serialize_token_id")]
     pub id: TokenId,
     pub name: String,
     pub quote: String,
     pub color: String,
     pub background_color: String,
-    pub rate: String,
+    pub rate: Rarity,
     pub sausage: String,
     pub sender: String,
     pub message: String,
     pub selling: bool,
     pub selling_price: U128,
+    pub energy: u64,
+    pub energy_updated_at: u64,
+    #[serde(serialize_with = "serialize_token_id_option")]
+    pub clone_of: Option<TokenId>, // the root original, also for clones of clones
+    pub clone_count: u64, // of a root original, clones of its clones included
+    pub metadata_frozen: bool,
+    pub locked: Option<u64>, // while locked by its owner: when it may be unlocked (ns)
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub parents: Vec<TokenId>,
+    pub generation: u64, // 0 for minted corgis, one above the older parent for bred ones
+    pub creator: AccountId, // account the corgi was minted to, paid royalties on every sale
+    pub royalty_bps: u32,
+    #[serde(serialize_with = "serialize_token_id_option")]
+    pub bonded_with: Option<TokenId>,
+    pub bond_changed_at: u64,
+    pub pass: Option<PassInfo>,
+    pub battles_fought: u64,
+    pub battles_won: u64,
+    pub times_bred: u64,
+    pub unlocked_traits: u8, // bit `slot` set once HIDDEN_TRAITS[slot] is revealed
+    pub skills: Vec<u64>, // level per `Skill`, indexed by `skill as usize`
+    pub skill_progress: Vec<u64>, // basis points towards the next level, kept from cancelled sessions
+    pub accessories: Vec<String>,
+}
+
+// A hidden trait slot of a corgi; `value` stays `None` until it is unlocked
+#[derive(Serialize, Debug)]
+pub struct HiddenTrait {
+    pub slot: u8,
+    pub name: String,
+    pub value: Option<String>,
 }
 
-const APPLE: usize = 0;
-const AVOCADO: usize = 1;
-const BANANA: usize = 2;
-const CUCUMBER: usize = 3;
-const LEMON: usize = 4;
-const LIME: usize = 5;
-const ORANGE: usize = 6;
-const TOTAL: usize = 7;
+// A premium mint with constrained traits; unset fields are rolled or chosen as usual
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct Edition {
+    pub name: String,
+    pub price: U128,
+    pub max_supply: u64,
+    pub minted: u64,
+    pub min_rarity: Option<Rarity>,
+    pub color: Option<String>,
+    pub background_color: Option<String>,
+}
+
+// A time-boxed discount on primary mints, or on the given contract-owned corgis
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct FlashSale {
+    pub id: u64,
+    pub discount_bps: u32,
+    pub starts_at: U64,
+    pub ends_at: U64,
+    pub per_account_limit: u64, // discounted purchases per account
+    pub token_ids: Vec<TokenId>, // empty for primary mints
+}
+
+// A mint phase: while configured, public mints are only open between `start_at`
+// and `end_at`, at `price`, and to allowlisted accounts if `allowlist_only`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct MintDrop {
+    pub id: u64,
+    pub start_at: U64,
+    pub end_at: U64,
+    pub price: U128,
+    pub per_account_limit: u64,
+    pub allowlist_only: bool,
+}
+
+// Membership data of corgis minted as subscription passes
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct PassInfo {
+    pub expires_at: u64,
+    pub auto_renew: bool,
+}
 
+// A paid mint waiting for `reveal_corgi`, which rolls its traits in a later
+// block than the one it was paid in
 #[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct PendingMint {
+    pub id: u64,
+    pub minter: AccountId,
+    pub spec: CorgiSpec,
+    pub committed_block: U64, // revealable from the next block on
+    pub fee: U128,
+    pub storage_reserve: U128, // paid for the corgi's storage, the unused part is refunded
+    pub kind: MintKind,
+}
+
+/// What a pending mint was paid for, which decides how `reveal_corgi` rolls the
+/// corgi and what it records about it.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MintKind {
+    // `create_corgi` and the other public mints
+    Public,
+    // `create_corgi_from_edition`, rolled at or above the edition's minimum rarity
+    Edition { edition_id: u64 },
+    // `mint_pass`, valid for `periods` pass periods from the reveal
+    Pass { periods: u64 },
+    // `partner_mint`; the partner paid the storage and gets the unused part back
+    Partner { partner_id: AccountId },
+    // `mint_with_voucher`; a voucher `rate` replaces the rolled rarity
+    Voucher { nonce: u64, rate: Option<Rarity> },
+    // `breed_corgis`, which inherits the parents' traits, or `breed_with_stud`,
+    // which rolls them like a public mint
+    Breeding {
+        #[serde(serialize_with = "serialize_token_id")]
+        parent_a: TokenId,
+        #[serde(serialize_with = "serialize_token_id")]
+        parent_b: TokenId,
+        inherit_traits: bool,
+    },
+}
+
+// Receipt returned by `reveal_corgi`
+#[derive(Serialize, Debug)]
+pub struct MintResult {
+    pub token_id: U64,
+    pub name: String,
+    pub rate: Rarity,
+    pub sausage: String,
+    pub storage_used: U64,
+    pub charged: U128,
+}
+
+// `msg` of an `ft_transfer_call` minting a corgi
+#[derive(Deserialize, Debug)]
+pub struct FtMintMsg {
+    pub mint: CorgiSpec,
+}
+
+// A mint signed off-chain by the minter key, redeemable once per `nonce` through
+// `mint_with_voucher`. The key signs the Borsh encoding of (contract account, voucher).
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct MintVoucher {
+    pub nonce: u64,
+    pub receiver_id: Option<AccountId>, // `None` lets anyone redeem it
+    pub spec: CorgiSpec,
+    pub rate: Option<Rarity>, // `None` rolls the rarity like a public mint
+    pub price: U128,
+    pub expires_at: Option<U64>,
+}
+
+// User-chosen fields of a new corgi
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct CorgiSpec {
+    pub name: String,
+    pub color: String,
+    pub background_color: String,
+    pub quote: String,
+}
+
+// IDs of the fruit kinds registered at init; more can be added with `add_fruit_kind`
+const APPLE: u64 = 0;
+const AVOCADO: u64 = 1;
+const BANANA: u64 = 2;
+const CUCUMBER: u64 = 3;
+const LEMON: u64 = 4;
+const LIME: u64 = 5;
+const ORANGE: u64 = 6;
+
+const DEFAULT_FRUIT_NAMES: [&str; 7] = [
+    "apple", "avocado", "banana", "cucumber", "lemon", "lime", "orange",
+];
+const MAX_FRUIT_NAME_LENGTH: usize = 32;
+
+const MAX_ENERGY: u64 = 100;
+/// Energy lost for every full day a corgi goes unfed.
+const HUNGER_ENERGY_LOSS: u64 = 10;
+const HUNGER_INTERVAL: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// A corgi yields its rarity tier in fruit for every full interval between harvests.
+const HARVEST_INTERVAL: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Intervals a corgi accrues fruit for at most, so idle corgis don't pile it up.
+const MAX_HARVEST_PERIODS: u64 = 7;
+/// Corgis of an account `harvest_fruit` looks at per call.
+const MAX_HARVEST_CORGIS: u64 = 100;
+const STAKING_EPOCH: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Share of the staking reward pool released to stakers at each epoch.
+const STAKING_RELEASE_BPS: u128 = 1_000;
+/// Epochs released at once when staking was idle; older ones are skipped.
+const MAX_STAKING_EPOCHS_PER_UPDATE: u64 = 365;
+/// Fixed-point scale of `reward_per_weight`.
+const STAKING_REWARD_PRECISION: u128 = 1_000_000_000_000;
+const MAX_STAKED_CORGIS: usize = 50;
+const FEED_SAUSAGE_GAIN: u64 = 5;
+const FEED_ENERGY_GAIN: u64 = 20;
+
+/// Rough per-entry cost of a persistent collection entry (trie keys, index slots),
+/// used when estimating storage attributable to an account.
+const STORAGE_ENTRY_OVERHEAD: u64 = 40;
+/// Minimum time between bonding changes of the same corgi.
+const BOND_COOLDOWN: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Battle power a bonded corgi gets from its companion.
+const BOND_POWER_BONUS: u64 = 10;
+/// Staking weight a bonded corgi gets on top of its rarity tier.
+const BOND_STAKE_WEIGHT_BONUS: u64 = 1;
+/// Sale observations kept per rarity tier for TWAP computation.
+const MAX_PRICE_OBSERVATIONS: usize = 32;
+/// Loans may borrow up to this share (in basis points) of the tier's TWAP.
+const LOAN_TO_VALUE_BPS: u128 = 3_000;
+const LOAN_TWAP_WINDOW_SEC: u64 = 7 * 24 * 60 * 60;
+const LOAN_DURATION: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+// sales back a loan only once they are this old (s), and only from this many
+// distinct seller/buyer pairs
+const MIN_LOAN_OBSERVATION_AGE_SEC: u64 = 24 * 60 * 60;
+const MIN_LOAN_OBSERVATION_PAIRS: usize = 3;
+// outstanding principal may not exceed this share of the treasury plus that principal
+const MAX_LOAN_BOOK_BPS: u128 = 5_000;
+const LIQUIDATION_AUCTION_HOURS: u64 = 48;
+const LIQUIDATION_BID_INCREMENT_BPS: u32 = 500;
+const MAX_RENTAL_HOURS: u64 = 90 * 24;
+const MAX_AUCTION_HOURS: u64 = 14 * 24;
+const MAX_INSTALLMENTS: u64 = 12;
+const INSTALLMENT_PERIOD: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+const DEFAULT_INSTALLMENT_FORFEIT_BPS: u32 = 2_000;
+const PASS_PRICE: Balance = 1_000_000_000_000_000_000_000_000;
+const PASS_PERIOD: u64 = 30 * 24 * 60 * 60 * 1_000_000_000;
+const MIN_NICKNAME_LENGTH: usize = 3;
+const MAX_NICKNAME_LENGTH: usize = 32;
+const MAX_KENNEL_SHOWCASE: usize = 20;
+/// Treasury shares issued per rarity tier step of a contributed corgi.
+const SHARES_PER_TIER: u64 = 100;
+const MAX_PEDIGREE_DEPTH: u64 = 5;
+const MAX_WISHLIST_ENTRIES: usize = 10;
+const MAX_WISHLIST_MATCHES: usize = 50;
+/// Wishers a listing is checked against at most, so listing gas stays bounded
+/// however many wishlists there are.
+const MAX_WISHLIST_SCAN: usize = 50;
+const MAX_OFFERS_PER_CORGI: usize = 20;
+const DEFAULT_ENUMERATION_LIMIT: u64 = 50;
+// most entries a paginated view returns per call, whatever `limit` asks for
+const MAX_VIEW_LIMIT: u64 = 100;
+/// Most corgis `create_corgis_batch`, `transfer_batch` and `accept_merge` handle in one call.
+const MAX_BATCH_SIZE: usize = 10;
+const BATCH_MINT_GAS_PER_CORGI: Gas = 10_000_000_000_000;
+/// Creator royalty of newly minted corgis; creators can change it up to `MAX_ROYALTY_BPS`.
+const DEFAULT_ROYALTY_BPS: u32 = 250;
+const MAX_ROYALTY_BPS: u32 = 1_000;
+/// Mint fee until the owner configures another one, charged on top of the storage
+/// a public mint uses.
+const MINT_PRICE: Balance = 1_000_000_000_000_000_000_000_000;
+/// Bytes a public mint is assumed to take on top of its strings: the corgi, its
+/// owner and index entries and the first mint of an account creating its token
+/// set, about 2_150 bytes measured for a 64-character account ID, with margin.
+/// Reserved when a mint is paid for; whatever the revealed corgi doesn't use is refunded.
+const MINT_STORAGE_ESTIMATE: u64 = 3_000;
+/// Bytes a pending mint is assumed to take on top of its strings when suggesting
+/// a deposit, about 480 bytes measured for a 64-character account ID, with margin.
+const PENDING_MINT_STORAGE_ESTIMATE: u64 = 600;
+/// Unrevealed mints an account may have at once.
+const MAX_PENDING_MINTS: usize = 20;
+const MAX_CLONES_PER_CORGI: u64 = 3;
+/// Prefix every access set shared before sets got per-account prefixes.
+const LEGACY_ACCESS_SET_PREFIX: &[u8] = b"new-access-set";
+/// Trade proposals an account may have open at once.
+const MAX_OPEN_TRADES: usize = 20;
+/// Fruit of every kind `burn_corgi` awards per rarity tier of the burned corgi.
+const BURN_FRUIT_PER_TIER: u64 = 2;
+/// Mint credit `burn_corgi` awards per rarity tier, in basis points of the mint price.
+const BURN_CREDIT_BPS_PER_TIER: u128 = 1_000;
+
+const BATTLE_GAS: Gas = 25_000_000_000_000;
+const BATTLE_CALLBACK_GAS: Gas = 20_000_000_000_000;
+const MAX_SAUSAGE: u64 = 300;
+/// A corgi gains a level for every this many battles won, starting at level 1.
+const WINS_PER_LEVEL: u64 = 2;
+/// Hidden trait slots: name, possible values, and what reveals it.
+/// Slot 0 at level 5, slot 1 once bred, slot 2 after 10 battles.
+const HIDDEN_TRAITS: [(&str, [&str; 4]); 3] = [
+    ("aura", ["golden", "silver", "shadow", "ember"]),
+    ("lineage", ["royal", "herding", "farm", "city"]),
+    ("scar", ["ear nick", "tail kink", "paw mark", "none"]),
+];
+const SKILL_COUNT: usize = 3;
+/// Length of a training session starting from no progress.
+const TRAINING_DURATION: u64 = 8 * 60 * 60 * 1_000_000_000;
+const AURA_UNLOCK_LEVEL: u64 = 5;
+const SCAR_UNLOCK_BATTLES: u64 = 10;
+const BATTLE_WIN_POINTS: u64 = 10;
+const MAX_DUEL_HISTORY: usize = 20;
+const BREED_COOLDOWN: u64 = 24 * 60 * 60 * 1_000_000_000;
+const VITAMIN_FRUIT_PER_KIND: u64 = 1;
+const VITAMIN_COOLDOWN_REDUCTION_HOURS: u64 = 6;
+const VITAMIN_MAX_PER_TOKEN: u32 = 3;
+const DEFAULT_KINSHIP_DEPTH: u64 = 2;
+const DEFAULT_GIFT_EXPIRY: u64 = 7 * 24 * 60 * 60 * 1_000_000_000;
+const MAX_KINSHIP_DEPTH: u64 = 6;
+const BREED_CALLBACK_GAS: Gas = 30_000_000_000_000;
+const PARTNER_VIEW_GAS: Gas = 10_000_000_000_000;
+const CAMPAIGN_CALLBACK_GAS: Gas = 20_000_000_000_000;
+const MAX_ACCESSORIES: usize = 10;
+const ON_APPROVE_GAS: Gas = 25_000_000_000_000;
+const ON_APPROVE_CALLBACK_GAS: Gas = 10_000_000_000_000;
+const FT_TRANSFER_GAS: Gas = 10_000_000_000_000;
+const FT_RESOLVE_GAS: Gas = 20_000_000_000_000;
+const PAYOUT_CALLBACK_GAS: Gas = 5_000_000_000_000;
+const PURCHASE_CALLBACK_GAS: Gas = 20_000_000_000_000;
+const BURN_NOTIFY_GAS: Gas = 5_000_000_000_000;
+const NFT_ON_TRANSFER_GAS: Gas = 25_000_000_000_000;
+const NFT_RESOLVE_TRANSFER_GAS: Gas = 10_000_000_000_000;
+/// Gas suggested to wallets by the `get_*_payload` views.
+const SUGGESTED_CALL_GAS: Gas = 30_000_000_000_000;
+const SUGGESTED_BUY_GAS: Gas = 50_000_000_000_000;
+const SUGGESTED_FT_BUY_GAS: Gas = 100_000_000_000_000;
+const BREEDING_LICENSE_PRICE: Balance = 500_000_000_000_000_000_000_000;
+/// Paid to the treasury by `breed_corgis`.
+const BREEDING_FEE: Balance = 1_000_000_000_000_000_000_000_000;
+/// Chance out of 50 that a bred child's rarity mutates one tier up, and the same
+/// chance that it mutates one tier down.
+const RARITY_MUTATION_ODDS: u32 = 5;
+/// Largest change of the inherited sausage length in either direction.
+const SAUSAGE_MUTATION: u64 = 10;
+const HOUSE_PRICE: Balance = 2_000_000_000_000_000_000_000_000;
+const HOUSE_FRUIT_PER_KIND: u64 = 2;
+const HOUSE_CAPACITY: u64 = 3;
+const HOUSE_POWER_BONUS: u64 = 10;
+const MIN_TEAM_SIZE: usize = 2;
+const MAX_TEAM_SIZE: usize = 5;
+/// Token IDs are `era << TOKEN_SERIAL_BITS | serial`, where `serial` counts the
+/// mints of this deployment. Eras fit the remaining bits below 2^53, so IDs stay
+/// exact as JSON numbers in JavaScript.
+const TOKEN_SERIAL_BITS: u32 = 40;
+const MAX_ID_ERA: u32 = (1 << 13) - 1;
+const MAX_TEAM_NAME_LENGTH: usize = 32;
+const MAX_TOURNAMENT_TEAMS: u64 = 64;
+/// Tokens of the ownership index `pick_random_holders` looks at per call.
+const MAX_HOLDER_SCAN: u64 = 1_000;
+const MAX_CERTIFICATES: usize = 10;
+/// Older admin log entries are dropped once this many are kept.
+const MAX_ADMIN_LOG_ENTRIES: u64 = 1_000;
+const MAX_CERTIFICATE_LENGTH: usize = 512;
+const MAX_MESSAGE_LENGTH: usize = 280;
+const MESSAGE_UPDATES_PER_DAY: u32 = 3;
+const MAX_CORGI_NAME_LENGTH: usize = 64;
+const MAX_QUOTE_LENGTH: usize = 280;
+const MAX_COLOR_LENGTH: usize = 32;
+/// Paid to the treasury for each rename, quote or color change.
+const DEFAULT_CUSTOMIZATION_FEE: Balance = 100_000_000_000_000_000_000_000;
+const MAX_EXTRA_LENGTH: usize = 2_048;
+const MAX_FEATURED: usize = 12;
+/// Accounts ranked in `get_leaderboard`.
+const MAX_LEADERBOARD_SIZE: usize = 20;
+const NFT_METADATA_SPEC: &str = "nft-1.0.0";
+const NFT_SYMBOL: &str = "CORGI";
+/// Flash sales running or scheduled at the same time.
+const MAX_FLASH_SALES: usize = 10;
+/// Sale parties remembered per token for wash-trading detection.
+const MAX_TRADE_HISTORY: usize = 10;
+/// Ownership changes kept per corgi by `get_corgi_history`; older ones are overwritten.
+const MAX_PROVENANCE_ENTRIES: u64 = 50;
+/// Sales between the same two accounts from which their trades look like wash trading.
+const WASH_PAIR_THRESHOLD: u32 = 3;
+/// Token IDs `get_login_state` returns at most.
+const MAX_LOGIN_HOLDINGS: u64 = 100;
+const TRUST_PER_CERTIFICATE: u64 = 20;
+/// Share of the season prize pool for each leaderboard place, in basis points.
+const SEASON_PRIZE_BPS: [u128; 3] = [5_000, 3_000, 2_000];
+/// Share of a tournament's prize pool for the winner and the runner-up, in basis points.
+const TOURNAMENT_PRIZE_BPS: [u128; 2] = [7_000, 3_000];
+
+/// Paid from the treasury to callers of maintenance methods per record they processed.
+const DEFAULT_KEEPER_BOUNTY: Balance = 1_000_000_000_000_000_000_000;
+/// Maintenance calls processing fewer records earn no keeper bounty.
+const MIN_KEEPER_BATCH: u64 = 5;
+/// Most keeper bounty paid out in total per `KEEPER_BOUNTY_PERIOD`.
+const MAX_KEEPER_PAYOUT_PER_PERIOD: Balance = 1_000_000_000_000_000_000_000_000;
+const KEEPER_BOUNTY_PERIOD: u64 = 24 * 60 * 60 * 1_000_000_000;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone, Default)]
 pub struct Fruit {
-    pub count: [u64; TOTAL],
+    pub count: BTreeMap<u64, u64>, // fruit kind ID -> amount, zero amounts are left out
+}
+
+impl Fruit {
+    /// `amount` of each of the kinds with IDs below `kinds`.
+    pub fn of_each(kinds: u64, amount: u64) -> Self {
+        let mut fruit = Self::default();
+        for kind in 0..kinds {
+            fruit.add(kind, amount);
+        }
+        fruit
+    }
+
+    pub fn get(&self, kind: u64) -> u64 {
+        self.count.get(&kind).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, kind: u64, amount: u64) {
+        if amount > 0 {
+            *self.count.entry(kind).or_insert(0) += amount;
+        }
+    }
+
+    pub fn add_all(&mut self, other: &Fruit) {
+        for (&kind, &amount) in other.count.iter() {
+            self.add(kind, amount);
+        }
+    }
+
+    /// Removes every amount of `other`, panicking if any kind falls short.
+    pub fn sub_all(&mut self, other: &Fruit) {
+        for (&kind, &amount) in other.count.iter() {
+            let left = match self.get(kind).checked_sub(amount) {
+                Some(left) => left,
+                None => env::panic(b"Not enough fruit"),
+            };
+            if left == 0 {
+                self.count.remove(&kind);
+            } else {
+                self.count.insert(kind, left);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count.is_empty()
+    }
+}
+
+// A consumable crafted from fruit; its index in `items` is the ID
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct Item {
+    pub id: u64,
+    pub name: String,
+    pub cost: Fruit,
+    pub breed_cooldown_reduction: u64, // ns
+    pub max_per_token: u32, // applications to one corgi over its lifetime
+}
+
+// An entry of the fruit registry; its index in `fruit_kinds` is the ID
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct FruitKind {
+    pub id: u64,
+    pub name: String, // also the NEP-245 token ID
+    pub effect: String,
 }
 
 #[derive(
@@ -113,18 +747,1175 @@ pub struct MazeGame {
     pub fruit: Vec<MazeFruit>,
 }
 
+/// Bumped whenever public methods change shape; legacy entry points keep working
+/// for at least one version and log a deprecation notice when called.
+pub const API_VERSION: u32 = 3;
+
+/// Version of the `RenderSpec` layout, bumped whenever its shape changes so the
+/// rendering service can keep supporting older specs.
+pub const RENDER_SPEC_VERSION: u32 = 1;
+
+/// Version of the `FullConfig` layout.
+pub const CONFIG_VERSION: u32 = 2;
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, PartialEq, Clone, Copy,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Rarity {
+    Common,
+    Uncommon,
+    Rare,
+    VeryRare,
+    UltraRare,
+}
+
+#[derive(
+    BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, PartialEq, Clone, Copy,
+)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum Skill {
+    Agility,
+    Strength,
+    Stamina,
+}
+
+impl Skill {
+    /// Highest level a corgi can train the skill to.
+    pub fn cap(self) -> u64 {
+        match self {
+            Skill::Agility => 10,
+            Skill::Strength => 10,
+            Skill::Stamina => 5,
+        }
+    }
+}
+
+// A training session in progress; the corgi can't be transferred until it ends
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Training {
+    pub skill: Skill,
+    pub started_at: U64,
+    pub ends_at: U64,
+}
+
+impl Rarity {
+    pub const ALL: [Rarity; 5] = [
+        Rarity::Common,
+        Rarity::Uncommon,
+        Rarity::Rare,
+        Rarity::VeryRare,
+        Rarity::UltraRare,
+    ];
+    /// Tiers the owner can cap per season.
+    pub const QUOTA_TIERS: [Rarity; 2] = [Rarity::VeryRare, Rarity::UltraRare];
+
+    /// Stable key front-ends use to look up a translated tier name.
+    pub fn localization_key(self) -> String {
+        let key = match self {
+            Rarity::UltraRare => "ultra_rare",
+            Rarity::VeryRare => "very_rare",
+            Rarity::Rare => "rare",
+            Rarity::Uncommon => "uncommon",
+            Rarity::Common => "common",
+        };
+        format!("rarity.{}", key)
+    }
+
+    /// English display name used until the owner configures another one.
+    pub fn default_name(self) -> &'static str {
+        match self {
+            Rarity::UltraRare => "ULTRA RARE",
+            Rarity::VeryRare => "VERY RARE",
+            Rarity::Rare => "RARE",
+            Rarity::Uncommon => "UNCOMMON",
+            Rarity::Common => "COMMON",
+        }
+    }
+
+    /// The next tier down; `Common` is the floor.
+    pub fn lower(self) -> Self {
+        match self {
+            Rarity::UltraRare => Rarity::VeryRare,
+            Rarity::VeryRare => Rarity::Rare,
+            Rarity::Rare => Rarity::Uncommon,
+            Rarity::Uncommon | Rarity::Common => Rarity::Common,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct RarityDisplay {
+    pub rarity: Rarity,
+    pub name: String,
+    pub localization_key: String,
+}
+
+// Normalized corgi description consumed by the 3D rendering service
+#[derive(Serialize, Debug)]
+pub struct RenderSpec {
+    pub spec_version: u32,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub name: String,
+    pub color: String,
+    pub background_color: String,
+    pub rarity: Rarity,
+    pub sausage: u64,
+    pub accessories: Vec<String>,
+    pub extra: Option<serde_json::Value>, // app-specific data set by the owner with `set_extra`
+}
+
+// Off-chain render of a corgi with the hash clients check it against
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct TokenMedia {
+    pub url: String,
+    pub sha256: Base64VecU8,
+}
+
+// NEP-177 contract metadata returned by `nft_metadata`
+#[derive(Serialize, Debug)]
+pub struct NFTContractMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>, // data URL
+    pub base_uri: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+}
+
+// NEP-177 token metadata; fields we have no data for are left unset
+#[derive(Serialize, Debug)]
+pub struct TokenMetadata {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub media: Option<String>,
+    pub media_hash: Option<Base64VecU8>, // SHA-256 of the file at `media`
+    pub copies: Option<U64>,
+    pub issued_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub starts_at: Option<String>,
+    pub updated_at: Option<String>,
+    pub extra: Option<String>, // the `RenderSpec` as JSON, app data included
+    pub reference: Option<String>,
+    pub reference_hash: Option<String>,
+}
+
+// A corgi in the NEP-171 JsonToken shape returned by `nft_token`
+#[derive(Serialize, Debug)]
+pub struct JsonToken {
+    pub token_id: String,
+    pub owner_id: AccountId,
+    pub metadata: TokenMetadata,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+}
+
+// NEP-199 split of a sale, amounts keyed by receiving account
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Payout {
+    pub payout: HashMap<AccountId, U128>,
+}
+
+/// How two corgis are related, if too closely to breed.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum KinshipConflict {
+    // `ancestor_id` is `generations` above `descendant_id`
+    Ancestor {
+        #[serde(serialize_with = "serialize_token_id")]
+        ancestor_id: TokenId,
+        #[serde(serialize_with = "serialize_token_id")]
+        descendant_id: TokenId,
+        generations: u64,
+    },
+    Siblings {
+        #[serde(serialize_with = "serialize_token_id")]
+        shared_parent_id: TokenId,
+    },
+}
+
+/// An action `simulate` checks without performing it.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum SimulatedAction {
+    // `buy_corgi` by `buyer` attaching `deposit`
+    Buy {
+        token_id: TokenId,
+        buyer: AccountId,
+        deposit: U128,
+    },
+    // `transfer`, or `transfer_from` if `sender` isn't the owner
+    Transfer {
+        token_id: TokenId,
+        sender: AccountId,
+        receiver: AccountId,
+    },
+    // `create_corgi` by `account_id` attaching `deposit`; the cost leaves out
+    // storage, which depends on the strings of the new corgi
+    Mint {
+        account_id: AccountId,
+        deposit: U128,
+    },
+}
+
+// Outcome of `simulate`: what the action would cost, or the error the real call would panic with
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Simulation {
+    pub ok: bool,
+    pub cost: U128,
+    pub error: Option<String>,
+}
+
+// Ancestor tree of a corgi; `name` and `rate` are unset for burned ancestors
+#[derive(Serialize, Debug)]
+pub struct PedigreeNode {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub name: Option<String>,
+    pub rate: Option<Rarity>,
+    pub parents: Vec<PedigreeNode>,
+}
+
+// Result of a `cleanup_expired` batch
+#[derive(Serialize, Debug)]
+pub struct CleanupReport {
+    pub pruned: u64,
+    pub next_from: u64, // 0 once the end was reached
+}
+
+// Result of an `accept_merge` batch
+#[derive(Serialize, Debug)]
+pub struct MergeReport {
+    pub moved: u64,
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub skipped: Vec<TokenId>, // locked, staked or otherwise blocked, left with the source
+    pub complete: bool, // false while corgis are left to move, call again
+}
+
+// Challenge registered by an account for an off-chain login
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct LoginNonce {
+    pub nonce_hash: String, // hex SHA-256 of the nonce the game server handed out
+    pub registered_at: U64,
+}
+
+// Everything a game server needs to let `account_id` log in with `token_id`
+#[derive(Serialize, Debug)]
+pub struct HoldingProof {
+    pub account_id: AccountId,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub holds: bool,
+    pub nonce: Option<LoginNonce>,
+    pub last_active_at: Option<U64>,
+}
+
+// An account's corgis and activity in one call, for login screens
+#[derive(Serialize, Debug)]
+pub struct LoginState {
+    pub account_id: AccountId,
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub token_ids: Vec<TokenId>, // the first `MAX_LOGIN_HOLDINGS`
+    pub nonce: Option<LoginNonce>,
+    pub last_active_at: Option<U64>,
+}
+
+// A corgi of the homepage carousel with what the front end shows next to it
+#[derive(Serialize, Debug)]
+pub struct FeaturedCorgi {
+    pub corgi: Corgi,
+    pub owner_id: AccountId,
+    pub level: u64,
+    pub rarity_score: u64,
+}
+
+// Records `migrate_records` converted from the first deployment's layout so far
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone, PartialEq)]
+pub struct MigrationProgress {
+    pub corgis: u64,
+    pub fruit_balances: u64, // converted once every corgi is
+}
+
+// Collection-wide counters, see `get_global_stats`
+#[derive(Serialize, Debug)]
+pub struct GlobalStats {
+    pub total_minted: u64,
+    pub total_burned: u64,
+    pub total_supply: u64,
+    pub rarity_counts: Vec<(Rarity, u64)>,
+    pub highest_sale_price: U128, // NEAR sales only
+}
+
+// Season supply cap of a top rarity tier, see `set_rarity_quota`
+#[derive(Serialize, Debug)]
+pub struct RarityQuota {
+    pub rarity: Rarity,
+    pub quota: Option<u64>, // `None` is uncapped
+    pub minted: u64, // since the current season started
+    pub reserved: u64, // by pending mints guaranteed the tier
+    pub remaining: Option<u64>,
+}
+
+// Wash-trading signals of a token, see `get_trade_flags`
+#[derive(Serialize, Debug)]
+pub struct TradeFlags {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub recent_sales: u64,
+    pub circular: bool, // an account sold or bought it more than once recently
+    pub max_pair_trades: u32, // most sales between two of its recent owners, any token
+    pub suspicious: bool,
+}
+
+// A prefilled function call for wallets and QR-code flows to sign as is
+#[derive(Serialize, Debug)]
+pub struct CallPayload {
+    pub receiver_id: AccountId,
+    pub method_name: String,
+    pub args: String, // JSON
+    pub deposit: U128,
+    pub gas: U64,
+}
+
+// A token ID split into the era of the deployment that minted it and its serial there
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TokenIdParts {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub token_id_str: String, // the form used by NEP-171 methods
+    pub era: u32,
+    pub serial: u64,
+}
+
+// Hash of one page of ownership data, see `state_checksum`
+#[derive(Serialize, Debug)]
+pub struct StateChecksum {
+    pub from: u64,
+    pub count: u64,
+    pub total: u64, // tokens in the whole ownership index
+    pub checksum: String, // hex
+}
+
+// NEP-145 storage balance; all of it is available, nothing is locked for registration
+#[derive(Serialize, Debug, PartialEq)]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize, Debug)]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+// Estimated bytes of contract storage attributable to one account
+#[derive(Serialize, Debug)]
+pub struct StorageReport {
+    pub corgis: u64,
+    pub access: u64,
+    pub fruit: u64,
+    pub listings: u64,
+    pub total: u64,
+    pub storage_cost: U128,
+}
+
+// A corgi held by the contract as collateral, and what was borrowed against it
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Loan {
+    pub borrower: AccountId,
+    pub principal: U128,
+    pub due_at: u64,
+}
+
+// A corgi lent to another account, which may battle with it until `ends_at`;
+// the owner keeps it but cannot transfer it meanwhile
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Rental {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub owner: AccountId,
+    pub renter: AccountId,
+    pub ends_at: U64,
+}
+
+// Corgi stats scaled to 0..=100 so partner arenas don't need to know our rarity model
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BattleStats {
+    pub token_id: U64,
+    pub owner: AccountId,
+    pub power: u64,
+    pub energy: u64,
+    pub size: u64,
+}
+
+// Response of a partner arena's `battle`
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BattleOutcome {
+    pub challenger_won: bool,
+}
+
+// A battle awaiting the arena's response; each side's wager is held in escrow
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Battle {
+    pub challenger: AccountId,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub arena: AccountId,
+    pub opponent_token_id: String,
+    pub wager: U128,
+}
+
+// An open PvP challenge; the challenger's wager is held until it is accepted or cancelled
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Challenge {
+    pub id: u64,
+    pub challenger: AccountId,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub opponent: AccountId,
+    pub wager: U128,
+    pub created_at: U64,
+}
+
+// A settled PvP battle, kept in the history of both corgis
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct DuelRecord {
+    pub challenge_id: u64,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub challenger_token_id: TokenId,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub opponent_token_id: TokenId,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub winner_token_id: TokenId,
+    pub pot: U128,
+    pub fought_at: U64,
+}
+
+// Holders of an NFT from `partner_contract` may claim `accessory` for one corgi
+// per partner token until `ends_at`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct Campaign {
+    pub partner_contract: AccountId,
+    pub accessory: String,
+    pub ends_at: U64,
+}
+
+// The part of a partner's `nft_token` response we rely on
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PartnerToken {
+    pub owner_id: AccountId,
+}
+
+// One change of owner of a corgi; `price` is set for NEAR sales
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ProvenanceEntry {
+    pub from: AccountId,
+    pub to: AccountId,
+    pub price: Option<U128>,
+    pub timestamp: U64,
+}
+
+// Ring buffer of a corgi's last `MAX_PROVENANCE_ENTRIES` owner changes
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ProvenanceLog {
+    pub entries: Vector<ProvenanceEntry>,
+    pub recorded: u64, // entry `i` is kept at `i % MAX_PROVENANCE_ENTRIES`
+}
+
+// A staked corgi; it earns `weight` (its rarity tier) shares of every epoch's release
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+pub struct Stake {
+    pub owner: AccountId,
+    pub staked_at: u64,
+    pub weight: u64,
+    pub reward_debt: Balance, // `weight * reward_per_weight` already accounted for
+}
+
+#[derive(Serialize, Debug)]
+pub struct StakedCorgi {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub staked_at: U64,
+    pub weight: u64,
+    pub pending_reward: U128,
+}
+
+// A competition period; `winners` and their prizes are filled in by `close_season`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Season {
+    pub id: u64,
+    pub start_at: u64,
+    pub end_at: u64,
+    pub prize_pool: U128,
+    pub winners: Vec<(AccountId, U128)>,
+}
+
+// Standing offer to buy any corgi of a rarity tier; `price` is held by the contract
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct CollectionOffer {
+    pub id: u64,
+    pub buyer: AccountId,
+    pub rarity: Rarity,
+    pub price: U128,
+}
+
+// Bid on a single corgi, listed or not; `price` is held by the contract until the
+// owner accepts it or the bidder cancels it
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct Offer {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub bidder: AccountId,
+    pub price: U128,
+    pub made_at: U64,
+}
+
+// Minimum raise over the highest bid of an auction
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum BidIncrement {
+    Absolute(U128),
+    Bps(u32), // share of the highest bid, in basis points
+}
+
+// A NEAR purchase paid over time; the corgi stays in escrow until `paid` reaches `price`
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct InstallmentPlan {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub buyer: AccountId,
+    pub seller: AccountId,
+    pub price: U128,
+    pub paid: U128,
+    pub installment: U128, // minimum for each payment, the last one may be smaller
+    pub next_due: U64,
+}
+
+// A corgi held by the contract while it is auctioned; bids are in yoctoNEAR
+#[derive(BorshDeserialize, BorshSerialize, Debug)]
+pub struct Auction {
+    pub seller: AccountId,
+    pub ends_at: u64,
+    pub min_increment: BidIncrement,
+    pub reserve_price: Option<Balance>, // hidden from views
+    pub highest_bid: Option<(AccountId, Balance)>,
+    pub buy_now_price: Option<Balance>,
+}
+
+// An auction as shown to bidders, revealing only whether the reserve is met
+#[derive(Serialize, Debug)]
+pub struct AuctionView {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub seller: AccountId,
+    pub ends_at: U64,
+    pub min_increment: BidIncrement,
+    pub highest_bidder: Option<AccountId>,
+    pub highest_bid: Option<U128>,
+    pub min_next_bid: U128,
+    pub reserve_met: bool,
+    pub buy_now_price: Option<U128>,
+}
+
+// Owners of every token recorded under one snapshot ID
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct HolderSnapshot {
+    pub created_at: u64,
+    pub block_height: u64,
+    pub token_count: u64, // when the snapshot started
+    pub ownership_changes: u64, // `Corgi3D::ownership_changes` when the snapshot started
+    pub cursor: u64,
+    pub complete: bool,
+    pub holders: Vector<(TokenId, AccountId)>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SnapshotInfo {
+    pub id: u64,
+    pub created_at: u64,
+    pub block_height: u64,
+    pub token_count: u64,
+    pub complete: bool,
+    pub recorded: u64,
+}
+
+// A guild of accounts; stats aggregate every corgi owned by its members
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Kennel {
+    pub id: u64,
+    pub name: String,
+    pub leader: AccountId,
+    pub members: u64,
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub showcase: Vec<TokenId>,
+    pub corgi_count: u64,
+    pub total_sausage: u64,
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub treasury_corgis: Vec<TokenId>,
+    pub total_shares: u64,
+    pub shareholders: Vec<(AccountId, u64)>,
+}
+
+// Shares of a departed member waiting to be bought by a remaining member
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct ShareBuyout {
+    pub kennel_id: u64,
+    pub seller: AccountId,
+    pub shares: u64,
+    pub price: U128,
+}
+
+// A corgi locked in the contract and split into transferable shares
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Fraction {
+    pub total_shares: u64,
+    pub reserve_price: U128,
+    pub holders: Vec<(AccountId, u64)>,
+}
+
+impl Fraction {
+    fn shares_of(&self, account: &AccountId) -> u64 {
+        self.holders
+            .iter()
+            .find(|(holder, _)| holder == account)
+            .map_or(0, |(_, shares)| *shares)
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Registration {
+    pub registered_at_block: u64,
+    pub last_mint_block: u64,
+    pub mints_in_block: u64,
+}
+
+// Who last changed a config entry, and when
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct ConfigChange {
+    pub changed_at: u64,
+    pub changed_by: AccountId,
+}
+
+// Entry of the append-only admin audit log
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct AdminAction {
+    pub index: u64,
+    pub action: String,
+    pub details: String,
+    pub actor: AccountId,
+    pub timestamp: u64,
+}
+
+// Every tunable of the contract, returned by `get_full_config`
+#[derive(Serialize, Debug)]
+pub struct FullConfig {
+    pub config_version: u32,
+    pub api_version: u32,
+    pub owner_id: AccountId,
+    pub mint_price: U128,
+    pub paused: bool,
+    pub pass_price: U128,
+    pub house_price: U128,
+    pub breeding_fee: U128,
+    pub keeper_bounty: U128,
+    pub max_clones_per_corgi: u64,
+    pub house_capacity: u64,
+    pub transfer_cooldown_hours: u64,
+    pub burn_grace_hours: u64,
+    pub bond_cooldown: u64,
+    pub breed_cooldown: u64,
+    pub kinship_depth: u64,
+    pub fruit_sink: FruitSink,
+    pub mint_gate: Option<MintGate>,
+    pub premium_colors: Vec<(String, Fruit)>,
+    pub rarity_names: Vec<RarityDisplay>,
+    pub world_state: Option<WorldState>,
+    pub world_oracle: Option<AccountId>,
+    pub arenas: Vec<AccountId>,
+    pub vets: Vec<AccountId>,
+    pub market_fee_bps: u32,
+    pub fee_tiers: Vec<FeeTier>,
+    pub max_corgis_per_account: Option<u64>,
+    pub gift_expiry_hours: u64,
+    pub listing_deposit: U128,
+    pub customization_fee: U128,
+    pub id_era: u32,
+    pub changes: Vec<(String, ConfigChange)>,
+}
+
+// Sellers whose lifetime NEAR sale volume reaches `min_volume` get `discount_bps`
+// off the marketplace fee
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct FeeTier {
+    pub min_volume: U128,
+    pub discount_bps: u32,
+}
+
+// An account's standing in the fee tiers; `tier` 0 means no discount yet
+#[derive(Serialize, Debug)]
+pub struct FeeTierStatus {
+    pub tier: u64,
+    pub volume: U128,
+    pub discount_bps: u32,
+    pub fee_bps: u32,
+}
+
+// Settings applied by `new`, so a fresh deployment needs no follow-up owner calls;
+// unset fields keep their defaults
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct InitConfig {
+    pub mint_price: Option<U128>,
+    pub market_fee_bps: Option<u32>,
+    pub fee_tiers: Option<Vec<FeeTier>>,
+    pub keeper_bounty: Option<U128>,
+    pub max_corgis_per_account: Option<u64>,
+    pub transfer_cooldown_hours: Option<u64>,
+    pub burn_grace_hours: Option<u64>,
+    pub kinship_depth: Option<u64>,
+    pub base_uri: Option<String>, // token media is `{base_uri}/{token_id}`
+    pub id_era: Option<u32>, // must differ between deployments sharing the collection
+}
+
+// Anti-bot mint gate, enabled by the owner during hyped drops
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct MintGate {
+    pub delay_blocks: u64,
+    pub max_mints_per_block: u64,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, PartialEq)]
+pub struct Profile {
+    pub nickname: String,
+    #[serde(serialize_with = "serialize_token_id_option")]
+    pub avatar_token_id: Option<TokenId>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct PriceObservation {
+    pub timestamp_sec: u64,
+    pub price: U128,
+    pub seller: AccountId,
+    pub buyer: AccountId,
+}
+
+// What a wisher is looking for; unset fields match anything
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct WishlistCriteria {
+    pub rarity: Option<Rarity>,
+    pub color: Option<String>,
+    pub max_price: Option<U128>,
+}
+
+impl WishlistCriteria {
+    fn matches(&self, corgi: &Corgi) -> bool {
+        self.rarity
+            .is_none_or(|rarity| rarity == corgi.rate)
+            && self
+                .color
+                .as_ref()
+                .is_none_or(|color| normalize_color(color) == normalize_color(&corgi.color))
+            && self
+                .max_price
+                .is_none_or(|max_price| corgi.selling_price.0 <= max_price.0)
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct WishlistMatch {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub price: U128,
+    pub listed_at: u64,
+}
+
+// Breeding request waiting for its resolution callback; `stud_fee` and
+// `mint_fee` are held by the contract until then
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Breeding {
+    pub breeder: AccountId,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub dam_id: TokenId,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub stud_id: TokenId,
+    pub stud_owner: AccountId,
+    pub stud_fee: U128,
+    pub mint_fee: U128,
+    pub storage_deposit: U128, // pays for the pending mint, the unused part is refunded
+    pub spec: CorgiSpec,
+}
+
+// Temporary global event such as "Lemon Season" that tweaks gameplay odds
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone)]
+pub struct WorldState {
+    pub name: String,
+    pub start_at: U64,
+    pub end_at: U64,
+    // subtracted from the rarity roll of new corgis, so higher means rarer drops
+    pub rarity_boost: u32,
+    // maze fruit multiplier in basis points, 10_000 is unchanged
+    pub fruit_bps: u64,
+    // added to the normalized battle power of our corgis
+    pub battle_power_bonus: u64,
+}
+
+// Claim of a physical plush corgi made by burning the token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Redemption {
+    pub id: u64,
+    pub account_id: AccountId,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub shipping_hash: String, // hash of the shipping details, which stay off-chain
+    pub redeemed_at: u64,
+    pub fulfilled: bool,
+}
+
+// A house crafted from fruit and NEAR; corgis living in it get a battle bonus
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct House {
+    pub id: u64,
+    pub owner: AccountId,
+    pub capacity: u64,
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub residents: Vec<TokenId>,
+}
+
+// Corgi held by the contract until claimed with the secret behind `claim_hash` or
+// accepted by `recipient`, or returned to the sender once `expires_at` passes
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Gift {
+    pub id: u64,
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub sender: AccountId,
+    pub claim_hash: Option<String>, // hex SHA-256 of the secret in the gift link
+    pub recipient: Option<AccountId>, // the only account that can accept an addressed gift
+    pub message: String,
+    pub expires_at: U64,
+}
+
+// Corgis of one owner battling together in tournaments; dissolved when a member changes hands
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Team {
+    pub id: u64,
+    pub owner: AccountId,
+    pub name: String,
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub members: Vec<TokenId>,
+}
+
+// Single-elimination bracket funded by entry fees; `rounds` holds the teams advancing from each round
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Tournament {
+    pub id: u64,
+    pub max_teams: u64,
+    pub entry_fee: U128,
+    pub prize_pool: U128,
+    pub entrants: Vec<(u64, AccountId)>, // (team, owner when it joined)
+    pub rounds: Vec<Vec<u64>>,
+    pub winner: Option<u64>,
+}
+
+// Health record attached to a corgi by a designated vet
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct HealthCertificate {
+    pub vet: AccountId,
+    pub data: String,
+    pub issued_at: u64,
+}
+
+// Corgis sold together for one NEAR price; they can't be transferred or sold
+// on their own until the bundle is bought or cancelled
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct Bundle {
+    pub id: u64,
+    pub seller: AccountId,
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub token_ids: Vec<TokenId>,
+    pub price: U128,
+    pub listed_at: U64,
+}
+
+// An offer to swap corgis with another owner, optionally with NEAR on top that
+// the proposer paid when proposing
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct TradeProposal {
+    pub id: u64,
+    pub proposer: AccountId,
+    pub counterparty: AccountId,
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub offered: Vec<TokenId>, // the proposer's corgis
+    #[serde(serialize_with = "serialize_token_ids")]
+    pub wanted: Vec<TokenId>,  // the counterparty's corgis
+    pub near_sweetener: U128,
+    pub proposed_at: U64,
+}
+
+// A corgi for sale, as shown to marketplace frontends
+#[derive(Serialize, Debug)]
+pub struct MarketListing {
+    #[serde(serialize_with = "serialize_token_id")]
+    pub token_id: TokenId,
+    pub price: U128,
+    pub currency: Currency,
+    pub trust_score: u64,
+    pub starts_at: Option<U64>, // set while the listing is scheduled and not yet buyable
+}
+
+/// How `pick_random_holders` weighs each token of a holder.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum HolderWeighting {
+    // every corgi counts once
+    Holdings,
+    // every corgi counts its rarity tier, 1 for common to 5 for ultra rare
+    Rarity,
+}
+
+/// What the owner gets for burning a corgi with `burn_corgi`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum BurnReward {
+    // `BURN_FRUIT_PER_TIER` of every fruit kind per rarity tier
+    Fruit,
+    // a discount on later public mints, `BURN_CREDIT_BPS_PER_TIER` of the mint price per rarity tier
+    MintCredit,
+}
+
+/// What a listing price is denominated in.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Currency {
+    // yoctoNEAR, paid as the attached deposit of `buy_corgi`
+    Near,
+    // smallest units of a whitelisted NEP-141 token, paid through `ft_transfer_call`
+    Ft(AccountId),
+}
+
+/// Where fruit paid as fees ends up.
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum FruitSink {
+    Burn,
+    // credited to the contract account's fruit balance
+    Treasury,
+    // pooled for distribution to stakers
+    Stakers,
+}
+
+// Cumulative fruit routed to each sink
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug, Clone)]
+pub struct FruitSinkTotals {
+    pub burned: Fruit,
+    pub treasury: Fruit,
+    pub stakers: Fruit,
+}
+
+// Lifetime flows of one fruit kind, returned by `get_fruit_stats`
+#[derive(Serialize, Debug)]
+pub struct FruitStats {
+    pub kind: u64,
+    pub name: String,
+    pub minted: u64,
+    pub burned: u64,
+    pub transferred: u64,
+    pub held: u64, // minted but not burned, including the treasury and stakers' pool
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Debug)]
+pub struct PartnerQuota {
+    pub quota: u64,
+    pub minted: u64,
+}
+
 // Begin implementation
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Corgi3D {
     pub corgi_to_account: UnorderedMap<TokenId, AccountId>,
     pub account_gives_access: UnorderedMap<AccountIdHash, UnorderedSet<AccountIdHash>>, // Vec<u8> is sha256 of account, makes it safer and is how fungible token also works
+    pub access_grantors: LookupMap<AccountIdHash, Vec<AccountIdHash>>, // escrow -> accounts giving it access
+    pub hashed_account_ids: LookupMap<AccountIdHash, AccountId>, // reverse of the hashes in the access maps
     pub owner_id: AccountId,
     pub corgis: UnorderedMap<TokenId, Corgi>,
     pub account_corgis: UnorderedMap<AccountIdHash, UnorderedSet<TokenId>>,
     pub next_corgi_id: TokenId,
     pub account_fruit: UnorderedMap<AccountId, Fruit>,
     pub account_maze_game: UnorderedMap<AccountId, MazeGame>,
+    pub pending_merges: UnorderedMap<AccountId, AccountId>, // source account -> target account
+    pub tick_cursor: u64,
+    pub treasury_balance: Balance,
+    pub keeper_bounty: Balance,
+    pub keeper_period_start: u64,
+    pub keeper_paid_in_period: Balance,
+    pub premium_colors: UnorderedMap<String, Fruit>, // color -> fruit burned to use it
+    pub partners: UnorderedMap<AccountId, PartnerQuota>,
+    pub fruit_sink: FruitSink,
+    pub fruit_sink_totals: FruitSinkTotals,
+    pub stakers_fruit_pool: Fruit,
+    pub deposits: LookupMap<AccountId, Balance>, // shared escrow ledger for offers, auctions, trades
+    pub wishlists: UnorderedMap<AccountId, Vec<WishlistCriteria>>,
+    pub wishlist_matches: LookupMap<AccountId, Vec<WishlistMatch>>,
+    pub wishlist_buckets: LookupMap<Vec<u8>, UnorderedSet<AccountId>>, // wishers by the (rarity, color) of an entry
+    pub price_observations: LookupMap<Rarity, Vec<PriceObservation>>,
+    pub loans: UnorderedMap<TokenId, Loan>,
+    pub loaned_principal: Balance, // outstanding principal of all loans
+    pub collection_offers: UnorderedMap<u64, CollectionOffer>,
+    pub next_collection_offer_id: u64,
+    pub snapshots: UnorderedMap<u64, HolderSnapshot>,
+    pub next_snapshot_id: u64,
+    pub ownership_changes: u64, // bumped whenever a token changes owner, see `snapshot_holders`
+    pub profiles: UnorderedMap<AccountId, Profile>,
+    pub nicknames: LookupMap<String, AccountId>, // lowercased nickname -> account
+    pub kennels: UnorderedMap<u64, Kennel>,
+    pub next_kennel_id: u64,
+    pub account_kennel: LookupMap<AccountId, u64>,
+    pub kennel_treasury: LookupMap<TokenId, u64>, // corgi held by the contract -> kennel
+    pub share_buyouts: UnorderedMap<AccountId, ShareBuyout>,
+    pub fractions: UnorderedMap<TokenId, Fraction>,
+    pub mint_gate: Option<MintGate>,
+    pub registrations: LookupMap<AccountId, Registration>,
+    pub selling_corgis: UnorderedSet<TokenId>,
+    pub bundles: UnorderedMap<u64, Bundle>,
+    pub next_bundle_id: u64,
+    pub bundled_corgis: LookupMap<TokenId, u64>, // corgi -> bundle it is sold in
+    pub trades: UnorderedMap<u64, TradeProposal>,
+    pub next_trade_id: u64,
+    pub outgoing_trades: LookupMap<AccountId, Vec<u64>>,
+    pub incoming_trades: LookupMap<AccountId, Vec<u64>>,
+    pub rarity_corgis: LookupMap<Rarity, UnorderedSet<TokenId>>,
+    pub rarity_names: LookupMap<Rarity, String>,
+    pub allowances: LookupMap<(AccountId, AccountId), Balance>, // (owner, spender) -> escrow allowance
+    pub arenas: UnorderedSet<AccountId>, // trusted partner contracts implementing `BattleInterface`
+    pub battles: UnorderedMap<u64, Battle>,
+    pub next_battle_id: u64,
+    pub challenges: UnorderedMap<u64, Challenge>,
+    pub next_challenge_id: u64,
+    pub duel_history: LookupMap<TokenId, Vec<DuelRecord>>, // latest `MAX_DUEL_HISTORY` duels of a corgi
+    pub seasons: UnorderedMap<u64, Season>,
+    pub current_season: Option<u64>,
+    pub season_scores: UnorderedMap<AccountId, u64>,
+    pub season_leaders: Vec<(AccountId, u64)>, // highest score first, at most `MAX_LEADERBOARD_SIZE`
+    pub season_prizes: LookupMap<AccountId, Balance>, // unclaimed prizes of closed seasons
+    pub vets: UnorderedSet<AccountId>,
+    pub certificates: LookupMap<TokenId, Vec<HealthCertificate>>, // newest last
+    pub studs: UnorderedMap<TokenId, U128>, // stud -> breeding fee
+    pub breedings: UnorderedMap<u64, Breeding>,
+    pub next_breeding_id: u64,
+    pub last_bred_at: LookupMap<TokenId, u64>,
+    pub houses: UnorderedMap<u64, House>,
+    pub next_house_id: u64,
+    pub corgi_house: LookupMap<TokenId, u64>,
+    pub world_state: Option<WorldState>,
+    pub world_oracle: Option<AccountId>, // may set the world state besides the owner
+    pub redemptions: Vector<Redemption>,
+    pub transfer_cooldown: u64, // ns a corgi stays untransferable after mint or purchase, 0 disables
+    pub transfer_locks: LookupMap<TokenId, u64>, // token -> locked until
+    pub config_changes: UnorderedMap<String, ConfigChange>,
+    pub admin_log: LookupMap<u64, AdminAction>,
+    pub admin_log_len: u64, // total entries ever logged, the next index
+    pub trait_counts: LookupMap<String, u64>, // "trait:value" -> corgis having it
+    pub rarity_ranking: TreeMap<(u64, TokenId), ()>, // (u64::MAX - score, corgi), rarest first
+    pub ranked_scores: LookupMap<TokenId, u64>, // corgi -> score it is ranked by
+    pub held_votes: LookupMap<AccountId, u64>, // governance weight of the corgis an account holds
+    pub delegates: LookupMap<AccountId, AccountId>, // absent means the account votes itself
+    pub votes: LookupMap<AccountId, u64>, // weight delegated to an account, own included
+    pub breeding_licenses: LookupMap<AccountId, u64>,
+    pub fruit_kinds: Vector<FruitKind>,
+    pub fruit_kind_ids: LookupMap<String, u64>, // name -> ID
+    pub rentals: UnorderedMap<TokenId, Rental>,
+    pub rented_out: LookupMap<AccountId, Vec<TokenId>>, // owner -> corgis lent to others
+    pub renting: LookupMap<AccountId, Vec<TokenId>>, // renter -> corgis borrowed
+    pub token_approvals: LookupMap<TokenId, HashMap<AccountId, u64>>, // approved account -> approval ID
+    pub next_approval_id: u64,
+    pub accepted_fts: UnorderedSet<AccountId>, // NEP-141 contracts listings may be priced in
+    pub ft_mint_prices: LookupMap<AccountId, Balance>, // NEP-141 contract -> price of a public mint in it
+    pub ft_balances: LookupMap<(AccountId, AccountId), Balance>, // (account, token) -> withdrawable tokens
+    pub listing_currencies: LookupMap<TokenId, Currency>, // absent means NEAR
+    pub trainings: LookupMap<TokenId, Training>,
+    pub market_fee_bps: u32, // taken from NEAR sales into the treasury
+    pub fee_tiers: Vec<FeeTier>, // ascending by `min_volume`
+    pub sale_volume: LookupMap<AccountId, Balance>, // lifetime NEAR sale proceeds per seller
+    pub max_corgis_per_account: Option<u64>,
+    pub cap_exempt: UnorderedSet<AccountId>, // e.g. the adoption center; the contract itself is always exempt
+    pub auctions: UnorderedMap<TokenId, Auction>,
+    pub campaigns: UnorderedMap<u64, Campaign>,
+    pub next_campaign_id: u64,
+    pub campaign_claims: LookupMap<(u64, String), TokenId>, // (campaign, partner token) -> corgi that claimed
+    pub listing_starts: LookupMap<TokenId, u64>, // scheduled listings -> when they go live
+    pub message_updates: LookupMap<TokenId, (u64, u32)>, // (day, updates made that day)
+    pub kinship_depth: u64, // generations checked for breeding between relatives
+    pub editions: UnorderedMap<u64, Edition>,
+    pub next_edition_id: u64,
+    pub mint_price: Balance,
+    pub paused: bool, // minting and trading stopped by the owner
+    pub customization_fee: Balance,
+    pub total_minted: u64,
+    pub total_burned: u64,
+    pub mint_credits: LookupMap<AccountId, Balance>, // earned by burning, spent on public mint fees
+    pub highest_sale_price: Balance,
+    pub top_holders: Vec<(AccountId, u64)>, // most corgis first, at most `MAX_LEADERBOARD_SIZE`
+    pub stakes: UnorderedMap<TokenId, Stake>,
+    pub account_stakes: LookupMap<AccountId, Vec<TokenId>>,
+    pub total_stake_weight: u64,
+    pub staking_reward_pool: Balance, // funded from the treasury, not released yet
+    pub reward_per_weight: u128, // released per unit of weight, scaled by `STAKING_REWARD_PRECISION`
+    pub staking_epoch: u64, // last epoch released
+    pub staking_rewards: LookupMap<AccountId, Balance>, // settled on unstake, not claimed yet
+    pub provenance: LookupMap<TokenId, ProvenanceLog>,
+    pub minter_public_key: Option<Vec<u8>>, // ed25519 key signing mint vouchers
+    pub voucher_mints: LookupMap<u64, TokenId>, // redeemed voucher nonce -> corgi minted
+    pub voucher_nonces: LookupSet<u64>, // of vouchers paid for, revealed or not
+    pub mint_drop: Option<MintDrop>,
+    pub next_drop_id: u64,
+    pub drop_allowlist: UnorderedSet<AccountId>, // of the current drop
+    pub drop_mints: LookupMap<(u64, AccountId), u64>, // (drop, account) -> corgis minted
+    pub pending_mints: UnorderedMap<u64, PendingMint>,
+    pub next_pending_mint_id: u64,
+    pub account_pending_mints: LookupMap<AccountId, Vec<u64>>,
+    pub base_uri: Option<String>,
+    pub admins: UnorderedSet<AccountId>, // may run operational owner actions, not change config
+    pub teams: UnorderedMap<u64, Team>,
+    pub next_team_id: u64,
+    pub corgi_team: LookupMap<TokenId, u64>,
+    pub tournaments: UnorderedMap<u64, Tournament>,
+    pub next_tournament_id: u64,
+    pub gifts: UnorderedMap<u64, Gift>,
+    pub next_gift_id: u64,
+    pub gift_expiry: u64, // ns an unclaimed gift waits before the sender can take it back
+    pub extras: LookupMap<TokenId, String>, // app-specific JSON attached by the owner
+    pub renderers: UnorderedSet<String>, // endpoints token media may be served from
+    pub token_media: LookupMap<TokenId, TokenMedia>,
+    pub fruit_minted: Fruit,
+    pub fruit_transferred: Fruit, // between accounts, merges included
+    pub installment_plans: UnorderedMap<TokenId, InstallmentPlan>,
+    pub installment_forfeit_bps: u32, // share of the paid amount a defaulting buyer loses to the seller
+    pub moderators: UnorderedSet<AccountId>, // may curate and hide corgis besides the owner
+    pub hidden_corgis: UnorderedSet<TokenId>,
+    pub featured: Vec<TokenId>, // homepage carousel, in display order
+    pub items: Vector<Item>,
+    pub item_balances: LookupMap<(AccountId, u64), u64>,
+    pub item_applications: LookupMap<(TokenId, u64), u32>,
+    pub login_nonces: LookupMap<AccountId, LoginNonce>,
+    pub last_active: LookupMap<AccountId, u64>, // last mint, transfer or login by the account
+    pub burn_registry: Option<AccountId>,
+    pub pair_trades: LookupMap<(AccountId, AccountId), u32>, // sorted pair -> sales between them
+    pub trade_history: LookupMap<TokenId, Vec<AccountId>>, // first seller, then each buyer
+    pub rarity_quotas: LookupMap<Rarity, u64>, // VERY RARE and ULTRA RARE mints allowed per season
+    pub season_rarity_minted: LookupMap<Rarity, u64>, // reset by `start_season`
+    pub rarity_reservations: LookupMap<Rarity, u64>, // quota slots held by pending mints until revealed
+    pub listing_deposit: Balance, // held while a corgi is listed, 0 to disable
+    pub edition_listing_deposits: LookupMap<u64, Balance>, // overrides `listing_deposit`
+    pub corgi_editions: LookupMap<TokenId, u64>, // corgis minted from an edition
+    pub listing_deposits: LookupMap<TokenId, (AccountId, Balance)>, // listed corgi -> depositor
+    pub flash_sales: Vec<FlashSale>, // running or scheduled, expired ones dropped on create
+    pub next_flash_sale_id: u64,
+    pub flash_sale_purchases: LookupMap<(u64, AccountId), u64>,
+    pub icon: Option<String>, // collection icon in `nft_metadata`, as a data URL
+    pub burn_grace_period: u64, // ns after a purchase during which `delete_corgi` refuses, 0 disables
+    pub purchased_at: LookupMap<TokenId, u64>, // last marketplace purchase
+    pub id_era: u32, // high bits of every token ID minted here, see `token_id_for`
+    pub offers: UnorderedMap<TokenId, Vec<Offer>>, // oldest first
+    pub account_offers: LookupMap<AccountId, Vec<TokenId>>, // bidder -> corgis they made offers on
+    pub last_harvested: LookupMap<TokenId, u64>,
+    pub migration: Option<MigrationProgress>, // `None` once every record is in the current layout
 }
 
 impl Default for Corgi3D {
@@ -133,411 +1924,421 @@ impl Default for Corgi3D {
     }
 }
 
-/// Methods not in the strict scope of the NFT spec (NEP171)
+/// Entry points are spread over the `nep4`, `marketplace`, `gameplay`, `storage`,
+/// `admin` and `views` modules, each with its internal helpers in a plain `impl`.
 #[near_bindgen]
 impl Corgi3D {
     #[init]
-    pub fn new(owner_id: AccountId) -> Self {
+    pub fn new(
+        owner_id: AccountId,
+        config: Option<InitConfig>,
+        admins: Option<Vec<AccountId>>,
+    ) -> Self {
         assert!(
             env::is_valid_account_id(owner_id.as_bytes()),
             "Owner's account ID is invalid."
         );
         assert!(!env::state_exists(), "Already initialized");
-        Self {
+        Self::initial_state(owner_id, config, admins)
+    }
+
+    /// State of a fresh deployment, also the base `migrate` fills from the old state.
+    fn initial_state(
+        owner_id: AccountId,
+        config: Option<InitConfig>,
+        admins: Option<Vec<AccountId>>,
+    ) -> Self {
+        let mut contract = Self {
             corgi_to_account: UnorderedMap::new(b"corgi-belongs-to".to_vec()),
             account_gives_access: UnorderedMap::new(b"gives-access".to_vec()),
+            access_grantors: LookupMap::new(b"access-grantors".to_vec()),
+            hashed_account_ids: LookupMap::new(b"hashed-account-ids".to_vec()),
             owner_id,
             corgis: UnorderedMap::new(b"corgis".to_vec()),
             account_corgis: UnorderedMap::new(b"account-corgis".to_vec()),
             next_corgi_id: 0,
             account_fruit: UnorderedMap::new(b"account-fruit".to_vec()),
             account_maze_game: UnorderedMap::new(b"account-maze-game".to_vec()),
-        }
-    }
-
-    pub fn get_corgis_by_owner(&self, owner: AccountId) -> Vec<Corgi> {
-        self.get_corgis_by_owner_range(owner, 0, self.next_corgi_id)
-    }
-
-    pub fn get_corgis_by_owner_range(
-        &self,
-        owner: AccountId,
-        from_index: u64,
-        limit: u64,
-    ) -> Vec<Corgi> {
-        let hash = env::sha256(owner.as_bytes());
-        let corgi_ids = self.account_corgis.get(&hash).expect("Account not found");
-        let corgi_ids_vec = corgi_ids.as_vector();
-        (from_index..std::cmp::min(from_index + limit, corgi_ids.len()))
-            .filter_map(|index| {
-                corgi_ids_vec
-                    .get(index)
-                    .map(|corgi_id| self.corgis.get(&corgi_id).unwrap())
-            })
-            .collect()
-    }
-
-    pub fn get_corgi(&self, id: TokenId) -> Corgi {
-        self.corgis.get(&id).expect("Corgi not found")
-    }
-
-    pub fn delete_corgi(&mut self, id: TokenId) {
-        let _corgi = self.corgis.get(&id).expect("Corgi not found");
-        let account = self.corgi_to_account.get(&id).unwrap();
-        let predecessor = env::predecessor_account_id();
-        if account == predecessor || self.check_access(account.clone()) {
-            self.delete_corgi_from_account(id, account);
-            self.corgis.remove(&id);
-        } else {
-            env::panic(b"Don't have permission to delete corgi");
-        }
-    }
-
-    pub fn transfer_from_with_message(
-        &mut self,
-        owner_id: AccountId,
-        new_owner_id: AccountId,
-        token_id: TokenId,
-        message: String,
-    ) {
-        self.transfer_from(owner_id, new_owner_id, token_id);
-        let mut corgi = self.corgis.get(&token_id).unwrap();
-        corgi.message = message;
-        let _ = self.corgis.insert(&token_id, &corgi);
-    }
-
-    pub fn transfer_with_message(
-        &mut self,
-        new_owner_id: AccountId,
-        token_id: TokenId,
-        message: String,
-    ) {
-        self.transfer(new_owner_id, token_id);
-        let mut corgi = self.corgis.get(&token_id).unwrap();
-        corgi.message = message;
-        let _ = self.corgis.insert(&token_id, &corgi);
-    }
-
-    pub fn display_global_corgis(&self) -> Vec<Corgi> {
-        self.display_global_corgis_range(0, self.next_corgi_id)
-    }
-
-    pub fn display_global_corgis_range(&self, from_index: u64, limit: u64) -> Vec<Corgi> {
-        (from_index..std::cmp::min(from_index + limit, self.next_corgi_id))
-            .filter_map(|index| self.corgis.get(&index))
-            .collect()
-    }
-
-    #[payable]
-    pub fn create_corgi(
-        &mut self,
-        name: String,
-        color: String,
-        background_color: String,
-        quote: String,
-    ) -> (String, TokenId) {
-        let attached_deposit = env::attached_deposit();
-        if attached_deposit != 3_000_000_000_000_000_000_000_000 {
-            env::panic(b"Each new corgi cost 3 NEAR");
-        }
-        let predecessor = env::predecessor_account_id();
-        let (rate, sausage) = self.generate_rate_sausage();
-        let id = self.next_corgi_id;
-        self.next_corgi_id += 1;
-        let corgi = Corgi {
-            id,
-            name: name.clone(),
-            color,
-            background_color,
-            quote,
-            rate,
-            sausage,
-            selling: false,
-            selling_price: U128(0),
-            message: "".to_string(),
-            sender: "".to_string(),
+            pending_merges: UnorderedMap::new(b"pending-merges".to_vec()),
+            tick_cursor: 0,
+            treasury_balance: 0,
+            keeper_bounty: DEFAULT_KEEPER_BOUNTY,
+            keeper_period_start: 0,
+            keeper_paid_in_period: 0,
+            premium_colors: UnorderedMap::new(b"premium-colors".to_vec()),
+            partners: UnorderedMap::new(b"partners".to_vec()),
+            fruit_sink: FruitSink::Burn,
+            fruit_sink_totals: FruitSinkTotals {
+                burned: Fruit::default(),
+                treasury: Fruit::default(),
+                stakers: Fruit::default(),
+            },
+            stakers_fruit_pool: Fruit::default(),
+            deposits: LookupMap::new(b"deposits".to_vec()),
+            wishlists: UnorderedMap::new(b"wishlists".to_vec()),
+            wishlist_matches: LookupMap::new(b"wishlist-matches".to_vec()),
+            wishlist_buckets: LookupMap::new(b"wishlist-buckets".to_vec()),
+            price_observations: LookupMap::new(b"price-observations".to_vec()),
+            loans: UnorderedMap::new(b"loans".to_vec()),
+            loaned_principal: 0,
+            collection_offers: UnorderedMap::new(b"collection-offers".to_vec()),
+            next_collection_offer_id: 0,
+            snapshots: UnorderedMap::new(b"snapshots".to_vec()),
+            next_snapshot_id: 0,
+            ownership_changes: 0,
+            profiles: UnorderedMap::new(b"profiles".to_vec()),
+            nicknames: LookupMap::new(b"nicknames".to_vec()),
+            kennels: UnorderedMap::new(b"kennels".to_vec()),
+            next_kennel_id: 0,
+            account_kennel: LookupMap::new(b"account-kennel".to_vec()),
+            kennel_treasury: LookupMap::new(b"kennel-treasury".to_vec()),
+            share_buyouts: UnorderedMap::new(b"share-buyouts".to_vec()),
+            fractions: UnorderedMap::new(b"fractions".to_vec()),
+            mint_gate: None,
+            registrations: LookupMap::new(b"registrations".to_vec()),
+            selling_corgis: UnorderedSet::new(b"selling-corgis".to_vec()),
+            bundles: UnorderedMap::new(b"bundles".to_vec()),
+            next_bundle_id: 0,
+            bundled_corgis: LookupMap::new(b"bundled-corgis".to_vec()),
+            trades: UnorderedMap::new(b"trades".to_vec()),
+            next_trade_id: 0,
+            outgoing_trades: LookupMap::new(b"outgoing-trades".to_vec()),
+            incoming_trades: LookupMap::new(b"incoming-trades".to_vec()),
+            rarity_corgis: LookupMap::new(b"rarity-corgis".to_vec()),
+            rarity_names: LookupMap::new(b"rarity-names".to_vec()),
+            allowances: LookupMap::new(b"allowances".to_vec()),
+            arenas: UnorderedSet::new(b"arenas".to_vec()),
+            battles: UnorderedMap::new(b"battles".to_vec()),
+            next_battle_id: 0,
+            challenges: UnorderedMap::new(b"challenges".to_vec()),
+            next_challenge_id: 0,
+            duel_history: LookupMap::new(b"duel-history".to_vec()),
+            seasons: UnorderedMap::new(b"seasons".to_vec()),
+            current_season: None,
+            season_scores: UnorderedMap::new(b"season-scores".to_vec()),
+            season_leaders: vec![],
+            season_prizes: LookupMap::new(b"season-prizes".to_vec()),
+            vets: UnorderedSet::new(b"vets".to_vec()),
+            certificates: LookupMap::new(b"certificates".to_vec()),
+            studs: UnorderedMap::new(b"studs".to_vec()),
+            breedings: UnorderedMap::new(b"breedings".to_vec()),
+            next_breeding_id: 0,
+            last_bred_at: LookupMap::new(b"last-bred-at".to_vec()),
+            houses: UnorderedMap::new(b"houses".to_vec()),
+            next_house_id: 0,
+            corgi_house: LookupMap::new(b"corgi-house".to_vec()),
+            world_state: None,
+            world_oracle: None,
+            redemptions: Vector::new(b"redemptions".to_vec()),
+            transfer_cooldown: 0,
+            transfer_locks: LookupMap::new(b"transfer-locks".to_vec()),
+            config_changes: UnorderedMap::new(b"config-changes".to_vec()),
+            admin_log: LookupMap::new(b"admin-log".to_vec()),
+            admin_log_len: 0,
+            trait_counts: LookupMap::new(b"trait-counts".to_vec()),
+            rarity_ranking: TreeMap::new(b"rarity-ranking".to_vec()),
+            ranked_scores: LookupMap::new(b"ranked-scores".to_vec()),
+            held_votes: LookupMap::new(b"held-votes".to_vec()),
+            delegates: LookupMap::new(b"delegates".to_vec()),
+            votes: LookupMap::new(b"votes".to_vec()),
+            breeding_licenses: LookupMap::new(b"breeding-licenses".to_vec()),
+            fruit_kinds: Vector::new(b"fruit-kinds".to_vec()),
+            fruit_kind_ids: LookupMap::new(b"fruit-kind-ids".to_vec()),
+            rentals: UnorderedMap::new(b"rentals".to_vec()),
+            rented_out: LookupMap::new(b"rented-out".to_vec()),
+            renting: LookupMap::new(b"renting".to_vec()),
+            token_approvals: LookupMap::new(b"token-approvals".to_vec()),
+            next_approval_id: 0,
+            accepted_fts: UnorderedSet::new(b"accepted-fts".to_vec()),
+            ft_mint_prices: LookupMap::new(b"ft-mint-prices".to_vec()),
+            ft_balances: LookupMap::new(b"ft-balances".to_vec()),
+            listing_currencies: LookupMap::new(b"listing-currencies".to_vec()),
+            trainings: LookupMap::new(b"trainings".to_vec()),
+            market_fee_bps: 0,
+            fee_tiers: vec![],
+            sale_volume: LookupMap::new(b"sale-volume".to_vec()),
+            max_corgis_per_account: None,
+            cap_exempt: UnorderedSet::new(b"cap-exempt".to_vec()),
+            auctions: UnorderedMap::new(b"auctions".to_vec()),
+            campaigns: UnorderedMap::new(b"campaigns".to_vec()),
+            next_campaign_id: 0,
+            campaign_claims: LookupMap::new(b"campaign-claims".to_vec()),
+            listing_starts: LookupMap::new(b"listing-starts".to_vec()),
+            message_updates: LookupMap::new(b"message-updates".to_vec()),
+            kinship_depth: DEFAULT_KINSHIP_DEPTH,
+            editions: UnorderedMap::new(b"editions".to_vec()),
+            next_edition_id: 0,
+            mint_price: MINT_PRICE,
+            paused: false,
+            customization_fee: DEFAULT_CUSTOMIZATION_FEE,
+            total_minted: 0,
+            total_burned: 0,
+            mint_credits: LookupMap::new(b"mint-credits".to_vec()),
+            highest_sale_price: 0,
+            top_holders: vec![],
+            stakes: UnorderedMap::new(b"stakes".to_vec()),
+            account_stakes: LookupMap::new(b"account-stakes".to_vec()),
+            total_stake_weight: 0,
+            staking_reward_pool: 0,
+            reward_per_weight: 0,
+            staking_epoch: 0,
+            staking_rewards: LookupMap::new(b"staking-rewards".to_vec()),
+            provenance: LookupMap::new(b"provenance".to_vec()),
+            minter_public_key: None,
+            voucher_mints: LookupMap::new(b"voucher-mints".to_vec()),
+            voucher_nonces: LookupSet::new(b"voucher-nonces".to_vec()),
+            mint_drop: None,
+            next_drop_id: 0,
+            drop_allowlist: UnorderedSet::new(b"drop-allowlist".to_vec()),
+            drop_mints: LookupMap::new(b"drop-mints".to_vec()),
+            pending_mints: UnorderedMap::new(b"pending-mints".to_vec()),
+            next_pending_mint_id: 0,
+            account_pending_mints: LookupMap::new(b"account-pending-mints".to_vec()),
+            base_uri: None,
+            admins: UnorderedSet::new(b"admins".to_vec()),
+            teams: UnorderedMap::new(b"teams".to_vec()),
+            next_team_id: 0,
+            corgi_team: LookupMap::new(b"corgi-team".to_vec()),
+            tournaments: UnorderedMap::new(b"tournaments".to_vec()),
+            next_tournament_id: 0,
+            gifts: UnorderedMap::new(b"gifts".to_vec()),
+            next_gift_id: 0,
+            gift_expiry: DEFAULT_GIFT_EXPIRY,
+            extras: LookupMap::new(b"extras".to_vec()),
+            renderers: UnorderedSet::new(b"renderers".to_vec()),
+            token_media: LookupMap::new(b"token-media".to_vec()),
+            fruit_minted: Fruit::default(),
+            fruit_transferred: Fruit::default(),
+            installment_plans: UnorderedMap::new(b"installment-plans".to_vec()),
+            installment_forfeit_bps: DEFAULT_INSTALLMENT_FORFEIT_BPS,
+            moderators: UnorderedSet::new(b"moderators".to_vec()),
+            hidden_corgis: UnorderedSet::new(b"hidden-corgis".to_vec()),
+            featured: vec![],
+            items: Vector::new(b"items".to_vec()),
+            item_balances: LookupMap::new(b"item-balances".to_vec()),
+            item_applications: LookupMap::new(b"item-applications".to_vec()),
+            login_nonces: LookupMap::new(b"login-nonces".to_vec()),
+            last_active: LookupMap::new(b"last-active".to_vec()),
+            burn_registry: None,
+            pair_trades: LookupMap::new(b"pair-trades".to_vec()),
+            trade_history: LookupMap::new(b"trade-history".to_vec()),
+            rarity_quotas: LookupMap::new(b"rarity-quotas".to_vec()),
+            season_rarity_minted: LookupMap::new(b"season-rarity-minted".to_vec()),
+            rarity_reservations: LookupMap::new(b"rarity-reservations".to_vec()),
+            listing_deposit: 0,
+            edition_listing_deposits: LookupMap::new(b"edition-listing-deposits".to_vec()),
+            corgi_editions: LookupMap::new(b"corgi-editions".to_vec()),
+            listing_deposits: LookupMap::new(b"listing-deposits".to_vec()),
+            flash_sales: vec![],
+            next_flash_sale_id: 0,
+            flash_sale_purchases: LookupMap::new(b"flash-sale-purchases".to_vec()),
+            icon: None,
+            burn_grace_period: 0,
+            purchased_at: LookupMap::new(b"purchased-at".to_vec()),
+            id_era: 0,
+            offers: UnorderedMap::new(b"offers".to_vec()),
+            account_offers: LookupMap::new(b"account-offers".to_vec()),
+            last_harvested: LookupMap::new(b"last-harvested".to_vec()),
+            migration: None,
         };
-        self.corgis.insert(&id, &corgi);
-        self.save_corgi_to_account(id, predecessor);
-        (name, id)
-    }
-
-    pub fn sell_corgi(&mut self, id: TokenId, price: U128) {
-        let mut corgi = self.corgis.get(&id).expect("Corgi not found");
-        let account = self.corgi_to_account.get(&id).unwrap();
-        let predecessor = env::predecessor_account_id();
-        if account == predecessor || self.check_access(account.clone()) {
-            corgi.selling = true;
-            corgi.selling_price = price;
-            self.corgis.insert(&id, &corgi);
-        } else {
-            env::panic(b"Don't have permission to sell corgi");
+        for name in DEFAULT_FRUIT_NAMES.iter() {
+            contract.register_fruit_kind(name.to_string(), "".to_string());
         }
-    }
-
-    #[payable]
-    pub fn buy_corgi(&mut self, id: TokenId) -> Promise {
-        let mut corgi = self.corgis.get(&id).expect("Corgi not found");
-        let seller = self.corgi_to_account.get(&id).unwrap();
-        let buyer = env::predecessor_account_id();
-        let attached_deposit = env::attached_deposit();
-        if attached_deposit < corgi.selling_price.0 {
-            env::panic(b"Don't pay enough money to buy corgi");
+        contract.items.push(&Item {
+            id: 0,
+            name: "vitamin".to_string(),
+            cost: Fruit::of_each(DEFAULT_FRUIT_NAMES.len() as u64, VITAMIN_FRUIT_PER_KIND),
+            breed_cooldown_reduction: VITAMIN_COOLDOWN_REDUCTION_HOURS * 60 * 60 * 1_000_000_000,
+            max_per_token: VITAMIN_MAX_PER_TOKEN,
+        });
+        if let Some(config) = config {
+            contract.apply_init_config(config);
         }
-        corgi.selling = false;
-        self.corgis.insert(&id, &corgi);
-        self.delete_corgi_from_account(id, seller.clone());
-        self.save_corgi_to_account(id, buyer);
-        Promise::new(seller).transfer(attached_deposit)
-    }
-
-    pub fn new_maze_game(&mut self) -> MazeGame {
-        let predecessor = env::predecessor_account_id();
-        let mut fruit = HashSet::new();
-        let mut rng = self.random_rng();
-        let total = 10 + rng.next_u32() % 10;
-        for _ in 0..total {
-            let kind = (rng.next_u32() % (TOTAL as u32)) as u64;
-            let x = (rng.next_u32() % 10) as u64;
-            let y = (rng.next_u32() % 10) as u64;
-            fruit.insert(MazeFruit { kind, x, y });
+        for admin in admins.unwrap_or_default() {
+            contract.admins.insert(&admin);
         }
-        let game = MazeGame {
-            fruit: Vec::from_iter(fruit),
-        };
-        self.account_maze_game.insert(&predecessor, &game);
-        game
+        contract
     }
+}
 
-    pub fn finish_maze_game(&mut self, eat: Vec<MazeFruit>) {
-        let predecessor = env::predecessor_account_id();
-        let game = self.account_maze_game.get(&predecessor).unwrap();
-        let mut fruit: HashSet<_> = HashSet::from_iter(game.fruit);
-        let mut account_fruit = self.account_fruit(predecessor.clone());
-        for e in eat {
-            if fruit.remove(&e) {
-                account_fruit.count[e.kind as usize] += 1;
-            }
-        }
-        self.account_fruit.insert(&predecessor, &account_fruit);
-        self.account_maze_game.remove(&predecessor);
-    }
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
 
-    pub fn account_fruit(&self, account_id: AccountId) -> Fruit {
-        self.account_fruit.get(&account_id).unwrap_or(Fruit {
-            count: [0u64; TOTAL],
-        })
-    }
+/// Bytes a corgi minted from `spec` is assumed to take.
+fn mint_storage_estimate(spec: &CorgiSpec) -> u64 {
+    let string_bytes =
+        spec.name.len() + spec.color.len() + spec.background_color.len() + spec.quote.len();
+    MINT_STORAGE_ESTIMATE + string_bytes as u64
 }
 
-#[near_bindgen]
-impl NEP171 for Corgi3D {
-    fn grant_access(&mut self, escrow_account_id: AccountId) {
-        let escrow_hash = env::sha256(escrow_account_id.as_bytes());
-        let predecessor = env::predecessor_account_id();
-        let predecessor_hash = env::sha256(predecessor.as_bytes());
-
-        let mut access_set = match self.account_gives_access.get(&predecessor_hash) {
-            Some(existing_set) => existing_set,
-            None => UnorderedSet::new(b"new-access-set".to_vec()),
-        };
-        access_set.insert(&escrow_hash);
-        self.account_gives_access
-            .insert(&predecessor_hash, &access_set);
-    }
+/// Bytes a pending mint of `spec` and the storage reserved for its corgi are
+/// assumed to take together.
+fn pending_mint_storage_estimate(spec: &CorgiSpec) -> u64 {
+    let string_bytes =
+        spec.name.len() + spec.color.len() + spec.background_color.len() + spec.quote.len();
+    PENDING_MINT_STORAGE_ESTIMATE + string_bytes as u64 + mint_storage_estimate(spec)
+}
 
-    fn revoke_access(&mut self, escrow_account_id: AccountId) {
-        let predecessor = env::predecessor_account_id();
-        let predecessor_hash = env::sha256(predecessor.as_bytes());
-        let mut existing_set = match self.account_gives_access.get(&predecessor_hash) {
-            Some(existing_set) => existing_set,
-            None => env::panic(b"Access does not exist."),
-        };
-        let escrow_hash = env::sha256(escrow_account_id.as_bytes());
-        if existing_set.contains(&escrow_hash) {
-            existing_set.remove(&escrow_hash);
-            self.account_gives_access
-                .insert(&predecessor_hash, &existing_set);
-            env::log(b"Successfully removed access.")
-        } else {
-            env::panic(b"Did not find access for escrow ID.")
-        }
-    }
+/// `limit` capped at `MAX_VIEW_LIMIT`, so a page fits in a view call's gas.
+fn view_limit(limit: u64) -> u64 {
+    std::cmp::min(limit, MAX_VIEW_LIMIT)
+}
 
-    fn transfer(&mut self, new_owner_id: AccountId, token_id: TokenId) {
-        let token_owner_account_id = self.get_token_owner(token_id);
-        let predecessor = env::predecessor_account_id();
-        if predecessor != token_owner_account_id {
-            env::panic(b"Attempt to call transfer on tokens belonging to another account.")
-        }
-        self.delete_corgi_from_account(token_id, token_owner_account_id);
-        self.save_corgi_to_account(token_id, new_owner_id)
-    }
+// Token IDs can exceed 2^53, so JSON carries them as strings like `U64` does.
 
-    fn transfer_from(&mut self, owner_id: AccountId, new_owner_id: AccountId, token_id: TokenId) {
-        let token_owner_account_id = self.get_token_owner(token_id);
-        if owner_id != token_owner_account_id {
-            env::panic(b"Attempt to transfer a token from a different owner.")
-        }
+fn serialize_token_id<S: near_sdk::serde::Serializer>(
+    token_id: &TokenId,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    near_sdk::serde::Serialize::serialize(&U64(*token_id), serializer)
+}
 
-        if !self.check_access(token_owner_account_id.clone()) {
-            env::panic(b"Attempt to transfer a token with no access.")
-        }
-        self.delete_corgi_from_account(token_id, token_owner_account_id);
-        self.save_corgi_to_account(token_id, new_owner_id)
-    }
+fn serialize_token_id_option<S: near_sdk::serde::Serializer>(
+    token_id: &Option<TokenId>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    near_sdk::serde::Serialize::serialize(&token_id.map(U64), serializer)
+}
 
-    fn check_access(&self, account_id: AccountId) -> bool {
-        let account_hash = env::sha256(account_id.as_bytes());
-        let predecessor = env::predecessor_account_id();
-        if predecessor == account_id {
-            return true;
-        }
-        match self.account_gives_access.get(&account_hash) {
-            Some(access) => {
-                let predecessor = env::predecessor_account_id();
-                let predecessor_hash = env::sha256(predecessor.as_bytes());
-                access.contains(&predecessor_hash)
-            }
-            None => false,
-        }
-    }
+fn serialize_token_ids<S: near_sdk::serde::Serializer>(
+    token_ids: &[TokenId],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let token_ids: Vec<U64> = token_ids.iter().map(|&token_id| U64(token_id)).collect();
+    near_sdk::serde::Serialize::serialize(&token_ids, serializer)
+}
 
-    fn get_token_owner(&self, token_id: TokenId) -> String {
-        match self.corgi_to_account.get(&token_id) {
-            Some(owner_id) => owner_id,
-            None => env::panic(b"No owner of the token ID specified"),
-        }
-    }
+fn log_deprecated(method: &str, replacement: &str) {
+    env::log(
+        format!(
+            "DEPRECATED: `{}` will be removed in a future version, use `{}` instead",
+            method, replacement
+        )
+        .as_bytes(),
+    );
+}
 
-    // follow nep 171
-    fn nft_token(&self,token_id: TokenId) -> Corgi {
-        self.get_corgi(token_id)
-    }
+/// Whether `account` is the contract itself, which holds corgis in custody for
+/// prize pools, kennel treasuries, collateral and other treasury holdings.
+fn is_custodian(account: &str) -> bool {
+    account == env::current_account_id()
+}
 
-    fn nft_transfer(&mut self,
-            new_owner_id: AccountId,
-            token_id: TokenId,
-            message: String,
-        ){
-            self.transfer_with_message(new_owner_id, token_id, message)
+/// 1 for common up to 5 for ultra rare.
+fn rarity_tier(rarity: Rarity) -> u64 {
+    match rarity {
+        Rarity::Common => 1,
+        Rarity::Uncommon => 2,
+        Rarity::Rare => 3,
+        Rarity::VeryRare => 4,
+        Rarity::UltraRare => 5,
     }
+}
 
-    // Enumeration
-
-    fn nft_total_supply(&self)-> String {
-        "1000000000".to_string()
-    }
+/// Trait values of a corgi counted for rarity scores, as `trait:value` keys.
+fn corgi_traits(corgi: &Corgi) -> Vec<String> {
+    vec![
+        format!("color:{}", normalize_color(&corgi.color)),
+        format!("background_color:{}", normalize_color(&corgi.background_color)),
+        format!("rarity:{}", rarity_tier(corgi.rate)),
+        // sausage lengths are bucketed so that nearly every value isn't unique
+        format!("sausage:{}", corgi.sausage.parse::<u64>().unwrap_or(0) / 50),
+    ]
+}
 
-    fn nft_tokens(&self, from_index: u64, limit: u64)-> Vec<Corgi> {
-        self.display_global_corgis_range(from_index, limit)
-    }
+fn corgi_level(corgi: &Corgi) -> u64 {
+    1 + corgi.battles_won / WINS_PER_LEVEL
+}
 
-    fn nft_supply_for_owner(&self, account_id: AccountId) -> String {
-        "Unlimited, Just make it".to_string()
-    }
+/// The value of hidden trait `slot`, fixed per corgi.
+fn hidden_trait_value(token_id: TokenId, slot: usize) -> &'static str {
+    let (_, values) = HIDDEN_TRAITS[slot];
+    let hash = env::sha256(format!("{}:{}", token_id, slot).as_bytes());
+    values[hash[0] as usize % values.len()]
+}
 
-    fn nft_tokens_for_owner(
-        &self, 
-        account_id: AccountId,
-        from_index: u64, 
-        limit: u64
-    )-> Vec<Corgi> {
-        self.get_corgis_by_owner_range(account_id, from_index, limit)
-    }
+/// Storage prefix of the access set of the account with `account_hash`.
+fn access_set_prefix(account_hash: &[u8]) -> Vec<u8> {
+    let mut prefix = b"access-set".to_vec();
+    prefix.extend(account_hash.iter());
+    prefix
+}
 
+/// Whether `access_set` is one of the sets older versions created under a single
+/// shared prefix, where the sets of different accounts overwrote each other.
+fn is_legacy_access_set(access_set: &UnorderedSet<AccountIdHash>) -> bool {
+    access_set
+        .try_to_vec()
+        .unwrap()
+        .windows(LEGACY_ACCESS_SET_PREFIX.len())
+        .any(|window| window == LEGACY_ACCESS_SET_PREFIX)
 }
 
-// Helper methods
-#[near_bindgen]
-impl Corgi3D {
-    fn generate_rate_sausage(&self) -> (String, String) {
-        let (r1, r2) = self.random_num();
-        let l = r1;
-        let rarity = if r2 > 30 {
-            "COMMON"
-        } else if r2 > 13 {
-            "UNCOMMON"
-        } else if r2 > 3 {
-            "RARE"
-        } else if r2 > 0 {
-            "VERY RARE"
-        } else {
-            "ULTRA RARE"
-        };
-        let mut sausage = l;
-        if rarity == "ULTRA RARE" {
-            sausage = l + 200;
-        } else if rarity == "VERY RARE" {
-            sausage = l + 150;
-        } else if rarity == "RARE" {
-            sausage = l + 100;
-        } else if rarity == "UNCOMMON" {
-            sausage = l + 50;
-        } else if rarity == "COMMON" {
-            sausage = l;
+/// Converts a stored color (CSS name, `#rgb` or `#rrggbb`) to lowercase `#rrggbb`.
+/// Unknown values fall back to black so the renderer always receives valid hex.
+fn normalize_color(color: &str) -> String {
+    let color = color.trim().to_lowercase();
+    if is_hex_color(&color) {
+        let hex = &color[1..];
+        if hex.len() == 6 {
+            return color;
         }
-        return (rarity.to_string(), sausage.to_string());
-    }
-
-    fn random_rng(&self) -> ChaCha20Rng {
-        let mut seed = [0u8; 32];
-        let v = env::random_seed();
-        let l = std::cmp::min(24, v.len());
-        seed[0..l].copy_from_slice(&v[0..l]);
-        let id = self.next_corgi_id.to_le_bytes();
-        seed[24..32].copy_from_slice(&id);
-        ChaCha20Rng::from_seed(seed)
-    }
-
-    fn random_num(&self) -> (u32, u32) {
-        let mut rng1 = self.random_rng();
-        (rng1.next_u32() % 100, rng1.next_u32() % 50)
-    }
-
-    fn delete_corgi_from_account(&mut self, id: TokenId, account: AccountId) {
-        self.corgi_to_account.remove(&id);
-        let account_hash = env::sha256(account.as_bytes());
-        let mut account_corgis = self.account_corgis.get(&account_hash).unwrap();
-        account_corgis.remove(&id);
-        self.account_corgis.insert(&account_hash, &account_corgis);
+        return hex.chars().fold("#".to_string(), |mut acc, c| {
+            acc.push(c);
+            acc.push(c);
+            acc
+        });
     }
+    named_color(&color).unwrap_or("#000000").to_string()
+}
 
-    fn save_corgi_to_account(&mut self, id: TokenId, account: AccountId) {
-        let account_hash = env::sha256(account.as_bytes());
+/// Whether a lowercase color is `#rgb` or `#rrggbb`.
+fn is_hex_color(color: &str) -> bool {
+    color.strip_prefix('#').is_some_and(|hex| {
+        (hex.len() == 3 || hex.len() == 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
 
-        self.corgi_to_account.insert(&id, &account);
-        let mut account_corgis = self.account_corgis.get(&account_hash).unwrap_or_else(|| {
-            let mut prefix = Vec::with_capacity(33);
-            prefix.push(b'u');
-            prefix.extend(account_hash.clone());
-            UnorderedSet::new(prefix)
-        });
-        account_corgis.insert(&id);
-        self.account_corgis.insert(&account_hash, &account_corgis);
-    }
+/// Hex value of a lowercase CSS color name the renderer knows.
+fn named_color(color: &str) -> Option<&'static str> {
+    let hex = match color {
+        "black" => "#000000",
+        "white" => "#ffffff",
+        "red" => "#ff0000",
+        "green" => "#008000",
+        "blue" => "#0000ff",
+        "yellow" => "#ffff00",
+        "orange" => "#ffa500",
+        "purple" => "#800080",
+        "pink" => "#ffc0cb",
+        "brown" => "#a52a2a",
+        "gray" | "grey" => "#808080",
+        _ => return None,
+    };
+    Some(hex)
 }
 
-// use the attribute below for unit tests
 #[cfg(test)]
-mod tests {
+mod test_utils {
     use super::*;
-    use near_sdk::MockedBlockchain;
-    use near_sdk::{testing_env, VMContext};
+    use near_sdk::{testing_env, MockedBlockchain, VMContext};
 
-    fn joe() -> AccountId {
+    pub fn joe() -> AccountId {
         "joe.testnet".to_string()
     }
-    fn robert() -> AccountId {
+
+    pub fn robert() -> AccountId {
         "robert.testnet".to_string()
     }
-    fn mike() -> AccountId {
+
+    pub fn mike() -> AccountId {
         "mike.testnet".to_string()
     }
 
     // part of writing unit tests is setting up a mock context
     // this is a useful list to peek at when wondering what's available in env::*
-    fn get_context(predecessor_account_id: String, storage_usage: u64) -> VMContext {
+    pub fn get_context(predecessor_account_id: String, storage_usage: u64) -> VMContext {
         VMContext {
             current_account_id: "alice.testnet".to_string(),
             signer_account_id: "jane.testnet".to_string(),
@@ -546,7 +2347,7 @@ mod tests {
             input: vec![],
             block_index: 0,
             block_timestamp: 0,
-            account_balance: 0,
+            account_balance: 10u128.pow(30),
             account_locked_balance: 0,
             storage_usage,
             attached_deposit: 3 * 10u128.pow(24),
@@ -558,315 +2359,46 @@ mod tests {
         }
     }
 
-    #[test]
-    fn grant_access() {
-        let context = get_context(robert(), 0);
-        testing_env!(context);
-        let mut contract = Corgi3D::new(robert());
-        let length_before = contract.account_gives_access.len();
-        assert_eq!(0, length_before, "Expected empty account access Map.");
-        contract.grant_access(mike());
-        contract.grant_access(joe());
-        let length_after = contract.account_gives_access.len();
-        assert_eq!(
-            1, length_after,
-            "Expected an entry in the account's access Map."
-        );
-        let predecessor_hash = env::sha256(robert().as_bytes());
-        let num_grantees = contract
-            .account_gives_access
-            .get(&predecessor_hash)
-            .unwrap();
-        assert_eq!(
-            2,
-            num_grantees.len(),
-            "Expected two accounts to have access to predecessor."
-        );
-    }
-
-    #[test]
-    #[should_panic(expected = r#"Access does not exist."#)]
-    fn revoke_access_and_panic() {
-        let context = get_context(robert(), 0);
-        testing_env!(context);
-        let mut contract = Corgi3D::new(robert());
-        contract.revoke_access(joe());
-    }
-
-    #[test]
-    fn add_revoke_access_and_check() {
-        // Joe grants access to Robert
-        let mut context = get_context(joe(), 0);
-        testing_env!(context);
-        let mut contract = Corgi3D::new(joe());
-        contract.grant_access(robert());
-
-        // does Robert have access to Joe's account? Yes.
-        context = get_context(robert(), env::storage_usage());
-        testing_env!(context);
-        let mut robert_has_access = contract.check_access(joe());
-        assert_eq!(
-            true, robert_has_access,
-            "After granting access, check_access call failed."
-        );
-
-        // Joe revokes access from Robert
-        context = get_context(joe(), env::storage_usage());
-        testing_env!(context);
-        contract.revoke_access(robert());
-
-        // does Robert have access to Joe's account? No
-        context = get_context(robert(), env::storage_usage());
-        testing_env!(context);
-        robert_has_access = contract.check_access(joe());
-        assert_eq!(
-            false, robert_has_access,
-            "After revoking access, check_access call failed."
-        );
-    }
-
-    #[test]
-    fn mint_token_get_token_owner() {
-        let context = get_context(robert(), 0);
-        testing_env!(context);
-        let mut contract = Corgi3D::new(robert());
-        let (_, id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-        let owner = contract.get_token_owner(id);
-        assert_eq!(robert(), owner, "Unexpected token owner.");
-    }
-
-    #[test]
-    #[should_panic(expected = r#"Attempt to transfer a token with no access."#)]
-    fn transfer_from_with_no_access_should_fail() {
-        // Robert owns the token.
-        // Mike is trying to transfer it to Mike's account without having access.
-        let context = get_context(robert(), 0);
-        testing_env!(context);
-        let mut contract = Corgi3D::new(robert());
-        let (_, id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-        let context = get_context(mike(), 0);
-        testing_env!(context);
-        contract.transfer_from(robert(), mike(), id.clone());
-    }
-
-    #[test]
-    fn transfer_from_with_escrow_access() {
-        // Escrow account: robert.testnet
-        // Owner account: mike.testnet
-        // New owner account: joe.testnet
-        let mut context = get_context(mike(), 0);
-        testing_env!(context);
-        let mut contract = Corgi3D::new(mike());
-        let (_, token_id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-        // Mike grants access to Robert
-        contract.grant_access(robert());
-
-        // Robert transfers the token to Joe
-        context = get_context(robert(), env::storage_usage());
-        testing_env!(context);
-        contract.transfer_from(mike(), joe(), token_id.clone());
-
-        // Check new owner
-        let owner = contract.get_token_owner(token_id.clone());
-        assert_eq!(
-            joe(),
-            owner,
-            "Token was not transferred after transfer call with escrow."
-        );
-    }
-
-    #[test]
-    #[should_panic(expected = r#"Attempt to transfer a token from a different owner."#)]
-    fn transfer_from_with_escrow_access_wrong_owner_id() {
-        // Escrow account: robert.testnet
-        // Owner account: mike.testnet
-        // New owner account: joe.testnet
-        let mut context = get_context(mike(), 0);
-        testing_env!(context);
-        let mut contract = Corgi3D::new(mike());
-        let (_, token_id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-        // Mike grants access to Robert
-        contract.grant_access(robert());
-
-        // Robert transfers the token to Joe
-        context = get_context(robert(), env::storage_usage());
-        testing_env!(context);
-        contract.transfer_from(robert(), joe(), token_id.clone());
-    }
-
-    #[test]
-    fn transfer_from_with_your_own_token() {
-        // Owner account: robert.testnet
-        // New owner account: joe.testnet
-
-        testing_env!(get_context(robert(), 0));
-        let mut contract = Corgi3D::new(robert());
-        let (_, token_id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-
-        // Robert transfers the token to Joe
-        contract.transfer_from(robert(), joe(), token_id.clone());
-
-        // Check new owner
-        let owner = contract.get_token_owner(token_id.clone());
-        assert_eq!(
-            joe(),
-            owner,
-            "Token was not transferred after transfer call with escrow."
-        );
-    }
-
-    #[test]
-    #[should_panic(
-        expected = r#"Attempt to call transfer on tokens belonging to another account."#
-    )]
-    fn transfer_with_escrow_access_fails() {
-        // Escrow account: robert.testnet
-        // Owner account: mike.testnet
-        // New owner account: joe.testnet
-        let mut context = get_context(mike(), 0);
-        testing_env!(context);
-        let mut contract = Corgi3D::new(mike());
-        let (_, token_id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        ); // Mike grants access to Robert
-        contract.grant_access(robert());
-
-        // Robert transfers the token to Joe
-        context = get_context(robert(), env::storage_usage());
-        testing_env!(context);
-        contract.transfer(joe(), token_id.clone());
-    }
-
-    #[test]
-    fn transfer_with_your_own_token() {
-        // Owner account: robert.testnet
-        // New owner account: joe.testnet
-
-        testing_env!(get_context(robert(), 0));
-        let mut contract = Corgi3D::new(robert());
-        let (_, token_id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-
-        // Robert transfers the token to Joe
-        contract.transfer(joe(), token_id.clone());
-
-        // Check new owner
-        let owner = contract.get_token_owner(token_id.clone());
-        assert_eq!(
-            joe(),
-            owner,
-            "Token was not transferred after transfer call with escrow."
-        );
-    }
-
-    #[test]
-    fn delete_corgi() {
-        testing_env!(get_context(robert(), 0));
-        let mut contract = Corgi3D::new(robert());
-        let (_, _token_id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 1);
-
-        let (_, token_id) = contract.create_corgi(
-            "b".to_string(),
-            "black".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 2);
-
-        contract.delete_corgi(token_id);
-        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 1);
-        assert_eq!(
-            contract.get_corgis_by_owner(robert())[0].name,
-            "a".to_string()
-        );
+    // the current context with another block index, keeping the contract's storage
+    fn context_at_block(block_index: u64) -> VMContext {
+        VMContext {
+            current_account_id: env::current_account_id(),
+            signer_account_id: env::signer_account_id(),
+            signer_account_pk: env::signer_account_pk(),
+            predecessor_account_id: env::predecessor_account_id(),
+            input: vec![],
+            block_index,
+            block_timestamp: env::block_timestamp(),
+            account_balance: env::account_balance(),
+            account_locked_balance: env::account_locked_balance(),
+            storage_usage: env::storage_usage(),
+            attached_deposit: env::attached_deposit(),
+            prepaid_gas: env::prepaid_gas(),
+            random_seed: env::random_seed(),
+            is_view: false,
+            output_data_receivers: vec![],
+            epoch_height: env::epoch_height(),
+        }
     }
 
-    #[test]
-    fn test_sell_corgi() {
-        testing_env!(get_context(robert(), 0));
-        let mut contract = Corgi3D::new(robert());
-        let (_, token_id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 1);
-
-        assert_eq!(contract.get_corgi(token_id).selling, false);
-        contract.sell_corgi(token_id, U128(10u128.pow(25)));
-        assert_eq!(contract.get_corgi(token_id).selling, true);
-        assert_eq!(
-            contract.get_corgi(token_id).selling_price,
-            U128(10u128.pow(25))
-        );
-
-        let mut context = get_context(mike(), env::storage_usage());
-        context.attached_deposit = 10u128.pow(25);
-        testing_env!(context);
-        contract.buy_corgi(token_id);
-
-        assert_eq!(contract.get_corgi(token_id).selling, false);
-        assert_eq!(contract.get_corgis_by_owner(mike()).len(), 1);
-        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 0);
+    // reveals a pending mint in the next block, then returns to the current one
+    pub fn reveal_next_block(contract: &mut Corgi3D, pending_id: u64) -> MintResult {
+        let block_index = env::block_index();
+        testing_env!(context_at_block(block_index + 1));
+        let result = contract.reveal_corgi(pending_id);
+        testing_env!(context_at_block(block_index));
+        result
     }
 
-    #[test]
-    fn test_maze_game() {
-        testing_env!(get_context(robert(), 0));
-        let mut contract = Corgi3D::new(robert());
-        let (_, token_id) = contract.create_corgi(
-            "a".to_string(),
-            "blue".to_string(),
-            "green".to_string(),
-            "haha".to_string(),
-        );
-
-        let game = contract.new_maze_game();
-        let mut count = [0u64; TOTAL];
-        for f in HashSet::<_>::from_iter(game.fruit.clone()) {
-            count[f.kind as usize] += 1;
-        }
-        contract.finish_maze_game(game.fruit);
-        let account_fruit = contract.account_fruit(robert());
-        assert_eq!(account_fruit.count, count);
+    // a public mint by the predecessor, revealed right away
+    pub fn mint_revealed(
+        contract: &mut Corgi3D,
+        name: String,
+        color: String,
+        background_color: String,
+        quote: String,
+    ) -> MintResult {
+        let pending = contract.create_corgi(name, color, background_color, quote);
+        reveal_next_block(contract, pending.id)
     }
 }
@@ -0,0 +1,4139 @@
+use crate::*;
+
+/// Listings, offers, lending, fractions and other trades between accounts.
+#[near_bindgen]
+impl Corgi3D {
+    /// Attaches a health certificate signed by the calling vet. Only the latest
+    /// `MAX_CERTIFICATES` are kept per corgi.
+    pub fn issue_certificate(&mut self, token_id: TokenId, data: String) {
+        let predecessor = env::predecessor_account_id();
+        if !self.vets.contains(&predecessor) {
+            env::panic(b"Only designated vets can issue certificates");
+        }
+        if !self.corgi_exists(token_id) {
+            ContractError::CorgiNotFound.panic();
+        }
+        if data.len() > MAX_CERTIFICATE_LENGTH {
+            env::panic(b"Certificate data is too long");
+        }
+        let mut certificates = self.certificates.get(&token_id).unwrap_or_default();
+        if certificates.len() == MAX_CERTIFICATES {
+            certificates.remove(0);
+        }
+        certificates.push(HealthCertificate {
+            vet: predecessor,
+            data,
+            issued_at: env::block_timestamp(),
+        });
+        self.certificates.insert(&token_id, &certificates);
+    }
+
+    pub fn get_certificates(&self, token_id: TokenId) -> Vec<HealthCertificate> {
+        self.certificates.get(&token_id).unwrap_or_default()
+    }
+
+    /// 0..=100, raised by certificates from vets that are still designated.
+    pub fn get_trust_score(&self, token_id: TokenId) -> u64 {
+        let certified = self
+            .get_certificates(token_id)
+            .iter()
+            .filter(|certificate| self.vets.contains(&certificate.vet))
+            .count() as u64;
+        std::cmp::min(certified * TRUST_PER_CERTIFICATE, 100)
+    }
+
+    pub fn get_market_listings(&self, from_index: u64, limit: u64) -> Vec<MarketListing> {
+        let limit = view_limit(limit);
+        let token_ids = self.selling_corgis.as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), token_ids.len()))
+            .filter_map(|index| token_ids.get(index))
+            .map(|token_id| MarketListing {
+                token_id,
+                price: self.get_corgi(token_id).selling_price,
+                currency: self.listing_currency(token_id),
+                trust_score: self.get_trust_score(token_id),
+                starts_at: self.get_listing_start(token_id),
+            })
+            .collect()
+    }
+
+    /// Offers the caller's corgi as a stud for others to breed with, for `fee`.
+    pub fn offer_stud(&mut self, token_id: TokenId, fee: U128) {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can offer a stud");
+        }
+        self.studs.insert(&token_id, &fee);
+    }
+
+    pub fn withdraw_stud(&mut self, token_id: TokenId) {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can withdraw a stud");
+        }
+        self.studs.remove(&token_id);
+    }
+
+    pub fn get_stud_fee(&self, token_id: TokenId) -> Option<U128> {
+        self.studs.get(&token_id)
+    }
+
+    /// Breeds the caller's `dam_id` with an offered stud. The attached deposit
+    /// must cover the stud fee, the mint price and the storage of the child; all
+    /// are escrowed and the child is only paid for in `resolve_breeding`, which
+    /// refunds everything if the breeding is no longer possible by then.
+    #[payable]
+    pub fn breed_with_stud(&mut self, dam_id: TokenId, stud_id: TokenId, spec: CorgiSpec) -> Promise {
+        self.assert_not_paused();
+        self.assert_valid_spec(&spec);
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(dam_id) != predecessor {
+            env::panic(b"Only the owner can breed a corgi");
+        }
+        if dam_id == stud_id {
+            env::panic(b"A corgi cannot breed with itself");
+        }
+        self.assert_not_kin(dam_id, stud_id);
+        if self.get_breeding_licenses(predecessor.clone()) == 0 {
+            env::panic(b"Breeding requires a license");
+        }
+        let stud_fee = self.studs.get(&stud_id).expect("Corgi is not offered as a stud");
+        let storage_deposit = pending_mint_storage_estimate(&spec) as u128 * env::storage_byte_cost();
+        if env::attached_deposit() < stud_fee.0 + self.mint_price + storage_deposit {
+            env::panic(b"Attached deposit must cover the stud fee, the mint price and storage");
+        }
+        let breeding_id = self.next_breeding_id;
+        self.next_breeding_id += 1;
+        self.breedings.insert(
+            &breeding_id,
+            &Breeding {
+                breeder: predecessor,
+                dam_id,
+                stud_id,
+                stud_owner: self.get_token_owner(stud_id),
+                stud_fee,
+                mint_fee: U128(self.mint_price),
+                storage_deposit: U128(env::attached_deposit() - stud_fee.0 - self.mint_price),
+                spec,
+            },
+        );
+        ext_self::resolve_breeding(
+            breeding_id,
+            &env::current_account_id(),
+            0,
+            BREED_CALLBACK_GAS,
+        )
+    }
+
+    /// Pays for the child of a pending breeding, revealed with `reveal_corgi`,
+    /// consuming one of the breeder's licenses, starting the parents' cooldown
+    /// and paying the stud owner. Refunds the breeder instead if either parent
+    /// changed hands, the stud was withdrawn or repriced, a parent is still
+    /// cooling down, the license was spent or the breeder has too many pending
+    /// mints meanwhile. Never panics once the breeding is found, so escrowed
+    /// funds can't get stuck.
+    #[private]
+    pub fn resolve_breeding(&mut self, breeding_id: u64) -> Option<PendingMint> {
+        let breeding = self.breedings.remove(&breeding_id).expect("Breeding not found");
+        let now = env::block_timestamp();
+        let cooled_down = |token_id: &TokenId, contract: &Self| {
+            contract
+                .last_bred_at
+                .get(token_id)
+                .is_none_or(|bred_at| now >= bred_at + BREED_COOLDOWN)
+        };
+        let valid = self.corgi_to_account.get(&breeding.dam_id) == Some(breeding.breeder.clone())
+            && self.corgi_to_account.get(&breeding.stud_id) == Some(breeding.stud_owner.clone())
+            && self.studs.get(&breeding.stud_id) == Some(breeding.stud_fee)
+            && cooled_down(&breeding.dam_id, self)
+            && cooled_down(&breeding.stud_id, self)
+            && self.get_breeding_licenses(breeding.breeder.clone()) > 0
+            && self.account_pending_mints.get(&breeding.breeder).map_or(0, |ids| ids.len())
+                < MAX_PENDING_MINTS;
+        if !valid {
+            env::log(format!("Breeding {} failed, refunding", breeding_id).as_bytes());
+            let escrowed = breeding.stud_fee.0 + breeding.mint_fee.0 + breeding.storage_deposit.0;
+            self.pay_out(breeding.breeder, escrowed);
+            return None;
+        }
+        let storage_before = env::storage_usage();
+        self.take_breeding_licenses(&breeding.breeder, 1);
+        self.treasury_balance += breeding.mint_fee.0;
+        self.last_bred_at.insert(&breeding.dam_id, &now);
+        self.last_bred_at.insert(&breeding.stud_id, &now);
+        let kind = MintKind::Breeding {
+            parent_a: breeding.dam_id,
+            parent_b: breeding.stud_id,
+            inherit_traits: false,
+        };
+        let pending = self.commit_mint(&breeding.breeder, breeding.spec, breeding.mint_fee.0, kind);
+        // the storage deposit was checked against the estimate, which covers
+        // this, so charge it without touching the breeder's storage balance
+        let storage_cost = (env::storage_usage() - storage_before) as u128 * env::storage_byte_cost();
+        let refund = breeding
+            .storage_deposit
+            .0
+            .saturating_sub(storage_cost + pending.storage_reserve.0);
+        if refund > 0 {
+            self.pay_out(breeding.breeder, refund);
+        }
+        if breeding.stud_fee.0 > 0 {
+            self.pay_out(breeding.stud_owner, breeding.stud_fee.0);
+        }
+        Some(pending)
+    }
+
+    /// Breeds two of the caller's corgis for `BREEDING_FEE`. The child takes each
+    /// color from either parent, and its rarity and sausage length from the
+    /// parents' average with a chance to mutate, rolled when it is revealed with
+    /// `reveal_corgi`. Both parents cool down for `BREED_COOLDOWN` from now on.
+    /// Storage is charged on top like `create_corgi` and the surplus refunded.
+    #[payable]
+    pub fn breed_corgis(&mut self, parent_a: TokenId, parent_b: TokenId) -> PendingMint {
+        self.assert_not_paused();
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(parent_a) != predecessor
+            || self.get_token_owner(parent_b) != predecessor
+        {
+            env::panic(b"Only the owner can breed a corgi");
+        }
+        if parent_a == parent_b {
+            env::panic(b"A corgi cannot breed with itself");
+        }
+        self.assert_not_kin(parent_a, parent_b);
+        let now = env::block_timestamp();
+        for parent_id in [parent_a, parent_b].iter() {
+            if let Some(bred_at) = self.last_bred_at.get(parent_id) {
+                if now < bred_at + BREED_COOLDOWN {
+                    env::panic(b"Parent is still in its breeding cooldown");
+                }
+            }
+        }
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < BREEDING_FEE {
+            env::panic(b"Attached deposit must cover the breeding fee");
+        }
+        let storage_before = env::storage_usage();
+        self.treasury_balance += BREEDING_FEE;
+        self.last_bred_at.insert(&parent_a, &now);
+        self.last_bred_at.insert(&parent_b, &now);
+        let (a, b) = (self.get_corgi(parent_a), self.get_corgi(parent_b));
+        // the longest strings the child can inherit, to reserve its storage
+        let longest = |x: &String, y: &String| if x.len() >= y.len() { x.clone() } else { y.clone() };
+        let spec = CorgiSpec {
+            name: format!("{} Jr.", a.name),
+            color: longest(&a.color, &b.color),
+            background_color: longest(&a.background_color, &b.background_color),
+            quote: b.quote,
+        };
+        let kind = MintKind::Breeding {
+            parent_a,
+            parent_b,
+            inherit_traits: true,
+        };
+        let pending = self.commit_mint(&predecessor, spec, BREEDING_FEE, kind);
+        let available = self.charge_storage(&predecessor, storage_before, attached_deposit - BREEDING_FEE);
+        let refund = self.charge(&predecessor, pending.storage_reserve.0, available);
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+        pending
+    }
+
+    /// Whether `a` and `b` are too closely related to breed: one is an ancestor
+    /// of the other within `kinship_depth` generations, or they share a parent.
+    pub fn check_kinship(&self, a: TokenId, b: TokenId) -> Option<KinshipConflict> {
+        if self.kinship_depth == 0 {
+            return None;
+        }
+        let ancestors_of_a = self.ancestors(a, self.kinship_depth);
+        let ancestors_of_b = self.ancestors(b, self.kinship_depth);
+        if let Some(&(_, generations)) = ancestors_of_a.iter().find(|(id, _)| *id == b) {
+            return Some(KinshipConflict::Ancestor {
+                ancestor_id: b,
+                descendant_id: a,
+                generations,
+            });
+        }
+        if let Some(&(_, generations)) = ancestors_of_b.iter().find(|(id, _)| *id == a) {
+            return Some(KinshipConflict::Ancestor {
+                ancestor_id: a,
+                descendant_id: b,
+                generations,
+            });
+        }
+        ancestors_of_a
+            .iter()
+            .filter(|(_, generations)| *generations == 1)
+            .find(|(parent, _)| ancestors_of_b.contains(&(*parent, 1)))
+            .map(|&(shared_parent_id, _)| KinshipConflict::Siblings { shared_parent_id })
+    }
+
+    pub fn get_breeding(&self, breeding_id: u64) -> Option<Breeding> {
+        self.breedings.get(&breeding_id)
+    }
+
+    /// Buys `count` breeding licenses from the contract.
+    #[payable]
+    pub fn buy_breeding_licenses(&mut self, count: u64) {
+        if count == 0 || env::attached_deposit() != BREEDING_LICENSE_PRICE * count as u128 {
+            env::panic(b"Each breeding license cost 0.5 NEAR");
+        }
+        self.treasury_balance += env::attached_deposit();
+        let predecessor = env::predecessor_account_id();
+        self.add_breeding_licenses(&predecessor, count);
+    }
+
+    pub fn transfer_breeding_licenses(&mut self, receiver_id: AccountId, count: u64) {
+        assert!(
+            env::is_valid_account_id(receiver_id.as_bytes()),
+            "Receiver's account ID is invalid."
+        );
+        let predecessor = env::predecessor_account_id();
+        self.take_breeding_licenses(&predecessor, count);
+        self.add_breeding_licenses(&receiver_id, count);
+    }
+
+    pub fn get_breeding_licenses(&self, account_id: AccountId) -> u64 {
+        self.breeding_licenses.get(&account_id).unwrap_or(0)
+    }
+
+    /// When the corgi's transfer cooldown ends, if it is still running.
+    pub fn get_transfer_locked_until(&self, token_id: TokenId) -> Option<U64> {
+        self.transfer_locks
+            .get(&token_id)
+            .filter(|&locked_until| env::block_timestamp() < locked_until)
+            .map(U64)
+    }
+
+    /// Lists a corgi for `price` in `currency`, NEAR if omitted. If a listing
+    /// deposit is configured, a new listing must attach it; it is held until
+    /// the corgi is sold or delisted and anything attached beyond it goes to
+    /// the caller's escrow.
+    #[payable]
+    pub fn sell_corgi(
+        &mut self,
+        id: TokenId,
+        price: U128,
+        memo: Option<String>,
+        currency: Option<Currency>,
+    ) {
+        self.assert_not_paused();
+        let mut corgi = self.corgis.get(&id).unwrap_or_else(|| ContractError::CorgiNotFound.panic());
+        let account = self.corgi_to_account.get(&id).unwrap();
+        let predecessor = env::predecessor_account_id();
+        if account == predecessor
+            || self.check_access(account.clone())
+            || self.is_approved_for(id, &predecessor)
+        {
+            self.assert_transferable(id);
+            self.take_listing_deposit(id, &account);
+            self.listing_starts.remove(&id);
+            let currency = currency.unwrap_or(Currency::Near);
+            match &currency {
+                Currency::Near => {
+                    self.listing_currencies.remove(&id);
+                }
+                Currency::Ft(ft_id) => {
+                    if !self.accepted_fts.contains(ft_id) {
+                        ContractError::CurrencyNotAccepted.panic();
+                    }
+                    self.listing_currencies.insert(&id, &currency);
+                }
+            }
+            corgi.selling = true;
+            corgi.selling_price = price;
+            self.corgis.insert(&id, &corgi);
+            self.selling_corgis.insert(&id);
+            // wishlist price limits are in yoctoNEAR
+            if currency == Currency::Near {
+                self.record_wishlist_matches(&corgi, &account);
+            }
+            emit_sale_listed(&account, id, price, &currency, memo);
+        } else {
+            ContractError::NoSellPermission.panic();
+        }
+    }
+
+    /// Lists a corgi for NEAR now, but only lets it be bought from `start_at` on.
+    pub fn sell_corgi_at(&mut self, token_id: TokenId, price: U128, start_at: U64) {
+        if start_at.0 <= env::block_timestamp() {
+            env::panic(b"Listing must start in the future");
+        }
+        self.sell_corgi(token_id, price, None, None);
+        self.listing_starts.insert(&token_id, &start_at.0);
+    }
+
+    /// Deprecated: use `cancel_sale`.
+    pub fn delist_corgi(&mut self, id: TokenId) {
+        log_deprecated("delist_corgi", "cancel_sale");
+        self.cancel_sale(id)
+    }
+
+    /// Takes the caller's corgi off the market and refunds its listing deposit
+    /// to the depositor's escrow.
+    pub fn cancel_sale(&mut self, id: TokenId) {
+        let corgi = self.corgis.get(&id).unwrap_or_else(|| ContractError::CorgiNotFound.panic());
+        let account = self.corgi_to_account.get(&id).unwrap();
+        let predecessor = env::predecessor_account_id();
+        if account != predecessor
+            && !self.check_access(account.clone())
+            && !self.is_approved_for(id, &predecessor)
+        {
+            ContractError::NoDelistPermission.panic();
+        }
+        if !corgi.selling {
+            ContractError::NotForSale.panic();
+        }
+        self.end_listing(id);
+        emit_event(
+            "corgi_sale_delisted",
+            json!({ "owner_id": account, "token_ids": [id.to_string()] }),
+        );
+    }
+
+    /// Changes the price of a listed corgi, keeping its currency, start and
+    /// listing deposit.
+    pub fn update_price(&mut self, id: TokenId, new_price: U128) {
+        let mut corgi = self.corgis.get(&id).unwrap_or_else(|| ContractError::CorgiNotFound.panic());
+        let account = self.corgi_to_account.get(&id).unwrap();
+        let predecessor = env::predecessor_account_id();
+        if account != predecessor
+            && !self.check_access(account.clone())
+            && !self.is_approved_for(id, &predecessor)
+        {
+            ContractError::NoPriceUpdatePermission.panic();
+        }
+        if !corgi.selling {
+            ContractError::NotForSale.panic();
+        }
+        let old_price = corgi.selling_price;
+        corgi.selling_price = new_price;
+        self.corgis.insert(&id, &corgi);
+        let currency = self.listing_currency(id);
+        if currency == Currency::Near {
+            self.record_wishlist_matches(&corgi, &account);
+        }
+        emit_event(
+            "corgi_sale_price_updated",
+            json!({
+                "owner_id": account,
+                "token_ids": [id.to_string()],
+                "old_price": old_price,
+                "price": new_price,
+                "currency": currency,
+            }),
+        );
+    }
+
+    /// Running and scheduled flash sales.
+    pub fn get_flash_sales(&self) -> Vec<FlashSale> {
+        let now = env::block_timestamp();
+        self.flash_sales
+            .iter()
+            .filter(|sale| now < sale.ends_at.0)
+            .cloned()
+            .collect()
+    }
+
+    /// What `account_id` would pay right now to mint (`token_id` of `None`) or
+    /// to buy the listed corgi, flash-sale discount included.
+    pub fn get_flash_price(&self, token_id: Option<TokenId>, account_id: AccountId) -> U128 {
+        let price = match token_id {
+            None => self.public_mint_price(),
+            Some(id) => self.get_corgi(id).selling_price.0,
+        };
+        let sale = match token_id {
+            Some(id) if !is_custodian(&self.get_token_owner(id)) => None,
+            _ => self.best_flash_sale(token_id, &account_id),
+        };
+        U128(sale.map_or(price, |(_, discount_bps)| discounted(price, discount_bps)))
+    }
+
+    /// Deposit a new listing of `token_id` must attach.
+    pub fn get_listing_deposit(&self, token_id: TokenId) -> U128 {
+        U128(self.required_listing_deposit(token_id))
+    }
+
+    /// When a scheduled listing goes live; `None` once it is live or if the
+    /// corgi isn't scheduled.
+    pub fn get_listing_start(&self, token_id: TokenId) -> Option<U64> {
+        self.listing_starts
+            .get(&token_id)
+            .filter(|&start_at| env::block_timestamp() < start_at)
+            .map(U64)
+    }
+
+    /// Changes the creator royalty of `token_id`, paid on every later sale. Only the
+    /// creator can change it.
+    pub fn set_royalty(&mut self, token_id: TokenId, royalty_bps: u32) {
+        let mut corgi = self.get_corgi(token_id);
+        if corgi.creator != env::predecessor_account_id() {
+            env::panic(b"Only the creator can change the royalty");
+        }
+        if royalty_bps > MAX_ROYALTY_BPS {
+            env::panic(b"Royalty cannot exceed 10%");
+        }
+        corgi.royalty_bps = royalty_bps;
+        self.corgis.insert(&token_id, &corgi);
+    }
+
+    #[payable]
+    pub fn buy_corgi(&mut self, id: TokenId, memo: Option<String>) {
+        self.assert_not_paused();
+        let corgi = self.corgis.get(&id).unwrap_or_else(|| ContractError::CorgiNotFound.panic());
+        let seller = self.corgi_to_account.get(&id).unwrap();
+        let buyer = env::predecessor_account_id();
+        if !corgi.selling {
+            ContractError::NotForSale.panic();
+        }
+        if self.listing_currency(id) != Currency::Near {
+            ContractError::PricedInFt.panic();
+        }
+        if self.get_listing_start(id).is_some() {
+            ContractError::ListingNotStarted.panic();
+        }
+        let price = if is_custodian(&seller) {
+            self.apply_flash_sale(Some(id), &buyer, corgi.selling_price.0)
+        } else {
+            corgi.selling_price.0
+        };
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < price {
+            ContractError::InsufficientPayment.panic();
+        }
+        if attached_deposit > price {
+            self.pay_out(buyer.clone(), attached_deposit - price);
+        }
+        let kennel_id = self.kennel_treasury.get(&id);
+        self.internal_transfer(id, seller.clone(), buyer.clone());
+        self.lock_transfers(id);
+        self.record_sale_price(corgi.rate, U128(price), &seller, &buyer);
+        self.record_trade(id, &seller, &buyer, Some(U128(price)));
+        emit_transfer(&seller, &buyer, id, None, memo);
+        emit_sold(&seller, &buyer, id, U128(price), &Currency::Near);
+        let proceeds = self.take_market_fee(&seller, price);
+        match kennel_id {
+            Some(kennel_id) => {
+                let proceeds = self.pay_royalty(id, &seller, price, proceeds);
+                self.distribute_kennel_proceeds(kennel_id, proceeds)
+            }
+            None => {
+                // the royalty is held back until the seller's payment goes through
+                let royalty = self
+                    .royalty_of(id, &seller, price)
+                    .map_or(0, |(_, royalty)| std::cmp::min(royalty, proceeds));
+                Promise::new(seller.clone()).transfer(proceeds - royalty).then(
+                    ext_self::on_purchase_complete(
+                        id,
+                        seller,
+                        buyer,
+                        U128(price),
+                        U128(proceeds - royalty),
+                        U128(royalty),
+                        &env::current_account_id(),
+                        0,
+                        PURCHASE_CALLBACK_GAS,
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Lists 2 to `MAX_BATCH_SIZE` of the caller's corgis as one lot for `price`
+    /// in NEAR. Until the bundle is bought or cancelled its corgis can't be
+    /// transferred, listed or burned on their own. Returns the bundle ID.
+    pub fn sell_bundle(&mut self, token_ids: Vec<TokenId>, price: U128) -> u64 {
+        self.assert_not_paused();
+        if token_ids.len() < 2 || token_ids.len() > MAX_BATCH_SIZE {
+            env::panic(b"Bundle must hold 2 to 10 corgis");
+        }
+        let seller = env::predecessor_account_id();
+        for (index, &token_id) in token_ids.iter().enumerate() {
+            if token_ids[..index].contains(&token_id) {
+                env::panic(b"Bundle lists a corgi twice");
+            }
+            if self.get_token_owner(token_id) != seller {
+                env::panic(b"Only the owner can bundle a corgi");
+            }
+            self.assert_transferable(token_id);
+            if self.get_corgi(token_id).selling {
+                env::panic(b"Cancel the corgi's own sale before bundling it");
+            }
+        }
+        let id = self.next_bundle_id;
+        self.next_bundle_id += 1;
+        for token_id in token_ids.iter() {
+            self.bundled_corgis.insert(token_id, &id);
+        }
+        let bundle = Bundle {
+            id,
+            seller: seller.clone(),
+            token_ids,
+            price,
+            listed_at: U64(env::block_timestamp()),
+        };
+        self.bundles.insert(&id, &bundle);
+        emit_event(
+            "corgi_bundle_listed",
+            json!({
+                "bundle_id": id.to_string(),
+                "owner_id": seller,
+                "token_ids": bundle.token_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>(),
+                "price": price,
+            }),
+        );
+        id
+    }
+
+    /// Buys every corgi of a bundle at once. The seller is paid once, after the
+    /// market fee and the creator royalties of the corgis, which split the price
+    /// evenly; anything attached beyond the price is refunded.
+    #[payable]
+    pub fn buy_bundle(&mut self, bundle_id: u64) {
+        self.assert_not_paused();
+        let bundle = self.bundles.get(&bundle_id).unwrap_or_else(|| ContractError::BundleNotFound.panic());
+        let buyer = env::predecessor_account_id();
+        if buyer == bundle.seller {
+            env::panic(b"Can't buy your own bundle");
+        }
+        let price = bundle.price.0;
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < price {
+            env::panic(b"Don't pay enough money to buy the bundle");
+        }
+        if attached_deposit > price {
+            self.pay_out(buyer.clone(), attached_deposit - price);
+        }
+        self.remove_bundle(&bundle);
+        let share = price / bundle.token_ids.len() as u128;
+        let mut proceeds = self.take_market_fee(&bundle.seller, price);
+        for &token_id in bundle.token_ids.iter() {
+            if self.get_token_owner(token_id) != bundle.seller {
+                env::panic(b"Bundle holds a corgi the seller no longer owns");
+            }
+            self.internal_transfer(token_id, bundle.seller.clone(), buyer.clone());
+            self.lock_transfers(token_id);
+            self.record_trade(token_id, &bundle.seller, &buyer, None);
+            proceeds = self.pay_royalty(token_id, &bundle.seller, share, proceeds);
+        }
+        emit_transfer_batch(&bundle.seller, &buyer, &bundle.token_ids, None, None);
+        emit_event(
+            "corgi_bundle_sold",
+            json!({
+                "bundle_id": bundle_id.to_string(),
+                "owner_id": bundle.seller,
+                "buyer_id": buyer,
+                "price": bundle.price,
+            }),
+        );
+        if proceeds > 0 {
+            self.pay_out(bundle.seller, proceeds);
+        }
+    }
+
+    /// Takes a bundle off the market, freeing its corgis.
+    pub fn cancel_bundle(&mut self, bundle_id: u64) {
+        let bundle = self.bundles.get(&bundle_id).unwrap_or_else(|| ContractError::BundleNotFound.panic());
+        if env::predecessor_account_id() != bundle.seller {
+            ContractError::NotBundleSeller.panic();
+        }
+        self.remove_bundle(&bundle);
+        emit_event(
+            "corgi_bundle_cancelled",
+            json!({ "bundle_id": bundle_id.to_string(), "owner_id": bundle.seller }),
+        );
+    }
+
+    pub fn get_bundle(&self, bundle_id: u64) -> Option<Bundle> {
+        self.bundles.get(&bundle_id)
+    }
+
+    pub fn get_bundles(&self, from_index: u64, limit: u64) -> Vec<Bundle> {
+        let limit = view_limit(limit);
+        let values = self.bundles.values_as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), values.len()))
+            .filter_map(|index| values.get(index))
+            .collect()
+    }
+
+    /// Proposes to swap the caller's `offered` corgis for the `wanted` corgis of
+    /// `counterparty`, plus `near_sweetener` in NEAR from the caller, which must be
+    /// attached and is held until the trade is accepted or cancelled. Anything
+    /// attached beyond it is refunded. Returns the trade ID.
+    #[payable]
+    pub fn propose_trade(
+        &mut self,
+        offered: Vec<TokenId>,
+        wanted: Vec<TokenId>,
+        counterparty: AccountId,
+        near_sweetener: U128,
+    ) -> u64 {
+        self.assert_not_paused();
+        let proposer = env::predecessor_account_id();
+        assert_valid_account_id(&counterparty);
+        if counterparty == proposer {
+            env::panic(b"Can't trade with yourself");
+        }
+        if offered.is_empty() && wanted.is_empty() {
+            env::panic(b"Trade must include a corgi");
+        }
+        if offered.len() + wanted.len() > MAX_BATCH_SIZE {
+            env::panic(b"Trade can include at most 10 corgis");
+        }
+        let all: Vec<TokenId> = offered.iter().chain(wanted.iter()).copied().collect();
+        for (index, token_id) in all.iter().enumerate() {
+            if all[..index].contains(token_id) {
+                env::panic(b"Trade lists a corgi twice");
+            }
+        }
+        self.assert_trade_owners(&offered, &proposer, &wanted, &counterparty);
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < near_sweetener.0 {
+            env::panic(b"Attach the NEAR offered with the trade");
+        }
+        let mut outgoing = self.outgoing_trades.get(&proposer).unwrap_or_default();
+        if outgoing.len() >= MAX_OPEN_TRADES {
+            env::panic(b"Too many open trade proposals");
+        }
+        let id = self.next_trade_id;
+        self.next_trade_id += 1;
+        outgoing.push(id);
+        self.outgoing_trades.insert(&proposer, &outgoing);
+        let mut incoming = self.incoming_trades.get(&counterparty).unwrap_or_default();
+        incoming.push(id);
+        self.incoming_trades.insert(&counterparty, &incoming);
+        self.trades.insert(
+            &id,
+            &TradeProposal {
+                id,
+                proposer: proposer.clone(),
+                counterparty,
+                offered,
+                wanted,
+                near_sweetener,
+                proposed_at: U64(env::block_timestamp()),
+            },
+        );
+        if attached_deposit > near_sweetener.0 {
+            self.pay_out(proposer, attached_deposit - near_sweetener.0);
+        }
+        id
+    }
+
+    /// Accepts a trade proposed to the caller: every corgi changes owners at once
+    /// and the caller receives the NEAR sweetener. Fails if any corgi has changed
+    /// owners since the proposal or can't be transferred right now.
+    pub fn accept_trade(&mut self, trade_id: u64) {
+        self.assert_not_paused();
+        let trade = self.trades.get(&trade_id).unwrap_or_else(|| ContractError::TradeNotFound.panic());
+        if env::predecessor_account_id() != trade.counterparty {
+            ContractError::NotTradeCounterparty.panic();
+        }
+        self.assert_trade_owners(&trade.offered, &trade.proposer, &trade.wanted, &trade.counterparty);
+        self.remove_trade(&trade);
+        for &token_id in trade.offered.iter() {
+            self.internal_transfer(token_id, trade.proposer.clone(), trade.counterparty.clone());
+            self.record_provenance(token_id, &trade.proposer, &trade.counterparty, None);
+        }
+        for &token_id in trade.wanted.iter() {
+            self.internal_transfer(token_id, trade.counterparty.clone(), trade.proposer.clone());
+            self.record_provenance(token_id, &trade.counterparty, &trade.proposer, None);
+        }
+        let memo = Some(format!("trade {}", trade_id));
+        if !trade.offered.is_empty() {
+            emit_transfer_batch(&trade.proposer, &trade.counterparty, &trade.offered, None, memo.clone());
+        }
+        if !trade.wanted.is_empty() {
+            emit_transfer_batch(&trade.counterparty, &trade.proposer, &trade.wanted, None, memo);
+        }
+        if trade.near_sweetener.0 > 0 {
+            self.pay_out(trade.counterparty, trade.near_sweetener.0);
+        }
+    }
+
+    /// Withdraws a trade proposal, or declines it if called by the counterparty.
+    /// The NEAR sweetener goes back to the proposer.
+    pub fn cancel_trade(&mut self, trade_id: u64) {
+        let trade = self.trades.get(&trade_id).unwrap_or_else(|| ContractError::TradeNotFound.panic());
+        let predecessor = env::predecessor_account_id();
+        if predecessor != trade.proposer && predecessor != trade.counterparty {
+            ContractError::NotTradeParty.panic();
+        }
+        self.remove_trade(&trade);
+        if trade.near_sweetener.0 > 0 {
+            self.pay_out(trade.proposer, trade.near_sweetener.0);
+        }
+    }
+
+    pub fn get_trade(&self, trade_id: u64) -> Option<TradeProposal> {
+        self.trades.get(&trade_id)
+    }
+
+    /// Trades proposed to `account_id`, oldest first.
+    pub fn get_incoming_trades(&self, account_id: AccountId, from_index: u64, limit: u64) -> Vec<TradeProposal> {
+        self.trade_page(self.incoming_trades.get(&account_id).unwrap_or_default(), from_index, limit)
+    }
+
+    /// Trades proposed by `account_id`, oldest first.
+    pub fn get_outgoing_trades(&self, account_id: AccountId, from_index: u64, limit: u64) -> Vec<TradeProposal> {
+        self.trade_page(self.outgoing_trades.get(&account_id).unwrap_or_default(), from_index, limit)
+    }
+
+    /// Pays the creator royalty of a sale once the seller is paid. If that payment
+    /// failed the sale is undone: the corgi goes back to the seller and the buyer
+    /// gets the price back, market fee included. If the corgi can't go back, e.g.
+    /// the buyer passed it on or rented it out, the seller's proceeds are escrowed
+    /// instead. Returns whether the payment went through.
+    #[private]
+    pub fn on_purchase_complete(
+        &mut self,
+        token_id: TokenId,
+        seller: AccountId,
+        buyer: AccountId,
+        price: U128,
+        proceeds: U128,
+        royalty: U128,
+    ) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            self.pay_creator(token_id, royalty.0);
+            return true;
+        }
+        if !self.undo_sale(token_id, &seller, &buyer) {
+            env::log(format!("Payment for corgi {} failed, credited to escrow", token_id).as_bytes());
+            self.credit_deposit(&seller, proceeds.0);
+            self.pay_creator(token_id, royalty.0);
+            return false;
+        }
+        env::log(format!("Payment for corgi {} failed, sale rolled back", token_id).as_bytes());
+        let fee = price.0.saturating_sub(proceeds.0 + royalty.0);
+        self.treasury_balance = self.treasury_balance.saturating_sub(fee);
+        let volume = self.sale_volume.get(&seller).unwrap_or(0).saturating_sub(price.0);
+        self.sale_volume.insert(&seller, &volume);
+        self.pay_out(buyer, price.0);
+        false
+    }
+
+    /// Pays the creator royalty of a sale for fungible tokens once the seller is
+    /// paid, like `on_purchase_complete`. If that payment failed the tokens go
+    /// back to the buyer, market fee included, or to the seller's token balance
+    /// if the corgi can't go back.
+    #[private]
+    pub fn on_ft_sale_resolved(
+        &mut self,
+        token_id: TokenId,
+        seller: AccountId,
+        buyer: AccountId,
+        ft_id: AccountId,
+        price: U128,
+        proceeds: U128,
+        royalty: U128,
+    ) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            self.ft_pay_creator(token_id, ft_id, royalty.0);
+            return true;
+        }
+        if !self.undo_sale(token_id, &seller, &buyer) {
+            env::log(format!("Payment for corgi {} failed, credited to token balance", token_id).as_bytes());
+            self.credit_ft(&seller, &ft_id, proceeds.0);
+            self.ft_pay_creator(token_id, ft_id, royalty.0);
+            return false;
+        }
+        env::log(format!("Payment for corgi {} failed, sale rolled back", token_id).as_bytes());
+        let owner_id = self.owner_id.clone();
+        self.debit_ft(&owner_id, &ft_id, price.0.saturating_sub(proceeds.0 + royalty.0));
+        self.ft_pay_out(ft_id, buyer, price.0);
+        false
+    }
+
+    /// Buys a NEAR listing in `installments` equal payments, one every
+    /// `INSTALLMENT_PERIOD`. The attached deposit is the down payment and must
+    /// cover the first installment. The corgi is held by the contract until
+    /// the last payment.
+    #[payable]
+    pub fn buy_corgi_in_installments(&mut self, id: TokenId, installments: u64) -> InstallmentPlan {
+        self.assert_not_paused();
+        let corgi = self.corgis.get(&id).unwrap_or_else(|| ContractError::CorgiNotFound.panic());
+        let seller = self.corgi_to_account.get(&id).unwrap();
+        let buyer = env::predecessor_account_id();
+        if !corgi.selling {
+            ContractError::NotForSale.panic();
+        }
+        if self.listing_currency(id) != Currency::Near {
+            env::panic(b"Only NEAR listings can be bought in installments");
+        }
+        if self.get_listing_start(id).is_some() {
+            ContractError::ListingNotStarted.panic();
+        }
+        if is_custodian(&seller) {
+            env::panic(b"Contract-held corgis can't be bought in installments");
+        }
+        if !(2..=MAX_INSTALLMENTS).contains(&installments) {
+            env::panic(b"Installments must be between 2 and 12");
+        }
+        let price = corgi.selling_price.0;
+        let installment = price.div_ceil(installments as u128);
+        let down_payment = env::attached_deposit();
+        if down_payment < installment {
+            env::panic(b"Down payment must cover the first installment");
+        }
+        if down_payment >= price {
+            env::panic(b"Down payment covers the price, use buy_corgi");
+        }
+        self.internal_transfer(id, seller.clone(), env::current_account_id());
+        emit_transfer(&seller, &env::current_account_id(), id, None, None);
+        let plan = InstallmentPlan {
+            token_id: id,
+            buyer,
+            seller,
+            price: U128(price),
+            paid: U128(down_payment),
+            installment: U128(installment),
+            next_due: U64(env::block_timestamp() + INSTALLMENT_PERIOD),
+        };
+        self.installment_plans.insert(&id, &plan);
+        plan
+    }
+
+    /// Pays the next installment. The final one hands the corgi to the buyer,
+    /// pays the seller and returns any overpayment to the buyer's escrow.
+    #[payable]
+    pub fn pay_installment(&mut self, id: TokenId) -> Option<InstallmentPlan> {
+        let mut plan = self.installment_plans.get(&id).expect("No installment plan");
+        if plan.buyer != env::predecessor_account_id() {
+            env::panic(b"Only the buyer can pay installments");
+        }
+        if env::block_timestamp() > plan.next_due.0 {
+            env::panic(b"Installment deadline has passed");
+        }
+        let payment = env::attached_deposit();
+        let remaining = plan.price.0 - plan.paid.0;
+        if payment < std::cmp::min(plan.installment.0, remaining) {
+            env::panic(b"Payment must cover the installment");
+        }
+        plan.paid = U128(plan.paid.0 + payment);
+        if plan.paid.0 < plan.price.0 {
+            plan.next_due = U64(plan.next_due.0 + INSTALLMENT_PERIOD);
+            self.installment_plans.insert(&id, &plan);
+            return Some(plan);
+        }
+        self.installment_plans.remove(&id);
+        self.credit_deposit(&plan.buyer, plan.paid.0 - plan.price.0);
+        let corgi = self.corgis.get(&id).unwrap();
+        self.internal_transfer(id, env::current_account_id(), plan.buyer.clone());
+        self.lock_transfers(id);
+        self.record_sale_price(corgi.rate, plan.price, &plan.seller, &plan.buyer);
+        self.record_trade(id, &plan.seller, &plan.buyer, Some(plan.price));
+        emit_transfer(&plan.seller, &plan.buyer, id, None, None);
+        emit_sold(&plan.seller, &plan.buyer, id, plan.price, &Currency::Near);
+        let proceeds = self.take_market_fee(&plan.seller, plan.price.0);
+        let proceeds = self.pay_royalty(id, &plan.seller, plan.price.0, proceeds);
+        self.pay_out(plan.seller, proceeds);
+        None
+    }
+
+    /// Ends a plan whose deadline passed: the seller gets the corgi back plus
+    /// `installment_forfeit_bps` of what was paid, the buyer the rest in escrow.
+    /// Anyone can call it.
+    pub fn forfeit_installments(&mut self, id: TokenId) {
+        let plan = self.installment_plans.get(&id).expect("No installment plan");
+        if env::block_timestamp() <= plan.next_due.0 {
+            env::panic(b"Installment is not overdue");
+        }
+        self.installment_plans.remove(&id);
+        let forfeit = plan.paid.0 * self.installment_forfeit_bps as u128 / 10_000;
+        self.credit_deposit(&plan.seller, forfeit);
+        self.credit_deposit(&plan.buyer, plan.paid.0 - forfeit);
+        self.internal_transfer(id, env::current_account_id(), plan.seller.clone());
+        emit_transfer(&env::current_account_id(), &plan.seller, id, None, None);
+    }
+
+    pub fn get_installment_plan(&self, id: TokenId) -> Option<InstallmentPlan> {
+        self.installment_plans.get(&id)
+    }
+
+    /// Signals that recent sales of a token may be wash trades, for front ends
+    /// and leaderboards to discount its volume.
+    pub fn get_trade_flags(&self, token_id: TokenId) -> TradeFlags {
+        let history = self.trade_history.get(&token_id).unwrap_or_default();
+        let circular = history
+            .iter()
+            .enumerate()
+            .any(|(i, account)| history[..i].contains(account));
+        let max_pair_trades = history
+            .windows(2)
+            .map(|pair| self.pair_trades.get(&trade_pair(&pair[0], &pair[1])).unwrap_or(0))
+            .max()
+            .unwrap_or(0);
+        TradeFlags {
+            token_id,
+            recent_sales: history.len().saturating_sub(1) as u64,
+            circular,
+            max_pair_trades,
+            suspicious: circular || max_pair_trades >= WASH_PAIR_THRESHOLD,
+        }
+    }
+
+    /// NEP-141 receiver. A `msg` holding a corgi's token ID buys that corgi with
+    /// the transferred tokens: the seller is paid the listing price and the rest
+    /// is returned to the buyer as unused. A `{"mint": <CorgiSpec>}` msg mints a
+    /// corgi for the token's mint price instead; its storage is paid from the
+    /// sender's storage balance.
+    pub fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> U128 {
+        self.assert_not_paused();
+        let ft_id = env::predecessor_account_id();
+        let id: TokenId = match msg.parse() {
+            Ok(id) => id,
+            Err(_) => match serde_json::from_str::<FtMintMsg>(&msg) {
+                Ok(mint_msg) => return self.mint_for_ft(sender_id, ft_id, amount, mint_msg.mint),
+                Err(_) => env::panic(b"msg must be the ID of the corgi to buy or a mint request"),
+            },
+        };
+        let corgi = self.corgis.get(&id).unwrap_or_else(|| ContractError::CorgiNotFound.panic());
+        if !corgi.selling {
+            ContractError::NotForSale.panic();
+        }
+        if self.listing_currency(id) != Currency::Ft(ft_id.clone()) {
+            env::panic(b"Corgi is not priced in this token");
+        }
+        if self.get_listing_start(id).is_some() {
+            ContractError::ListingNotStarted.panic();
+        }
+        let seller = self.corgi_to_account.get(&id).unwrap();
+        let price = if is_custodian(&seller) {
+            self.apply_flash_sale(Some(id), &sender_id, corgi.selling_price.0)
+        } else {
+            corgi.selling_price.0
+        };
+        if amount.0 < price {
+            ContractError::InsufficientPayment.panic();
+        }
+        self.internal_transfer(id, seller.clone(), sender_id.clone());
+        self.lock_transfers(id);
+        self.record_trade(id, &seller, &sender_id, None);
+        emit_transfer(&seller, &sender_id, id, None, None);
+        emit_sold(&seller, &sender_id, id, U128(price), &Currency::Ft(ft_id.clone()));
+        // the market fee goes to the owner's token balance, like FT mint payments
+        let fee = price * self.get_fee_tier(seller.clone()).fee_bps as u128 / 10_000;
+        if fee > 0 {
+            let owner_id = self.owner_id.clone();
+            self.credit_ft(&owner_id, &ft_id, fee);
+        }
+        let proceeds = price - fee;
+        // the royalty is held back until the seller's payment goes through
+        let royalty = self
+            .royalty_of(id, &seller, price)
+            .map_or(0, |(_, royalty)| std::cmp::min(royalty, proceeds));
+        let proceeds = U128(proceeds - royalty);
+        ext_ft::ft_transfer(seller.clone(), proceeds, None, &ft_id, 1, FT_TRANSFER_GAS).then(
+            ext_self::on_ft_sale_resolved(
+                id,
+                seller,
+                sender_id,
+                ft_id,
+                U128(price),
+                proceeds,
+                U128(royalty),
+                &env::current_account_id(),
+                0,
+                FT_RESOLVE_GAS,
+            ),
+        );
+        U128(amount.0 - price)
+    }
+
+    /// The marketplace fee `account_id` pays as a seller, discounted by the
+    /// highest tier its lifetime NEAR sale volume reaches.
+    pub fn get_fee_tier(&self, account_id: AccountId) -> FeeTierStatus {
+        let volume = self.sale_volume.get(&account_id).unwrap_or(0);
+        let reached = self
+            .fee_tiers
+            .iter()
+            .take_while(|tier| volume >= tier.min_volume.0)
+            .count();
+        let discount_bps = if reached == 0 {
+            0
+        } else {
+            self.fee_tiers[reached - 1].discount_bps
+        };
+        FeeTierStatus {
+            tier: reached as u64,
+            volume: U128(volume),
+            discount_bps,
+            fee_bps: (self.market_fee_bps as u64 * (10_000 - discount_bps) as u64 / 10_000) as u32,
+        }
+    }
+
+    /// Currency of the corgi's listing, `None` if it is not for sale.
+    pub fn get_listing_currency(&self, token_id: TokenId) -> Option<Currency> {
+        let corgi = self.corgis.get(&token_id)?;
+        if corgi.selling {
+            Some(self.listing_currency(token_id))
+        } else {
+            None
+        }
+    }
+
+    /// Time-weighted average sale price of `rarity` corgis over the last
+    /// `window_sec` seconds, where each sale price holds until the next sale.
+    /// `None` if the tier has never been sold.
+    pub fn get_twap(&self, rarity: Rarity, window_sec: u64) -> Option<U128> {
+        let observations = self.price_observations.get(&rarity)?;
+        time_weighted_price(&observations, env::block_timestamp() / 1_000_000_000, window_sec)
+    }
+
+    /// The price loans against `rarity` corgis are limited by: the TWAP of sales
+    /// at least `MIN_LOAN_OBSERVATION_AGE_SEC` old between two different accounts
+    /// whose pair isn't flagged for wash trading. `None` unless such sales come
+    /// from `MIN_LOAN_OBSERVATION_PAIRS` distinct pairs.
+    pub fn get_loan_reference_price(&self, rarity: Rarity) -> Option<U128> {
+        let now = env::block_timestamp() / 1_000_000_000;
+        let observations = self
+            .price_observations
+            .get(&rarity)?
+            .into_iter()
+            .filter(|observation| {
+                now.saturating_sub(observation.timestamp_sec) >= MIN_LOAN_OBSERVATION_AGE_SEC
+                    && observation.seller != observation.buyer
+                    && self
+                        .pair_trades
+                        .get(&trade_pair(&observation.seller, &observation.buyer))
+                        .unwrap_or(0)
+                        < WASH_PAIR_THRESHOLD
+            })
+            .collect::<Vec<_>>();
+        let pairs = observations
+            .iter()
+            .map(|observation| trade_pair(&observation.seller, &observation.buyer))
+            .collect::<HashSet<_>>();
+        if pairs.len() < MIN_LOAN_OBSERVATION_PAIRS {
+            return None;
+        }
+        time_weighted_price(&observations, now, LOAN_TWAP_WINDOW_SEC)
+    }
+
+    /// Moves the caller's corgi into contract custody so it can be borrowed against.
+    pub fn deposit_collateral(&mut self, token_id: TokenId) {
+        let borrower = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != borrower {
+            env::panic(b"Only the owner can deposit a corgi as collateral");
+        }
+        self.internal_transfer(token_id, borrower.clone(), env::current_account_id());
+        self.loans.insert(
+            &token_id,
+            &Loan {
+                borrower,
+                principal: U128(0),
+                due_at: 0,
+            },
+        );
+    }
+
+    /// Borrows `amount` from the treasury against a deposited corgi, up to
+    /// `LOAN_TO_VALUE_BPS` of `get_loan_reference_price`.
+    pub fn borrow(&mut self, token_id: TokenId, amount: U128) -> Promise {
+        let mut loan = self.loans.get(&token_id).expect("Corgi is not collateral");
+        let borrower = env::predecessor_account_id();
+        if loan.borrower != borrower {
+            env::panic(b"Only the borrower can borrow against this corgi");
+        }
+        let rarity = self.get_corgi(token_id).rate;
+        let reference_price = self
+            .get_loan_reference_price(rarity)
+            .expect("No price reference for this rarity");
+        let max_principal = reference_price.0 * LOAN_TO_VALUE_BPS / 10_000;
+        let principal = loan.principal.0 + amount.0;
+        if principal > max_principal {
+            env::panic(b"Borrow amount exceeds loan-to-value limit");
+        }
+        if self.treasury_balance < amount.0 {
+            env::panic(b"Not enough funds in the treasury");
+        }
+        let loaned_principal = self.loaned_principal + amount.0;
+        if loaned_principal > (self.treasury_balance + self.loaned_principal) * MAX_LOAN_BOOK_BPS / 10_000 {
+            env::panic(b"Outstanding loans would exceed the treasury's lending limit");
+        }
+        self.treasury_balance -= amount.0;
+        self.loaned_principal = loaned_principal;
+        if loan.principal.0 == 0 {
+            loan.due_at = env::block_timestamp() + LOAN_DURATION;
+        }
+        loan.principal = U128(principal);
+        self.loans.insert(&token_id, &loan);
+        self.pay_out(borrower, amount.0)
+    }
+
+    /// Repays the loan in full and returns the corgi; surplus deposit is refunded.
+    #[payable]
+    pub fn repay(&mut self, token_id: TokenId) {
+        let loan = self.loans.get(&token_id).expect("Corgi is not collateral");
+        let borrower = env::predecessor_account_id();
+        if loan.borrower != borrower {
+            env::panic(b"Only the borrower can repay this loan");
+        }
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < loan.principal.0 {
+            env::panic(b"Attached deposit does not cover the loan");
+        }
+        self.treasury_balance += loan.principal.0;
+        self.loaned_principal -= loan.principal.0;
+        self.loans.remove(&token_id);
+        self.internal_transfer(token_id, env::current_account_id(), borrower.clone());
+        if attached_deposit > loan.principal.0 {
+            self.pay_out(borrower, attached_deposit - loan.principal.0);
+        }
+    }
+
+    /// Anyone may liquidate an overdue loan. The corgi is auctioned for
+    /// `LIQUIDATION_AUCTION_HOURS` with the principal as reserve, and the
+    /// proceeds go to the treasury. Unsold, it stays in the treasury's custody.
+    pub fn liquidate(&mut self, token_id: TokenId) {
+        let loan = self.loans.get(&token_id).expect("Corgi is not collateral");
+        if loan.principal.0 == 0 || env::block_timestamp() <= loan.due_at {
+            env::panic(b"Loan is not overdue");
+        }
+        self.loans.remove(&token_id);
+        self.loaned_principal -= loan.principal.0;
+        self.open_auction(
+            token_id,
+            env::current_account_id(),
+            LIQUIDATION_AUCTION_HOURS,
+            BidIncrement::Bps(LIQUIDATION_BID_INCREMENT_BPS),
+            Some(loan.principal.0),
+            None,
+        );
+        emit_event(
+            "corgi_liquidated",
+            json!({
+                "borrower": loan.borrower,
+                "token_ids": [token_id.to_string()],
+                "principal": loan.principal,
+            }),
+        );
+    }
+
+    pub fn get_loan(&self, token_id: TokenId) -> Option<Loan> {
+        self.loans.get(&token_id)
+    }
+
+    /// Pays for a membership pass corgi valid for `periods` pass periods from its
+    /// reveal with `reveal_corgi`. The attached deposit must cover the pass price
+    /// of each; storage is charged on top like `create_corgi` and the surplus
+    /// refunded.
+    #[payable]
+    pub fn mint_pass(&mut self, spec: CorgiSpec, periods: u64) -> PendingMint {
+        self.assert_not_paused();
+        self.assert_valid_spec(&spec);
+        if periods == 0 {
+            env::panic(b"A pass must run for at least one period");
+        }
+        let attached_deposit = env::attached_deposit();
+        let price = periods as u128 * PASS_PRICE;
+        if attached_deposit < price {
+            env::panic(b"Attached deposit must cover the pass price");
+        }
+        let storage_before = env::storage_usage();
+        self.treasury_balance += price;
+        let predecessor = env::predecessor_account_id();
+        let pending = self.commit_mint(&predecessor, spec, price, MintKind::Pass { periods });
+        let available = self.charge_storage(&predecessor, storage_before, attached_deposit - price);
+        let refund = self.charge(&predecessor, pending.storage_reserve.0, available);
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+        pending
+    }
+
+    /// Extends a pass by as many periods as the attached deposit pays for.
+    #[payable]
+    pub fn renew_pass(&mut self, token_id: TokenId) {
+        let periods = pass_periods(env::attached_deposit());
+        self.treasury_balance += env::attached_deposit();
+        self.extend_pass(token_id, periods);
+    }
+
+    pub fn set_pass_auto_renew(&mut self, token_id: TokenId, auto_renew: bool) {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can configure a pass");
+        }
+        let mut corgi = self.get_corgi(token_id);
+        let mut pass = corgi.pass.expect("Corgi is not a pass");
+        pass.auto_renew = auto_renew;
+        corgi.pass = Some(pass);
+        self.corgis.insert(&token_id, &corgi);
+    }
+
+    /// Renews an expired auto-renew pass for one period, paid from the holder's
+    /// escrow deposit. Callable by anyone so keepers can keep passes alive.
+    pub fn auto_renew_pass(&mut self, token_id: TokenId) {
+        let pass = self.get_corgi(token_id).pass.expect("Corgi is not a pass");
+        if !pass.auto_renew {
+            env::panic(b"Auto-renew is disabled for this pass");
+        }
+        if pass.expires_at > env::block_timestamp() {
+            env::panic(b"Pass has not expired yet");
+        }
+        let holder = self.get_token_owner(token_id);
+        self.debit_deposit(&holder, PASS_PRICE);
+        self.treasury_balance += PASS_PRICE;
+        self.extend_pass(token_id, 1);
+    }
+
+    pub fn is_pass_active(&self, token_id: TokenId) -> bool {
+        self.corgis
+            .get(&token_id)
+            .and_then(|corgi| corgi.pass)
+            .is_some_and(|pass| pass.expires_at > env::block_timestamp())
+    }
+
+    /// Offers the attached deposit for any corgi of `rarity`.
+    #[payable]
+    pub fn make_collection_offer(&mut self, rarity: Rarity) -> u64 {
+        self.assert_not_paused();
+        let price = env::attached_deposit();
+        if price == 0 {
+            env::panic(b"Attach the offered price");
+        }
+        let id = self.next_collection_offer_id;
+        self.next_collection_offer_id += 1;
+        self.collection_offers.insert(
+            &id,
+            &CollectionOffer {
+                id,
+                buyer: env::predecessor_account_id(),
+                rarity,
+                price: U128(price),
+            },
+        );
+        id
+    }
+
+    pub fn cancel_collection_offer(&mut self, offer_id: u64) -> Promise {
+        let offer = self
+            .collection_offers
+            .get(&offer_id)
+            .expect("Offer not found");
+        if offer.buyer != env::predecessor_account_id() {
+            env::panic(b"Only the buyer can cancel an offer");
+        }
+        self.collection_offers.remove(&offer_id);
+        self.pay_out(offer.buyer, offer.price.0)
+    }
+
+    /// Sells the caller's `token_id` into a matching collection offer, settling
+    /// like `accept_offer`.
+    pub fn accept_collection_offer(&mut self, offer_id: u64, token_id: TokenId) -> Promise {
+        self.assert_not_paused();
+        let offer = self
+            .collection_offers
+            .get(&offer_id)
+            .expect("Offer not found");
+        let seller = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != seller {
+            env::panic(b"Only the owner can accept an offer");
+        }
+        if offer.buyer == seller {
+            env::panic(b"Cannot accept your own offer");
+        }
+        let corgi = self.get_corgi(token_id);
+        if corgi.rate != offer.rarity {
+            env::panic(b"Corgi does not match the offer rarity");
+        }
+        self.collection_offers.remove(&offer_id);
+        self.internal_transfer(token_id, seller.clone(), offer.buyer.clone());
+        self.lock_transfers(token_id);
+        self.record_sale_price(offer.rarity, offer.price, &seller, &offer.buyer);
+        self.record_trade(token_id, &seller, &offer.buyer, Some(offer.price));
+        emit_transfer(&seller, &offer.buyer, token_id, None, None);
+        emit_sold(&seller, &offer.buyer, token_id, offer.price, &Currency::Near);
+        let proceeds = self.take_market_fee(&seller, offer.price.0);
+        let proceeds = self.pay_royalty(token_id, &seller, offer.price.0, proceeds);
+        self.pay_out(seller, proceeds)
+    }
+
+    pub fn get_collection_offers(&self, from_index: u64, limit: u64) -> Vec<CollectionOffer> {
+        let limit = view_limit(limit);
+        let values = self.collection_offers.values_as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), values.len()))
+            .filter_map(|index| values.get(index))
+            .collect()
+    }
+
+    /// Offers the attached deposit for `token_id`, whether it is listed or not.
+    /// Another offer by the same bidder raises their current one.
+    #[payable]
+    pub fn make_offer(&mut self, token_id: TokenId) -> Offer {
+        self.assert_not_paused();
+        let amount = env::attached_deposit();
+        if amount == 0 {
+            env::panic(b"Attach the offered price");
+        }
+        let bidder = env::predecessor_account_id();
+        if self.get_token_owner(token_id) == bidder {
+            env::panic(b"Cannot make an offer on your own corgi");
+        }
+        let mut offers = self.offers.get(&token_id).unwrap_or_default();
+        let offer = match offers.iter_mut().find(|offer| offer.bidder == bidder) {
+            Some(offer) => {
+                offer.price = U128(offer.price.0 + amount);
+                offer.made_at = U64(env::block_timestamp());
+                offer.clone()
+            }
+            None => {
+                if offers.len() >= MAX_OFFERS_PER_CORGI {
+                    env::panic(b"Too many offers on this corgi");
+                }
+                let offer = Offer {
+                    token_id,
+                    bidder: bidder.clone(),
+                    price: U128(amount),
+                    made_at: U64(env::block_timestamp()),
+                };
+                offers.push(offer.clone());
+                let mut token_ids = self.account_offers.get(&bidder).unwrap_or_default();
+                token_ids.push(token_id);
+                self.account_offers.insert(&bidder, &token_ids);
+                offer
+            }
+        };
+        self.offers.insert(&token_id, &offers);
+        offer
+    }
+
+    /// Withdraws the caller's offer on `token_id` and refunds it.
+    pub fn cancel_offer(&mut self, token_id: TokenId) -> Promise {
+        let bidder = env::predecessor_account_id();
+        let offer = self.remove_offer(token_id, &bidder);
+        self.pay_out(bidder, offer.price.0)
+    }
+
+    /// Sells the caller's corgi to `bidder` for their offer, settling like `buy_corgi`.
+    /// Other offers on the corgi stay open for the new owner.
+    pub fn accept_offer(&mut self, token_id: TokenId, bidder: AccountId) -> Promise {
+        self.assert_not_paused();
+        let seller = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != seller {
+            env::panic(b"Only the owner can accept an offer");
+        }
+        let offer = self.remove_offer(token_id, &bidder);
+        self.internal_transfer(token_id, seller.clone(), bidder.clone());
+        self.lock_transfers(token_id);
+        let rarity = self.get_corgi(token_id).rate;
+        self.record_sale_price(rarity, offer.price, &seller, &bidder);
+        self.record_trade(token_id, &seller, &bidder, Some(offer.price));
+        emit_transfer(&seller, &bidder, token_id, None, Some("offer".to_string()));
+        emit_sold(&seller, &bidder, token_id, offer.price, &Currency::Near);
+        let proceeds = self.take_market_fee(&seller, offer.price.0);
+        let proceeds = self.pay_royalty(token_id, &seller, offer.price.0, proceeds);
+        self.pay_out(seller, proceeds)
+    }
+
+    pub fn get_offers_for_corgi(&self, token_id: TokenId) -> Vec<Offer> {
+        self.offers.get(&token_id).unwrap_or_default()
+    }
+
+    pub fn get_offers_by_account(&self, account_id: AccountId) -> Vec<Offer> {
+        self.account_offers
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|token_id| {
+                self.get_offers_for_corgi(token_id)
+                    .into_iter()
+                    .find(|offer| offer.bidder == account_id)
+            })
+            .collect()
+    }
+
+    /// Locks the caller's corgi in the contract and issues `total_shares` to them.
+    pub fn fractionalize(&mut self, token_id: TokenId, total_shares: u64, reserve_price: U128) {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can fractionalize a corgi");
+        }
+        if total_shares == 0 {
+            env::panic(b"Total shares must be positive");
+        }
+        self.internal_transfer(token_id, predecessor.clone(), env::current_account_id());
+        self.fractions.insert(
+            &token_id,
+            &Fraction {
+                total_shares,
+                reserve_price,
+                holders: vec![(predecessor, total_shares)],
+            },
+        );
+    }
+
+    pub fn transfer_shares(&mut self, token_id: TokenId, receiver_id: AccountId, amount: u64) {
+        assert!(
+            env::is_valid_account_id(receiver_id.as_bytes()),
+            "Receiver's account ID is invalid."
+        );
+        let mut fraction = self.fractions.get(&token_id).expect("Corgi is not fractionalized");
+        let sender = env::predecessor_account_id();
+        if sender == receiver_id {
+            env::panic(b"Cannot transfer shares to yourself");
+        }
+        if amount == 0 || fraction.shares_of(&sender) < amount {
+            env::panic(b"Not enough shares");
+        }
+        for (holder, shares) in fraction.holders.iter_mut() {
+            if *holder == sender {
+                *shares -= amount;
+            } else if *holder == receiver_id {
+                *shares += amount;
+            }
+        }
+        if fraction.shares_of(&receiver_id) == 0 {
+            fraction.holders.push((receiver_id, amount));
+        }
+        fraction.holders.retain(|(_, shares)| *shares > 0);
+        self.fractions.insert(&token_id, &fraction);
+    }
+
+    /// Holders of a majority of shares decide the buyout reserve price.
+    pub fn set_reserve_price(&mut self, token_id: TokenId, reserve_price: U128) {
+        let mut fraction = self.fractions.get(&token_id).expect("Corgi is not fractionalized");
+        if fraction.shares_of(&env::predecessor_account_id()) * 2 <= fraction.total_shares {
+            env::panic(b"Only a majority shareholder can set the reserve price");
+        }
+        fraction.reserve_price = reserve_price;
+        self.fractions.insert(&token_id, &fraction);
+    }
+
+    /// Buys the whole corgi at the reserve price; holders are paid pro rata
+    /// into their escrow balances.
+    #[payable]
+    pub fn buyout(&mut self, token_id: TokenId) {
+        let fraction = self.fractions.get(&token_id).expect("Corgi is not fractionalized");
+        let price = env::attached_deposit();
+        if price < fraction.reserve_price.0 {
+            env::panic(b"Attached deposit is below the reserve price");
+        }
+        let mut paid = 0;
+        for (holder, shares) in fraction.holders.iter() {
+            let amount = price * *shares as u128 / fraction.total_shares as u128;
+            self.credit_deposit(holder, amount);
+            paid += amount;
+        }
+        self.treasury_balance += price - paid;
+        self.fractions.remove(&token_id);
+        let buyer = env::predecessor_account_id();
+        self.internal_transfer(token_id, env::current_account_id(), buyer.clone());
+        emit_transfer(&env::current_account_id(), &buyer, token_id, None, None);
+    }
+
+    /// Returns the corgi to an account that holds every share.
+    pub fn redeem(&mut self, token_id: TokenId) {
+        let fraction = self.fractions.get(&token_id).expect("Corgi is not fractionalized");
+        let predecessor = env::predecessor_account_id();
+        if fraction.shares_of(&predecessor) != fraction.total_shares {
+            env::panic(b"Redeeming requires all shares");
+        }
+        self.fractions.remove(&token_id);
+        self.internal_transfer(token_id, env::current_account_id(), predecessor);
+    }
+
+    pub fn get_fraction(&self, token_id: TokenId) -> Option<Fraction> {
+        self.fractions.get(&token_id)
+    }
+
+    /// Adds an entry to the caller's wishlist, indexed by its rarity and color so
+    /// listings only look at wishers who could match. The storage it takes is
+    /// charged like `create_corgi` and the surplus refunded.
+    #[payable]
+    pub fn add_to_wishlist(&mut self, criteria: WishlistCriteria) {
+        let predecessor = env::predecessor_account_id();
+        let mut wishlist = self.wishlists.get(&predecessor).unwrap_or_default();
+        if wishlist.len() >= MAX_WISHLIST_ENTRIES {
+            env::panic(b"Wishlist is full");
+        }
+        let storage_before = env::storage_usage();
+        self.update_wishlist_bucket(&criteria, &predecessor, true);
+        wishlist.push(criteria);
+        self.wishlists.insert(&predecessor, &wishlist);
+        let refund = self.charge_storage(&predecessor, storage_before, env::attached_deposit());
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+    }
+
+    /// Removes an entry from the caller's wishlist, crediting the storage it
+    /// frees to the caller's storage balance.
+    pub fn remove_from_wishlist(&mut self, index: u64) {
+        let predecessor = env::predecessor_account_id();
+        let mut wishlist = self.wishlists.get(&predecessor).unwrap_or_default();
+        if index as usize >= wishlist.len() {
+            env::panic(b"Wishlist entry not found");
+        }
+        let storage_before = env::storage_usage();
+        let removed = wishlist.remove(index as usize);
+        let same_bucket = |criteria: &WishlistCriteria| {
+            criteria.rarity == removed.rarity
+                && criteria.color.as_deref().map(normalize_color)
+                    == removed.color.as_deref().map(normalize_color)
+        };
+        if !wishlist.iter().any(same_bucket) {
+            self.update_wishlist_bucket(&removed, &predecessor, false);
+        }
+        if wishlist.is_empty() {
+            self.wishlists.remove(&predecessor);
+        } else {
+            self.wishlists.insert(&predecessor, &wishlist);
+        }
+        let freed = storage_before.saturating_sub(env::storage_usage());
+        self.credit_deposit(&predecessor, freed as u128 * env::storage_byte_cost());
+    }
+
+    pub fn clear_wishlist_matches(&mut self) {
+        self.wishlist_matches.remove(&env::predecessor_account_id());
+    }
+
+    /// Approves `marketplace_contract` to transfer the caller's corgi and calls its
+    /// `nft_on_approve` with `price_msg` in the same transaction. The approval is
+    /// revoked again in `on_listing_resolved` if the marketplace rejects it.
+    /// Requires exactly 1 yoctoNEAR attached.
+    #[payable]
+    pub fn approve_and_list(
+        &mut self,
+        token_id: TokenId,
+        marketplace_contract: AccountId,
+        price_msg: String,
+    ) -> Promise {
+        if env::attached_deposit() != 1 {
+            env::panic(b"Requires attached deposit of exactly 1 yoctoNEAR");
+        }
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can list a corgi");
+        }
+        self.assert_transferable(token_id);
+        let approval_id = self.next_approval_id;
+        self.next_approval_id += 1;
+        let mut approvals = self.token_approvals.get(&token_id).unwrap_or_default();
+        approvals.insert(marketplace_contract.clone(), approval_id);
+        self.token_approvals.insert(&token_id, &approvals);
+        ext_approval_receiver::nft_on_approve(
+            token_id.to_string(),
+            predecessor,
+            U64(approval_id),
+            price_msg,
+            &marketplace_contract,
+            0,
+            ON_APPROVE_GAS,
+        )
+        .then(ext_self::on_listing_resolved(
+            token_id,
+            marketplace_contract,
+            approval_id,
+            &env::current_account_id(),
+            0,
+            ON_APPROVE_CALLBACK_GAS,
+        ))
+    }
+
+    /// Keeps the approval only if the marketplace accepted the listing. Returns
+    /// whether it did.
+    #[private]
+    pub fn on_listing_resolved(&mut self, token_id: TokenId, account_id: AccountId, approval_id: u64) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+        if let Some(mut approvals) = self.token_approvals.get(&token_id) {
+            if approvals.get(&account_id) == Some(&approval_id) {
+                approvals.remove(&account_id);
+                self.token_approvals.insert(&token_id, &approvals);
+            }
+        }
+        false
+    }
+
+    pub fn revoke_approval(&mut self, token_id: TokenId, account_id: AccountId) {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can revoke an approval");
+        }
+        if let Some(mut approvals) = self.token_approvals.get(&token_id) {
+            approvals.remove(&account_id);
+            self.token_approvals.insert(&token_id, &approvals);
+        }
+    }
+
+    pub fn get_approval_id(&self, token_id: TokenId, account_id: AccountId) -> Option<U64> {
+        self.token_approvals
+            .get(&token_id)
+            .and_then(|approvals| approvals.get(&account_id).copied())
+            .map(U64)
+    }
+
+    /// NEP-178: lets `account_id` transfer, list or burn this one corgi, unlike
+    /// `grant_access` which covers all of the owner's corgis. Calls the account's
+    /// `nft_on_approve` if `msg` is given. Requires at least 1 yoctoNEAR attached.
+    #[payable]
+    pub fn nft_approve(
+        &mut self,
+        token_id: String,
+        account_id: AccountId,
+        msg: Option<String>,
+    ) -> Option<Promise> {
+        if env::attached_deposit() == 0 {
+            env::panic(b"Requires attached deposit of at least 1 yoctoNEAR");
+        }
+        let id = self.approvable_token(&token_id);
+        self.assert_transferable(id);
+        let approval_id = self.next_approval_id;
+        self.next_approval_id += 1;
+        let mut approvals = self.token_approvals.get(&id).unwrap_or_default();
+        approvals.insert(account_id.clone(), approval_id);
+        self.token_approvals.insert(&id, &approvals);
+        msg.map(|msg| {
+            ext_approval_receiver::nft_on_approve(
+                token_id,
+                env::predecessor_account_id(),
+                U64(approval_id),
+                msg,
+                &account_id,
+                0,
+                ON_APPROVE_GAS,
+            )
+        })
+    }
+
+    /// NEP-178: revokes the approval of `account_id`. Requires exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn nft_revoke(&mut self, token_id: String, account_id: AccountId) {
+        if env::attached_deposit() != 1 {
+            env::panic(b"Requires attached deposit of exactly 1 yoctoNEAR");
+        }
+        let id = self.approvable_token(&token_id);
+        if let Some(mut approvals) = self.token_approvals.get(&id) {
+            approvals.remove(&account_id);
+            self.token_approvals.insert(&id, &approvals);
+        }
+    }
+
+    /// NEP-178: revokes every approval of the corgi. Requires exactly 1 yoctoNEAR.
+    #[payable]
+    pub fn nft_revoke_all(&mut self, token_id: String) {
+        if env::attached_deposit() != 1 {
+            env::panic(b"Requires attached deposit of exactly 1 yoctoNEAR");
+        }
+        let id = self.approvable_token(&token_id);
+        self.token_approvals.remove(&id);
+    }
+
+    /// NEP-178: whether `approved_account_id` is approved, with `approval_id` if given.
+    pub fn nft_is_approved(
+        &self,
+        token_id: String,
+        approved_account_id: AccountId,
+        approval_id: Option<u64>,
+    ) -> bool {
+        let id = match token_id.parse() {
+            Ok(id) => id,
+            Err(_) => return false,
+        };
+        match self.get_approval_id(id, approved_account_id) {
+            Some(U64(current)) => approval_id.is_none_or(|approval_id| approval_id == current),
+            None => false,
+        }
+    }
+
+    /// Puts the caller's corgi up for auction, holding it in the contract until
+    /// `finalize_auction`. Every bid after the first must beat the highest one by
+    /// `min_increment`; below a `reserve_price` the corgi goes back unsold.
+    /// With a `buy_now_price`, `buy_now` ends the auction early at that price.
+    pub fn start_auction(
+        &mut self,
+        token_id: TokenId,
+        duration_hours: u64,
+        min_increment: BidIncrement,
+        reserve_price: Option<U128>,
+        buy_now_price: Option<U128>,
+    ) {
+        self.assert_not_paused();
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can auction a corgi");
+        }
+        if duration_hours == 0 || duration_hours > MAX_AUCTION_HOURS {
+            env::panic(b"Invalid auction duration");
+        }
+        if let BidIncrement::Bps(bps) = min_increment {
+            if bps > 10_000 {
+                env::panic(b"Bid increment cannot exceed 100%");
+            }
+        }
+        self.internal_transfer(token_id, predecessor.clone(), env::current_account_id());
+        self.open_auction(
+            token_id,
+            predecessor,
+            duration_hours,
+            min_increment,
+            reserve_price.map(|price| price.0),
+            buy_now_price.map(|price| price.0),
+        );
+    }
+
+    /// Bids the attached deposit. The bid it beats is refunded to that bidder,
+    /// or credited to their escrow balance if the refund fails.
+    #[payable]
+    pub fn place_bid(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let mut auction = self.auctions.get(&token_id).expect("Corgi is not auctioned");
+        if env::block_timestamp() >= auction.ends_at {
+            env::panic(b"Auction has ended");
+        }
+        let bid = env::attached_deposit();
+        if bid == 0 || bid < min_next_bid(&auction) {
+            env::panic(b"Bid is below the minimum increment");
+        }
+        if let Some((bidder, amount)) = auction.highest_bid.take() {
+            self.pay_out(bidder, amount);
+        }
+        auction.highest_bid = Some((env::predecessor_account_id(), bid));
+        self.auctions.insert(&token_id, &auction);
+    }
+
+    /// Ends a running auction at its buy-now price, which must be attached. The
+    /// highest bid is refunded like in `place_bid` and the sale settles like
+    /// `buy_corgi`.
+    #[payable]
+    pub fn buy_now(&mut self, token_id: TokenId) {
+        self.assert_not_paused();
+        let auction = self.auctions.get(&token_id).expect("Corgi is not auctioned");
+        if env::block_timestamp() >= auction.ends_at {
+            env::panic(b"Auction has ended");
+        }
+        let price = auction.buy_now_price.expect("Auction has no buy-now price");
+        if env::attached_deposit() != price {
+            env::panic(b"Attached deposit must equal the buy-now price");
+        }
+        self.auctions.remove(&token_id);
+        if let Some((bidder, amount)) = auction.highest_bid {
+            self.pay_out(bidder, amount);
+        }
+        let buyer = env::predecessor_account_id();
+        self.internal_transfer(token_id, env::current_account_id(), buyer.clone());
+        self.lock_transfers(token_id);
+        let rarity = self.get_corgi(token_id).rate;
+        self.record_sale_price(rarity, U128(price), &auction.seller, &buyer);
+        self.record_trade(token_id, &auction.seller, &buyer, Some(U128(price)));
+        emit_transfer(&auction.seller, &buyer, token_id, None, Some("buy now".to_string()));
+        emit_sold(&auction.seller, &buyer, token_id, U128(price), &Currency::Near);
+        let proceeds = self.take_market_fee(&auction.seller, price);
+        let proceeds = self.pay_royalty(token_id, &auction.seller, price, proceeds);
+        self.pay_auction_seller(auction.seller, proceeds);
+    }
+
+    /// Deprecated: use `finalize_auction`.
+    pub fn close_auction(&mut self, token_id: TokenId) {
+        log_deprecated("close_auction", "finalize_auction");
+        self.finalize_auction(token_id)
+    }
+
+    /// Settles an ended auction; anyone may call it. The highest bidder gets the
+    /// corgi and the seller the bid. If there were no bids or the reserve wasn't
+    /// met, the corgi returns to the seller and the highest bid is refunded.
+    /// The caller is paid the keeper bounty.
+    pub fn finalize_auction(&mut self, token_id: TokenId) {
+        self.settle_auction(token_id);
+        // an auction settles in one call, so it counts as a full batch
+        self.pay_keeper_bounty(MIN_KEEPER_BATCH);
+    }
+
+    pub fn get_auction(&self, token_id: TokenId) -> Option<AuctionView> {
+        self.auctions
+            .get(&token_id)
+            .map(|auction| auction_view(token_id, &auction))
+    }
+
+    /// Auctions still taking bids among `limit` auctions from `from_index`;
+    /// ended ones awaiting `finalize_auction` are left out.
+    pub fn get_active_auctions(&self, from_index: u64, limit: u64) -> Vec<AuctionView> {
+        let limit = view_limit(limit);
+        let now = env::block_timestamp();
+        let token_ids = self.auctions.keys_as_vector();
+        let auctions = self.auctions.values_as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), token_ids.len()))
+            .filter_map(|index| Some((token_ids.get(index)?, auctions.get(index)?)))
+            .filter(|(_, auction)| auction.ends_at > now)
+            .map(|(token_id, auction)| auction_view(token_id, &auction))
+            .collect()
+    }
+
+    /// Lets `renter_id` battle with the caller's corgi for `duration_hours`. The
+    /// corgi stays with its owner but cannot be transferred until the rental ends.
+    pub fn rent_out(&mut self, token_id: TokenId, renter_id: AccountId, duration_hours: u64) {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can rent out a corgi");
+        }
+        if renter_id == predecessor {
+            env::panic(b"Cannot rent a corgi to its owner");
+        }
+        if duration_hours == 0 || duration_hours > MAX_RENTAL_HOURS {
+            env::panic(b"Invalid rental duration");
+        }
+        if self.active_rental(token_id).is_some() {
+            env::panic(b"Corgi is already rented out");
+        }
+        self.assert_transferable(token_id);
+        // an expired rental may not have been swept yet
+        self.remove_rental(token_id);
+        let mut rented_out = self.rented_out.get(&predecessor).unwrap_or_default();
+        rented_out.push(token_id);
+        self.rented_out.insert(&predecessor, &rented_out);
+        let mut renting = self.renting.get(&renter_id).unwrap_or_default();
+        renting.push(token_id);
+        self.renting.insert(&renter_id, &renting);
+        self.rentals.insert(
+            &token_id,
+            &Rental {
+                token_id,
+                owner: predecessor,
+                renter: renter_id,
+                ends_at: U64(env::block_timestamp() + duration_hours * 60 * 60 * 1_000_000_000),
+            },
+        );
+    }
+
+    /// Returns a rented corgi early; only the renter can give up the rental.
+    pub fn end_rental(&mut self, token_id: TokenId) {
+        let rental = self.rentals.get(&token_id).expect("Corgi is not rented out");
+        if rental.renter != env::predecessor_account_id() {
+            env::panic(b"Only the renter can end a rental early");
+        }
+        self.remove_rental(token_id);
+    }
+
+    /// Removes up to `limit` expired rentals from both parties' indexes. Callers
+    /// are paid the keeper bounty per removed rental.
+    pub fn sweep_expired_rentals(&mut self, limit: u64) -> u64 {
+        let now = env::block_timestamp();
+        let expired: Vec<TokenId> = self
+            .rentals
+            .iter()
+            .filter(|(_, rental)| rental.ends_at.0 <= now)
+            .map(|(token_id, _)| token_id)
+            .take(limit as usize)
+            .collect();
+        for &token_id in expired.iter() {
+            self.remove_rental(token_id);
+        }
+        self.pay_keeper_bounty(expired.len() as u64);
+        expired.len() as u64
+    }
+
+    pub fn get_rental(&self, token_id: TokenId) -> Option<Rental> {
+        self.active_rental(token_id)
+    }
+
+    /// Active rentals of corgis `account_id` lent to others.
+    pub fn get_rented_out(&self, account_id: AccountId) -> Vec<Rental> {
+        self.rented_out
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|token_id| self.active_rental(token_id))
+            .collect()
+    }
+
+    /// Active rentals of corgis `account_id` borrowed from others.
+    pub fn get_renting(&self, account_id: AccountId) -> Vec<Rental> {
+        self.renting
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|token_id| self.active_rental(token_id))
+            .collect()
+    }
+
+    pub fn get_wishlist(&self, account_id: AccountId) -> Vec<WishlistCriteria> {
+        self.wishlists.get(&account_id).unwrap_or_default()
+    }
+
+    pub fn get_wishlist_matches(&self, account_id: AccountId) -> Vec<WishlistMatch> {
+        self.wishlist_matches.get(&account_id).unwrap_or_default()
+    }
+}
+
+// Helper methods
+impl Corgi3D {
+    /// Settles an ended auction for `finalize_auction` and `tick`.
+    pub(crate) fn settle_auction(&mut self, token_id: TokenId) {
+        let auction = self.auctions.get(&token_id).expect("Corgi is not auctioned");
+        if env::block_timestamp() < auction.ends_at {
+            env::panic(b"Auction has not ended yet");
+        }
+        self.auctions.remove(&token_id);
+        let contract_id = env::current_account_id();
+        match auction.highest_bid {
+            Some((bidder, amount)) if amount >= auction.reserve_price.unwrap_or(0) => {
+                self.internal_transfer(token_id, contract_id, bidder.clone());
+                self.lock_transfers(token_id);
+                let rarity = self.get_corgi(token_id).rate;
+                self.record_sale_price(rarity, U128(amount), &auction.seller, &bidder);
+                self.record_trade(token_id, &auction.seller, &bidder, Some(U128(amount)));
+                emit_transfer(&auction.seller, &bidder, token_id, None, Some("auction".to_string()));
+                emit_sold(&auction.seller, &bidder, token_id, U128(amount), &Currency::Near);
+                let proceeds = self.take_market_fee(&auction.seller, amount);
+                let proceeds = self.pay_royalty(token_id, &auction.seller, amount, proceeds);
+                self.pay_auction_seller(auction.seller, proceeds);
+            }
+            highest_bid => {
+                if let Some((bidder, amount)) = highest_bid {
+                    self.pay_out(bidder, amount);
+                }
+                self.internal_transfer(token_id, contract_id, auction.seller);
+            }
+        }
+    }
+
+    /// ID and discount of the best running flash sale for minting (`token_id` of
+    /// `None`) or buying `token_id` that `buyer` has purchases left in.
+    fn best_flash_sale(&self, token_id: Option<TokenId>, buyer: &AccountId) -> Option<(u64, u32)> {
+        let now = env::block_timestamp();
+        self.flash_sales
+            .iter()
+            .filter(|sale| sale.starts_at.0 <= now && now < sale.ends_at.0)
+            .filter(|sale| match token_id {
+                None => sale.token_ids.is_empty(),
+                Some(id) => sale.token_ids.contains(&id),
+            })
+            .filter(|sale| {
+                self.flash_sale_purchases.get(&(sale.id, buyer.clone())).unwrap_or(0)
+                    < sale.per_account_limit
+            })
+            .max_by_key(|sale| sale.discount_bps)
+            .map(|sale| (sale.id, sale.discount_bps))
+    }
+
+    /// Discounts `price` by the best flash sale `buyer` is eligible for and counts
+    /// the purchase against its per-account limit. Past the limit, or once the
+    /// sale has ended, the full price applies.
+    pub(crate) fn apply_flash_sale(
+        &mut self,
+        token_id: Option<TokenId>,
+        buyer: &AccountId,
+        price: Balance,
+    ) -> Balance {
+        match self.best_flash_sale(token_id, buyer) {
+            Some((sale_id, discount_bps)) => {
+                let key = (sale_id, buyer.clone());
+                let purchases = self.flash_sale_purchases.get(&key).unwrap_or(0);
+                self.flash_sale_purchases.insert(&key, &(purchases + 1));
+                discounted(price, discount_bps)
+            }
+            None => price,
+        }
+    }
+
+    /// Whether `account` holds a per-token approval for `token_id`.
+    pub(crate) fn is_approved_for(&self, token_id: TokenId, account: &AccountId) -> bool {
+        self.token_approvals
+            .get(&token_id)
+            .is_some_and(|approvals| approvals.contains_key(account))
+    }
+
+    /// Parses `token_id` and checks that the caller owns it.
+    fn approvable_token(&self, token_id: &str) -> TokenId {
+        let id: TokenId = match token_id.parse() {
+            Ok(id) => id,
+            Err(_) => env::panic(b"Invalid token ID"),
+        };
+        if self.get_token_owner(id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can manage approvals");
+        }
+        id
+    }
+
+    /// Clears the listing of `id`, if any, and refunds its deposit.
+    fn assert_trade_owners(
+        &self,
+        offered: &[TokenId],
+        proposer: &AccountId,
+        wanted: &[TokenId],
+        counterparty: &AccountId,
+    ) {
+        if offered.iter().any(|&token_id| &self.get_token_owner(token_id) != proposer) {
+            env::panic(b"Proposer doesn't own every offered corgi");
+        }
+        if wanted.iter().any(|&token_id| &self.get_token_owner(token_id) != counterparty) {
+            env::panic(b"Counterparty doesn't own every wanted corgi");
+        }
+    }
+
+    fn remove_trade(&mut self, trade: &TradeProposal) {
+        self.trades.remove(&trade.id);
+        remove_trade_id(&mut self.outgoing_trades, &trade.proposer, trade.id);
+        remove_trade_id(&mut self.incoming_trades, &trade.counterparty, trade.id);
+    }
+
+    fn trade_page(&self, trade_ids: Vec<u64>, from_index: u64, limit: u64) -> Vec<TradeProposal> {
+        let limit = view_limit(limit);
+        trade_ids
+            .into_iter()
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .filter_map(|trade_id| self.trades.get(&trade_id))
+            .collect()
+    }
+
+    fn remove_bundle(&mut self, bundle: &Bundle) {
+        self.bundles.remove(&bundle.id);
+        for token_id in bundle.token_ids.iter() {
+            self.bundled_corgis.remove(token_id);
+        }
+    }
+
+    pub(crate) fn end_listing(&mut self, id: TokenId) {
+        let mut corgi = self.corgis.get(&id).unwrap();
+        if corgi.selling {
+            corgi.selling = false;
+            corgi.selling_price = U128(0);
+            self.corgis.insert(&id, &corgi);
+            self.selling_corgis.remove(&id);
+            self.listing_currencies.remove(&id);
+            self.listing_starts.remove(&id);
+        }
+        self.release_listing_deposit(id);
+    }
+
+    /// Returns the listing deposit held for `id` to the depositor's escrow.
+    pub(crate) fn release_listing_deposit(&mut self, id: TokenId) {
+        if let Some((account, amount)) = self.listing_deposits.remove(&id) {
+            self.credit_deposit(&account, amount);
+        }
+    }
+
+    fn required_listing_deposit(&self, token_id: TokenId) -> Balance {
+        self.corgi_editions
+            .get(&token_id)
+            .and_then(|edition_id| self.edition_listing_deposits.get(&edition_id))
+            .unwrap_or(self.listing_deposit)
+    }
+
+    /// Holds the listing deposit of a new listing by `owner`. Relisting a listed
+    /// corgi and listings of contract-held corgis need none.
+    fn take_listing_deposit(&mut self, id: TokenId, owner: &AccountId) {
+        let required = self.required_listing_deposit(id);
+        if required == 0 || self.listing_deposits.get(&id).is_some() || is_custodian(owner) {
+            return;
+        }
+        let attached = env::attached_deposit();
+        if attached < required {
+            env::panic(b"Attach the listing deposit to list a corgi");
+        }
+        let depositor = env::predecessor_account_id();
+        self.listing_deposits.insert(&id, &(depositor.clone(), required));
+        if attached > required {
+            self.credit_deposit(&depositor, attached - required);
+        }
+    }
+
+    /// Known ancestors of `token_id` up to `depth` generations up, with how many
+    /// generations up each is. Burned corgis end their branch.
+    fn ancestors(&self, token_id: TokenId, depth: u64) -> Vec<(TokenId, u64)> {
+        let mut found = vec![];
+        let mut generation = vec![token_id];
+        for generations in 1..=depth {
+            let parents: Vec<TokenId> = generation
+                .iter()
+                .filter_map(|id| self.corgis.get(id))
+                .flat_map(|corgi| corgi.parents)
+                .collect();
+            found.extend(parents.iter().map(|&parent| (parent, generations)));
+            generation = parents;
+        }
+        found
+    }
+
+    /// Moves the seller's marketplace fee on a NEAR sale to the treasury and
+    /// counts the sale towards its volume tier. Returns what the seller is owed.
+    fn assert_not_kin(&self, a: TokenId, b: TokenId) {
+        if let Some(conflict) = self.check_kinship(a, b) {
+            env::panic(
+                format!("Kinship conflict: {}", serde_json::to_string(&conflict).unwrap()).as_bytes(),
+            );
+        }
+    }
+
+    /// Links a freshly minted `child` to its parents, skipping parents burned
+    /// since the breeding was paid for, and returns the updated child.
+    pub(crate) fn record_breeding(&mut self, mut child: Corgi, parent_a: TokenId, parent_b: TokenId) -> Corgi {
+        let mut generation = 0;
+        for parent_id in [parent_a, parent_b].iter() {
+            let mut parent = match self.corgis.get(parent_id) {
+                Some(parent) => parent,
+                None => continue,
+            };
+            parent.times_bred += 1;
+            generation = std::cmp::max(generation, parent.generation + 1);
+            self.unlock_hidden_traits(&mut parent);
+            self.corgis.insert(parent_id, &parent);
+        }
+        child.parents = vec![parent_a, parent_b];
+        child.generation = generation;
+        self.corgis.insert(&child.id, &child);
+        child
+    }
+
+    /// Rolls the traits of a child of `a` and `b`. Rarity tiers whose season quota
+    /// is used up degrade like in `generate_rate_sausage_at_least`.
+    pub(crate) fn inherit_traits(&self, a: &Corgi, b: &Corgi) -> (CorgiSpec, Rarity, String) {
+        let (r1, r2) = self.random_num();
+        let spec = CorgiSpec {
+            name: format!("{} Jr.", a.name),
+            color: if r1 % 2 == 0 { a.color.clone() } else { b.color.clone() },
+            background_color: if r1 / 2 % 2 == 0 {
+                a.background_color.clone()
+            } else {
+                b.background_color.clone()
+            },
+            quote: b.quote.clone(),
+        };
+        let mut tier = (rarity_tier(a.rate) + rarity_tier(b.rate)).div_ceil(2);
+        if r2 < RARITY_MUTATION_ODDS {
+            tier = std::cmp::min(tier + 1, rarity_tier(Rarity::UltraRare));
+        } else if r2 >= 50 - RARITY_MUTATION_ODDS {
+            tier = std::cmp::max(tier - 1, rarity_tier(Rarity::Common));
+        }
+        let mut rarity = Rarity::ALL[tier as usize - 1];
+        while self.rarity_quota_reached(rarity) && rarity != Rarity::Common {
+            rarity = rarity.lower();
+        }
+        // the length without the 50 per tier above common that generated corgis get
+        let base_length = |corgi: &Corgi| {
+            let sausage = corgi.sausage.parse::<u64>().unwrap_or(0);
+            sausage.saturating_sub((rarity_tier(corgi.rate) - 1) * 50)
+        };
+        let length = (base_length(a) + base_length(b)) / 2 + r1 as u64 % (2 * SAUSAGE_MUTATION + 1);
+        let length = std::cmp::min(length.saturating_sub(SAUSAGE_MUTATION), 99);
+        let sausage = length + (rarity_tier(rarity) - 1) * 50;
+        (spec, rarity, sausage.to_string())
+    }
+
+    /// Removes the offer of `bidder` on `token_id` from both indexes.
+    pub(crate) fn remove_offer(&mut self, token_id: TokenId, bidder: &AccountId) -> Offer {
+        let mut offers = self.offers.get(&token_id).unwrap_or_default();
+        let index = offers
+            .iter()
+            .position(|offer| &offer.bidder == bidder)
+            .expect("Offer not found");
+        let offer = offers.remove(index);
+        if offers.is_empty() {
+            self.offers.remove(&token_id);
+        } else {
+            self.offers.insert(&token_id, &offers);
+        }
+        let mut token_ids = self.account_offers.get(bidder).unwrap_or_default();
+        token_ids.retain(|&id| id != token_id);
+        if token_ids.is_empty() {
+            self.account_offers.remove(bidder);
+        } else {
+            self.account_offers.insert(bidder, &token_ids);
+        }
+        offer
+    }
+
+    /// Hands a sold corgi back from `buyer` to `seller` if nothing has happened to
+    /// it since, returning whether it did.
+    fn undo_sale(&mut self, token_id: TokenId, seller: &AccountId, buyer: &AccountId) -> bool {
+        // the cooldown set by the sale itself must not block undoing it
+        let locked_until = self.transfer_locks.remove(&token_id);
+        if self.corgi_to_account.get(&token_id).as_ref() != Some(buyer)
+            || self.transfer_blocker(token_id).is_some()
+        {
+            if let Some(locked_until) = locked_until {
+                self.transfer_locks.insert(&token_id, &locked_until);
+            }
+            return false;
+        }
+        self.internal_transfer(token_id, buyer.clone(), seller.clone());
+        emit_transfer(buyer, seller, token_id, None, Some("purchase rollback".to_string()));
+        true
+    }
+
+    /// Pays for a corgi minted to `minter` with `amount` of `ft_id`, returning the
+    /// unused amount. The price goes to the owner's token balance; the corgi is
+    /// revealed with `reveal_corgi`.
+    fn mint_for_ft(&mut self, minter: AccountId, ft_id: AccountId, amount: U128, spec: CorgiSpec) -> U128 {
+        let price = self.ft_mint_prices.get(&ft_id).expect("Corgis can't be minted with this token");
+        if amount.0 < price {
+            env::panic(b"Don't pay enough money to mint a corgi");
+        }
+        self.enter_drop(&minter, 1);
+        let storage_before = env::storage_usage();
+        let owner_id = self.owner_id.clone();
+        self.credit_ft(&owner_id, &ft_id, price);
+        let pending = self.commit_public_mint(&minter, spec, 0);
+        env::log(format!("Pending mint {} paid, reveal it with reveal_corgi", pending.id).as_bytes());
+        self.charge_storage(&minter, storage_before, 0);
+        self.charge(&minter, pending.storage_reserve.0, 0);
+        U128(amount.0 - price)
+    }
+
+    /// Creator and royalty owed on a sale of `token_id` by `seller` for `price`;
+    /// creators don't pay royalties to themselves.
+    fn royalty_of(&self, token_id: TokenId, seller: &AccountId, price: Balance) -> Option<(AccountId, Balance)> {
+        let corgi = self.corgis.get(&token_id)?;
+        let royalty = price * corgi.royalty_bps as u128 / 10_000;
+        if corgi.creator == *seller || royalty == 0 {
+            return None;
+        }
+        Some((corgi.creator, royalty))
+    }
+
+    /// Pays the royalty on a sale out of the seller's `proceeds`, returning what is
+    /// left of them.
+    fn pay_royalty(&self, token_id: TokenId, seller: &AccountId, price: Balance, proceeds: Balance) -> Balance {
+        match self.royalty_of(token_id, seller, price) {
+            Some((creator, royalty)) => {
+                let royalty = std::cmp::min(royalty, proceeds);
+                self.pay_out(creator, royalty);
+                proceeds - royalty
+            }
+            None => proceeds,
+        }
+    }
+
+    fn pay_creator(&self, token_id: TokenId, royalty: Balance) {
+        if royalty > 0 {
+            if let Some(corgi) = self.corgis.get(&token_id) {
+                self.pay_out(corgi.creator, royalty);
+            }
+        }
+    }
+
+    fn ft_pay_creator(&self, token_id: TokenId, ft_id: AccountId, royalty: Balance) {
+        if royalty > 0 {
+            if let Some(corgi) = self.corgis.get(&token_id) {
+                self.ft_pay_out(ft_id, corgi.creator, royalty);
+            }
+        }
+    }
+
+    /// NEP-199 split of `balance` between the creator and `owner_id`.
+    pub(crate) fn payout_for(
+        &self,
+        token_id: TokenId,
+        owner_id: &AccountId,
+        balance: Balance,
+        max_len_payout: u32,
+    ) -> Payout {
+        let mut payout = HashMap::new();
+        let mut remaining = balance;
+        if let Some((creator, royalty)) = self.royalty_of(token_id, owner_id, balance) {
+            payout.insert(creator, U128(royalty));
+            remaining -= royalty;
+        }
+        payout.insert(owner_id.clone(), U128(remaining));
+        if payout.len() > max_len_payout as usize {
+            env::panic(b"Payout exceeds max_len_payout");
+        }
+        Payout { payout }
+    }
+
+    fn take_market_fee(&mut self, seller: &AccountId, amount: Balance) -> Balance {
+        let fee = amount * self.get_fee_tier(seller.clone()).fee_bps as u128 / 10_000;
+        self.treasury_balance += fee;
+        let volume = self.sale_volume.get(seller).unwrap_or(0) + amount;
+        self.sale_volume.insert(seller, &volume);
+        amount - fee
+    }
+
+    pub(crate) fn listing_currency(&self, token_id: TokenId) -> Currency {
+        self.listing_currencies.get(&token_id).unwrap_or(Currency::Near)
+    }
+
+    /// The rental of a corgi unless it has expired, even if not swept yet.
+    pub(crate) fn active_rental(&self, token_id: TokenId) -> Option<Rental> {
+        self.rentals
+            .get(&token_id)
+            .filter(|rental| env::block_timestamp() < rental.ends_at.0)
+    }
+
+    pub(crate) fn remove_rental(&mut self, token_id: TokenId) {
+        if let Some(rental) = self.rentals.remove(&token_id) {
+            let mut rented_out = self.rented_out.get(&rental.owner).unwrap_or_default();
+            rented_out.retain(|&id| id != token_id);
+            if rented_out.is_empty() {
+                self.rented_out.remove(&rental.owner);
+            } else {
+                self.rented_out.insert(&rental.owner, &rented_out);
+            }
+            let mut renting = self.renting.get(&rental.renter).unwrap_or_default();
+            renting.retain(|&id| id != token_id);
+            if renting.is_empty() {
+                self.renting.remove(&rental.renter);
+            } else {
+                self.renting.insert(&rental.renter, &renting);
+            }
+        }
+    }
+
+    pub(crate) fn add_breeding_licenses(&mut self, account: &AccountId, count: u64) {
+        let licenses = self.breeding_licenses.get(account).unwrap_or(0) + count;
+        self.breeding_licenses.insert(account, &licenses);
+    }
+
+    fn take_breeding_licenses(&mut self, account: &AccountId, count: u64) {
+        let licenses = self.breeding_licenses.get(account).unwrap_or(0);
+        if licenses < count {
+            env::panic(b"Not enough breeding licenses");
+        }
+        if licenses == count {
+            self.breeding_licenses.remove(account);
+        } else {
+            self.breeding_licenses.insert(account, &(licenses - count));
+        }
+    }
+
+    fn update_wishlist_bucket(
+        &mut self,
+        criteria: &WishlistCriteria,
+        wisher: &AccountId,
+        added: bool,
+    ) {
+        let key = wishlist_bucket_key(criteria.rarity, criteria.color.as_deref());
+        let mut wishers = self.wishlist_buckets.get(&key).unwrap_or_else(|| {
+            let mut prefix = b"wishlist-bucket".to_vec();
+            prefix.extend(env::sha256(&key));
+            UnorderedSet::new(prefix)
+        });
+        if added {
+            wishers.insert(wisher);
+        } else {
+            wishers.remove(wisher);
+        }
+        self.wishlist_buckets.insert(&key, &wishers);
+    }
+
+    /// Records a match for every wisher (other than the seller) whose criteria
+    /// fit the newly listed corgi, keeping only the latest matches per wisher.
+    /// Only the wishlist buckets of the corgi's rarity and color are looked at,
+    /// the most specific first, and at most `MAX_WISHLIST_SCAN` wishers of them.
+    fn record_wishlist_matches(&mut self, corgi: &Corgi, seller: &AccountId) {
+        let now = env::block_timestamp();
+        let keys = [
+            wishlist_bucket_key(Some(corgi.rate), Some(corgi.color.as_str())),
+            wishlist_bucket_key(Some(corgi.rate), None),
+            wishlist_bucket_key(None, Some(corgi.color.as_str())),
+            wishlist_bucket_key(None, None),
+        ];
+        let mut wishers: Vec<AccountId> = vec![];
+        for bucket in keys.iter().filter_map(|key| self.wishlist_buckets.get(key)) {
+            let remaining = MAX_WISHLIST_SCAN - wishers.len();
+            let new_wishers: Vec<AccountId> =
+                bucket.iter().filter(|wisher| !wishers.contains(wisher)).take(remaining).collect();
+            wishers.extend(new_wishers);
+        }
+        for wisher in wishers {
+            let wishlist = self.wishlists.get(&wisher).unwrap_or_default();
+            if &wisher == seller || !wishlist.iter().any(|criteria| criteria.matches(corgi)) {
+                continue;
+            }
+            let mut matches = self.wishlist_matches.get(&wisher).unwrap_or_default();
+            if matches.len() >= MAX_WISHLIST_MATCHES {
+                matches.remove(0);
+            }
+            matches.push(WishlistMatch {
+                token_id: corgi.id,
+                price: corgi.selling_price,
+                listed_at: now,
+            });
+            self.wishlist_matches.insert(&wisher, &matches);
+        }
+    }
+
+    /// Bookkeeping of a sale; `price` is `None` for sales in fungible tokens.
+    fn record_trade(
+        &mut self,
+        token_id: TokenId,
+        seller: &AccountId,
+        buyer: &AccountId,
+        price: Option<U128>,
+    ) {
+        self.record_provenance(token_id, seller, buyer, price);
+        self.purchased_at.insert(&token_id, &env::block_timestamp());
+        let pair = trade_pair(seller, buyer);
+        let count = self.pair_trades.get(&pair).unwrap_or(0);
+        self.pair_trades.insert(&pair, &(count + 1));
+        let mut history = self.trade_history.get(&token_id).unwrap_or_default();
+        if history.last() != Some(seller) {
+            history.push(seller.clone());
+        }
+        history.push(buyer.clone());
+        if history.len() > MAX_TRADE_HISTORY {
+            history.drain(..history.len() - MAX_TRADE_HISTORY);
+        }
+        self.trade_history.insert(&token_id, &history);
+    }
+
+    /// An auction of a corgi already in the contract's custody.
+    fn open_auction(
+        &mut self,
+        token_id: TokenId,
+        seller: AccountId,
+        duration_hours: u64,
+        min_increment: BidIncrement,
+        reserve_price: Option<Balance>,
+        buy_now_price: Option<Balance>,
+    ) {
+        self.auctions.insert(
+            &token_id,
+            &Auction {
+                seller,
+                ends_at: env::block_timestamp() + duration_hours * 60 * 60 * 1_000_000_000,
+                min_increment,
+                reserve_price,
+                highest_bid: None,
+                buy_now_price,
+            },
+        );
+    }
+
+    /// Proceeds of liquidation auctions, sold by the contract itself, go to the treasury.
+    fn pay_auction_seller(&mut self, seller: AccountId, proceeds: Balance) {
+        if is_custodian(&seller) {
+            self.treasury_balance += proceeds;
+        } else {
+            self.pay_out(seller, proceeds);
+        }
+    }
+
+    fn record_sale_price(&mut self, rarity: Rarity, price: U128, seller: &AccountId, buyer: &AccountId) {
+        self.highest_sale_price = std::cmp::max(self.highest_sale_price, price.0);
+        let mut observations = self.price_observations.get(&rarity).unwrap_or_default();
+        if observations.len() >= MAX_PRICE_OBSERVATIONS {
+            observations.remove(0);
+        }
+        observations.push(PriceObservation {
+            timestamp_sec: env::block_timestamp() / 1_000_000_000,
+            price,
+            seller: seller.clone(),
+            buyer: buyer.clone(),
+        });
+        self.price_observations.insert(&rarity, &observations);
+    }
+
+    /// Extends from the current expiry, or from now if the pass already lapsed.
+    fn extend_pass(&mut self, token_id: TokenId, periods: u64) {
+        let mut corgi = self.get_corgi(token_id);
+        let mut pass = corgi.pass.expect("Corgi is not a pass");
+        let start = std::cmp::max(pass.expires_at, env::block_timestamp());
+        pass.expires_at = start + periods * PASS_PERIOD;
+        corgi.pass = Some(pass);
+        self.corgis.insert(&token_id, &corgi);
+    }
+}
+
+/// Average of `observations` over the last `window_sec` seconds before `now`, each
+/// price holding until the next one.
+fn time_weighted_price(observations: &[PriceObservation], now: u64, window_sec: u64) -> Option<U128> {
+    let start = now.saturating_sub(window_sec);
+    let mut weighted_sum: u128 = 0;
+    let mut total_time: u128 = 0;
+    for (i, observation) in observations.iter().enumerate() {
+        let until = observations
+            .get(i + 1)
+            .map_or(now, |next| next.timestamp_sec);
+        let from = std::cmp::max(observation.timestamp_sec, start);
+        if until > from {
+            let duration = (until - from) as u128;
+            weighted_sum += observation.price.0 * duration;
+            total_time += duration;
+        }
+    }
+    if total_time == 0 {
+        return observations.last().map(|observation| observation.price);
+    }
+    Some(U128(weighted_sum / total_time))
+}
+
+fn auction_view(token_id: TokenId, auction: &Auction) -> AuctionView {
+    AuctionView {
+        token_id,
+        seller: auction.seller.clone(),
+        ends_at: U64(auction.ends_at),
+        min_increment: auction.min_increment.clone(),
+        highest_bidder: auction.highest_bid.as_ref().map(|(bidder, _)| bidder.clone()),
+        highest_bid: auction.highest_bid.as_ref().map(|(_, amount)| U128(*amount)),
+        min_next_bid: U128(min_next_bid(auction)),
+        reserve_met: auction.highest_bid.as_ref().map_or(0, |(_, amount)| *amount)
+            >= auction.reserve_price.unwrap_or(0),
+        buy_now_price: auction.buy_now_price.map(U128),
+    }
+}
+
+/// Lowest bid `place_bid` accepts next; 1 yoctoNEAR before the first bid.
+fn min_next_bid(auction: &Auction) -> Balance {
+    match &auction.highest_bid {
+        None => 1,
+        Some((_, amount)) => {
+            let increment = match &auction.min_increment {
+                BidIncrement::Absolute(increment) => increment.0,
+                BidIncrement::Bps(bps) => amount * *bps as u128 / 10_000,
+            };
+            amount + std::cmp::max(increment, 1)
+        }
+    }
+}
+
+/// Number of pass periods paid for by `deposit`.
+fn pass_periods(deposit: Balance) -> u64 {
+    if deposit == 0 || !deposit.is_multiple_of(PASS_PRICE) {
+        env::panic(b"Attached deposit must be a multiple of the pass price");
+    }
+    (deposit / PASS_PRICE) as u64
+}
+
+/// Key of `wishlist_buckets` for entries of exactly `rarity` and `color`, `None`
+/// standing for entries that match any.
+fn wishlist_bucket_key(rarity: Option<Rarity>, color: Option<&str>) -> Vec<u8> {
+    (rarity, color.map(normalize_color)).try_to_vec().unwrap()
+}
+
+/// Key of `pair_trades`, the same whichever side sold.
+fn trade_pair(a: &AccountId, b: &AccountId) -> (AccountId, AccountId) {
+    if a <= b {
+        (a.clone(), b.clone())
+    } else {
+        (b.clone(), a.clone())
+    }
+}
+
+fn discounted(price: Balance, discount_bps: u32) -> Balance {
+    price - price * discount_bps as u128 / 10_000
+}
+
+/// Drops `trade_id` from the trades `account` is listed with in `index`.
+fn remove_trade_id(index: &mut LookupMap<AccountId, Vec<u64>>, account: &AccountId, trade_id: u64) {
+    let mut trade_ids = index.get(account).unwrap_or_default();
+    trade_ids.retain(|&id| id != trade_id);
+    if trade_ids.is_empty() {
+        index.remove(account);
+    } else {
+        index.insert(account, &trade_ids);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn test_sell_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 1);
+
+        assert_eq!(contract.get_corgi(token_id).selling, false);
+        contract.sell_corgi(token_id, U128(10u128.pow(25)), None, None);
+        assert_eq!(contract.get_corgi(token_id).selling, true);
+        assert_eq!(
+            contract.get_corgi(token_id).selling_price,
+            U128(10u128.pow(25))
+        );
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(25);
+        testing_env!(context);
+        contract.buy_corgi(token_id, None);
+
+        assert_eq!(contract.get_corgi(token_id).selling, false);
+        assert_eq!(contract.get_corgis_by_owner(mike()).len(), 1);
+        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 0);
+    }
+
+    #[test]
+    fn vet_certificates_raise_trust_score() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_vet(joe());
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+
+        testing_env!(get_context(joe(), env::storage_usage()));
+        for _ in 0..MAX_CERTIFICATES + 1 {
+            contract.issue_certificate(token_id, "healthy".to_string());
+        }
+        assert_eq!(contract.get_certificates(token_id).len(), MAX_CERTIFICATES);
+        assert_eq!(contract.get_trust_score(token_id), 100);
+        assert_eq!(contract.get_market_listings(0, 10)[0].trust_score, 100);
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.remove_vet(joe());
+        assert_eq!(contract.get_trust_score(token_id), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only designated vets can issue certificates"#)]
+    fn non_vet_cannot_issue_certificate() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.issue_certificate(token_id, "healthy".to_string());
+    }
+
+    #[test]
+    fn breed_with_stud_mints_child_in_callback() {
+        testing_env!(get_context(mike(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let stud_id = mint_revealed(&mut contract,
+            "stud".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.offer_stud(stud_id, U128(10u128.pow(24)));
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.grant_breeding_licenses(robert(), 1);
+        let dam_id = mint_revealed(&mut contract,
+            "dam".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24) + MINT_PRICE + 10u128.pow(23); // with storage
+        testing_env!(context);
+        contract.breed_with_stud(
+            dam_id,
+            stud_id,
+            CorgiSpec {
+                name: "pup".to_string(),
+                color: "blue".to_string(),
+                background_color: "green".to_string(),
+                quote: "woof".to_string(),
+            },
+        );
+        assert_eq!(contract.get_breeding(0).unwrap().stud_owner, mike());
+
+        testing_env!(get_context(env::current_account_id(), env::storage_usage()));
+        let pending = contract.resolve_breeding(0).unwrap();
+        assert_eq!(contract.get_breeding_licenses(robert()), 0);
+        let child_id = reveal_next_block(&mut contract, pending.id).token_id.0;
+        let child = contract.get_corgi(child_id);
+        assert_eq!(child.parents, vec![dam_id, stud_id]);
+        assert_eq!(contract.get_token_owner(child_id), robert());
+        assert!(contract.get_breeding(0).is_none());
+        assert!(contract.get_hidden_traits(dam_id)[1].value.is_some());
+        assert!(contract.get_hidden_traits(child_id)[1].value.is_none());
+    }
+
+    #[test]
+    fn breed_own_corgis() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mut parents = vec![];
+        for name in &["a", "b"] {
+            parents.push(mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            ).token_id.0);
+        }
+        let mut context = get_context(robert(), env::storage_usage());
+        // the breeding fee plus storage
+        context.attached_deposit = BREEDING_FEE + 10u128.pow(23);
+        testing_env!(context);
+        let pending = contract.breed_corgis(parents[0], parents[1]);
+        let child_id = reveal_next_block(&mut contract, pending.id).token_id.0;
+        let child = contract.get_corgi(child_id);
+        assert_eq!(child.parents, parents);
+        assert_eq!(child.generation, 1);
+        assert_eq!(child.name, "a Jr.".to_string());
+        assert_eq!(child.color, "blue".to_string());
+        assert_eq!(contract.get_corgi(parents[0]).times_bred, 1);
+        assert_eq!(contract.get_token_owner(child_id), robert());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Parent is still in its breeding cooldown"#)]
+    fn breed_own_corgis_cooldown() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mut ids = vec![];
+        for name in &["a", "b", "c"] {
+            ids.push(mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            ).token_id.0);
+        }
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = BREEDING_FEE + 10u128.pow(23);
+        testing_env!(context);
+        contract.breed_corgis(ids[0], ids[1]);
+        contract.breed_corgis(ids[0], ids[2]);
+    }
+
+    #[test]
+    fn breeding_refunds_when_stud_withdrawn() {
+        testing_env!(get_context(mike(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let stud_id = mint_revealed(&mut contract,
+            "stud".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.offer_stud(stud_id, U128(0));
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.grant_breeding_licenses(robert(), 1);
+        let dam_id = mint_revealed(&mut contract,
+            "dam".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = MINT_PRICE + 10u128.pow(23); // with storage
+        testing_env!(context);
+        contract.breed_with_stud(
+            dam_id,
+            stud_id,
+            CorgiSpec {
+                name: "pup".to_string(),
+                color: "blue".to_string(),
+                background_color: "green".to_string(),
+                quote: "woof".to_string(),
+            },
+        );
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.withdraw_stud(stud_id);
+
+        testing_env!(get_context(env::current_account_id(), env::storage_usage()));
+        assert!(contract.resolve_breeding(0).is_none());
+        assert_eq!(contract.get_breeding_licenses(robert()), 1);
+        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 1);
+    }
+
+    #[test]
+    fn transfer_cooldown_after_mint() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_transfer_cooldown(1);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let locked_until = 60 * 60 * 1_000_000_000;
+        assert_eq!(contract.get_transfer_locked_until(token_id), Some(U64(locked_until)));
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = locked_until;
+        testing_env!(context);
+        assert_eq!(contract.get_transfer_locked_until(token_id), None);
+        contract.transfer(mike(), token_id, None);
+        assert_eq!(contract.get_token_owner(token_id), mike());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi is in its transfer cooldown"#)]
+    fn transfer_during_cooldown() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_transfer_cooldown(1);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.transfer(mike(), token_id, None);
+    }
+
+    #[test]
+    fn wishlist_match_on_listing() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.add_to_wishlist(WishlistCriteria {
+            rarity: None,
+            color: Some("#0000FF".to_string()),
+            max_price: Some(U128(10u128.pow(25))),
+        });
+        contract.add_to_wishlist(WishlistCriteria {
+            rarity: None,
+            color: Some("red".to_string()),
+            max_price: None,
+        });
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        let matches = contract.get_wishlist_matches(mike());
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].token_id, token_id);
+        assert_eq!(contract.get_wishlist_matches(robert()).len(), 0);
+    }
+
+    #[test]
+    fn removed_wishlist_entry_stops_matching() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.add_to_wishlist(WishlistCriteria {
+            rarity: None,
+            color: Some("blue".to_string()),
+            max_price: None,
+        });
+        contract.remove_from_wishlist(0);
+        assert!(contract.get_deposit(mike()).0 > 0);
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        assert!(contract.get_wishlist_matches(mike()).is_empty());
+    }
+
+    #[test]
+    fn transfer_delists_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        contract.transfer(joe(), token_id, None);
+        let corgi = contract.get_corgi(token_id);
+        assert_eq!(corgi.selling, false);
+        assert_eq!(corgi.selling_price, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi is not for sale"#)]
+    fn buy_after_transfer_fails() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        contract.transfer(joe(), token_id, None);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+    }
+
+    #[test]
+    fn twap_weights_prices_by_time() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let second = 1_000_000_000;
+        let rarity = Rarity::Rare;
+        assert_eq!(contract.get_twap(rarity, 100), None);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 10 * second;
+        testing_env!(context);
+        contract.record_sale_price(rarity, U128(100), &robert(), &mike());
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 40 * second;
+        testing_env!(context);
+        contract.record_sale_price(rarity, U128(200), &mike(), &joe());
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 50 * second;
+        testing_env!(context);
+        // 30s at 100 and 10s at 200
+        assert_eq!(contract.get_twap(rarity, 40), Some(U128(125)));
+        // only the last 10s at 200
+        assert_eq!(contract.get_twap(rarity, 10), Some(U128(200)));
+    }
+
+    #[test]
+    fn borrow_and_repay() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let rarity = contract.get_corgi(token_id).rate;
+        record_reference_sales(&mut contract, rarity, 10u128.pow(25));
+        contract.deposit_collateral(token_id);
+        assert_eq!(contract.get_token_owner(token_id), env::current_account_id());
+
+        contract.borrow(token_id, U128(MINT_PRICE / 2));
+        assert_eq!(contract.get_treasury_balance().0, MINT_PRICE / 2);
+        contract.repay(token_id);
+        assert_eq!(contract.get_token_owner(token_id), robert());
+        assert!(contract.get_loan(token_id).is_none());
+        assert_eq!(contract.get_treasury_balance().0, MINT_PRICE);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"No price reference for this rarity"#)]
+    fn fresh_or_self_sales_do_not_back_loans() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let rarity = contract.get_corgi(token_id).rate;
+        contract.record_sale_price(rarity, U128(10u128.pow(26)), &robert(), &robert());
+        contract.record_sale_price(rarity, U128(10u128.pow(26)), &robert(), &mike());
+        contract.record_sale_price(rarity, U128(10u128.pow(26)), &mike(), &joe());
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = MIN_LOAN_OBSERVATION_AGE_SEC * 1_000_000_000;
+        testing_env!(context);
+        // a fresh sale from a third pair is too young to count
+        contract.record_sale_price(rarity, U128(10u128.pow(26)), &joe(), &robert());
+        assert!(contract.get_loan_reference_price(rarity).is_none());
+        contract.deposit_collateral(token_id);
+        contract.borrow(token_id, U128(1));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Outstanding loans would exceed the treasury's lending limit"#)]
+    fn borrow_over_lending_limit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let rarity = contract.get_corgi(token_id).rate;
+        record_reference_sales(&mut contract, rarity, 10u128.pow(25));
+        contract.deposit_collateral(token_id);
+        contract.borrow(token_id, U128(MINT_PRICE));
+    }
+
+    #[test]
+    fn liquidation_auctions_collateral_for_the_treasury() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let rarity = contract.get_corgi(token_id).rate;
+        record_reference_sales(&mut contract, rarity, 10u128.pow(25));
+        contract.deposit_collateral(token_id);
+        contract.borrow(token_id, U128(MINT_PRICE / 2));
+        let due_at = contract.get_loan(token_id).unwrap().due_at;
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.block_timestamp = due_at + 1;
+        context.attached_deposit = MINT_PRICE;
+        testing_env!(context.clone());
+        contract.liquidate(token_id);
+        assert!(contract.get_loan(token_id).is_none());
+        assert_eq!(contract.loaned_principal, 0);
+        let auction = contract.get_auction(token_id).unwrap();
+        assert_eq!(auction.seller, env::current_account_id());
+        contract.place_bid(token_id);
+
+        context.block_timestamp = auction.ends_at.0;
+        testing_env!(context);
+        let treasury = contract.get_treasury_balance().0;
+        contract.finalize_auction(token_id);
+        assert_eq!(contract.get_token_owner(token_id), joe());
+        assert!(contract.get_treasury_balance().0 > treasury + MINT_PRICE / 2);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Borrow amount exceeds loan-to-value limit"#)]
+    fn borrow_over_ltv() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let rarity = contract.get_corgi(token_id).rate;
+        record_reference_sales(&mut contract, rarity, 10u128.pow(25));
+        contract.deposit_collateral(token_id);
+        contract.borrow(token_id, U128(4 * 10u128.pow(24)));
+    }
+
+    #[test]
+    fn pass_expiry_and_renewal() {
+        let mut context = get_context(robert(), 0);
+        // the pass price plus storage
+        context.attached_deposit = PASS_PRICE + 10u128.pow(23);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let pending = contract.mint_pass(
+            CorgiSpec {
+                name: "pass".to_string(),
+                color: "yellow".to_string(),
+                background_color: "black".to_string(),
+                quote: "members only".to_string(),
+            },
+            1,
+        );
+        let token_id = reveal_next_block(&mut contract, pending.id).token_id.0;
+        assert!(contract.is_pass_active(token_id));
+        contract.set_pass_auto_renew(token_id, true);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = PASS_PERIOD;
+        context.attached_deposit = PASS_PRICE;
+        testing_env!(context);
+        assert!(!contract.is_pass_active(token_id));
+        contract.deposit();
+        contract.auto_renew_pass(token_id);
+        assert!(contract.is_pass_active(token_id));
+        assert_eq!(contract.get_deposit(robert()).0, 0);
+    }
+
+    #[test]
+    fn accept_collection_offer() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let rarity = contract.get_corgi(token_id).rate;
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let offer_id = contract.make_collection_offer(rarity);
+        assert_eq!(contract.get_collection_offers(0, 10).len(), 1);
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.set_market_fee(500);
+        let treasury = contract.get_treasury_balance().0;
+        contract.accept_collection_offer(offer_id, token_id);
+        assert_eq!(contract.get_token_owner(token_id), mike());
+        assert_eq!(contract.get_collection_offers(0, 10).len(), 0);
+        // the offer is the context's 3 NEAR deposit
+        assert_eq!(contract.get_treasury_balance().0, treasury + 3 * 10u128.pow(24) * 500 / 10_000);
+        assert_eq!(contract.get_fee_tier(robert()).volume.0, 3 * 10u128.pow(24));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Contract is paused"#)]
+    fn paused_contract_refuses_collection_offers() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.pause();
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.make_collection_offer(Rarity::Common);
+    }
+
+    #[test]
+    fn accept_offer_on_unlisted_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.make_offer(token_id);
+        let offer = contract.make_offer(token_id);
+        assert_eq!(offer.price, U128(6 * 10u128.pow(24)));
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.make_offer(token_id);
+        assert_eq!(contract.get_offers_for_corgi(token_id).len(), 2);
+        assert_eq!(contract.get_offers_by_account(mike()).len(), 1);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.account_balance = 9 * 10u128.pow(24); // the escrowed offers
+        testing_env!(context);
+        contract.accept_offer(token_id, mike());
+        assert_eq!(contract.get_token_owner(token_id), mike());
+        assert!(contract.get_offers_by_account(mike()).is_empty());
+        assert_eq!(contract.get_offers_for_corgi(token_id)[0].bidder, joe());
+
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.cancel_offer(token_id);
+        assert!(contract.get_offers_for_corgi(token_id).is_empty());
+    }
+
+    #[test]
+    fn fractionalize_transfer_and_redeem() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.fractionalize(token_id, 100, U128(10u128.pow(25)));
+        assert_eq!(contract.get_token_owner(token_id), env::current_account_id());
+        contract.transfer_shares(token_id, joe(), 40);
+        let fraction = contract.get_fraction(token_id).unwrap();
+        assert_eq!(fraction.shares_of(&robert()), 60);
+        assert_eq!(fraction.shares_of(&joe()), 40);
+
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.transfer_shares(token_id, robert(), 40);
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.redeem(token_id);
+        assert_eq!(contract.get_token_owner(token_id), robert());
+    }
+
+    #[test]
+    fn buyout_pays_holders() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.fractionalize(token_id, 4, U128(10u128.pow(24)));
+        contract.transfer_shares(token_id, joe(), 1);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buyout(token_id);
+        assert_eq!(contract.get_token_owner(token_id), mike());
+        assert_eq!(contract.get_deposit(joe()).0, 3 * 10u128.pow(24) / 4);
+        assert!(contract.get_fraction(token_id).is_none());
+    }
+
+    #[test]
+    fn buy_and_transfer_breeding_licenses() {
+        let mut context = get_context(robert(), 0);
+        context.attached_deposit = 2 * BREEDING_LICENSE_PRICE;
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.buy_breeding_licenses(2);
+        contract.transfer_breeding_licenses(mike(), 1);
+        assert_eq!(contract.get_breeding_licenses(robert()), 1);
+        assert_eq!(contract.get_breeding_licenses(mike()), 1);
+        assert_eq!(contract.get_treasury_balance().0, 2 * BREEDING_LICENSE_PRICE);
+    }
+
+    #[test]
+    fn rental_indexed_for_both_parties_until_swept() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.rent_out(token_id, mike(), 2);
+        assert_eq!(contract.get_rented_out(robert())[0].renter, mike());
+        assert_eq!(contract.get_renting(mike())[0].owner, robert());
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.block_timestamp = 2 * 60 * 60 * 1_000_000_000;
+        testing_env!(context);
+        assert!(contract.get_renting(mike()).is_empty());
+        assert_eq!(contract.sweep_expired_rentals(10), 1);
+        assert_eq!(contract.sweep_expired_rentals(10), 0);
+        assert!(contract.rented_out.get(&robert()).is_none());
+        assert!(contract.renting.get(&mike()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi is already rented out"#)]
+    fn rent_out_rented_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.rent_out(token_id, mike(), 2);
+        contract.rent_out(token_id, joe(), 2);
+    }
+
+    #[test]
+    fn rent_out_again_after_rental_expired() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.rent_out(token_id, mike(), 2);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = 2 * 60 * 60 * 1_000_000_000;
+        testing_env!(context);
+        contract.rent_out(token_id, joe(), 2);
+        assert_eq!(contract.get_rental(token_id).unwrap().renter, joe());
+        assert_eq!(contract.rented_out.get(&robert()), Some(vec![token_id]));
+        assert!(contract.renting.get(&mike()).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi is rented out"#)]
+    fn transfer_rented_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.rent_out(token_id, mike(), 2);
+        contract.transfer(joe(), token_id, None);
+    }
+
+    #[test]
+    fn approved_marketplace_can_transfer() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.approve_and_list(token_id, mike(), "{\"price\":\"1\"}".to_string());
+        assert_eq!(contract.get_approval_id(token_id, mike()), Some(U64(0)));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.transfer_from(robert(), joe(), token_id, None);
+        assert_eq!(contract.get_token_owner(token_id), joe());
+        assert_eq!(contract.get_approval_id(token_id, mike()), None);
+    }
+
+    #[test]
+    fn buy_corgi_with_fungible_token() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.add_accepted_ft("usdc.testnet".to_string());
+        contract.sell_corgi(token_id, U128(100), None, Some(Currency::Ft("usdc.testnet".to_string())));
+        assert_eq!(
+            contract.get_market_listings(0, 10)[0].currency,
+            Currency::Ft("usdc.testnet".to_string())
+        );
+
+        testing_env!(get_context("usdc.testnet".to_string(), env::storage_usage()));
+        let unused = contract.ft_on_transfer(mike(), U128(120), token_id.to_string());
+        assert_eq!(unused, U128(20));
+        assert_eq!(contract.get_token_owner(token_id), mike());
+        assert_eq!(contract.get_listing_currency(token_id), None);
+    }
+
+    #[test]
+    fn fungible_token_sale_takes_market_fee() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.set_market_fee(500);
+        contract.add_accepted_ft("usdc.testnet".to_string());
+        contract.transfer(mike(), token_id, None);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.sell_corgi(token_id, U128(1_000), None, Some(Currency::Ft("usdc.testnet".to_string())));
+
+        testing_env!(get_context("usdc.testnet".to_string(), env::storage_usage()));
+        contract.ft_on_transfer(joe(), U128(1_000), token_id.to_string());
+        assert_eq!(contract.get_token_owner(token_id), joe());
+        assert_eq!(contract.get_ft_balance(robert(), "usdc.testnet".to_string()), U128(50));
+    }
+
+    #[test]
+    fn mint_corgi_with_fungible_token() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_accepted_ft("usdc.testnet".to_string());
+        contract.set_ft_mint_price("usdc.testnet".to_string(), Some(U128(100)));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.storage_deposit(None, None);
+
+        testing_env!(get_context("usdc.testnet".to_string(), env::storage_usage()));
+        let msg = json!({
+            "mint": {
+                "name": "a",
+                "color": "blue",
+                "background_color": "green",
+                "quote": "haha",
+            }
+        });
+        let unused = contract.ft_on_transfer(mike(), U128(120), msg.to_string());
+        assert_eq!(unused, U128(20));
+        let pending = contract.get_pending_mints(mike());
+        assert_eq!(pending.len(), 1);
+        reveal_next_block(&mut contract, pending[0].id);
+        assert_eq!(contract.get_corgis_by_owner_range(mike(), 0, 10).len(), 1);
+        assert_eq!(contract.get_ft_balance(robert(), "usdc.testnet".to_string()), U128(100));
+        assert!(contract.get_deposit(mike()).0 < 3 * 10u128.pow(24));
+    }
+
+    #[test]
+    fn creator_royalty_in_payout() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let balance = U128(10u128.pow(24));
+        let payout = contract.nft_payout(token_id.to_string(), balance, 1).payout;
+        assert_eq!(payout.get(&robert()), Some(&balance));
+
+        contract.set_royalty(token_id, 500);
+        contract.transfer(mike(), token_id, None);
+        let payout = contract.nft_payout(token_id.to_string(), balance, 2).payout;
+        assert_eq!(payout.len(), 2);
+        assert_eq!(payout.get(&robert()), Some(&U128(5 * 10u128.pow(22))));
+        assert_eq!(payout.get(&mike()), Some(&U128(95 * 10u128.pow(22))));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the creator can change the royalty"#)]
+    fn only_creator_sets_royalty() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.transfer(mike(), token_id, None);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.set_royalty(token_id, 0);
+    }
+
+    #[test]
+    fn overpayment_is_not_charged() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_market_fee(500);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        let treasury = contract.get_treasury_balance().0;
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+        assert_eq!(contract.get_token_owner(token_id), mike());
+        assert_eq!(
+            contract.get_treasury_balance().0,
+            treasury + 10u128.pow(24) * 500 / 10_000
+        );
+        assert_eq!(contract.get_deposit(mike()).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi is priced in a fungible token, buy it with ft_transfer_call"#)]
+    fn buy_ft_listing_with_near() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.add_accepted_ft("usdc.testnet".to_string());
+        contract.sell_corgi(token_id, U128(100), None, Some(Currency::Ft("usdc.testnet".to_string())));
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+    }
+
+    #[test]
+    fn fee_discount_after_volume_tier() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_market_fee(500);
+        contract.set_fee_tiers(vec![FeeTier {
+            min_volume: U128(10u128.pow(24)),
+            discount_bps: 5_000,
+        }]);
+        let treasury = contract.get_treasury_balance().0;
+        for _ in 0..2 {
+            let token_id = mint_revealed(&mut contract,
+                "a".to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            ).token_id.0;
+            contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        }
+        assert_eq!(contract.get_fee_tier(robert()).fee_bps, 500);
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.buy_corgi(0, None);
+        let status = contract.get_fee_tier(robert());
+        assert_eq!(status.tier, 1);
+        assert_eq!(status.fee_bps, 250);
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.buy_corgi(1, None);
+        assert_eq!(
+            contract.get_treasury_balance().0,
+            treasury + 2 * MINT_PRICE + 10u128.pow(24) * 750 / 10_000
+        );
+    }
+
+    #[test]
+    fn auction_sells_above_reserve() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.start_auction(
+            token_id,
+            1,
+            BidIncrement::Bps(1_000),
+            Some(U128(10u128.pow(24))),
+            None,
+        );
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(23);
+        testing_env!(context);
+        contract.place_bid(token_id);
+        assert!(!contract.get_auction(token_id).unwrap().reserve_met);
+        let mut context = get_context(joe(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.place_bid(token_id);
+        assert_eq!(contract.get_deposit(mike()), U128(0));
+        assert_eq!(
+            contract.get_auction(token_id).unwrap().min_next_bid,
+            U128(11 * 10u128.pow(23))
+        );
+        let active = contract.get_active_auctions(0, 10);
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].highest_bidder, Some(joe()));
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.block_timestamp = 60 * 60 * 1_000_000_000;
+        testing_env!(context);
+        assert!(contract.get_active_auctions(0, 10).is_empty());
+        contract.finalize_auction(token_id);
+        assert_eq!(contract.get_token_owner(token_id), joe());
+        assert!(contract.get_auction(token_id).is_none());
+    }
+
+    #[test]
+    fn auction_below_reserve_returns_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.start_auction(
+            token_id,
+            1,
+            BidIncrement::Absolute(U128(1)),
+            Some(U128(10u128.pow(24))),
+            None,
+        );
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(23);
+        testing_env!(context);
+        contract.place_bid(token_id);
+        let treasury = contract.get_treasury_balance().0;
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.block_timestamp = 60 * 60 * 1_000_000_000;
+        testing_env!(context);
+        contract.finalize_auction(token_id);
+        assert_eq!(contract.get_token_owner(token_id), robert());
+        assert_eq!(contract.get_deposit(mike()), U128(0));
+        assert_eq!(
+            contract.get_treasury_balance().0,
+            treasury - MIN_KEEPER_BATCH as u128 * DEFAULT_KEEPER_BOUNTY
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Bid is below the minimum increment"#)]
+    fn bid_below_increment() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.start_auction(token_id, 1, BidIncrement::Absolute(U128(10u128.pow(23))), None, None);
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.place_bid(token_id);
+        context = get_context(joe(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24) + 1;
+        testing_env!(context);
+        contract.place_bid(token_id);
+    }
+
+    #[test]
+    fn buy_now_ends_auction() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.start_auction(
+            token_id,
+            1,
+            BidIncrement::Absolute(U128(1)),
+            None,
+            Some(U128(2 * 10u128.pow(24))),
+        );
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.place_bid(token_id);
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.attached_deposit = 2 * 10u128.pow(24);
+        context.account_balance = 10u128.pow(24); // mike's bid
+        testing_env!(context);
+        contract.buy_now(token_id);
+        assert_eq!(contract.get_token_owner(token_id), joe());
+        assert_eq!(contract.get_deposit(mike()), U128(0));
+        assert!(contract.get_auction(token_id).is_none());
+        assert_eq!(contract.get_fee_tier(robert()).volume, U128(2 * 10u128.pow(24)));
+    }
+
+    #[test]
+    fn scheduled_listing_goes_live() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi_at(token_id, U128(10u128.pow(24)), U64(100));
+        assert_eq!(contract.get_market_listings(0, 10)[0].starts_at, Some(U64(100)));
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        context.block_timestamp = 100;
+        testing_env!(context);
+        assert_eq!(contract.get_market_listings(0, 10)[0].starts_at, None);
+        contract.buy_corgi(token_id, None);
+        assert_eq!(contract.get_token_owner(token_id), mike());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Listing has not started yet"#)]
+    fn buy_scheduled_listing_early() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi_at(token_id, U128(10u128.pow(24)), U64(100));
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.buy_corgi(token_id, None);
+    }
+
+    #[test]
+    fn kinship_conflicts() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["dam", "stud", "pup", "pup2", "grandpup"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        for (child, parents) in &[(2, vec![0, 1]), (3, vec![0, 1]), (4, vec![2])] {
+            let mut corgi = contract.get_corgi(*child);
+            corgi.parents = parents.clone();
+            contract.corgis.insert(child, &corgi);
+        }
+        assert_eq!(contract.check_kinship(0, 1), None);
+        assert_eq!(
+            contract.check_kinship(0, 4),
+            Some(KinshipConflict::Ancestor {
+                ancestor_id: 0,
+                descendant_id: 4,
+                generations: 2,
+            })
+        );
+        assert_eq!(
+            contract.check_kinship(2, 3),
+            Some(KinshipConflict::Siblings { shared_parent_id: 0 })
+        );
+        contract.set_kinship_depth(1);
+        assert_eq!(contract.check_kinship(0, 4), None);
+    }
+
+    #[test]
+    fn installments_transfer_on_final_payment() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(4 * 10u128.pow(24)), None, None);
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 2 * 10u128.pow(24);
+        testing_env!(context);
+        let plan = contract.buy_corgi_in_installments(token_id, 2);
+        assert_eq!(plan.installment.0, 2 * 10u128.pow(24));
+        assert_eq!(contract.get_token_owner(token_id), "alice.testnet");
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 3 * 10u128.pow(24);
+        context.account_balance = 2 * 10u128.pow(24); // the first installment
+        context.block_timestamp = INSTALLMENT_PERIOD;
+        testing_env!(context);
+        assert!(contract.pay_installment(token_id).is_none());
+        assert_eq!(contract.get_token_owner(token_id), mike());
+        assert_eq!(contract.get_deposit(mike()).0, 10u128.pow(24));
+        assert!(contract.get_installment_plan(token_id).is_none());
+    }
+
+    #[test]
+    fn missed_installment_forfeits_to_seller() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(4 * 10u128.pow(24)), None, None);
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.buy_corgi_in_installments(token_id, 4);
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.block_timestamp = INSTALLMENT_PERIOD + 1;
+        testing_env!(context);
+        contract.forfeit_installments(token_id);
+        assert_eq!(contract.get_token_owner(token_id), robert());
+        assert_eq!(contract.get_deposit(robert()).0, 2 * 10u128.pow(23));
+        assert_eq!(contract.get_deposit(mike()).0, 8 * 10u128.pow(23));
+    }
+
+    #[test]
+    fn buying_back_flags_circular_trade() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+        assert!(!contract.get_trade_flags(token_id).suspicious);
+
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+        let flags = contract.get_trade_flags(token_id);
+        assert_eq!(flags.recent_sales, 2);
+        assert!(flags.circular);
+        assert_eq!(flags.max_pair_trades, 2);
+        assert!(flags.suspicious);
+    }
+
+    #[test]
+    fn listing_deposit_refunded_on_delist_and_forfeited_on_force_delist() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.set_listing_deposit(U128(10u128.pow(24)));
+        let treasury = contract.get_treasury_balance().0;
+
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        assert_eq!(contract.get_deposit(robert()).0, 2 * 10u128.pow(24));
+        contract.cancel_sale(token_id);
+        assert!(!contract.get_corgi(token_id).selling);
+        assert_eq!(contract.get_deposit(robert()).0, 3 * 10u128.pow(24));
+
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        contract.add_moderator(mike());
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.force_delist(token_id);
+        assert_eq!(contract.get_treasury_balance().0, treasury + 10u128.pow(24));
+        assert_eq!(contract.get_deposit(robert()).0, 5 * 10u128.pow(24));
+    }
+
+
+    #[test]
+    fn update_price_and_transfer_clear_listing() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        contract.update_price(token_id, U128(2 * 10u128.pow(24)));
+        let corgi = contract.get_corgi(token_id);
+        assert!(corgi.selling);
+        assert_eq!(corgi.selling_price, U128(2 * 10u128.pow(24)));
+
+        contract.transfer(mike(), token_id, None);
+        assert!(!contract.get_corgi(token_id).selling);
+        assert!(contract.get_market_listings(0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Don't have permission to update the price"#)]
+    fn update_price_of_someone_elses_listing() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.update_price(token_id, U128(1));
+    }
+
+    #[test]
+    fn per_token_approval_is_scoped_to_the_token() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mint = |contract: &mut Corgi3D| {
+            mint_revealed(contract,
+                "a".to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            ).token_id.0
+        };
+        let approved = mint(&mut contract);
+        let other = mint(&mut contract);
+        contract.nft_approve(approved.to_string(), mike(), None);
+        assert!(contract.nft_is_approved(approved.to_string(), mike(), None));
+        assert!(!contract.nft_is_approved(other.to_string(), mike(), None));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.sell_corgi(approved, U128(10u128.pow(24)), None, None);
+        assert!(contract.get_corgi(approved).selling);
+        contract.transfer_from(robert(), joe(), approved, None);
+        assert_eq!(contract.get_token_owner(approved), joe());
+        assert!(!contract.nft_is_approved(approved.to_string(), mike(), None));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attempt to transfer a token with no access."#)]
+    fn approval_does_not_cover_other_tokens() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let approved = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let other = mint_revealed(&mut contract,
+            "b".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.nft_approve(approved.to_string(), mike(), None);
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.nft_revoke_all(approved.to_string());
+        contract.nft_approve(approved.to_string(), mike(), None);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.transfer_from(robert(), joe(), other, None);
+    }
+
+    #[test]
+    fn approval_with_msg_notifies_approved_contract() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(
+            &mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        )
+        .token_id
+        .0;
+        let market = "market.testnet".to_string();
+        assert!(contract.nft_approve(token_id.to_string(), mike(), None).is_none());
+        let msg = json!({ "sale_conditions": "1" }).to_string();
+        assert!(contract.nft_approve(token_id.to_string(), market.clone(), Some(msg)).is_some());
+        assert_eq!(contract.get_approval_id(token_id, market), Some(U64(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "NO_SELL_PERMISSION")]
+    fn selling_other_owners_corgi_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = create_corgi_for(&mut contract, "a");
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.sell_corgi(token_id, U128(1), None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "INSUFFICIENT_PAYMENT")]
+    fn underpaid_purchase_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = create_corgi_for(&mut contract, "a");
+        contract.sell_corgi(token_id, U128(5 * 10u128.pow(24)), None, None);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "NOT_FOR_SALE")]
+    fn unlisted_corgi_cannot_be_bought() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = create_corgi_for(&mut contract, "a");
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "NOT_TRANSFERABLE: Corgi is in a bundle")]
+    fn bundled_corgi_transfer_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_ids = vec![create_corgi_for(&mut contract, "a"), create_corgi_for(&mut contract, "b")];
+        contract.sell_bundle(token_ids.clone(), U128(10u128.pow(24)));
+        contract.transfer(mike(), token_ids[0], None);
+    }
+
+    #[test]
+    fn bundle_sold_as_one_lot() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_ids: Vec<TokenId> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                mint_revealed(
+                    &mut contract,
+                    name.to_string(),
+                    "blue".to_string(),
+                    "green".to_string(),
+                    "haha".to_string(),
+                )
+                .token_id
+                .0
+            })
+            .collect();
+        let bundle_id = contract.sell_bundle(token_ids[..2].to_vec(), U128(10u128.pow(24)));
+        assert_eq!(contract.get_bundles(0, 10).len(), 1);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_bundle(bundle_id);
+        assert_eq!(contract.get_corgis_by_owner_range(mike(), 0, 10).len(), 2);
+        assert_eq!(contract.get_token_owner(token_ids[2]), robert());
+        assert!(contract.get_bundle(bundle_id).is_none());
+        contract.transfer(joe(), token_ids[0], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Corgi is in a bundle")]
+    fn bundled_corgi_cannot_be_sold_alone() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_ids: Vec<TokenId> = ["a", "b"]
+            .iter()
+            .map(|name| {
+                mint_revealed(
+                    &mut contract,
+                    name.to_string(),
+                    "blue".to_string(),
+                    "green".to_string(),
+                    "haha".to_string(),
+                )
+                .token_id
+                .0
+            })
+            .collect();
+        let bundle_id = contract.sell_bundle(token_ids.clone(), U128(10u128.pow(24)));
+        contract.cancel_bundle(bundle_id);
+        contract.sell_bundle(token_ids.clone(), U128(10u128.pow(24)));
+        contract.sell_corgi(token_ids[0], U128(1), None, None);
+    }
+
+    #[test]
+    fn trade_swaps_corgis_and_pays_sweetener() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let offered = create_corgi_for(&mut contract, "a");
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let wanted = create_corgi_for(&mut contract, "b");
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        let trade_id = contract.propose_trade(vec![offered], vec![wanted], mike(), U128(10u128.pow(24)));
+        assert_eq!(contract.get_outgoing_trades(robert(), 0, 10).len(), 1);
+        assert_eq!(contract.get_incoming_trades(mike(), 0, 10)[0].id, trade_id);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.accept_trade(trade_id);
+        assert_eq!(contract.get_token_owner(offered), mike());
+        assert_eq!(contract.get_token_owner(wanted), robert());
+        assert!(contract.get_trade(trade_id).is_none());
+        assert!(contract.get_incoming_trades(mike(), 0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Proposer doesn't own every offered corgi")]
+    fn trade_checks_owners_on_accept() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let offered = create_corgi_for(&mut contract, "a");
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let wanted = create_corgi_for(&mut contract, "b");
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        let trade_id = contract.propose_trade(vec![offered], vec![wanted], mike(), U128(0));
+        contract.transfer(joe(), offered, None);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.accept_trade(trade_id);
+    }
+
+    // a corgi minted by the predecessor
+    fn create_corgi_for(contract: &mut Corgi3D, name: &str) -> TokenId {
+        mint_revealed(
+            contract,
+            name.to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        )
+        .token_id
+        .0
+    }
+
+    // sales of `rarity` between three distinct pairs, old enough to back a loan
+    fn record_reference_sales(contract: &mut Corgi3D, rarity: Rarity, price: Balance) {
+        contract.record_sale_price(rarity, U128(price), &robert(), &mike());
+        contract.record_sale_price(rarity, U128(price), &mike(), &joe());
+        contract.record_sale_price(rarity, U128(price), &joe(), &robert());
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = env::block_timestamp() + MIN_LOAN_OBSERVATION_AGE_SEC * 1_000_000_000;
+        testing_env!(context);
+    }
+}
@@ -0,0 +1,318 @@
+use crate::*;
+
+// `Corgi3D` as first deployed; `migrate` reads it once
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyCorgi3D {
+    pub corgi_to_account: UnorderedMap<TokenId, AccountId>,
+    pub account_gives_access: UnorderedMap<AccountIdHash, UnorderedSet<AccountIdHash>>,
+    pub owner_id: AccountId,
+    pub corgis: UnorderedMap<TokenId, LegacyCorgi>,
+    pub account_corgis: UnorderedMap<AccountIdHash, UnorderedSet<TokenId>>,
+    pub next_corgi_id: TokenId,
+    pub account_fruit: UnorderedMap<AccountId, LegacyFruit>,
+    pub account_maze_game: UnorderedMap<AccountId, MazeGame>,
+}
+
+// `Corgi` as first deployed, with the rarity as its display name
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyCorgi {
+    pub id: TokenId,
+    pub name: String,
+    pub quote: String,
+    pub color: String,
+    pub background_color: String,
+    pub rate: String,
+    pub sausage: String,
+    pub sender: String,
+    pub message: String,
+    pub selling: bool,
+    pub selling_price: U128,
+}
+
+// `Fruit` as first deployed, indexed by the IDs the default fruit kinds got
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyFruit {
+    pub count: [u64; DEFAULT_FRUIT_NAMES.len()],
+}
+
+/// Upgrade from the first deployment's layout.
+#[near_bindgen]
+impl Corgi3D {
+    /// Converts the state of the first deployment, keeping its collections in
+    /// place. Corgis and fruit balances stay in the old layout until
+    /// `migrate_records` converts them, so the contract stays paused until then.
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        if env::predecessor_account_id() != env::current_account_id() {
+            env::panic(b"Only the contract account can migrate");
+        }
+        let old: LegacyCorgi3D = env::state_read().expect("Contract is not initialized");
+        let mut contract = Self::initial_state(old.owner_id, None, None);
+        contract.corgi_to_account = old.corgi_to_account;
+        contract.account_gives_access = old.account_gives_access;
+        contract.corgis = retype(&old.corgis);
+        contract.account_corgis = old.account_corgis;
+        contract.next_corgi_id = old.next_corgi_id;
+        contract.total_minted = old.next_corgi_id;
+        contract.account_fruit = retype(&old.account_fruit);
+        contract.account_maze_game = old.account_maze_game;
+        contract.paused = true;
+        contract.migration = Some(MigrationProgress {
+            corgis: 0,
+            fruit_balances: 0,
+        });
+        contract
+    }
+
+    /// Converts up to `limit` more records left in the first deployment's
+    /// layout, corgis first, indexing them like new mints, then fruit balances.
+    /// Returns what's left to do, `None` once the contract can be unpaused.
+    pub fn migrate_records(&mut self, limit: u64) -> Option<MigrationProgress> {
+        self.assert_owner();
+        let mut progress = self.migration.clone().expect("Nothing to migrate");
+        let converted = self.migrate_corgis(progress.corgis, limit);
+        progress.corgis += converted;
+        let converted = if progress.corgis < self.corgis.len() {
+            0
+        } else {
+            self.migrate_fruit_balances(progress.fruit_balances, limit - converted)
+        };
+        progress.fruit_balances += converted;
+        let done = progress.corgis >= self.corgis.len()
+            && progress.fruit_balances >= self.account_fruit.len();
+        self.migration = if done { None } else { Some(progress) };
+        self.migration.clone()
+    }
+
+    pub fn get_migration(&self) -> Option<MigrationProgress> {
+        self.migration.clone()
+    }
+}
+
+impl Corgi3D {
+    /// Converts up to `limit` corgis from the `from`-th on; returns how many.
+    fn migrate_corgis(&mut self, from: u64, limit: u64) -> u64 {
+        let legacy_corgis: UnorderedMap<TokenId, LegacyCorgi> = retype(&self.corgis);
+        let values = legacy_corgis.values_as_vector();
+        let end = std::cmp::min(from.saturating_add(limit), values.len());
+        let now = env::block_timestamp();
+        for index in from..end {
+            let legacy = values.get(index).unwrap();
+            let owner = self.corgi_to_account.get(&legacy.id).unwrap();
+            let corgi = Corgi {
+                id: legacy.id,
+                name: legacy.name,
+                color: legacy.color,
+                background_color: legacy.background_color,
+                quote: legacy.quote,
+                rate: legacy_rarity(&legacy.rate),
+                sausage: legacy.sausage,
+                selling: legacy.selling,
+                selling_price: legacy.selling_price,
+                message: legacy.message,
+                sender: legacy.sender,
+                energy: MAX_ENERGY,
+                energy_updated_at: now,
+                clone_of: None,
+                clone_count: 0,
+                metadata_frozen: false,
+                locked: None,
+                parents: vec![],
+                generation: 0,
+                creator: owner.clone(),
+                royalty_bps: DEFAULT_ROYALTY_BPS,
+                bonded_with: None,
+                bond_changed_at: 0,
+                pass: None,
+                battles_fought: 0,
+                battles_won: 0,
+                times_bred: 0,
+                unlocked_traits: 0,
+                skills: vec![0; SKILL_COUNT],
+                skill_progress: vec![0; SKILL_COUNT],
+                accessories: vec![],
+            };
+            // the old value can't be read back as a `Corgi`
+            self.corgis.insert_raw(
+                &corgi.id.try_to_vec().unwrap(),
+                &corgi.try_to_vec().unwrap(),
+            );
+            if corgi.selling {
+                self.selling_corgis.insert(&corgi.id);
+            }
+            let held = self
+                .account_corgis
+                .get(&env::sha256(owner.as_bytes()))
+                .map_or(0, |corgis| corgis.len());
+            self.update_top_holders(&owner, held);
+            self.update_trait_counts(&corgi, true);
+            self.update_rarity_index(&corgi, true);
+        }
+        end - from
+    }
+
+    /// Converts up to `limit` fruit balances from the `from`-th on; returns how many.
+    fn migrate_fruit_balances(&mut self, from: u64, limit: u64) -> u64 {
+        let legacy_fruit: UnorderedMap<AccountId, LegacyFruit> = retype(&self.account_fruit);
+        let keys = legacy_fruit.keys_as_vector();
+        let values = legacy_fruit.values_as_vector();
+        let end = std::cmp::min(from.saturating_add(limit), values.len());
+        for index in from..end {
+            let mut fruit = Fruit::default();
+            for (kind, &amount) in values.get(index).unwrap().count.iter().enumerate() {
+                fruit.add(kind as u64, amount);
+            }
+            let account = keys.get(index).unwrap();
+            self.account_fruit
+                .insert_raw(&account.try_to_vec().unwrap(), &fruit.try_to_vec().unwrap());
+        }
+        end - from
+    }
+}
+
+/// The same collection read with another value type, for records converted in place.
+fn retype<T: BorshSerialize, U: BorshDeserialize>(collection: &T) -> U {
+    U::try_from_slice(&collection.try_to_vec().unwrap()).unwrap()
+}
+
+/// The rarity the first deployment stored by its display name.
+fn legacy_rarity(rate: &str) -> Rarity {
+    Rarity::ALL
+        .iter()
+        .copied()
+        .find(|rarity| rarity.default_name() == rate)
+        .unwrap_or(Rarity::Common)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    // what the first deployment stored for `corgis`, owned by robert unless listed in `owners`
+    fn write_legacy_state(corgis: &[(&str, &str)], owners: &[(TokenId, AccountId)]) {
+        let mut state = LegacyCorgi3D {
+            corgi_to_account: UnorderedMap::new(b"corgi-belongs-to".to_vec()),
+            account_gives_access: UnorderedMap::new(b"gives-access".to_vec()),
+            owner_id: robert(),
+            corgis: UnorderedMap::new(b"corgis".to_vec()),
+            account_corgis: UnorderedMap::new(b"account-corgis".to_vec()),
+            next_corgi_id: 0,
+            account_fruit: UnorderedMap::new(b"account-fruit".to_vec()),
+            account_maze_game: UnorderedMap::new(b"account-maze-game".to_vec()),
+        };
+        for (name, rate) in corgis {
+            let id = state.next_corgi_id;
+            state.next_corgi_id += 1;
+            state.corgis.insert(
+                &id,
+                &LegacyCorgi {
+                    id,
+                    name: name.to_string(),
+                    quote: "haha".to_string(),
+                    color: "blue".to_string(),
+                    background_color: "green".to_string(),
+                    rate: rate.to_string(),
+                    sausage: "120".to_string(),
+                    sender: "".to_string(),
+                    message: "".to_string(),
+                    selling: id == 0,
+                    selling_price: U128(if id == 0 { 10u128.pow(24) } else { 0 }),
+                },
+            );
+            let owner = owners
+                .iter()
+                .find(|(token_id, _)| *token_id == id)
+                .map_or(robert(), |(_, owner)| owner.clone());
+            state.corgi_to_account.insert(&id, &owner);
+            let account_hash = env::sha256(owner.as_bytes());
+            let mut account_corgis = state.account_corgis.get(&account_hash).unwrap_or_else(|| {
+                let mut prefix = Vec::with_capacity(33);
+                prefix.push(b'u');
+                prefix.extend(account_hash.clone());
+                UnorderedSet::new(prefix)
+            });
+            account_corgis.insert(&id);
+            state.account_corgis.insert(&account_hash, &account_corgis);
+        }
+        state.account_fruit.insert(
+            &mike(),
+            &LegacyFruit {
+                count: [1, 0, 0, 0, 0, 0, 3],
+            },
+        );
+        env::state_write(&state);
+    }
+
+    #[test]
+    fn migrate_converts_legacy_state() {
+        testing_env!(get_context(robert(), 0));
+        write_legacy_state(
+            &[("a", "VERY RARE"), ("b", "COMMON"), ("c", "RARE")],
+            &[(2, mike())],
+        );
+        testing_env!(get_context(env::current_account_id(), env::storage_usage()));
+        let mut contract = Corgi3D::migrate();
+        assert!(contract.is_paused());
+        assert_eq!(contract.owner_id, robert());
+
+        testing_env!(get_context(robert(), env::storage_usage()));
+        let progress = MigrationProgress {
+            corgis: 2,
+            fruit_balances: 0,
+        };
+        assert_eq!(contract.migrate_records(2), Some(progress));
+        assert_eq!(contract.migrate_records(2), None);
+        contract.unpause();
+
+        let corgi = contract.get_corgi(0);
+        assert_eq!(corgi.name, "a");
+        assert_eq!(corgi.rate, Rarity::VeryRare);
+        assert_eq!(corgi.creator, robert());
+        assert!(contract.selling_corgis.contains(&0));
+        assert_eq!(contract.get_corgi(2).rate, Rarity::Rare);
+        assert_eq!(contract.get_token_owner(2), mike());
+        assert_eq!(contract.nft_total_supply(), U128(3));
+        assert_eq!(
+            contract.get_corgis_by_rarity(Rarity::Common, 0, 10)[0].name,
+            "b"
+        );
+        assert_eq!(
+            contract.trait_counts.get(&"color:#0000ff".to_string()),
+            Some(3)
+        );
+        let fruit = contract.account_fruit(mike());
+        assert_eq!(
+            (fruit.get(APPLE), fruit.get(ORANGE), fruit.count.len()),
+            (1, 3, 2)
+        );
+
+        // new mints continue after the migrated ones
+        let token_id = mint_revealed(
+            &mut contract,
+            "d".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        )
+        .token_id
+        .0;
+        assert_eq!(token_id, 3);
+        contract.transfer(mike(), 1, None);
+        assert_eq!(contract.nft_supply_for_owner(mike()), U128(2));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Records are still being migrated"#)]
+    fn unpause_waits_for_migrated_records() {
+        testing_env!(get_context(robert(), 0));
+        write_legacy_state(&[("a", "COMMON"), ("b", "COMMON")], &[]);
+        testing_env!(get_context(env::current_account_id(), env::storage_usage()));
+        let mut contract = Corgi3D::migrate();
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.migrate_records(1);
+        contract.unpause();
+    }
+}
@@ -0,0 +1,3330 @@
+use crate::*;
+use ed25519_dalek::Verifier;
+use std::convert::TryFrom;
+
+#[near_bindgen]
+impl NEP171 for Corgi3D {
+    fn grant_access(&mut self, escrow_account_id: AccountId) {
+        let escrow_hash = env::sha256(escrow_account_id.as_bytes());
+        let predecessor = env::predecessor_account_id();
+        let predecessor_hash = env::sha256(predecessor.as_bytes());
+
+        let mut access_set = match self.account_gives_access.get(&predecessor_hash) {
+            Some(existing_set) => existing_set,
+            None => UnorderedSet::new(access_set_prefix(&predecessor_hash)),
+        };
+        access_set.insert(&escrow_hash);
+        self.account_gives_access
+            .insert(&predecessor_hash, &access_set);
+        self.index_access_grant(&predecessor, &escrow_account_id);
+    }
+
+    fn revoke_access(&mut self, escrow_account_id: AccountId) {
+        let predecessor = env::predecessor_account_id();
+        let predecessor_hash = env::sha256(predecessor.as_bytes());
+        let mut existing_set = match self.account_gives_access.get(&predecessor_hash) {
+            Some(existing_set) => existing_set,
+            None => env::panic(b"Access does not exist."),
+        };
+        let escrow_hash = env::sha256(escrow_account_id.as_bytes());
+        if existing_set.contains(&escrow_hash) {
+            existing_set.remove(&escrow_hash);
+            self.account_gives_access
+                .insert(&predecessor_hash, &existing_set);
+            self.unindex_access_grant(&predecessor_hash, &escrow_hash);
+            env::log(b"Successfully removed access.")
+        } else {
+            env::panic(b"Did not find access for escrow ID.")
+        }
+    }
+
+    fn transfer(&mut self, new_owner_id: AccountId, token_id: TokenId, memo: Option<String>) {
+        self.transfer_as_caller(None, new_owner_id, token_id, memo, None);
+    }
+
+    fn transfer_from(
+        &mut self,
+        owner_id: AccountId,
+        new_owner_id: AccountId,
+        token_id: TokenId,
+        memo: Option<String>,
+    ) {
+        self.transfer_as_caller(Some(owner_id), new_owner_id, token_id, memo, None);
+    }
+
+    fn check_access(&self, account_id: AccountId) -> bool {
+        let account_hash = env::sha256(account_id.as_bytes());
+        let predecessor = env::predecessor_account_id();
+        if predecessor == account_id {
+            return true;
+        }
+        match self.account_gives_access.get(&account_hash) {
+            Some(access) => {
+                let predecessor = env::predecessor_account_id();
+                let predecessor_hash = env::sha256(predecessor.as_bytes());
+                access.contains(&predecessor_hash)
+            }
+            None => false,
+        }
+    }
+
+    fn get_token_owner(&self, token_id: TokenId) -> String {
+        match self.corgi_to_account.get(&token_id) {
+            Some(owner_id) => owner_id,
+            None => env::panic(b"No owner of the token ID specified"),
+        }
+    }
+
+    // follow nep 171
+    fn nft_metadata(&self) -> NFTContractMetadata {
+        NFTContractMetadata {
+            spec: NFT_METADATA_SPEC.to_string(),
+            name: "Corgi3D".to_string(),
+            symbol: NFT_SYMBOL.to_string(),
+            icon: self.icon.clone(),
+            base_uri: self.base_uri.clone(),
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    fn nft_token(&self, token_id: TokenId) -> Option<JsonToken> {
+        let owner_id = self.corgi_to_account.get(&token_id)?;
+        let corgi = self.corgis.get(&token_id)?;
+        let extra = self
+            .get_render_spec(token_id)
+            .and_then(|spec| serde_json::to_string(&spec).ok());
+        let media = self.token_media.get(&token_id);
+        Some(JsonToken {
+            token_id: token_id.to_string(),
+            owner_id,
+            metadata: TokenMetadata {
+                title: Some(corgi.name),
+                description: Some(corgi.quote),
+                media: match media.as_ref() {
+                    Some(media) => Some(media.url.clone()),
+                    None => self
+                        .base_uri
+                        .as_ref()
+                        .map(|base_uri| format!("{}/{}", base_uri, token_id)),
+                },
+                media_hash: media.map(|media| media.sha256),
+                copies: Some(U64(1)),
+                issued_at: None,
+                expires_at: None,
+                starts_at: None,
+                updated_at: None,
+                extra,
+                reference: None,
+                reference_hash: None,
+            },
+            approved_account_ids: self.token_approvals.get(&token_id).unwrap_or_default(),
+        })
+    }
+
+    #[payable]
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) {
+        self.nft_transfer_internal(receiver_id, &token_id, approval_id, memo);
+    }
+
+    #[payable]
+    fn nft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let (previous_owner_id, approved_account_ids) =
+            self.nft_transfer_internal(receiver_id.clone(), &token_id, approval_id, memo);
+        ext_nft_receiver::nft_on_transfer(
+            env::predecessor_account_id(),
+            previous_owner_id.clone(),
+            token_id.clone(),
+            msg,
+            &receiver_id,
+            0,
+            NFT_ON_TRANSFER_GAS,
+        )
+        .then(ext_self::nft_resolve_transfer(
+            previous_owner_id,
+            receiver_id,
+            token_id,
+            approved_account_ids,
+            &env::current_account_id(),
+            0,
+            NFT_RESOLVE_TRANSFER_GAS,
+        ))
+        .into()
+    }
+
+    // Enumeration
+
+    fn nft_total_supply(&self) -> U128 {
+        U128(self.corgis.len() as u128)
+    }
+
+    fn nft_tokens(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonToken> {
+        let token_ids = self.corgis.keys_as_vector();
+        let from_index = from_index.map_or(0, |index| index.0 as u64);
+        let limit = limit.map_or(DEFAULT_ENUMERATION_LIMIT, view_limit);
+        (from_index..std::cmp::min(from_index.saturating_add(limit), token_ids.len()))
+            .filter_map(|index| token_ids.get(index))
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+
+    fn nft_supply_for_owner(&self, account_id: AccountId) -> U128 {
+        let hash = env::sha256(account_id.as_bytes());
+        U128(self.account_corgis.get(&hash).map_or(0, |corgi_ids| corgi_ids.len()) as u128)
+    }
+
+    fn nft_tokens_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<JsonToken> {
+        let hash = env::sha256(account_id.as_bytes());
+        let corgi_ids = match self.account_corgis.get(&hash) {
+            Some(corgi_ids) => corgi_ids,
+            None => return vec![],
+        };
+        let corgi_ids = corgi_ids.as_vector();
+        let from_index = from_index.map_or(0, |index| index.0 as u64);
+        let limit = limit.map_or(DEFAULT_ENUMERATION_LIMIT, view_limit);
+        (from_index..std::cmp::min(from_index.saturating_add(limit), corgi_ids.len()))
+            .filter_map(|index| corgi_ids.get(index))
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+
+}
+
+#[near_bindgen]
+impl NEP199 for Corgi3D {
+    fn nft_payout(&self, token_id: String, balance: U128, max_len_payout: u32) -> Payout {
+        let id = parse_token_id(&token_id);
+        self.payout_for(id, &self.get_token_owner(id), balance.0, max_len_payout)
+    }
+
+    #[payable]
+    fn nft_transfer_payout(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: String,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+        balance: U128,
+        max_len_payout: u32,
+    ) -> Payout {
+        let id = parse_token_id(&token_id);
+        let payout = self.payout_for(id, &self.get_token_owner(id), balance.0, max_len_payout);
+        self.nft_transfer_internal(receiver_id, &token_id, approval_id, memo);
+        payout
+    }
+}
+
+/// Minting, burning and transfer entry points beyond the NEP-4 trait.
+#[near_bindgen]
+impl Corgi3D {
+    /// Returns the corgi to `previous_owner_id` if `nft_on_transfer` failed or asked for
+    /// it back and the receiver still owns it. Returns whether the transfer stuck.
+    #[private]
+    pub fn nft_resolve_transfer(
+        &mut self,
+        previous_owner_id: AccountId,
+        receiver_id: AccountId,
+        token_id: String,
+        approved_account_ids: HashMap<AccountId, u64>,
+    ) -> bool {
+        let must_revert = match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                serde_json::from_slice::<bool>(&value).unwrap_or(true)
+            }
+            _ => true,
+        };
+        if !must_revert {
+            return true;
+        }
+        let id = parse_token_id(&token_id);
+        if !self.corgi_exists(id) || self.get_token_owner(id) != receiver_id {
+            return true;
+        }
+        self.internal_transfer(id, receiver_id.clone(), previous_owner_id.clone());
+        self.record_provenance(id, &receiver_id, &previous_owner_id, None);
+        if !approved_account_ids.is_empty() {
+            self.token_approvals.insert(&id, &approved_account_ids);
+        }
+        emit_transfer(&receiver_id, &previous_owner_id, id, None, None);
+        false
+    }
+
+    pub fn delete_corgi(&mut self, id: TokenId) {
+        let corgi = self.corgis.get(&id).unwrap_or_else(|| ContractError::CorgiNotFound.panic());
+        let account = self.corgi_to_account.get(&id).unwrap();
+        let predecessor = env::predecessor_account_id();
+        if account == predecessor
+            || self.check_access(account.clone())
+            || self.is_approved_for(id, &predecessor)
+        {
+            self.assert_burnable(&corgi);
+            if let Some(purchased_at) = self.purchased_at.get(&id) {
+                if env::block_timestamp() < purchased_at + self.burn_grace_period {
+                    env::panic(b"Corgi was purchased too recently to be deleted");
+                }
+            }
+            self.remove_corgi(id, account);
+        } else {
+            env::panic(b"Don't have permission to delete corgi");
+        }
+    }
+
+    /// Burns the caller's corgi for a reward weighted by its rarity tier: fruit of
+    /// every kind, or mint credit that discounts later `create_corgi` fees (the
+    /// default). `delete_corgi` removes a corgi without a reward.
+    pub fn burn_corgi(&mut self, token_id: TokenId, reward: Option<BurnReward>) {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can burn a corgi");
+        }
+        let corgi = self.get_corgi(token_id);
+        self.assert_burnable(&corgi);
+        if let Some(purchased_at) = self.purchased_at.get(&token_id) {
+            if env::block_timestamp() < purchased_at + self.burn_grace_period {
+                env::panic(b"Corgi was purchased too recently to be deleted");
+            }
+        }
+        let tier = rarity_tier(corgi.rate);
+        self.remove_corgi(token_id, predecessor.clone());
+        match reward.unwrap_or(BurnReward::MintCredit) {
+            BurnReward::Fruit => {
+                let fruit = Fruit::of_each(self.fruit_kinds.len(), tier * BURN_FRUIT_PER_TIER);
+                self.mint_fruit(&predecessor, &fruit);
+            }
+            BurnReward::MintCredit => {
+                let credit = self.mint_price * tier as u128 * BURN_CREDIT_BPS_PER_TIER / 10_000;
+                let balance = self.mint_credits.get(&predecessor).unwrap_or(0);
+                self.mint_credits.insert(&predecessor, &(balance + credit));
+            }
+        }
+    }
+
+    pub fn get_mint_credit(&self, account_id: AccountId) -> U128 {
+        U128(self.mint_credits.get(&account_id).unwrap_or(0))
+    }
+
+    pub fn transfer_from_with_message(
+        &mut self,
+        owner_id: AccountId,
+        new_owner_id: AccountId,
+        token_id: TokenId,
+        message: String,
+        memo: Option<String>,
+    ) {
+        self.transfer_as_caller(Some(owner_id), new_owner_id, token_id, memo, Some(message));
+    }
+
+    /// Moves up to `MAX_BATCH_SIZE` of the caller's corgis to `new_owner_id` at once,
+    /// logged as one transfer event. Either all of them move or none.
+    pub fn transfer_batch(&mut self, new_owner_id: AccountId, token_ids: Vec<TokenId>, memo: Option<String>) {
+        self.transfer_batch_as_caller(None, new_owner_id, token_ids, memo);
+    }
+
+    /// `transfer_batch` for corgis of `owner_id` the caller has access to.
+    pub fn transfer_from_batch(
+        &mut self,
+        owner_id: AccountId,
+        new_owner_id: AccountId,
+        token_ids: Vec<TokenId>,
+        memo: Option<String>,
+    ) {
+        self.transfer_batch_as_caller(Some(owner_id), new_owner_id, token_ids, memo);
+    }
+
+    pub fn transfer_with_message(
+        &mut self,
+        new_owner_id: AccountId,
+        token_id: TokenId,
+        message: String,
+        memo: Option<String>,
+    ) {
+        self.transfer_as_caller(None, new_owner_id, token_id, memo, Some(message));
+    }
+
+    /// Puts a corgi in escrow for whoever presents the secret whose SHA-256 is
+    /// `claim_hash` (hex). The sender can't cancel it until it expires.
+    pub fn send_gift(&mut self, token_id: TokenId, claim_hash: String) -> u64 {
+        let sender = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != sender {
+            env::panic(b"Only the owner can gift a corgi");
+        }
+        if claim_hash.len() != 64 {
+            env::panic(b"Claim hash must be a hex SHA-256");
+        }
+        self.escrow_gift(token_id, sender, Some(claim_hash.to_lowercase()), None, String::new())
+    }
+
+    /// Puts a corgi in escrow for `recipient`, who takes it with `accept_gift`. It
+    /// arrives with `message`, signed by the caller as its sender. Until then the
+    /// caller can take it back once it expires.
+    pub fn send_gift_to(&mut self, token_id: TokenId, recipient: AccountId, message: String) -> u64 {
+        let sender = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != sender {
+            env::panic(b"Only the owner can gift a corgi");
+        }
+        assert!(
+            env::is_valid_account_id(recipient.as_bytes()),
+            "Recipient's account ID is invalid."
+        );
+        if message.len() > MAX_MESSAGE_LENGTH {
+            ContractError::MessageTooLong.panic();
+        }
+        self.escrow_gift(token_id, sender, None, Some(recipient), message)
+    }
+
+    pub fn claim_gift(&mut self, gift_id: u64, secret: String) -> TokenId {
+        let gift = self.gifts.get(&gift_id).expect("Gift not found");
+        if env::block_timestamp() >= gift.expires_at.0 {
+            env::panic(b"Gift has expired");
+        }
+        if gift.claim_hash != Some(to_hex(&env::sha256(secret.as_bytes()))) {
+            env::panic(b"Wrong gift secret");
+        }
+        let token_id = gift.token_id;
+        self.settle_gift(gift, env::predecessor_account_id());
+        token_id
+    }
+
+    /// Takes a gift sent to the caller with `send_gift_to`.
+    pub fn accept_gift(&mut self, gift_id: u64) -> TokenId {
+        let gift = self.gifts.get(&gift_id).expect("Gift not found");
+        let predecessor = env::predecessor_account_id();
+        if gift.recipient.as_ref() != Some(&predecessor) {
+            env::panic(b"Gift is addressed to another account");
+        }
+        if env::block_timestamp() >= gift.expires_at.0 {
+            env::panic(b"Gift has expired");
+        }
+        let token_id = gift.token_id;
+        self.settle_gift(gift, predecessor);
+        token_id
+    }
+
+    /// Returns an expired, unclaimed gift to its sender.
+    pub fn reclaim_gift(&mut self, gift_id: u64) {
+        let gift = self.gifts.get(&gift_id).expect("Gift not found");
+        if gift.sender != env::predecessor_account_id() {
+            env::panic(b"Only the sender can reclaim a gift");
+        }
+        if env::block_timestamp() < gift.expires_at.0 {
+            env::panic(b"Gift can't be reclaimed before it expires");
+        }
+        let sender = gift.sender.clone();
+        self.settle_gift(gift, sender);
+    }
+
+    /// Returns up to `limit` expired gifts to their senders. Callers are paid the
+    /// keeper bounty per returned gift.
+    pub fn sweep_expired_gifts(&mut self, limit: u64) -> u64 {
+        let now = env::block_timestamp();
+        let expired: Vec<Gift> = self
+            .gifts
+            .values()
+            .filter(|gift| gift.expires_at.0 <= now)
+            .take(limit as usize)
+            .collect();
+        let swept = expired.len() as u64;
+        for gift in expired {
+            emit_event(
+                "corgi_gift_expired",
+                json!({
+                    "gift_id": gift.id,
+                    "token_id": gift.token_id.to_string(),
+                    "sender_id": gift.sender,
+                }),
+            );
+            let sender = gift.sender.clone();
+            self.settle_gift(gift, sender);
+        }
+        self.pay_keeper_bounty(swept);
+        swept
+    }
+
+    pub fn get_gift(&self, gift_id: u64) -> Option<Gift> {
+        self.gifts.get(&gift_id)
+    }
+
+    /// Gifts waiting for `account_id` to accept them.
+    pub fn get_gifts_for(&self, account_id: AccountId) -> Vec<Gift> {
+        self.gifts
+            .values()
+            .filter(|gift| gift.recipient.as_ref() == Some(&account_id))
+            .collect()
+    }
+
+    /// Attaches app-specific JSON to a corgi, replacing what was there; an empty
+    /// string removes it. Storage growth is paid from the caller's escrow deposit
+    /// and freed storage is credited back to it.
+    pub fn set_extra(&mut self, token_id: TokenId, json_string: String) {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can set extra data");
+        }
+        if json_string.len() > MAX_EXTRA_LENGTH {
+            env::panic(b"Extra data is too long");
+        }
+        let storage_before = env::storage_usage();
+        if json_string.is_empty() {
+            self.extras.remove(&token_id);
+        } else {
+            if serde_json::from_str::<serde_json::Value>(&json_string).is_err() {
+                env::panic(b"Extra data must be valid JSON");
+            }
+            self.extras.insert(&token_id, &json_string);
+        }
+        let storage_after = env::storage_usage();
+        if storage_after > storage_before {
+            let cost = (storage_after - storage_before) as u128 * env::storage_byte_cost();
+            self.debit_deposit(&predecessor, cost);
+        } else {
+            let refund = (storage_before - storage_after) as u128 * env::storage_byte_cost();
+            self.credit_deposit(&predecessor, refund);
+        }
+    }
+
+    /// Lets the owner change the display message without a transfer, up to
+    /// `MESSAGE_UPDATES_PER_DAY` times per UTC day.
+    pub fn set_corgi_message(&mut self, token_id: TokenId, message: String) {
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can set the message");
+        }
+        if message.len() > MAX_MESSAGE_LENGTH {
+            ContractError::MessageTooLong.panic();
+        }
+        let day = env::block_timestamp() / (24 * 60 * 60 * 1_000_000_000);
+        let updates = match self.message_updates.get(&token_id) {
+            Some((updated_day, updates)) if updated_day == day => updates,
+            _ => 0,
+        };
+        if updates >= MESSAGE_UPDATES_PER_DAY {
+            env::panic(b"Message can't be changed again today");
+        }
+        self.message_updates.insert(&token_id, &(day, updates + 1));
+        let mut corgi = self.corgis.get(&token_id).unwrap();
+        corgi.message = message;
+        self.corgis.insert(&token_id, &corgi);
+    }
+
+    #[payable]
+    pub fn create_corgi(
+        &mut self,
+        name: String,
+        color: String,
+        background_color: String,
+        quote: String,
+    ) -> PendingMint {
+        self.create_corgi_v2(CorgiSpec {
+            name,
+            color,
+            background_color,
+            quote,
+        })
+    }
+
+    /// Pays for up to `MAX_BATCH_SIZE` corgis in one call, each revealed with
+    /// `reveal_corgi` like a single mint. The attached deposit must cover every
+    /// mint fee; storage is charged like `create_corgi` and the surplus refunded.
+    /// Pending mints are in the order of `specs`.
+    #[payable]
+    pub fn create_corgis_batch(&mut self, specs: Vec<CorgiSpec>) -> Vec<PendingMint> {
+        if specs.is_empty() || specs.len() > MAX_BATCH_SIZE {
+            ContractError::InvalidBatchSize.panic();
+        }
+        if env::prepaid_gas() < specs.len() as u64 * BATCH_MINT_GAS_PER_CORGI {
+            env::panic(b"Not enough gas attached for the batch");
+        }
+        let attached_deposit = env::attached_deposit();
+        let predecessor = env::predecessor_account_id();
+        self.enter_drop(&predecessor, specs.len() as u64);
+        let price = self.public_mint_price();
+        let fees: Vec<Balance> = specs
+            .iter()
+            .map(|_| {
+                let fee = self.apply_flash_sale(None, &predecessor, price);
+                self.apply_mint_credit(&predecessor, fee)
+            })
+            .collect();
+        let total_fee: Balance = fees.iter().sum();
+        if attached_deposit < total_fee {
+            env::panic(b"Attached deposit must cover the mint fees");
+        }
+        self.treasury_balance += total_fee;
+        let storage_before = env::storage_usage();
+        let pending: Vec<PendingMint> = specs
+            .into_iter()
+            .zip(fees)
+            .map(|(spec, fee)| self.commit_public_mint(&predecessor, spec, fee))
+            .collect();
+        let reserve: Balance = pending.iter().map(|pending| pending.storage_reserve.0).sum();
+        let available = self.charge_storage(&predecessor, storage_before, attached_deposit - total_fee);
+        let refund = self.charge(&predecessor, reserve, available);
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+        pending
+    }
+
+    /// Mints the corgi of a pending mint. Its rarity and sausage are rolled from
+    /// the random seed of the reveal block, which didn't exist when the mint was
+    /// paid for, so a mint can't be simulated and abandoned for better traits.
+    /// Anyone can reveal, e.g. a keeper on the minter's behalf; the part of the
+    /// storage reservation the corgi doesn't use is refunded to whoever paid it.
+    pub fn reveal_corgi(&mut self, pending_id: u64) -> MintResult {
+        let pending = self.pending_mints.get(&pending_id).expect("Pending mint not found");
+        if env::block_index() <= pending.committed_block.0 {
+            env::panic(b"Mint can be revealed from the next block on");
+        }
+        let storage_before = env::storage_usage();
+        self.pending_mints.remove(&pending_id);
+        let mut pending_ids = self.account_pending_mints.get(&pending.minter).unwrap_or_default();
+        pending_ids.retain(|&id| id != pending_id);
+        if pending_ids.is_empty() {
+            self.account_pending_mints.remove(&pending.minter);
+        } else {
+            self.account_pending_mints.insert(&pending.minter, &pending_ids);
+        }
+        let payer = match &pending.kind {
+            MintKind::Partner { partner_id } => partner_id.clone(),
+            _ => pending.minter.clone(),
+        };
+        let (fee, storage_reserve) = (pending.fee.0, pending.storage_reserve.0);
+        if let Some(rarity) = self.guaranteed_rarity(&pending.kind) {
+            self.update_rarity_reservation(rarity, false);
+        }
+        let corgi_storage_before = env::storage_usage();
+        let corgi = self.mint_pending(pending);
+        let storage_used = env::storage_usage() - corgi_storage_before;
+        let unused = self.charge_storage(&payer, storage_before, storage_reserve);
+        if unused > 0 {
+            self.pay_out(payer, unused);
+        }
+        MintResult {
+            token_id: U64(corgi.id),
+            name: corgi.name,
+            rate: corgi.rate,
+            sausage: corgi.sausage,
+            storage_used: U64(storage_used),
+            charged: U128(fee + storage_used as u128 * env::storage_byte_cost()),
+        }
+    }
+
+    pub fn get_pending_mint(&self, pending_id: u64) -> Option<PendingMint> {
+        self.pending_mints.get(&pending_id)
+    }
+
+    pub fn get_pending_mints(&self, account_id: AccountId) -> Vec<PendingMint> {
+        self.account_pending_mints
+            .get(&account_id)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|pending_id| self.pending_mints.get(pending_id))
+            .collect()
+    }
+
+    /// Pays for a corgi of a premium edition, revealed with `reveal_corgi` like a
+    /// public mint. The edition's fixed colors replace the given ones and its
+    /// minimum rarity constrains the roll. Storage is charged on top like
+    /// `create_corgi` and the surplus refunded.
+    #[payable]
+    pub fn create_corgi_from_edition(
+        &mut self,
+        edition_id: u64,
+        name: String,
+        color: String,
+        background_color: String,
+        quote: String,
+    ) -> PendingMint {
+        self.assert_not_paused();
+        let mut edition = self.editions.get(&edition_id).expect("Edition not found");
+        if edition.minted >= edition.max_supply {
+            env::panic(b"Edition is sold out");
+        }
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < edition.price.0 {
+            env::panic(b"Attached deposit must cover the edition price");
+        }
+        if edition.min_rarity.is_some_and(|rarity| self.rarity_quota_reached(rarity)) {
+            env::panic(b"Rarity quota of this season is used up");
+        }
+        let storage_before = env::storage_usage();
+        let predecessor = env::predecessor_account_id();
+        self.check_mint_gate(&predecessor);
+        edition.minted += 1;
+        self.editions.insert(&edition_id, &edition);
+        self.treasury_balance += edition.price.0;
+        let spec = CorgiSpec {
+            name,
+            color: edition.color.unwrap_or(color),
+            background_color: edition.background_color.unwrap_or(background_color),
+            quote,
+        };
+        self.assert_valid_spec(&spec);
+        let pending =
+            self.commit_mint(&predecessor, spec, edition.price.0, MintKind::Edition { edition_id });
+        let available =
+            self.charge_storage(&predecessor, storage_before, attached_deposit - edition.price.0);
+        let refund = self.charge(&predecessor, pending.storage_reserve.0, available);
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+        pending
+    }
+
+    pub fn get_edition(&self, edition_id: u64) -> Option<Edition> {
+        self.editions.get(&edition_id)
+    }
+
+    /// Deprecated: returns a `[name, id]` tuple like the old `create_corgi`. Since
+    /// mints are revealed later, `id` is the pending mint ID, not a token ID.
+    #[payable]
+    pub fn create_corgi_legacy(
+        &mut self,
+        name: String,
+        color: String,
+        background_color: String,
+        quote: String,
+    ) -> (String, u64) {
+        log_deprecated("create_corgi_legacy", "create_corgi");
+        let pending = self.create_corgi_v2(CorgiSpec {
+            name,
+            color,
+            background_color,
+            quote,
+        });
+        (pending.spec.name, pending.id)
+    }
+
+    /// Mints a copy of `token_id` with the same visuals, one rarity tier lower.
+    /// Clones of clones count against the root original's clone limit, so
+    /// chaining clones doesn't get around it. Charged like a public mint: the
+    /// fee plus the storage the copy takes.
+    #[payable]
+    pub fn clone_corgi(&mut self, token_id: TokenId) -> TokenId {
+        self.assert_not_paused();
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < self.mint_price {
+            ContractError::InsufficientMintFee.panic();
+        }
+        let original = self.get_corgi(token_id);
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can clone a corgi");
+        }
+        let root_id = original.clone_of.unwrap_or(token_id);
+        let mut root = match self.corgis.get(&root_id) {
+            Some(root) => root,
+            None => env::panic(b"Clones of a burned corgi can't be cloned"),
+        };
+        if root.clone_count >= MAX_CLONES_PER_CORGI {
+            env::panic(b"Clone limit reached for this corgi");
+        }
+        let storage_before = env::storage_usage();
+        self.treasury_balance += self.mint_price;
+        root.clone_count += 1;
+        self.corgis.insert(&root_id, &root);
+
+        let rarity = original.rate;
+        let lowered = rarity.lower();
+        let sausage: u64 = original.sausage.parse().unwrap_or(0);
+        let sausage = if lowered != rarity {
+            sausage.saturating_sub(50)
+        } else {
+            sausage
+        };
+        let mut clone = self.mint_corgi(
+            predecessor.clone(),
+            CorgiSpec {
+                name: original.name,
+                color: original.color,
+                background_color: original.background_color,
+                quote: original.quote,
+            },
+            lowered,
+            sausage.to_string(),
+        );
+        clone.clone_of = Some(root_id);
+        self.corgis.insert(&clone.id, &clone);
+        emit_mint(&predecessor, clone.id, "clone");
+        let refund =
+            self.charge_storage(&predecessor, storage_before, attached_deposit - self.mint_price);
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+        clone.id
+    }
+
+    /// Renames the caller's corgi for the customization fee. Storage a longer
+    /// name takes is charged on top; any surplus is refunded.
+    #[payable]
+    pub fn rename_corgi(&mut self, token_id: TokenId, new_name: String) {
+        assert_valid_name(&new_name);
+        self.customize_corgi(token_id, "name", |corgi| corgi.name = new_name);
+    }
+
+    #[payable]
+    pub fn set_quote(&mut self, token_id: TokenId, quote: String) {
+        if quote.len() > MAX_QUOTE_LENGTH {
+            ContractError::QuoteTooLong.panic();
+        }
+        self.customize_corgi(token_id, "quote", |corgi| corgi.quote = quote);
+    }
+
+    /// Premium colors cost their fruit like at mint. Corgis of editions with
+    /// fixed colors can't be recolored.
+    #[payable]
+    pub fn set_colors(&mut self, token_id: TokenId, color: String, background_color: String) {
+        self.assert_valid_colors(&[&color, &background_color]);
+        let edition = self
+            .corgi_editions
+            .get(&token_id)
+            .and_then(|edition_id| self.editions.get(&edition_id));
+        if edition.is_some_and(|edition| {
+            edition.color.is_some() || edition.background_color.is_some()
+        }) {
+            env::panic(b"Corgi keeps the colors of its edition");
+        }
+        let predecessor = env::predecessor_account_id();
+        self.charge_premium_colors(&predecessor, &[&color, &background_color]);
+        self.customize_corgi(token_id, "colors", |corgi| {
+            corgi.color = color;
+            corgi.background_color = background_color;
+        });
+    }
+
+    /// Locks the caller's corgi so it can't be transferred, listed or deleted,
+    /// guarding it against compromised approvals. With `min_duration_hours` it
+    /// can't be unlocked before that time has passed.
+    pub fn lock_corgi(&mut self, token_id: TokenId, min_duration_hours: Option<u64>) {
+        let mut corgi = self.get_corgi(token_id);
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can lock a corgi");
+        }
+        if corgi.locked.is_some() {
+            env::panic(b"Corgi is already locked");
+        }
+        if corgi.selling {
+            env::panic(b"Cancel the sale before locking the corgi");
+        }
+        let duration = min_duration_hours.unwrap_or(0) * 60 * 60 * 1_000_000_000;
+        corgi.locked = Some(env::block_timestamp() + duration);
+        self.corgis.insert(&token_id, &corgi);
+    }
+
+    pub fn unlock_corgi(&mut self, token_id: TokenId) {
+        let mut corgi = self.get_corgi(token_id);
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can unlock a corgi");
+        }
+        match corgi.locked {
+            None => env::panic(b"Corgi is not locked"),
+            Some(unlock_at) if env::block_timestamp() < unlock_at => {
+                env::panic(b"Corgi is locked for its minimum duration")
+            }
+            Some(_) => {}
+        }
+        corgi.locked = None;
+        self.corgis.insert(&token_id, &corgi);
+    }
+
+    /// Permanently locks name, quote and colors of the corgi. Cannot be undone.
+    pub fn finalize_metadata(&mut self, token_id: TokenId) {
+        let mut corgi = self.get_corgi(token_id);
+        if self.get_token_owner(token_id) != env::predecessor_account_id() {
+            env::panic(b"Only the owner can finalize corgi metadata");
+        }
+        if corgi.metadata_frozen {
+            env::panic(b"Corgi metadata is already finalized");
+        }
+        corgi.metadata_frozen = true;
+        self.corgis.insert(&token_id, &corgi);
+    }
+
+    /// Burns the caller's corgi in exchange for a physical plush, recording the
+    /// hashed shipping reference. Returns the redemption ID.
+    pub fn redeem_for_merch(&mut self, token_id: TokenId, shipping_hash: String) -> u64 {
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can redeem a corgi");
+        }
+        if shipping_hash.is_empty() || shipping_hash.len() > 128 {
+            env::panic(b"Invalid shipping hash");
+        }
+        self.assert_burnable(&self.get_corgi(token_id));
+        self.remove_corgi(token_id, predecessor.clone());
+        let id = self.redemptions.len();
+        self.redemptions.push(&Redemption {
+            id,
+            account_id: predecessor,
+            token_id,
+            shipping_hash,
+            redeemed_at: env::block_timestamp(),
+            fulfilled: false,
+        });
+        id
+    }
+
+    pub fn get_redemptions(&self, from_index: u64, limit: u64) -> Vec<Redemption> {
+        let limit = view_limit(limit);
+        (from_index..std::cmp::min(from_index.saturating_add(limit), self.redemptions.len()))
+            .filter_map(|index| self.redemptions.get(index))
+            .collect()
+    }
+
+    /// Mint for approved partner contracts, revealed with `reveal_corgi` like a
+    /// public mint; payment is settled off-chain, so only the storage and any
+    /// premium colors are charged, to the partner like `create_corgi`, but each
+    /// partner is limited by its quota.
+    #[payable]
+    pub fn partner_mint(&mut self, spec: CorgiSpec, receiver: AccountId) -> PendingMint {
+        self.assert_not_paused();
+        self.assert_valid_spec(&spec);
+        assert!(
+            env::is_valid_account_id(receiver.as_bytes()),
+            "Receiver's account ID is invalid."
+        );
+        let partner_id = env::predecessor_account_id();
+        let mut partner = match self.partners.get(&partner_id) {
+            Some(partner) => partner,
+            None => env::panic(b"Caller is not an approved partner"),
+        };
+        if partner.minted >= partner.quota {
+            env::panic(b"Partner mint quota exhausted");
+        }
+        let storage_before = env::storage_usage();
+        partner.minted += 1;
+        self.partners.insert(&partner_id, &partner);
+        self.charge_premium_colors(&partner_id, &[&spec.color, &spec.background_color]);
+
+        let kind = MintKind::Partner {
+            partner_id: partner_id.clone(),
+        };
+        let pending = self.commit_mint(&receiver, spec, 0, kind);
+        let available = self.charge_storage(&partner_id, storage_before, env::attached_deposit());
+        let refund = self.charge(&partner_id, pending.storage_reserve.0, available);
+        if refund > 0 {
+            self.pay_out(partner_id, refund);
+        }
+        pending
+    }
+
+    /// Pays for a corgi of an off-chain drop with a voucher signed by the minter
+    /// key, revealed with `reveal_corgi` like a public mint. The voucher's price
+    /// goes to the treasury; storage and any premium colors are charged on top
+    /// like `create_corgi` and the surplus refunded. Each nonce mints once.
+    #[payable]
+    pub fn mint_with_voucher(&mut self, voucher: MintVoucher, signature: Base64VecU8) -> PendingMint {
+        self.assert_not_paused();
+        let public_key = self.minter_public_key.as_ref().expect("Voucher minting is disabled");
+        let message = (env::current_account_id(), voucher.clone()).try_to_vec().unwrap();
+        if !verify_ed25519(public_key, &message, &signature.0) {
+            env::panic(b"Invalid voucher signature");
+        }
+        self.assert_valid_spec(&voucher.spec);
+        if voucher.rate.is_some_and(|rate| self.rarity_quota_reached(rate)) {
+            env::panic(b"Rarity quota of this season is used up");
+        }
+        if self.voucher_nonces.contains(&voucher.nonce) {
+            env::panic(b"Voucher has already been redeemed");
+        }
+        if voucher.expires_at.is_some_and(|expires_at| env::block_timestamp() >= expires_at.0) {
+            env::panic(b"Voucher has expired");
+        }
+        let predecessor = env::predecessor_account_id();
+        if voucher.receiver_id.as_ref().is_some_and(|receiver_id| *receiver_id != predecessor) {
+            env::panic(b"Voucher is for another account");
+        }
+        let attached_deposit = env::attached_deposit();
+        if attached_deposit < voucher.price.0 {
+            env::panic(b"Attached deposit must cover the voucher price");
+        }
+        let storage_before = env::storage_usage();
+        self.treasury_balance += voucher.price.0;
+        self.voucher_nonces.insert(&voucher.nonce);
+        self.charge_premium_colors(
+            &predecessor,
+            &[&voucher.spec.color, &voucher.spec.background_color],
+        );
+        let kind = MintKind::Voucher {
+            nonce: voucher.nonce,
+            rate: voucher.rate,
+        };
+        let pending = self.commit_mint(&predecessor, voucher.spec, voucher.price.0, kind);
+        let available =
+            self.charge_storage(&predecessor, storage_before, attached_deposit - voucher.price.0);
+        let refund = self.charge(&predecessor, pending.storage_reserve.0, available);
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+        pending
+    }
+
+    /// The corgi minted from the voucher with `nonce`, if it was redeemed.
+    pub fn get_voucher_mint(&self, nonce: u64) -> Option<U64> {
+        self.voucher_mints.get(&nonce).map(U64)
+    }
+
+    /// Free registration required before minting while the mint gate is enabled.
+    pub fn register(&mut self) {
+        let predecessor = env::predecessor_account_id();
+        if self.registrations.get(&predecessor).is_none() {
+            self.registrations.insert(
+                &predecessor,
+                &Registration {
+                    registered_at_block: env::block_index(),
+                    last_mint_block: 0,
+                    mints_in_block: 0,
+                },
+            );
+        }
+    }
+
+    pub fn get_registration(&self, account_id: AccountId) -> Option<Registration> {
+        self.registrations.get(&account_id)
+    }
+
+    /// Whether `account_id` may mint in the current block under the mint gate.
+    pub fn can_mint(&self, account_id: AccountId) -> bool {
+        match (&self.mint_gate, self.registrations.get(&account_id)) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(gate), Some(registration)) => {
+                let block = env::block_index();
+                block >= registration.registered_at_block + gate.delay_blocks
+                    && (registration.last_mint_block != block
+                        || registration.mints_in_block < gate.max_mints_per_block)
+            }
+        }
+    }
+
+    /// Re-ranks `token_ids` in `get_rarity_ranking` by their current rarity
+    /// score; corgis are ranked as of their last mint or trait change, and other
+    /// mints shift scores since. Anyone can call it; unknown IDs are skipped.
+    pub fn refresh_rarity_ranking(&mut self, token_ids: Vec<TokenId>) {
+        for token_id in token_ids {
+            if let Some(corgi) = self.corgis.get(&token_id) {
+                self.rank_rarity(&corgi);
+            }
+        }
+    }
+}
+
+// Helper methods
+impl Corgi3D {
+    fn check_mint_gate(&mut self, account: &AccountId) {
+        if self.mint_gate.is_none() {
+            return;
+        }
+        if !self.can_mint(account.clone()) {
+            env::panic(b"Account is not registered long enough or exceeded mints in this block");
+        }
+        let mut registration = self.registrations.get(account).unwrap();
+        let block = env::block_index();
+        if registration.last_mint_block != block {
+            registration.last_mint_block = block;
+            registration.mints_in_block = 0;
+        }
+        registration.mints_in_block += 1;
+        self.registrations.insert(account, &registration);
+    }
+
+    /// Shared implementation behind `create_corgi` and its legacy variant.
+    /// Charges the mint fee, less any mint credit, and reserves the storage the
+    /// new corgi takes, which depends on the length of its strings, then leaves
+    /// the corgi pending until `reveal_corgi`. Storage the attached deposit doesn't cover is taken from the
+    /// caller's storage balance; any surplus is refunded.
+    fn create_corgi_v2(&mut self, spec: CorgiSpec) -> PendingMint {
+        let attached_deposit = env::attached_deposit();
+        let predecessor = env::predecessor_account_id();
+        self.enter_drop(&predecessor, 1);
+        let fee = self.apply_flash_sale(None, &predecessor, self.public_mint_price());
+        let fee = self.apply_mint_credit(&predecessor, fee);
+        if attached_deposit < fee {
+            ContractError::InsufficientMintFee.panic();
+        }
+        let storage_before = env::storage_usage();
+        self.treasury_balance += fee;
+        let pending = self.commit_public_mint(&predecessor, spec, fee);
+        let available = self.charge_storage(&predecessor, storage_before, attached_deposit - fee);
+        let refund = self.charge(&predecessor, pending.storage_reserve.0, available);
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+        pending
+    }
+
+    /// Applies a cosmetic change by the owner, charging the customization fee and
+    /// the storage the change takes, then refunds the surplus.
+    fn customize_corgi(&mut self, token_id: TokenId, field: &str, update: impl FnOnce(&mut Corgi)) {
+        let attached_deposit = env::attached_deposit();
+        let predecessor = env::predecessor_account_id();
+        if self.get_token_owner(token_id) != predecessor {
+            env::panic(b"Only the owner can customize a corgi");
+        }
+        if attached_deposit < self.customization_fee {
+            env::panic(b"Attached deposit must cover the customization fee");
+        }
+        let mut corgi = self.get_corgi(token_id);
+        if corgi.metadata_frozen {
+            env::panic(b"Corgi metadata is finalized");
+        }
+        let storage_before = env::storage_usage();
+        self.update_trait_counts(&corgi, false);
+        update(&mut corgi);
+        self.update_trait_counts(&corgi, true);
+        self.corgis.insert(&token_id, &corgi);
+        self.treasury_balance += self.customization_fee;
+        emit_metadata_update(token_id, field);
+        let refund = self.charge_storage(
+            &predecessor,
+            storage_before,
+            attached_deposit - self.customization_fee,
+        );
+        if refund > 0 {
+            self.pay_out(predecessor, refund);
+        }
+    }
+
+    /// Fee of a public mint before flash sales: the drop price while a drop is
+    /// configured, the mint price otherwise.
+    pub(crate) fn public_mint_price(&self) -> Balance {
+        self.mint_drop.as_ref().map_or(self.mint_price, |drop| drop.price.0)
+    }
+
+    /// Why `account` can't mint `count` more corgis in the current drop, if anything stops it.
+    pub(crate) fn drop_blocker(&self, account: &AccountId, count: u64) -> Option<&'static str> {
+        let drop = self.mint_drop.as_ref()?;
+        let now = env::block_timestamp();
+        if now < drop.start_at.0 {
+            return Some("Drop has not started");
+        }
+        if now >= drop.end_at.0 {
+            return Some("Drop has ended");
+        }
+        if drop.allowlist_only && !self.drop_allowlist.contains(account) {
+            return Some("Account is not on the drop allowlist");
+        }
+        let minted = self.drop_mints.get(&(drop.id, account.clone())).unwrap_or(0);
+        if minted + count > drop.per_account_limit {
+            return Some("Account reached the drop mint limit");
+        }
+        None
+    }
+
+    /// Checks `account` may mint `count` corgis in the current drop and counts them.
+    pub(crate) fn enter_drop(&mut self, account: &AccountId, count: u64) {
+        if let Some(reason) = self.drop_blocker(account, count) {
+            env::panic(reason.as_bytes());
+        }
+        if let Some(drop) = self.mint_drop.as_ref() {
+            let key = (drop.id, account.clone());
+            let minted = self.drop_mints.get(&key).unwrap_or(0);
+            self.drop_mints.insert(&key, &(minted + count));
+        }
+    }
+
+    /// Takes what it can of `fee` from the mint credit of `account`, returning
+    /// the rest of the fee.
+    fn apply_mint_credit(&mut self, account: &AccountId, fee: Balance) -> Balance {
+        let credit = self.mint_credits.get(account).unwrap_or(0);
+        if credit == 0 {
+            return fee;
+        }
+        let used = std::cmp::min(credit, fee);
+        if credit == used {
+            self.mint_credits.remove(account);
+        } else {
+            self.mint_credits.insert(account, &(credit - used));
+        }
+        fee - used
+    }
+
+    /// Checks the user-chosen fields of a public mint.
+    pub(crate) fn assert_valid_spec(&self, spec: &CorgiSpec) {
+        assert_valid_name(&spec.name);
+        if spec.quote.len() > MAX_QUOTE_LENGTH {
+            ContractError::QuoteTooLong.panic();
+        }
+        self.assert_valid_colors(&[&spec.color, &spec.background_color]);
+    }
+
+    /// Colors must be hex, a color name the renderer knows or a premium color.
+    pub(crate) fn assert_valid_colors(&self, colors: &[&String]) {
+        for color in colors {
+            if color.is_empty() || color.len() > MAX_COLOR_LENGTH {
+                ContractError::InvalidColorLength.panic();
+            }
+            let color = color.trim().to_lowercase();
+            if !is_hex_color(&color)
+                && named_color(&color).is_none()
+                && self.premium_colors.get(&color).is_none()
+            {
+                ContractError::UnknownColor.panic();
+            }
+        }
+    }
+
+    /// Records a public mint to `minter` paid for by the caller, with `fee` already
+    /// taken. The caller charges the returned storage reservation.
+    pub(crate) fn commit_public_mint(&mut self, minter: &AccountId, spec: CorgiSpec, fee: Balance) -> PendingMint {
+        self.assert_not_paused();
+        self.assert_valid_spec(&spec);
+        self.check_mint_gate(minter);
+        self.charge_premium_colors(minter, &[&spec.color, &spec.background_color]);
+        self.commit_mint(minter, spec, fee, MintKind::Public)
+    }
+
+    /// Records a mint of `kind` to `minter` whose checks and `fee` the caller
+    /// already took care of. The caller charges the returned storage reservation.
+    pub(crate) fn commit_mint(
+        &mut self,
+        minter: &AccountId,
+        spec: CorgiSpec,
+        fee: Balance,
+        kind: MintKind,
+    ) -> PendingMint {
+        let mut pending_ids = self.account_pending_mints.get(minter).unwrap_or_default();
+        if pending_ids.len() >= MAX_PENDING_MINTS {
+            env::panic(b"Reveal pending mints before minting more");
+        }
+        if let Some(rarity) = self.guaranteed_rarity(&kind) {
+            self.update_rarity_reservation(rarity, true);
+        }
+        let id = self.next_pending_mint_id;
+        self.next_pending_mint_id += 1;
+        let pending = PendingMint {
+            id,
+            minter: minter.clone(),
+            storage_reserve: U128(mint_storage_estimate(&spec) as u128 * env::storage_byte_cost()),
+            spec,
+            committed_block: U64(env::block_index()),
+            fee: U128(fee),
+            kind,
+        };
+        self.pending_mints.insert(&id, &pending);
+        pending_ids.push(id);
+        self.account_pending_mints.insert(minter, &pending_ids);
+        pending
+    }
+
+    /// Rolls and stores the corgi of a pending mint along with what its kind
+    /// records about it.
+    fn mint_pending(&mut self, pending: PendingMint) -> Corgi {
+        let minter = pending.minter;
+        match pending.kind {
+            MintKind::Public => {
+                let (rate, sausage) = self.generate_rate_sausage();
+                let corgi = self.mint_corgi(minter.clone(), pending.spec, rate, sausage);
+                self.lock_transfers(corgi.id);
+                emit_mint(&minter, corgi.id, "public");
+                corgi
+            }
+            MintKind::Edition { edition_id } => {
+                let min_rarity = self.editions.get(&edition_id).and_then(|edition| edition.min_rarity);
+                let (rate, sausage) =
+                    self.generate_rate_sausage_at_least(min_rarity.unwrap_or(Rarity::Common));
+                let corgi = self.mint_corgi(minter.clone(), pending.spec, rate, sausage);
+                self.lock_transfers(corgi.id);
+                self.corgi_editions.insert(&corgi.id, &edition_id);
+                emit_mint(&minter, corgi.id, "edition");
+                corgi
+            }
+            MintKind::Pass { periods } => {
+                let (rate, sausage) = self.generate_rate_sausage();
+                let mut corgi = self.mint_corgi(minter.clone(), pending.spec, rate, sausage);
+                corgi.pass = Some(PassInfo {
+                    expires_at: env::block_timestamp() + periods * PASS_PERIOD,
+                    auto_renew: false,
+                });
+                self.corgis.insert(&corgi.id, &corgi);
+                emit_mint(&minter, corgi.id, "pass");
+                corgi
+            }
+            MintKind::Partner { partner_id } => {
+                let (rate, sausage) = self.generate_rate_sausage();
+                let corgi = self.mint_corgi(minter.clone(), pending.spec, rate, sausage);
+                emit_mint(&minter, corgi.id, &format!("partner:{}", partner_id));
+                corgi
+            }
+            MintKind::Voucher { nonce, rate } => {
+                let (rolled_rate, sausage) = self.generate_rate_sausage();
+                // a quota tier `rate` had its slot reserved at commit
+                let rate = rate.unwrap_or(rolled_rate);
+                let corgi = self.mint_corgi(minter.clone(), pending.spec, rate, sausage);
+                self.lock_transfers(corgi.id);
+                self.voucher_mints.insert(&nonce, &corgi.id);
+                emit_mint(&minter, corgi.id, "voucher");
+                corgi
+            }
+            MintKind::Breeding {
+                parent_a,
+                parent_b,
+                inherit_traits,
+            } => {
+                // a burned parent leaves the child with the traits reserved at commit
+                let parents = match (self.corgis.get(&parent_a), self.corgis.get(&parent_b)) {
+                    (Some(a), Some(b)) if inherit_traits => Some((a, b)),
+                    _ => None,
+                };
+                let (spec, rate, sausage) = match parents {
+                    Some((a, b)) => self.inherit_traits(&a, &b),
+                    None => {
+                        let (rate, sausage) = self.generate_rate_sausage();
+                        (pending.spec, rate, sausage)
+                    }
+                };
+                let child = self.mint_corgi(minter.clone(), spec, rate, sausage);
+                let child = self.record_breeding(child, parent_a, parent_b);
+                emit_mint(&minter, child.id, "breeding");
+                child
+            }
+        }
+    }
+
+    /// Stores a new corgi owned by `owner` and returns it. Every mint path ends
+    /// here, so this is where the season quota of `rate` is enforced.
+    pub(crate) fn mint_corgi(
+        &mut self,
+        owner: AccountId,
+        spec: CorgiSpec,
+        rate: Rarity,
+        sausage: String,
+    ) -> Corgi {
+        if self.next_corgi_id >= 1 << TOKEN_SERIAL_BITS {
+            env::panic(b"Token IDs of this era are used up");
+        }
+        if self.rarity_quota_reached(rate) {
+            env::panic(b"Rarity quota of this season is used up");
+        }
+        let id = self.token_id_for(self.next_corgi_id);
+        self.next_corgi_id += 1;
+        self.total_minted += 1;
+        let corgi = Corgi {
+            id,
+            name: spec.name,
+            color: spec.color,
+            background_color: spec.background_color,
+            quote: spec.quote,
+            rate,
+            sausage,
+            selling: false,
+            selling_price: U128(0),
+            message: "".to_string(),
+            sender: "".to_string(),
+            energy: MAX_ENERGY,
+            energy_updated_at: env::block_timestamp(),
+            clone_of: None,
+            clone_count: 0,
+            metadata_frozen: false,
+            locked: None,
+            parents: vec![],
+            generation: 0,
+            creator: owner.clone(),
+            royalty_bps: DEFAULT_ROYALTY_BPS,
+            bonded_with: None,
+            bond_changed_at: 0,
+            pass: None,
+            battles_fought: 0,
+            battles_won: 0,
+            times_bred: 0,
+            unlocked_traits: 0,
+            skills: vec![0; SKILL_COUNT],
+            skill_progress: vec![0; SKILL_COUNT],
+            accessories: vec![],
+        };
+        self.corgis.insert(&id, &corgi);
+        if Rarity::QUOTA_TIERS.contains(&rate) {
+            let minted = self.season_rarity_minted.get(&rate).unwrap_or(0);
+            self.season_rarity_minted.insert(&rate, &(minted + 1));
+        }
+        if owner == env::predecessor_account_id() {
+            self.record_activity(&owner);
+        }
+        self.save_corgi_to_account(id, owner);
+        self.update_trait_counts(&corgi, true);
+        self.update_rarity_index(&corgi, true);
+        corgi
+    }
+
+    /// Token ID of the `serial`-th corgi minted by this deployment.
+    pub(crate) fn token_id_for(&self, serial: u64) -> TokenId {
+        (self.id_era as u64) << TOKEN_SERIAL_BITS | serial
+    }
+
+    pub(crate) fn update_trait_counts(&mut self, corgi: &Corgi, added: bool) {
+        for key in corgi_traits(corgi) {
+            let count = self.trait_counts.get(&key).unwrap_or(0);
+            let count = if added { count + 1 } else { count - 1 };
+            if count == 0 {
+                self.trait_counts.remove(&key);
+            } else {
+                self.trait_counts.insert(&key, &count);
+            }
+        }
+        if added {
+            self.rank_rarity(corgi);
+        } else if let Some(score) = self.ranked_scores.remove(&corgi.id) {
+            self.rarity_ranking.remove(&(u64::MAX - score, corgi.id));
+        }
+    }
+
+    /// Moves `corgi` to its current rarity score in `rarity_ranking`.
+    pub(crate) fn rank_rarity(&mut self, corgi: &Corgi) {
+        if let Some(score) = self.ranked_scores.get(&corgi.id) {
+            self.rarity_ranking.remove(&(u64::MAX - score, corgi.id));
+        }
+        let score = self.rarity_score(corgi);
+        self.rarity_ranking.insert(&(u64::MAX - score, corgi.id), &());
+        self.ranked_scores.insert(&corgi.id, &score);
+    }
+
+    pub(crate) fn update_rarity_index(&mut self, corgi: &Corgi, added: bool) {
+        let mut token_ids = self.rarity_corgis.get(&corgi.rate).unwrap_or_else(|| {
+            let mut prefix = b"rarity-set".to_vec();
+            prefix.push(corgi.rate as u8);
+            UnorderedSet::new(prefix)
+        });
+        if added {
+            token_ids.insert(&corgi.id);
+        } else {
+            token_ids.remove(&corgi.id);
+        }
+        self.rarity_corgis.insert(&corgi.rate, &token_ids);
+    }
+
+    pub(crate) fn generate_rate_sausage(&self) -> (Rarity, String) {
+        self.generate_rate_sausage_at_least(Rarity::Common)
+    }
+
+    /// Like `generate_rate_sausage`, but the rarity roll only ranges over the
+    /// outcomes of `min_rarity` and above, keeping their relative odds. Tiers
+    /// whose season quota is used up degrade to the next tier down, but never
+    /// below `min_rarity`.
+    pub(crate) fn generate_rate_sausage_at_least(&self, min_rarity: Rarity) -> (Rarity, String) {
+        let (r1, r2) = self.random_num();
+        let r2 = r2 % (max_rarity_roll(min_rarity) + 1);
+        let r2 = r2.saturating_sub(self.get_world_state().map_or(0, |state| state.rarity_boost));
+        let mut rarity = if r2 > 30 {
+            Rarity::Common
+        } else if r2 > 13 {
+            Rarity::Uncommon
+        } else if r2 > 3 {
+            Rarity::Rare
+        } else if r2 > 0 {
+            Rarity::VeryRare
+        } else {
+            Rarity::UltraRare
+        };
+        while self.rarity_quota_reached(rarity) && rarity_tier(rarity) > rarity_tier(min_rarity) {
+            rarity = rarity.lower();
+        }
+        if self.rarity_quota_reached(rarity) {
+            env::panic(b"Rarity quota of this season is used up");
+        }
+        // each tier above common adds 50 to the sausage length
+        let sausage = r1 as u64 + (rarity_tier(rarity) - 1) * 50;
+        (rarity, sausage.to_string())
+    }
+
+    /// Whether the season quota of `rarity` is used up, counting the slots
+    /// pending mints have reserved.
+    pub(crate) fn rarity_quota_reached(&self, rarity: Rarity) -> bool {
+        self.rarity_quotas.get(&rarity).is_some_and(|quota| {
+            let minted = self.season_rarity_minted.get(&rarity).unwrap_or(0);
+            minted + self.rarity_reservations.get(&rarity).unwrap_or(0) >= quota
+        })
+    }
+
+    /// The quota tier a mint of `kind` is guaranteed at least, whose slot it
+    /// reserves from commit to reveal so the reveal can't run out of quota.
+    fn guaranteed_rarity(&self, kind: &MintKind) -> Option<Rarity> {
+        let rarity = match kind {
+            MintKind::Edition { edition_id } => {
+                self.editions.get(edition_id).and_then(|edition| edition.min_rarity)
+            }
+            MintKind::Voucher { rate, .. } => *rate,
+            _ => None,
+        };
+        rarity.filter(|rarity| Rarity::QUOTA_TIERS.contains(rarity))
+    }
+
+    fn update_rarity_reservation(&mut self, rarity: Rarity, added: bool) {
+        let reserved = self.rarity_reservations.get(&rarity).unwrap_or(0);
+        let reserved = if added { reserved + 1 } else { reserved - 1 };
+        if reserved == 0 {
+            self.rarity_reservations.remove(&rarity);
+        } else {
+            self.rarity_reservations.insert(&rarity, &reserved);
+        }
+    }
+
+    pub(crate) fn random_rng(&self) -> ChaCha20Rng {
+        let mut seed = [0u8; 32];
+        let v = env::random_seed();
+        let l = std::cmp::min(24, v.len());
+        seed[0..l].copy_from_slice(&v[0..l]);
+        let id = self.next_corgi_id.to_le_bytes();
+        seed[24..32].copy_from_slice(&id);
+        ChaCha20Rng::from_seed(seed)
+    }
+
+    pub(crate) fn random_num(&self) -> (u32, u32) {
+        let mut rng1 = self.random_rng();
+        (rng1.next_u32() % 100, rng1.next_u32() % 50)
+    }
+
+    /// The one path behind every NEP-4 and NEP-171 transfer entry point, so
+    /// checks, side effects and events are the same whichever the caller uses.
+    /// With `owner_id` the caller transfers on the owner's behalf and needs
+    /// escrow access or a token approval; without it the caller must be the owner.
+    pub(crate) fn transfer_as_caller(
+        &mut self,
+        owner_id: Option<AccountId>,
+        new_owner_id: AccountId,
+        token_id: TokenId,
+        memo: Option<String>,
+        message: Option<String>,
+    ) {
+        let token_owner_account_id = self.checked_transfer(owner_id, &new_owner_id, token_id, message);
+        let predecessor = env::predecessor_account_id();
+        let authorized_id = if predecessor != token_owner_account_id {
+            Some(predecessor.as_str())
+        } else {
+            None
+        };
+        emit_transfer(
+            &token_owner_account_id,
+            &new_owner_id,
+            token_id,
+            authorized_id,
+            memo,
+        );
+    }
+
+    /// `transfer_as_caller` of several corgis from the same owner, with a single event.
+    fn transfer_batch_as_caller(
+        &mut self,
+        owner_id: Option<AccountId>,
+        new_owner_id: AccountId,
+        token_ids: Vec<TokenId>,
+        memo: Option<String>,
+    ) {
+        if token_ids.is_empty() || token_ids.len() > MAX_BATCH_SIZE {
+            ContractError::InvalidBatchSize.panic();
+        }
+        let predecessor = env::predecessor_account_id();
+        // every corgi is checked to belong to `owner_id`, or the caller if omitted
+        let old_owner_id = owner_id.clone().unwrap_or_else(|| predecessor.clone());
+        for &token_id in &token_ids {
+            self.checked_transfer(owner_id.clone(), &new_owner_id, token_id, None);
+        }
+        let authorized_id = if predecessor != old_owner_id {
+            Some(predecessor.as_str())
+        } else {
+            None
+        };
+        emit_transfer_batch(&old_owner_id, &new_owner_id, &token_ids, authorized_id, memo);
+    }
+
+    /// Checks the caller may move `token_id` and moves it, returning the previous
+    /// owner. Events are left to the caller.
+    fn checked_transfer(
+        &mut self,
+        owner_id: Option<AccountId>,
+        new_owner_id: &AccountId,
+        token_id: TokenId,
+        message: Option<String>,
+    ) -> AccountId {
+        let token_owner_account_id = self.get_token_owner(token_id);
+        let predecessor = env::predecessor_account_id();
+        match owner_id {
+            None => {
+                if predecessor != token_owner_account_id {
+                    ContractError::NotTokenOwner.panic()
+                }
+            }
+            Some(owner_id) => {
+                if owner_id != token_owner_account_id {
+                    ContractError::WrongOwner.panic()
+                }
+                if !self.check_access(token_owner_account_id.clone())
+                    && !self.is_approved_for(token_id, &predecessor)
+                {
+                    ContractError::NoTransferAccess.panic()
+                }
+            }
+        }
+        if message.as_ref().is_some_and(|message| message.len() > MAX_MESSAGE_LENGTH) {
+            ContractError::MessageTooLong.panic();
+        }
+        assert_valid_account_id(new_owner_id);
+        self.internal_transfer(token_id, token_owner_account_id.clone(), new_owner_id.clone());
+        self.record_provenance(token_id, &token_owner_account_id, new_owner_id, None);
+        if let Some(message) = message {
+            // storage a longer message takes is paid from the caller's storage balance
+            let storage_before = env::storage_usage();
+            let mut corgi = self.corgis.get(&token_id).unwrap();
+            corgi.message = message;
+            self.corgis.insert(&token_id, &corgi);
+            self.charge_storage(&predecessor, storage_before, 0);
+        }
+        self.record_activity(&predecessor);
+        token_owner_account_id
+    }
+
+    /// Checks the 1 yoctoNEAR deposit and `approval_id` of a NEP-171 transfer and
+    /// performs it. Returns the previous owner and the approvals it cleared.
+    fn nft_transfer_internal(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: &str,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    ) -> (AccountId, HashMap<AccountId, u64>) {
+        if env::attached_deposit() != 1 {
+            env::panic(b"Requires attached deposit of exactly 1 yoctoNEAR");
+        }
+        let id = parse_token_id(token_id);
+        let owner_id = self.get_token_owner(id);
+        let predecessor = env::predecessor_account_id();
+        let approvals = self.token_approvals.get(&id).unwrap_or_default();
+        if predecessor == owner_id {
+            self.transfer_as_caller(None, receiver_id, id, memo, None);
+        } else {
+            if let Some(approval_id) = approval_id {
+                if approvals.get(&predecessor) != Some(&approval_id) {
+                    env::panic(b"Approval ID does not match");
+                }
+            }
+            self.transfer_as_caller(Some(owner_id.clone()), receiver_id, id, memo, None);
+        }
+        (owner_id, approvals)
+    }
+
+    /// Appends to the provenance of `token_id`, overwriting the oldest entry once
+    /// `MAX_PROVENANCE_ENTRIES` are kept.
+    pub(crate) fn record_provenance(
+        &mut self,
+        token_id: TokenId,
+        from: &AccountId,
+        to: &AccountId,
+        price: Option<U128>,
+    ) {
+        let mut log = self.provenance.get(&token_id).unwrap_or_else(|| {
+            let mut prefix = b"provenance-log".to_vec();
+            prefix.extend(token_id.to_le_bytes().iter());
+            ProvenanceLog {
+                entries: Vector::new(prefix),
+                recorded: 0,
+            }
+        });
+        let entry = ProvenanceEntry {
+            from: from.clone(),
+            to: to.clone(),
+            price,
+            timestamp: U64(env::block_timestamp()),
+        };
+        if log.entries.len() < MAX_PROVENANCE_ENTRIES {
+            log.entries.push(&entry);
+        } else {
+            log.entries.replace(log.recorded % MAX_PROVENANCE_ENTRIES, &entry);
+        }
+        log.recorded += 1;
+        self.provenance.insert(&token_id, &log);
+    }
+
+    /// Shared hook for every ownership change of an existing corgi. Anything that
+    /// must not survive a change of owner (listings, ...) is cleared here.
+    pub(crate) fn internal_transfer(&mut self, id: TokenId, old_owner: AccountId, new_owner: AccountId) {
+        if old_owner != env::current_account_id() {
+            self.assert_transferable(id);
+        }
+        self.end_listing(id);
+        let corgi = self.corgis.get(&id).unwrap();
+        if corgi.bonded_with.is_some() {
+            self.break_bond(corgi, env::block_timestamp());
+        }
+        if let Some(kennel_id) = self.kennel_treasury.remove(&id) {
+            if let Some(mut kennel) = self.kennels.get(&kennel_id) {
+                kennel.treasury_corgis.retain(|&treasury_id| treasury_id != id);
+                self.kennels.insert(&kennel_id, &kennel);
+            }
+        }
+        self.leave_house(id);
+        self.dissolve_team_of(id);
+        self.token_approvals.remove(&id);
+        self.delete_corgi_from_account(id, old_owner);
+        self.save_corgi_to_account(id, new_owner);
+    }
+
+    fn escrow_gift(
+        &mut self,
+        token_id: TokenId,
+        sender: AccountId,
+        claim_hash: Option<String>,
+        recipient: Option<AccountId>,
+        message: String,
+    ) -> u64 {
+        self.internal_transfer(token_id, sender.clone(), env::current_account_id());
+        emit_transfer(&sender, &env::current_account_id(), token_id, None, None);
+        let id = self.next_gift_id;
+        self.next_gift_id += 1;
+        self.gifts.insert(
+            &id,
+            &Gift {
+                id,
+                token_id,
+                sender,
+                claim_hash,
+                recipient,
+                message,
+                expires_at: U64(env::block_timestamp() + self.gift_expiry),
+            },
+        );
+        id
+    }
+
+    /// Hands an escrowed gift's corgi to `receiver_id` and forgets the gift. A
+    /// corgi going to someone other than the sender is signed with the sender and
+    /// the gift's message.
+    fn settle_gift(&mut self, gift: Gift, receiver_id: AccountId) {
+        self.gifts.remove(&gift.id);
+        self.internal_transfer(gift.token_id, env::current_account_id(), receiver_id.clone());
+        if receiver_id != gift.sender {
+            let mut corgi = self.corgis.get(&gift.token_id).unwrap();
+            corgi.sender = gift.sender;
+            if !gift.message.is_empty() {
+                corgi.message = gift.message;
+            }
+            self.corgis.insert(&gift.token_id, &corgi);
+        }
+        emit_transfer(&env::current_account_id(), &receiver_id, gift.token_id, None, None);
+    }
+
+    /// Records the plain account IDs behind a grant, so that grants can be listed
+    /// from either side.
+    pub(crate) fn index_access_grant(&mut self, granter: &AccountId, escrow: &AccountId) {
+        let granter_hash = env::sha256(granter.as_bytes());
+        let escrow_hash = env::sha256(escrow.as_bytes());
+        self.hashed_account_ids.insert(&granter_hash, granter);
+        self.hashed_account_ids.insert(&escrow_hash, escrow);
+        let mut grantors = self.access_grantors.get(&escrow_hash).unwrap_or_default();
+        if !grantors.contains(&granter_hash) {
+            grantors.push(granter_hash);
+            self.access_grantors.insert(&escrow_hash, &grantors);
+        }
+    }
+
+    pub(crate) fn unindex_access_grant(&mut self, granter_hash: &AccountIdHash, escrow_hash: &AccountIdHash) {
+        let mut grantors = self.access_grantors.get(escrow_hash).unwrap_or_default();
+        grantors.retain(|hash| hash != granter_hash);
+        if grantors.is_empty() {
+            self.access_grantors.remove(escrow_hash);
+        } else {
+            self.access_grantors.insert(escrow_hash, &grantors);
+        }
+    }
+
+    /// Starts the anti-flipping cooldown of a freshly minted or bought corgi.
+    pub(crate) fn lock_transfers(&mut self, token_id: TokenId) {
+        if self.transfer_cooldown > 0 {
+            self.transfer_locks
+                .insert(&token_id, &(env::block_timestamp() + self.transfer_cooldown));
+        }
+    }
+
+    pub(crate) fn assert_transferable(&self, token_id: TokenId) {
+        if let Some(reason) = self.transfer_blocker(token_id) {
+            ContractError::NotTransferable(reason).panic();
+        }
+    }
+
+    /// Locked and staked corgis can't be burned by their owner.
+    pub(crate) fn assert_burnable(&self, corgi: &Corgi) {
+        if corgi.locked.is_some() {
+            env::panic(b"Corgi is locked");
+        }
+        if self.stakes.get(&corgi.id).is_some() {
+            env::panic(b"Corgi is staked");
+        }
+        if self.bundled_corgis.contains_key(&corgi.id) {
+            env::panic(b"Corgi is in a bundle");
+        }
+    }
+
+    /// Why `token_id` can't change owners right now, if anything stops it.
+    pub(crate) fn transfer_blocker(&self, token_id: TokenId) -> Option<&'static str> {
+        if self.corgis.get(&token_id).is_some_and(|corgi| corgi.locked.is_some()) {
+            return Some("Corgi is locked");
+        }
+        if self.stakes.get(&token_id).is_some() {
+            return Some("Corgi is staked");
+        }
+        if self.bundled_corgis.contains_key(&token_id) {
+            return Some("Corgi is in a bundle");
+        }
+        if self.get_transfer_locked_until(token_id).is_some() {
+            return Some("Corgi is in its transfer cooldown");
+        }
+        if self.active_rental(token_id).is_some() {
+            return Some("Corgi is rented out");
+        }
+        if self.trainings.get(&token_id).is_some() {
+            return Some("Corgi is in training");
+        }
+        None
+    }
+
+    /// Removes a corgi for good, along with its listing and house slot.
+    fn remove_corgi(&mut self, id: TokenId, owner: AccountId) {
+        let corgi = self.corgis.get(&id).unwrap();
+        self.update_trait_counts(&corgi, false);
+        self.update_rarity_index(&corgi, false);
+        if corgi.bonded_with.is_some() {
+            self.break_bond(corgi, env::block_timestamp());
+        }
+        self.leave_house(id);
+        self.dissolve_team_of(id);
+        self.remove_rental(id);
+        self.token_approvals.remove(&id);
+        self.trainings.remove(&id);
+        self.message_updates.remove(&id);
+        self.extras.remove(&id);
+        self.token_media.remove(&id);
+        self.hidden_corgis.remove(&id);
+        self.trade_history.remove(&id);
+        self.featured.retain(|&featured| featured != id);
+        for item_id in 0..self.items.len() {
+            self.item_applications.remove(&(id, item_id));
+        }
+        self.delete_corgi_from_account(id, owner.clone());
+        self.release_listing_deposit(id);
+        self.corgi_editions.remove(&id);
+        self.purchased_at.remove(&id);
+        self.corgis.remove(&id);
+        self.selling_corgis.remove(&id);
+        self.total_burned += 1;
+        if let Some(mut log) = self.provenance.remove(&id) {
+            log.entries.clear();
+        }
+        emit_burn(&owner, id);
+        // No callback: a failing registry must not affect the burn
+        if let Some(registry) = self.burn_registry.as_ref() {
+            ext_burn_registry::on_nft_burned(id.to_string(), owner, registry, 0, BURN_NOTIFY_GAS);
+        }
+    }
+}
+
+/// Names must have a visible character and fit `MAX_CORGI_NAME_LENGTH` bytes.
+fn assert_valid_name(name: &str) {
+    if name.trim().is_empty() || name.len() > MAX_CORGI_NAME_LENGTH {
+        ContractError::InvalidName.panic();
+    }
+}
+
+/// Whether `signature` is a valid ed25519 signature of `message` by `public_key`.
+fn verify_ed25519(public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    let public_key = match ed25519_dalek::PublicKey::from_bytes(public_key) {
+        Ok(public_key) => public_key,
+        Err(_) => return false,
+    };
+    match ed25519_dalek::Signature::try_from(signature) {
+        Ok(signature) => public_key.verify(message, &signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Highest value of the rarity roll (0..50) that still yields at least `rarity`.
+fn max_rarity_roll(rarity: Rarity) -> u32 {
+    match rarity {
+        Rarity::Common => 49,
+        Rarity::Uncommon => 30,
+        Rarity::Rare => 13,
+        Rarity::VeryRare => 3,
+        Rarity::UltraRare => 0,
+    }
+}
+
+/// Token IDs are strings in the NEP-171 interface.
+fn parse_token_id(token_id: &str) -> TokenId {
+    match token_id.parse() {
+        Ok(id) => id,
+        Err(_) => env::panic(b"Invalid token ID"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn grant_access() {
+        let context = get_context(robert(), 0);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let length_before = contract.account_gives_access.len();
+        assert_eq!(0, length_before, "Expected empty account access Map.");
+        contract.grant_access(mike());
+        contract.grant_access(joe());
+        let length_after = contract.account_gives_access.len();
+        assert_eq!(
+            1, length_after,
+            "Expected an entry in the account's access Map."
+        );
+        let predecessor_hash = env::sha256(robert().as_bytes());
+        let num_grantees = contract
+            .account_gives_access
+            .get(&predecessor_hash)
+            .unwrap();
+        assert_eq!(
+            2,
+            num_grantees.len(),
+            "Expected two accounts to have access to predecessor."
+        );
+    }
+
+    #[test]
+    fn access_sets_of_grantors_are_separate() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.grant_access(mike());
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.grant_access(mike());
+        contract.grant_access(robert());
+        assert_eq!(contract.get_access_grantees(robert()), vec![mike()]);
+        assert_eq!(contract.get_access_grantees(joe()), vec![mike(), robert()]);
+
+        contract.revoke_access(mike());
+        testing_env!(get_context(mike(), env::storage_usage()));
+        assert!(contract.check_access(robert()));
+        assert!(!contract.check_access(joe()));
+    }
+
+    #[test]
+    fn repair_legacy_access_sets() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        // two grantors as older versions stored them, sharing one prefix
+        for (grantor, escrow) in [(robert(), mike()), (joe(), robert())].iter() {
+            let mut access_set = UnorderedSet::new(LEGACY_ACCESS_SET_PREFIX.to_vec());
+            access_set.insert(&env::sha256(escrow.as_bytes()));
+            contract
+                .account_gives_access
+                .insert(&env::sha256(grantor.as_bytes()), &access_set);
+        }
+        let robert_hash = env::sha256(robert().as_bytes());
+        // joe's grant overwrote robert's
+        assert_eq!(
+            contract.account_gives_access.get(&robert_hash).unwrap().to_vec(),
+            vec![robert_hash.clone()]
+        );
+
+        contract.repair_access_sets(vec![(robert(), vec![mike()]), (joe(), vec![robert()])]);
+        assert_eq!(contract.get_access_grantees(robert()), vec![mike()]);
+        assert_eq!(contract.get_access_grantees(joe()), vec![robert()]);
+        assert_eq!(contract.get_accounts_granting_access_to(robert()), vec![joe()]);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        assert!(contract.check_access(robert()));
+        assert!(!contract.check_access(joe()));
+    }
+
+    #[test]
+    fn access_grants_listed_both_ways() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.grant_access(mike());
+        contract.grant_access(joe());
+        assert_eq!(contract.get_access_grantees(robert()), vec![mike(), joe()]);
+        assert_eq!(contract.get_accounts_granting_access_to(mike()), vec![robert()]);
+
+        contract.revoke_access(mike());
+        assert_eq!(contract.get_access_grantees(robert()), vec![joe()]);
+        assert!(contract.get_accounts_granting_access_to(mike()).is_empty());
+    }
+
+    #[test]
+    fn legacy_access_grants_indexed() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.grant_access(mike());
+        // as recorded before grants kept plain account IDs
+        contract.access_grantors.remove(&env::sha256(mike().as_bytes()));
+        contract.hashed_account_ids.remove(&env::sha256(mike().as_bytes()));
+        assert!(contract.get_access_grantees(robert()).is_empty());
+        assert!(contract.get_accounts_granting_access_to(mike()).is_empty());
+
+        assert_eq!(contract.index_access_grants(vec![joe(), mike(), robert()]), 1);
+        assert_eq!(contract.get_access_grantees(robert()), vec![mike()]);
+        assert_eq!(contract.get_accounts_granting_access_to(mike()), vec![robert()]);
+        assert_eq!(contract.index_access_grants(vec![robert()]), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Access does not exist."#)]
+    fn revoke_access_and_panic() {
+        let context = get_context(robert(), 0);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.revoke_access(joe());
+    }
+
+    #[test]
+    fn add_revoke_access_and_check() {
+        // Joe grants access to Robert
+        let mut context = get_context(joe(), 0);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(joe(), None, None);
+        contract.grant_access(robert());
+
+        // does Robert have access to Joe's account? Yes.
+        context = get_context(robert(), env::storage_usage());
+        testing_env!(context);
+        let mut robert_has_access = contract.check_access(joe());
+        assert_eq!(
+            true, robert_has_access,
+            "After granting access, check_access call failed."
+        );
+
+        // Joe revokes access from Robert
+        context = get_context(joe(), env::storage_usage());
+        testing_env!(context);
+        contract.revoke_access(robert());
+
+        // does Robert have access to Joe's account? No
+        context = get_context(robert(), env::storage_usage());
+        testing_env!(context);
+        robert_has_access = contract.check_access(joe());
+        assert_eq!(
+            false, robert_has_access,
+            "After revoking access, check_access call failed."
+        );
+    }
+
+    #[test]
+    fn mint_token_get_token_owner() {
+        let context = get_context(robert(), 0);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let owner = contract.get_token_owner(id);
+        assert_eq!(robert(), owner, "Unexpected token owner.");
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attempt to transfer a token with no access."#)]
+    fn transfer_from_with_no_access_should_fail() {
+        // Robert owns the token.
+        // Mike is trying to transfer it to Mike's account without having access.
+        let context = get_context(robert(), 0);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let context = get_context(mike(), 0);
+        testing_env!(context);
+        contract.transfer_from(robert(), mike(), id.clone(), None);
+    }
+
+    #[test]
+    fn transfer_from_with_escrow_access() {
+        // Escrow account: robert.testnet
+        // Owner account: mike.testnet
+        // New owner account: joe.testnet
+        let mut context = get_context(mike(), 0);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(mike(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        // Mike grants access to Robert
+        contract.grant_access(robert());
+
+        // Robert transfers the token to Joe
+        context = get_context(robert(), env::storage_usage());
+        testing_env!(context);
+        contract.transfer_from(mike(), joe(), token_id.clone(), None);
+
+        // Check new owner
+        let owner = contract.get_token_owner(token_id.clone());
+        assert_eq!(
+            joe(),
+            owner,
+            "Token was not transferred after transfer call with escrow."
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attempt to transfer a token from a different owner."#)]
+    fn transfer_from_with_escrow_access_wrong_owner_id() {
+        // Escrow account: robert.testnet
+        // Owner account: mike.testnet
+        // New owner account: joe.testnet
+        let mut context = get_context(mike(), 0);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(mike(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        // Mike grants access to Robert
+        contract.grant_access(robert());
+
+        // Robert transfers the token to Joe
+        context = get_context(robert(), env::storage_usage());
+        testing_env!(context);
+        contract.transfer_from(robert(), joe(), token_id.clone(), None);
+    }
+
+    #[test]
+    fn transfer_from_with_your_own_token() {
+        // Owner account: robert.testnet
+        // New owner account: joe.testnet
+
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+
+        // Robert transfers the token to Joe
+        contract.transfer_from(robert(), joe(), token_id.clone(), None);
+
+        // Check new owner
+        let owner = contract.get_token_owner(token_id.clone());
+        assert_eq!(
+            joe(),
+            owner,
+            "Token was not transferred after transfer call with escrow."
+        );
+    }
+
+    #[test]
+    #[should_panic(
+        expected = r#"Attempt to call transfer on tokens belonging to another account."#
+    )]
+    fn transfer_with_escrow_access_fails() {
+        // Escrow account: robert.testnet
+        // Owner account: mike.testnet
+        // New owner account: joe.testnet
+        let mut context = get_context(mike(), 0);
+        testing_env!(context);
+        let mut contract = Corgi3D::new(mike(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0; // Mike grants access to Robert
+        contract.grant_access(robert());
+
+        // Robert transfers the token to Joe
+        context = get_context(robert(), env::storage_usage());
+        testing_env!(context);
+        contract.transfer(joe(), token_id.clone(), None);
+    }
+
+    #[test]
+    fn transfer_with_your_own_token() {
+        // Owner account: robert.testnet
+        // New owner account: joe.testnet
+
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+
+        // Robert transfers the token to Joe
+        contract.transfer(joe(), token_id.clone(), None);
+
+        // Check new owner
+        let owner = contract.get_token_owner(token_id.clone());
+        assert_eq!(
+            joe(),
+            owner,
+            "Token was not transferred after transfer call with escrow."
+        );
+    }
+
+    #[test]
+    fn delete_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let _token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 1);
+
+        let token_id = mint_revealed(&mut contract,
+            "b".to_string(),
+            "black".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 2);
+
+        contract.delete_corgi(token_id);
+        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 1);
+        assert_eq!(
+            contract.get_corgis_by_owner(robert())[0].name,
+            "a".to_string()
+        );
+    }
+
+    #[test]
+    fn clone_corgi_lowers_rarity() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let clone_id = contract.clone_corgi(token_id);
+        let original = contract.get_corgi(token_id);
+        let clone = contract.get_corgi(clone_id);
+        assert_eq!(original.clone_count, 1);
+        assert_eq!(clone.clone_of, Some(token_id));
+        assert_eq!(clone.color, original.color);
+        assert_eq!(
+            clone.rate,
+            original.rate.lower()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Clone limit reached for this corgi"#)]
+    fn clone_corgi_limit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        for _ in 0..=MAX_CLONES_PER_CORGI {
+            contract.clone_corgi(token_id);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Clone limit reached for this corgi"#)]
+    fn clones_of_clones_count_against_the_root() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mut token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let root_id = token_id;
+        for _ in 0..MAX_CLONES_PER_CORGI {
+            token_id = contract.clone_corgi(token_id);
+            assert_eq!(contract.get_corgi(token_id).clone_of, Some(root_id));
+        }
+        assert_eq!(contract.get_corgi(root_id).clone_count, MAX_CLONES_PER_CORGI);
+        contract.clone_corgi(token_id);
+    }
+
+    #[test]
+    fn partner_mint_within_quota() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_partner(mike(), 1);
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(23);
+        testing_env!(context);
+        let spec = CorgiSpec {
+            name: "a".to_string(),
+            color: "blue".to_string(),
+            background_color: "green".to_string(),
+            quote: "haha".to_string(),
+        };
+        let pending = contract.partner_mint(spec, joe());
+        let id = reveal_next_block(&mut contract, pending.id).token_id.0;
+        assert_eq!(contract.get_token_owner(id), joe());
+        assert_eq!(contract.get_partner(mike()).unwrap().minted, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Partner mint quota exhausted"#)]
+    fn partner_mint_over_quota() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_partner(mike(), 0);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.partner_mint(
+            CorgiSpec {
+                name: "a".to_string(),
+                color: "blue".to_string(),
+                background_color: "green".to_string(),
+                quote: "haha".to_string(),
+            },
+            joe(),
+        );
+    }
+
+    #[test]
+    fn partner_mint_charges_premium_colors() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_partner(mike(), 1);
+        contract.set_premium_color("gold".to_string(), vec![2, 0, 0, 0, 0, 0, 0]);
+        let mut fruit = Fruit::default();
+        fruit.add(APPLE, 3);
+        contract.account_fruit.insert(&mike(), &fruit);
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(23);
+        testing_env!(context);
+        contract.partner_mint(
+            CorgiSpec {
+                name: "a".to_string(),
+                color: "gold".to_string(),
+                background_color: "green".to_string(),
+                quote: "haha".to_string(),
+            },
+            joe(),
+        );
+        assert_eq!(contract.account_fruit(mike()).get(APPLE), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Colors must be hex, a named color or a premium color"#)]
+    fn partner_mint_validates_spec() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.add_partner(mike(), 1);
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.partner_mint(
+            CorgiSpec {
+                name: "a".to_string(),
+                color: "not a color".to_string(),
+                background_color: "green".to_string(),
+                quote: "haha".to_string(),
+            },
+            joe(),
+        );
+    }
+
+    #[test]
+    fn finalize_metadata() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert_eq!(contract.get_corgi(token_id).metadata_frozen, false);
+        contract.finalize_metadata(token_id);
+        assert_eq!(contract.get_corgi(token_id).metadata_frozen, true);
+    }
+
+    /// Signs `voucher` with a fixed test key, returning the key and signature.
+    fn sign_voucher(voucher: &MintVoucher) -> (Base58PublicKey, Base64VecU8) {
+        use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+        let secret = SecretKey::from_bytes(&[7; 32]).unwrap();
+        let public = PublicKey::from(&secret);
+        let keypair = Keypair { secret, public };
+        let message = (env::current_account_id(), voucher.clone()).try_to_vec().unwrap();
+        let mut public_key = vec![0];
+        public_key.extend(public.as_bytes().iter());
+        (
+            Base58PublicKey(public_key),
+            Base64VecU8(keypair.sign(&message).to_bytes().to_vec()),
+        )
+    }
+
+    fn drop_voucher(nonce: u64) -> MintVoucher {
+        MintVoucher {
+            nonce,
+            receiver_id: Some(mike()),
+            spec: CorgiSpec {
+                name: "Drop".to_string(),
+                color: "yellow".to_string(),
+                background_color: "black".to_string(),
+                quote: "limited".to_string(),
+            },
+            rate: Some(Rarity::UltraRare),
+            price: U128(10u128.pow(24)),
+            expires_at: None,
+        }
+    }
+
+    #[test]
+    fn mint_with_voucher_once() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let voucher = drop_voucher(1);
+        let (public_key, signature) = sign_voucher(&voucher);
+        contract.set_minter_key(Some(public_key));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let pending = contract.mint_with_voucher(voucher, signature);
+        let result = reveal_next_block(&mut contract, pending.id);
+        let corgi = contract.get_corgi(result.token_id.0);
+        assert_eq!(corgi.name, "Drop");
+        assert_eq!(corgi.rate, Rarity::UltraRare);
+        assert_eq!(contract.get_token_owner(corgi.id), mike());
+        assert_eq!(contract.get_voucher_mint(1), Some(U64(corgi.id)));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not enough fruit"#)]
+    fn voucher_charges_premium_colors() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_premium_color("gold".to_string(), vec![1, 0, 0, 0, 0, 0, 0]);
+        let mut voucher = drop_voucher(1);
+        voucher.spec.color = "gold".to_string();
+        let (public_key, signature) = sign_voucher(&voucher);
+        contract.set_minter_key(Some(public_key));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.mint_with_voucher(voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Voucher has already been redeemed"#)]
+    fn voucher_cannot_be_replayed() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let voucher = drop_voucher(1);
+        let (public_key, signature) = sign_voucher(&voucher);
+        contract.set_minter_key(Some(public_key));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.mint_with_voucher(voucher.clone(), signature.clone());
+        contract.mint_with_voucher(voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Rarity quota of this season is used up"#)]
+    fn voucher_respects_rarity_quota() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let voucher = drop_voucher(1);
+        let (public_key, signature) = sign_voucher(&voucher);
+        contract.set_minter_key(Some(public_key));
+        contract.set_rarity_quota(Rarity::UltraRare, Some(0));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.mint_with_voucher(voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Invalid voucher signature"#)]
+    fn tampered_voucher_is_refused() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mut voucher = drop_voucher(1);
+        let (public_key, signature) = sign_voucher(&voucher);
+        contract.set_minter_key(Some(public_key));
+        voucher.price = U128(0);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.mint_with_voucher(voucher, signature);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi is locked"#)]
+    fn locked_corgi_cannot_be_transferred() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.lock_corgi(token_id, None);
+        assert!(contract.get_corgi(token_id).locked.is_some());
+        contract.transfer(mike(), token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi is locked for its minimum duration"#)]
+    fn lock_duration_delays_unlock() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.lock_corgi(token_id, None);
+        contract.unlock_corgi(token_id);
+        contract.delete_corgi(token_id);
+        let token_id = mint_revealed(&mut contract,
+            "b".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.lock_corgi(token_id, Some(1));
+        contract.unlock_corgi(token_id);
+    }
+
+    #[test]
+    fn customize_corgi_charges_fee() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let treasury = contract.treasury_balance;
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.rename_corgi(token_id, "Biscuit".to_string());
+        contract.set_quote(token_id, "woof".to_string());
+        contract.set_colors(token_id, "red".to_string(), "white".to_string());
+        let corgi = contract.get_corgi(token_id);
+        assert_eq!(corgi.name, "Biscuit");
+        assert_eq!(corgi.quote, "woof");
+        assert_eq!((corgi.color.as_str(), corgi.background_color.as_str()), ("red", "white"));
+        assert_eq!(contract.treasury_balance, treasury + 3 * DEFAULT_CUSTOMIZATION_FEE);
+        let count = |key: &str| contract.trait_counts.get(&key.to_string());
+        assert_eq!(count("color:#0000ff"), None);
+        assert_eq!(count("color:#ff0000"), Some(1));
+        assert_eq!(count("background_color:#ffffff"), Some(1));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Corgi metadata is finalized"#)]
+    fn finalized_corgi_cannot_be_renamed() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.finalize_metadata(token_id);
+        contract.rename_corgi(token_id, "Biscuit".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Only the owner can finalize corgi metadata"#)]
+    fn finalize_metadata_not_owner() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.finalize_metadata(token_id);
+    }
+
+    #[test]
+    fn burn_corgi_awards_fruit_or_mint_credit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let minted: Vec<MintResult> = (0..2)
+            .map(|_| {
+                mint_revealed(
+                    &mut contract,
+                    "a".to_string(),
+                    "blue".to_string(),
+                    "green".to_string(),
+                    "haha".to_string(),
+                )
+            })
+            .collect();
+        let (fruit_corgi, credit_corgi) = (&minted[0], &minted[1]);
+
+        contract.burn_corgi(fruit_corgi.token_id.0, Some(BurnReward::Fruit));
+        let per_kind = rarity_tier(fruit_corgi.rate) * BURN_FRUIT_PER_TIER;
+        assert_eq!(contract.account_fruit(robert()).get(0), per_kind);
+        assert_eq!(contract.get_global_stats().total_burned, 1);
+
+        contract.burn_corgi(credit_corgi.token_id.0, None);
+        let credit =
+            MINT_PRICE * rarity_tier(credit_corgi.rate) as u128 * BURN_CREDIT_BPS_PER_TIER / 10_000;
+        assert_eq!(contract.get_mint_credit(robert()).0, credit);
+        assert_eq!(contract.nft_total_supply(), U128(0));
+
+        let treasury = contract.get_treasury_balance().0;
+        let pending = contract.create_corgi(
+            "b".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        assert_eq!(pending.fee.0, MINT_PRICE - credit);
+        assert_eq!(contract.get_treasury_balance().0, treasury + MINT_PRICE - credit);
+        assert_eq!(contract.get_mint_credit(robert()).0, 0);
+    }
+
+    #[test]
+    fn redeem_for_merch_burns_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let redemption_id = contract.redeem_for_merch(token_id, "3f2a9c".to_string());
+        assert!(!contract.corgi_exists(token_id));
+        assert!(!contract.get_redemptions(0, 10)[0].fulfilled);
+
+        contract.mark_fulfilled(redemption_id);
+        let redemption = &contract.get_redemptions(0, 10)[0];
+        assert!(redemption.fulfilled);
+        assert_eq!(redemption.token_id, token_id);
+    }
+
+    #[test]
+    fn transfer_with_memo() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.storage_deposit(None, None);
+        contract.transfer_with_message(
+            joe(),
+            token_id,
+            "hi".to_string(),
+            Some("exchange-tx-42".to_string()),
+        );
+        assert_eq!(contract.get_token_owner(token_id), joe());
+        assert_eq!(contract.get_corgi(token_id).message, "hi".to_string());
+    }
+
+    #[test]
+    fn mint_gate_requires_registration_delay() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_mint_gate(Some(MintGate {
+            delay_blocks: 5,
+            max_mints_per_block: 1,
+        }));
+        assert!(!contract.can_mint(robert()));
+        contract.register();
+        assert!(!contract.can_mint(robert()));
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_index = 5;
+        testing_env!(context);
+        assert!(contract.can_mint(robert()));
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        assert!(!contract.can_mint(robert()));
+    }
+
+    #[test]
+    fn create_corgi_returns_receipt() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let result = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        let corgi = contract.get_corgi(result.token_id.0);
+        assert_eq!(result.name, "a".to_string());
+        assert_eq!(result.rate, corgi.rate);
+        assert_eq!(result.sausage, corgi.sausage);
+        assert!(result.storage_used.0 > 0);
+        assert_eq!(
+            result.charged.0,
+            MINT_PRICE + result.storage_used.0 as u128 * env::storage_byte_cost()
+        );
+
+        let (name, pending_id) = contract.create_corgi_legacy(
+            "b".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        assert_eq!(name, "b".to_string());
+        let token_id = reveal_next_block(&mut contract, pending_id).token_id.0;
+        assert_eq!(contract.get_corgi(token_id).name, name);
+        assert_eq!(contract.get_api_version(), API_VERSION);
+    }
+
+    #[test]
+    fn nft_token_json_shape() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let token = contract.nft_token(token_id).unwrap();
+        assert_eq!(token.token_id, token_id.to_string());
+        assert_eq!(token.owner_id, robert());
+        assert_eq!(token.metadata.title, Some("a".to_string()));
+        assert!(token.approved_account_ids.is_empty());
+        assert!(contract.nft_token(token_id + 1).is_none());
+    }
+
+    #[test]
+    fn batch_mint_and_transfer() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let spec = CorgiSpec {
+            name: "a".to_string(),
+            color: "blue".to_string(),
+            background_color: "green".to_string(),
+            quote: "haha".to_string(),
+        };
+        let pending = contract.create_corgis_batch(vec![spec.clone(), spec]);
+        assert_eq!(pending.len(), 2);
+        assert_eq!(contract.get_pending_mints(robert()).len(), 2);
+        assert_eq!(contract.get_treasury_balance().0, 2 * MINT_PRICE);
+        let results: Vec<MintResult> = pending
+            .iter()
+            .map(|pending| reveal_next_block(&mut contract, pending.id))
+            .collect();
+        assert!(results.iter().all(|result| result.charged.0 > MINT_PRICE));
+        assert!(contract.get_pending_mints(robert()).is_empty());
+
+        let token_ids: Vec<TokenId> = results.iter().map(|result| result.token_id.0).collect();
+        contract.transfer_batch(mike(), token_ids.clone(), None);
+        assert_eq!(contract.get_corgis_by_owner_range(mike(), 0, 10).len(), 2);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.grant_access(joe());
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.transfer_from_batch(mike(), robert(), token_ids, None);
+        assert_eq!(contract.get_corgis_by_owner_range(robert(), 0, 10).len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "INVALID_NAME: Name must be 1 to 64 bytes")]
+    fn blank_name_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.create_corgi(" ".to_string(), "blue".to_string(), "green".to_string(), "haha".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "QUOTE_TOO_LONG")]
+    fn long_quote_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let quote = "a".repeat(MAX_QUOTE_LENGTH + 1);
+        contract.create_corgi("a".to_string(), "blue".to_string(), "green".to_string(), quote);
+    }
+
+    #[test]
+    #[should_panic(expected = "UNKNOWN_COLOR")]
+    fn unknown_color_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.create_corgi("a".to_string(), "#ab".to_string(), "green".to_string(), "haha".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "INVALID_COLOR_LENGTH")]
+    fn long_color_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let color = "b".repeat(MAX_COLOR_LENGTH + 1);
+        contract.create_corgi("a".to_string(), color, "green".to_string(), "haha".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "INVALID_ACCOUNT_ID")]
+    fn transfer_to_invalid_account_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(
+            &mut contract,
+            "a".to_string(),
+            "#FFD700".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        )
+        .token_id
+        .0;
+        contract.transfer("Not An Account".to_string(), token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "NOT_TOKEN_OWNER")]
+    fn transfer_of_other_owners_corgi_rejected() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(
+            &mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        )
+        .token_id
+        .0;
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.transfer(joe(), token_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Mint can be revealed from the next block on")]
+    fn mint_cannot_be_revealed_in_its_block() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let pending = contract.create_corgi(
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        assert_eq!(contract.get_pending_mint(pending.id).unwrap().minter, robert());
+        assert_eq!(contract.nft_total_supply(), U128(0));
+        contract.reveal_corgi(pending.id);
+    }
+
+    #[test]
+    fn mint_and_reveal_without_storage_deposit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let spec = CorgiSpec {
+            name: "a".repeat(MAX_CORGI_NAME_LENGTH),
+            color: "blue".to_string(),
+            background_color: "green".to_string(),
+            quote: "q".repeat(MAX_QUOTE_LENGTH),
+        };
+        // the longest account ID takes the most storage
+        let minter = format!("{}.testnet", "m".repeat(56));
+        let mut context = get_context(minter.clone(), env::storage_usage());
+        context.attached_deposit = contract.get_mint_payload(spec.clone()).deposit.0;
+        testing_env!(context);
+        assert!(contract.storage_balance_of(minter.clone()).is_none());
+        let pending = contract.create_corgi(spec.name, spec.color, spec.background_color, spec.quote);
+        let result = reveal_next_block(&mut contract, pending.id);
+        assert!(result.storage_used.0 as u128 * env::storage_byte_cost() <= pending.storage_reserve.0);
+        assert_eq!(contract.get_token_owner(result.token_id.0), minter);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Attempt to call transfer on tokens belonging to another account."#)]
+    fn batch_transfer_is_all_or_nothing() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mut token_ids = vec![];
+        for name in &["a", "b"] {
+            token_ids.push(mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            ).token_id.0);
+        }
+        contract.transfer(joe(), token_ids[1], None);
+        contract.transfer_batch(mike(), token_ids, None);
+    }
+
+    #[test]
+    fn nft_enumeration() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for _ in 0..3 {
+            mint_revealed(&mut contract,
+                "a".to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        contract.transfer(mike(), 0, None);
+        assert_eq!(contract.nft_total_supply(), U128(3));
+        assert_eq!(contract.nft_supply_for_owner(robert()), U128(2));
+        assert_eq!(contract.nft_supply_for_owner(joe()), U128(0));
+        assert_eq!(contract.nft_tokens(None, None).len(), 3);
+        assert_eq!(contract.nft_tokens(Some(U128(1)), Some(1)).len(), 1);
+        let tokens = contract.nft_tokens_for_owner(mike(), None, None);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].token_id, "0".to_string());
+        assert_eq!(tokens[0].owner_id, mike());
+    }
+
+    #[test]
+    fn nft_metadata_describes_collection() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_icon(Some("data:image/svg+xml,<svg/>".to_string()));
+        let metadata = contract.nft_metadata();
+        assert_eq!(metadata.spec, "nft-1.0.0");
+        assert_eq!(metadata.name, "Corgi3D");
+        assert_eq!(metadata.symbol, "CORGI");
+        assert_eq!(metadata.icon, Some("data:image/svg+xml,<svg/>".to_string()));
+        assert_eq!(metadata.base_uri, None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Message can't be changed again today"#)]
+    fn set_corgi_message_rate_limited() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        for i in 0..MESSAGE_UPDATES_PER_DAY {
+            contract.set_corgi_message(token_id, format!("hi {}", i));
+        }
+        assert_eq!(contract.get_corgi(token_id).message, "hi 2".to_string());
+        contract.set_corgi_message(token_id, "again".to_string());
+    }
+
+    #[test]
+    fn edition_guarantees_rarity_and_colors() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let edition_id = contract.add_edition(Edition {
+            name: "gold".to_string(),
+            price: U128(3 * 10u128.pow(24)),
+            max_supply: 5,
+            minted: 0,
+            min_rarity: Some(Rarity::VeryRare),
+            color: Some("#ffd700".to_string()),
+            background_color: None,
+        });
+        for i in 0..5 {
+            let mut context = get_context(robert(), env::storage_usage());
+            context.random_seed = vec![i; 32];
+            // the price plus storage
+            context.attached_deposit = 4 * 10u128.pow(24);
+            testing_env!(context);
+            let pending = contract.create_corgi_from_edition(
+                edition_id,
+                "a".to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+            let result = reveal_next_block(&mut contract, pending.id);
+            assert!(rarity_tier(result.rate) >= rarity_tier(Rarity::VeryRare));
+            assert_eq!(contract.get_corgi(result.token_id.0).color, "#ffd700");
+        }
+        assert_eq!(contract.get_edition(edition_id).unwrap().minted, 5);
+    }
+
+    #[test]
+    fn gift_claimed_with_secret() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let gift_id = contract.send_gift(token_id, to_hex(&env::sha256(b"open sesame")));
+        assert_eq!(contract.get_token_owner(token_id), "alice.testnet");
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        assert_eq!(contract.claim_gift(gift_id, "open sesame".to_string()), token_id);
+        assert_eq!(contract.get_token_owner(token_id), mike());
+        assert!(contract.get_gift(gift_id).is_none());
+    }
+
+    #[test]
+    fn addressed_gift_accepted_with_message() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let gift_id = contract.send_gift_to(token_id, mike(), "happy birthday".to_string());
+        assert_eq!(contract.get_gifts_for(mike()).len(), 1);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        assert_eq!(contract.accept_gift(gift_id), token_id);
+        let corgi = contract.get_corgi(token_id);
+        assert_eq!(contract.get_token_owner(token_id), mike());
+        assert_eq!(corgi.sender, robert());
+        assert_eq!(corgi.message, "happy birthday".to_string());
+        assert!(contract.get_gifts_for(mike()).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Gift is addressed to another account"#)]
+    fn addressed_gift_not_accepted_by_others() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let gift_id = contract.send_gift_to(token_id, mike(), "hi".to_string());
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.accept_gift(gift_id);
+    }
+
+    #[test]
+    fn expired_gift_swept_back() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let gift_id = contract.send_gift(token_id, to_hex(&env::sha256(b"open sesame")));
+        assert_eq!(contract.sweep_expired_gifts(10), 0);
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.block_timestamp = DEFAULT_GIFT_EXPIRY;
+        testing_env!(context);
+        assert_eq!(contract.sweep_expired_gifts(10), 1);
+        assert_eq!(contract.get_token_owner(token_id), robert());
+        assert!(contract.get_gift(gift_id).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Gift can't be reclaimed before it expires"#)]
+    fn gift_not_reclaimable_early() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let gift_id = contract.send_gift(token_id, to_hex(&env::sha256(b"open sesame")));
+        contract.reclaim_gift(gift_id);
+    }
+
+    #[test]
+    fn set_extra_charges_deposit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let deposited = contract.deposit().0;
+        contract.set_extra(token_id, r#"{"level":7}"#.to_string());
+        let charged = deposited - contract.get_deposit(robert()).0;
+        assert!(charged > 0);
+        assert_eq!(contract.get_render_spec(token_id).unwrap().extra, Some(json!({"level": 7})));
+
+        contract.set_extra(token_id, "".to_string());
+        assert_eq!(contract.get_deposit(robert()).0, deposited);
+        assert!(contract.get_render_spec(token_id).unwrap().extra.is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Extra data must be valid JSON"#)]
+    fn set_extra_rejects_invalid_json() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.deposit();
+        contract.set_extra(token_id, "{level".to_string());
+    }
+
+    /// Transfers `token_id` of a fresh contract through one entry point and
+    /// returns the resulting ownership and message.
+    fn transfer_outcome(transfer: impl Fn(&mut Corgi3D, TokenId)) -> (Vec<(U64, AccountId)>, String, u64) {
+        // start each outcome from empty storage
+        env::take_blockchain_interface();
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        transfer(&mut contract, token_id);
+        (
+            contract.get_ownership_pairs(0, 10),
+            contract.get_corgi(token_id).message,
+            contract.selling_corgis.len(),
+        )
+    }
+
+    #[test]
+    fn nep4_and_nep171_transfers_match() {
+        let nep4 = transfer_outcome(|contract, token_id| {
+            contract.transfer(joe(), token_id, None)
+        });
+        let nep171 = transfer_outcome(|contract, token_id| {
+            let mut context = get_context(robert(), env::storage_usage());
+            context.attached_deposit = 1;
+            testing_env!(context);
+            contract.nft_transfer(joe(), token_id.to_string(), None, None)
+        });
+        let delegated = transfer_outcome(|contract, token_id| {
+            contract.transfer_from(robert(), joe(), token_id, None)
+        });
+        assert_eq!(nep4, nep171);
+        assert_eq!(nep4, delegated);
+        assert_eq!(nep4, (vec![(U64(0), joe())], "".to_string(), 0));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Message is too long"#)]
+    fn transfer_checks_message_length() {
+        transfer_outcome(|contract, token_id| {
+            contract.transfer_with_message(joe(), token_id, "x".repeat(MAX_MESSAGE_LENGTH + 1), None)
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Requires attached deposit of exactly 1 yoctoNEAR"#)]
+    fn nft_transfer_requires_one_yocto() {
+        transfer_outcome(|contract, token_id| {
+            contract.nft_transfer(joe(), token_id.to_string(), None, None)
+        });
+    }
+
+    #[test]
+    fn nft_transfer_call_moves_corgi() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        contract.nft_transfer_call(joe(), token_id.to_string(), None, None, "".to_string());
+        assert_eq!(contract.get_token_owner(token_id), joe());
+    }
+
+    #[test]
+    fn burn_notifies_registry() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.set_burn_registry(Some("burns.testnet".to_string()));
+        assert_eq!(contract.get_burn_registry(), Some("burns.testnet".to_string()));
+        contract.delete_corgi(token_id);
+        assert!(!contract.corgi_exists(token_id));
+    }
+
+    #[test]
+    fn exhausted_rarity_quota_degrades() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_rarity_quota(Rarity::UltraRare, Some(0));
+        contract.set_rarity_quota(Rarity::VeryRare, Some(0));
+        for _ in 0..20 {
+            let (rarity, _) = contract.generate_rate_sausage_at_least(Rarity::Rare);
+            assert_eq!(rarity, Rarity::Rare);
+            contract.next_corgi_id += 1;
+        }
+        let quotas = contract.get_rarity_quotas();
+        assert_eq!(quotas[1].rarity, Rarity::UltraRare);
+        assert_eq!(quotas[1].remaining, Some(0));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Rarity quota of this season is used up"#)]
+    fn edition_minimum_respects_quota() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_rarity_quota(Rarity::UltraRare, Some(0));
+        contract.set_rarity_quota(Rarity::VeryRare, Some(0));
+        contract.generate_rate_sausage_at_least(Rarity::VeryRare);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Rarity quota of this season is used up"#)]
+    fn edition_mint_reserves_quota_until_revealed() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_rarity_quota(Rarity::UltraRare, Some(0));
+        contract.set_rarity_quota(Rarity::VeryRare, Some(1));
+        let edition_id = contract.add_edition(Edition {
+            name: "gold".to_string(),
+            price: U128(10u128.pow(24)),
+            max_supply: 5,
+            minted: 0,
+            min_rarity: Some(Rarity::VeryRare),
+            color: None,
+            background_color: None,
+        });
+        let mint = |contract: &mut Corgi3D| {
+            contract.create_corgi_from_edition(
+                edition_id,
+                "a".to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            )
+        };
+        let pending = mint(&mut contract);
+        assert_eq!(contract.get_rarity_quotas()[0].reserved, 1);
+        assert_eq!(contract.get_rarity_quotas()[0].remaining, Some(0));
+        let result = reveal_next_block(&mut contract, pending.id);
+        assert_eq!(result.rate, Rarity::VeryRare);
+        assert_eq!(contract.get_rarity_quotas()[0].reserved, 0);
+        mint(&mut contract);
+    }
+
+    #[test]
+    fn flash_sale_discounts_mints_up_to_the_limit() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.create_flash_sale(FlashSale {
+            id: 0,
+            discount_bps: 5_000,
+            starts_at: U64(0),
+            ends_at: U64(100),
+            per_account_limit: 1,
+            token_ids: vec![],
+        });
+        let half_price = MINT_PRICE / 2;
+        assert_eq!(contract.get_flash_price(None, robert()), U128(half_price));
+
+        let result = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        assert_eq!(
+            result.charged.0,
+            half_price + result.storage_used.0 as u128 * env::storage_byte_cost()
+        );
+        assert_eq!(contract.get_flash_price(None, robert()), U128(MINT_PRICE));
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.block_timestamp = 100;
+        testing_env!(context);
+        assert_eq!(contract.get_flash_price(None, mike()), U128(MINT_PRICE));
+        assert!(contract.get_flash_sales().is_empty());
+    }
+
+
+    #[test]
+    #[should_panic(expected = r#"Corgi was purchased too recently to be deleted"#)]
+    fn recently_purchased_corgi_cannot_be_deleted() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.set_burn_grace_period(24);
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+        contract.delete_corgi(token_id);
+    }
+
+    #[test]
+    fn purchased_corgi_can_be_deleted_after_grace_period() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.set_burn_grace_period(24);
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+        let mut context = get_context(mike(), env::storage_usage());
+        context.block_timestamp = 24 * 60 * 60 * 1_000_000_000;
+        testing_env!(context);
+        contract.delete_corgi(token_id);
+        assert!(!contract.corgi_exists(token_id));
+    }
+
+}
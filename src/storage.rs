@@ -0,0 +1,860 @@
+use crate::*;
+
+/// Escrow ledger, account merges and storage accounting.
+#[near_bindgen]
+impl Corgi3D {
+    /// First step of merging the caller's account into `target_account`.
+    /// Nothing moves until the target confirms with `accept_merge`.
+    pub fn merge_accounts_into(&mut self, target_account: AccountId) {
+        assert!(
+            env::is_valid_account_id(target_account.as_bytes()),
+            "Target account ID is invalid."
+        );
+        let predecessor = env::predecessor_account_id();
+        if predecessor == target_account {
+            env::panic(b"Cannot merge an account into itself");
+        }
+        self.pending_merges.insert(&predecessor, &target_account);
+    }
+
+    pub fn cancel_merge(&mut self) {
+        let predecessor = env::predecessor_account_id();
+        if self.pending_merges.remove(&predecessor).is_none() {
+            env::panic(b"No pending merge");
+        }
+    }
+
+    /// Second step, called by the target account: moves the fruit, escrow
+    /// deposit, staking rewards and mint credits of `source_account` into the
+    /// caller, along with up to `limit` (at most `MAX_BATCH_SIZE`) of its corgis.
+    /// Corgis that can't be transferred right now, e.g. locked or staked ones,
+    /// are skipped and stay with the source. Call again until the report is
+    /// complete; the merge stays pending until then.
+    pub fn accept_merge(&mut self, source_account: AccountId, limit: u64) -> MergeReport {
+        let target = env::predecessor_account_id();
+        match self.pending_merges.get(&source_account) {
+            Some(pending_target) if pending_target == target => {}
+            _ => env::panic(b"No pending merge into this account"),
+        }
+        let limit = std::cmp::min(limit, MAX_BATCH_SIZE as u64);
+
+        let source_hash = env::sha256(source_account.as_bytes());
+        let corgi_ids = self
+            .account_corgis
+            .get(&source_hash)
+            .map_or(vec![], |corgi_ids| corgi_ids.to_vec());
+        let mut moved = vec![];
+        let mut skipped = vec![];
+        let mut complete = true;
+        for id in corgi_ids {
+            if moved.len() as u64 >= limit {
+                complete = false;
+                break;
+            }
+            if self.transfer_blocker(id).is_some() {
+                skipped.push(id);
+                continue;
+            }
+            self.internal_transfer(id, source_account.clone(), target.clone());
+            moved.push(id);
+        }
+        if !moved.is_empty() {
+            emit_transfer_batch(&source_account, &target, &moved, None, Some("account merge".to_string()));
+        }
+
+        if let Some(source_fruit) = self.account_fruit.remove(&source_account) {
+            let mut target_fruit = self.account_fruit(target.clone());
+            target_fruit.add_all(&source_fruit);
+            self.account_fruit.insert(&target, &target_fruit);
+            self.fruit_transferred.add_all(&source_fruit);
+        }
+        if let Some(deposit) = self.deposits.remove(&source_account) {
+            self.credit_deposit(&target, deposit);
+        }
+        if let Some(rewards) = self.staking_rewards.remove(&source_account) {
+            let target_rewards = self.staking_rewards.get(&target).unwrap_or(0);
+            self.staking_rewards.insert(&target, &(target_rewards + rewards));
+        }
+        if let Some(credits) = self.mint_credits.remove(&source_account) {
+            let target_credits = self.mint_credits.get(&target).unwrap_or(0);
+            self.mint_credits.insert(&target, &(target_credits + credits));
+        }
+        if complete {
+            self.pending_merges.remove(&source_account);
+            self.account_maze_game.remove(&source_account);
+        }
+        MergeReport {
+            moved: moved.len() as u64,
+            skipped,
+            complete,
+        }
+    }
+
+    pub fn get_pending_merge(&self, account_id: AccountId) -> Option<AccountId> {
+        self.pending_merges.get(&account_id)
+    }
+
+    /// Adds the attached deposit to the caller's escrow balance.
+    #[payable]
+    pub fn deposit(&mut self) -> U128 {
+        let predecessor = env::predecessor_account_id();
+        U128(self.credit_deposit(&predecessor, env::attached_deposit()))
+    }
+
+    pub fn withdraw(&mut self, amount: U128) -> Promise {
+        let predecessor = env::predecessor_account_id();
+        self.debit_deposit(&predecessor, amount.0);
+        self.pay_out(predecessor, amount.0)
+    }
+
+    /// Prunes lapsed records of tokens with serials `from..from + limit`: ended transfer
+    /// cooldowns and rentals, approvals left on burned tokens, ended auctions, which
+    /// are settled like `finalize_auction`, and offers that can't be accepted
+    /// anymore, on burned corgis or by their owner, which are refunded to the
+    /// bidder's escrow. Anyone can call it and is paid the keeper bounty per
+    /// pruned record.
+    pub fn cleanup_expired(&mut self, from: u64, limit: u64) -> CleanupReport {
+        let now = env::block_timestamp();
+        let end = std::cmp::min(from.saturating_add(limit), self.next_corgi_id);
+        let mut pruned = 0;
+        for serial in from..end {
+            let token_id = self.token_id_for(serial);
+            if let Some(locked_until) = self.transfer_locks.get(&token_id) {
+                if locked_until <= now {
+                    self.transfer_locks.remove(&token_id);
+                    pruned += 1;
+                }
+            }
+            if let Some(rental) = self.rentals.get(&token_id) {
+                if rental.ends_at.0 <= now {
+                    self.remove_rental(token_id);
+                    pruned += 1;
+                }
+            }
+            if !self.corgi_exists(token_id) && self.token_approvals.remove(&token_id).is_some() {
+                pruned += 1;
+            }
+            if self.auctions.get(&token_id).is_some_and(|auction| auction.ends_at <= now) {
+                self.settle_auction(token_id);
+                pruned += 1;
+            }
+            let owner = self.corgi_to_account.get(&token_id);
+            for offer in self.offers.get(&token_id).unwrap_or_default() {
+                if owner.is_none() || owner.as_ref() == Some(&offer.bidder) {
+                    self.remove_offer(token_id, &offer.bidder);
+                    self.credit_deposit(&offer.bidder, offer.price.0);
+                    pruned += 1;
+                }
+            }
+        }
+        self.pay_keeper_bounty(pruned);
+        CleanupReport {
+            pruned,
+            next_from: if end >= self.next_corgi_id { 0 } else { end },
+        }
+    }
+
+    /// Indexes access grants made before grants were recorded with plain account
+    /// IDs, so they show up in `get_access_grantees` and
+    /// `get_accounts_granting_access_to`. Takes the IDs of granting accounts and
+    /// of their escrows; IDs that don't hash to a recorded grant are ignored, so
+    /// anyone can call it. Returns how many grants were newly indexed.
+    pub fn index_access_grants(&mut self, account_ids: Vec<AccountId>) -> u64 {
+        let mut indexed = 0;
+        for account_id in account_ids.iter() {
+            let account_hash = env::sha256(account_id.as_bytes());
+            let escrow_hashes = match self.account_gives_access.get(&account_hash) {
+                Some(access_set) => access_set.to_vec(),
+                None => continue,
+            };
+            self.hashed_account_ids.insert(&account_hash, account_id);
+            for escrow_hash in escrow_hashes {
+                let mut grantors = self.access_grantors.get(&escrow_hash).unwrap_or_default();
+                if !grantors.contains(&account_hash) {
+                    grantors.push(account_hash.clone());
+                    self.access_grantors.insert(&escrow_hash, &grantors);
+                    indexed += 1;
+                }
+            }
+        }
+        // escrows are known once a grant to them is indexed
+        for account_id in account_ids.iter() {
+            let account_hash = env::sha256(account_id.as_bytes());
+            if self.access_grantors.contains_key(&account_hash) {
+                self.hashed_account_ids.insert(&account_hash, account_id);
+            }
+        }
+        indexed
+    }
+
+    /// Removes empty per-account corgi sets left behind by older versions, which
+    /// kept them after an account's last corgi left. Scans `limit` entries of the
+    /// index from `from`; anyone can call it and is paid the keeper bounty per
+    /// removed set.
+    pub fn sweep_empty_accounts(&mut self, from: u64, limit: u64) -> CleanupReport {
+        let mut index = from;
+        let mut end = std::cmp::min(from.saturating_add(limit), self.account_corgis.len());
+        let mut pruned = 0;
+        while index < end {
+            let account_hash = self.account_corgis.keys_as_vector().get(index).unwrap();
+            if self.account_corgis.values_as_vector().get(index).unwrap().is_empty() {
+                // the last entry is swapped into `index`, so look at it next
+                self.account_corgis.remove(&account_hash);
+                pruned += 1;
+                end -= 1;
+            } else {
+                index += 1;
+            }
+        }
+        self.pay_keeper_bounty(pruned);
+        CleanupReport {
+            pruned,
+            next_from: if end >= self.account_corgis.len() { 0 } else { end },
+        }
+    }
+
+    /// Credits a payout whose transfer failed, e.g. to a deleted account, to the
+    /// recipient's escrow balance so it can be withdrawn later. Returns whether
+    /// the transfer went through.
+    #[private]
+    pub fn on_payout_resolved(&mut self, account_id: AccountId, amount: U128) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+        env::log(format!("Payout of {} to {} failed, credited to escrow", amount.0, account_id).as_bytes());
+        self.credit_deposit(&account_id, amount.0);
+        false
+    }
+
+    pub fn get_deposit(&self, account_id: AccountId) -> U128 {
+        U128(self.deposits.get(&account_id).unwrap_or(0))
+    }
+
+    /// Credits fungible tokens whose transfer failed to the recipient's token
+    /// balance, like `on_payout_resolved`.
+    #[private]
+    pub fn on_ft_payout_resolved(&mut self, account_id: AccountId, ft_id: AccountId, amount: U128) -> bool {
+        if let PromiseResult::Successful(_) = env::promise_result(0) {
+            return true;
+        }
+        env::log(format!("Payout of {} {} to {} failed, credited to token balance", amount.0, ft_id, account_id).as_bytes());
+        self.credit_ft(&account_id, &ft_id, amount.0);
+        false
+    }
+
+    /// Withdraws `amount` of the caller's `ft_id` balance, all of it if omitted.
+    pub fn withdraw_ft(&mut self, ft_id: AccountId, amount: Option<U128>) -> Promise {
+        let predecessor = env::predecessor_account_id();
+        let key = (predecessor.clone(), ft_id.clone());
+        let balance = self.ft_balances.get(&key).unwrap_or(0);
+        let amount = amount.map_or(balance, |amount| amount.0);
+        if amount == 0 || balance < amount {
+            env::panic(b"Not enough token balance");
+        }
+        if balance == amount {
+            self.ft_balances.remove(&key);
+        } else {
+            self.ft_balances.insert(&key, &(balance - amount));
+        }
+        self.ft_pay_out(ft_id, predecessor, amount)
+    }
+
+    /// Fungible tokens held for `account_id`, from failed payouts or FT mints.
+    pub fn get_ft_balance(&self, account_id: AccountId, ft_id: AccountId) -> U128 {
+        U128(self.ft_balances.get(&(account_id, ft_id)).unwrap_or(0))
+    }
+
+    /// Lets `spender` (e.g. a battle or competition contract) charge up to `amount`
+    /// from the caller's escrow balance. Zero revokes the allowance.
+    pub fn approve_spender(&mut self, spender: AccountId, amount: U128) {
+        assert!(
+            env::is_valid_account_id(spender.as_bytes()),
+            "Spender's account ID is invalid."
+        );
+        let key = (env::predecessor_account_id(), spender);
+        if amount.0 == 0 {
+            self.allowances.remove(&key);
+        } else {
+            self.allowances.insert(&key, &amount.0);
+        }
+    }
+
+    pub fn get_allowance(&self, owner_id: AccountId, spender: AccountId) -> U128 {
+        U128(self.allowances.get(&(owner_id, spender)).unwrap_or(0))
+    }
+
+    /// Called by an approved spender to move `amount` from the owner's escrow
+    /// balance into its own. Returns the remaining allowance.
+    pub fn spend_deposit(&mut self, owner_id: AccountId, amount: U128) -> U128 {
+        let spender = env::predecessor_account_id();
+        let key = (owner_id.clone(), spender.clone());
+        let allowance = self.allowances.get(&key).unwrap_or(0);
+        if allowance < amount.0 {
+            env::panic(b"Not enough allowance");
+        }
+        self.debit_deposit(&owner_id, amount.0);
+        self.credit_deposit(&spender, amount.0);
+        let allowance = allowance - amount.0;
+        if allowance == 0 {
+            self.allowances.remove(&key);
+        } else {
+            self.allowances.insert(&key, &allowance);
+        }
+        U128(allowance)
+    }
+
+    /// Estimates storage used by the account's corgis, access grants, fruit and
+    /// marketplace entries (listed corgis and wishlist), with its cost in yoctoNEAR.
+    pub fn get_storage_report(&self, account_id: AccountId) -> StorageReport {
+        let account_hash = env::sha256(account_id.as_bytes());
+        let mut corgis = 0;
+        let mut listings = 0;
+        if let Some(corgi_ids) = self.account_corgis.get(&account_hash) {
+            corgis += account_hash.len() as u64 + STORAGE_ENTRY_OVERHEAD;
+            for id in corgi_ids.iter() {
+                let corgi = self.corgis.get(&id).unwrap();
+                let corgi_bytes = corgi.try_to_vec().unwrap().len() as u64;
+                // corgis, corgi_to_account and account_corgis entries
+                corgis += corgi_bytes + account_id.len() as u64 + 3 * (8 + STORAGE_ENTRY_OVERHEAD);
+                // the corgi itself is counted above, a listing only adds its
+                // selling_corgis entries and whatever currency, start and
+                // deposit it was listed with
+                if corgi.selling {
+                    listings += 2 * (8 + STORAGE_ENTRY_OVERHEAD) + 8;
+                    if let Some(currency) = self.listing_currencies.get(&id) {
+                        listings += 8 + currency.try_to_vec().unwrap().len() as u64 + STORAGE_ENTRY_OVERHEAD;
+                    }
+                    if self.listing_starts.get(&id).is_some() {
+                        listings += 16 + STORAGE_ENTRY_OVERHEAD;
+                    }
+                }
+                if let Some(deposit) = self.listing_deposits.get(&id) {
+                    listings += 8 + deposit.try_to_vec().unwrap().len() as u64 + STORAGE_ENTRY_OVERHEAD;
+                }
+            }
+        }
+        let access = self
+            .account_gives_access
+            .get(&account_hash)
+            .map_or(0, |set| {
+                STORAGE_ENTRY_OVERHEAD + set.len() * (account_hash.len() as u64 + STORAGE_ENTRY_OVERHEAD)
+            });
+        let fruit = self.account_fruit.get(&account_id).map_or(0, |fruit| {
+            fruit.try_to_vec().unwrap().len() as u64 + account_id.len() as u64 + STORAGE_ENTRY_OVERHEAD
+        });
+        listings += self.wishlists.get(&account_id).map_or(0, |wishlist| {
+            wishlist.try_to_vec().unwrap().len() as u64 + STORAGE_ENTRY_OVERHEAD
+        });
+        let total = corgis + access + fruit + listings;
+        StorageReport {
+            corgis,
+            access,
+            fruit,
+            listings,
+            total,
+            storage_cost: U128(total as u128 * env::storage_byte_cost()),
+        }
+    }
+}
+
+#[near_bindgen]
+impl NEP145 for Corgi3D {
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        assert!(
+            env::is_valid_account_id(account_id.as_bytes()),
+            "Account ID is invalid."
+        );
+        let amount = env::attached_deposit();
+        if registration_only.unwrap_or(false) {
+            if amount > 0 {
+                self.pay_out(env::predecessor_account_id(), amount);
+            }
+        } else {
+            self.credit_deposit(&account_id, amount);
+        }
+        storage_balance(self.deposits.get(&account_id).unwrap_or(0))
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        if env::attached_deposit() != 1 {
+            env::panic(b"Requires attached deposit of exactly 1 yoctoNEAR");
+        }
+        let predecessor = env::predecessor_account_id();
+        let balance = self.deposits.get(&predecessor).unwrap_or(0);
+        let amount = amount.map_or(balance, |amount| amount.0);
+        let left = self.debit_deposit(&predecessor, amount);
+        if amount > 0 {
+            self.pay_out(predecessor, amount);
+        }
+        storage_balance(left)
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.deposits.get(&account_id).map(storage_balance)
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(0),
+            max: None,
+        }
+    }
+}
+
+// Helper methods
+impl Corgi3D {
+    /// Charges the storage used since `storage_before` to `account`, out of
+    /// `available` first and its storage balance for the rest. Returns what is
+    /// left of `available`.
+    pub(crate) fn charge_storage(
+        &mut self,
+        account: &AccountId,
+        storage_before: u64,
+        available: Balance,
+    ) -> Balance {
+        let used = env::storage_usage().saturating_sub(storage_before);
+        self.charge(account, used as u128 * env::storage_byte_cost(), available)
+    }
+
+    /// Charges `cost` to `account` out of `available` first and its storage
+    /// balance for the rest. Returns what is left of `available`.
+    pub(crate) fn charge(&mut self, account: &AccountId, cost: Balance, available: Balance) -> Balance {
+        if cost <= available {
+            return available - cost;
+        }
+        self.debit_deposit(account, cost - available);
+        0
+    }
+
+    /// Sends `amount` to `account`, falling back to its escrow balance if the
+    /// transfer fails. Every NEAR payout goes through here.
+    pub(crate) fn pay_out(&self, account: AccountId, amount: Balance) -> Promise {
+        Promise::new(account.clone()).transfer(amount).then(ext_self::on_payout_resolved(
+            account,
+            U128(amount),
+            &env::current_account_id(),
+            0,
+            PAYOUT_CALLBACK_GAS,
+        ))
+    }
+
+    /// Sends `amount` of `ft_id` to `account`, falling back to its token balance.
+    pub(crate) fn ft_pay_out(&self, ft_id: AccountId, account: AccountId, amount: Balance) -> Promise {
+        ext_ft::ft_transfer(account.clone(), U128(amount), None, &ft_id, 1, FT_TRANSFER_GAS).then(
+            ext_self::on_ft_payout_resolved(
+                account,
+                ft_id,
+                U128(amount),
+                &env::current_account_id(),
+                0,
+                PAYOUT_CALLBACK_GAS,
+            ),
+        )
+    }
+
+    pub(crate) fn credit_ft(&mut self, account: &AccountId, ft_id: &AccountId, amount: Balance) {
+        let key = (account.clone(), ft_id.clone());
+        let balance = self.ft_balances.get(&key).unwrap_or(0) + amount;
+        self.ft_balances.insert(&key, &balance);
+    }
+
+    /// Takes back up to `amount` of what `credit_ft` credited to `account`.
+    pub(crate) fn debit_ft(&mut self, account: &AccountId, ft_id: &AccountId, amount: Balance) {
+        let key = (account.clone(), ft_id.clone());
+        let balance = self.ft_balances.get(&key).unwrap_or(0).saturating_sub(amount);
+        if balance == 0 {
+            self.ft_balances.remove(&key);
+        } else {
+            self.ft_balances.insert(&key, &balance);
+        }
+    }
+
+    /// Credits `amount` to the escrow balance of `account`, returning the new balance.
+    pub(crate) fn credit_deposit(&mut self, account: &AccountId, amount: Balance) -> Balance {
+        let balance = self.deposits.get(account).unwrap_or(0) + amount;
+        self.deposits.insert(account, &balance);
+        balance
+    }
+
+    /// Debits `amount` from the escrow balance of `account`, returning the new balance.
+    pub(crate) fn debit_deposit(&mut self, account: &AccountId, amount: Balance) -> Balance {
+        let balance = self.deposits.get(account).unwrap_or(0);
+        if balance < amount {
+            env::panic(b"Not enough deposited balance");
+        }
+        let balance = balance - amount;
+        if balance == 0 {
+            self.deposits.remove(account);
+        } else {
+            self.deposits.insert(account, &balance);
+        }
+        balance
+    }
+
+    pub(crate) fn delete_corgi_from_account(&mut self, id: TokenId, account: AccountId) {
+        self.update_kennel_stats(&account, id, false);
+        self.leave_showcase(&account, id);
+        self.update_held_votes(&account, id, false);
+        self.corgi_to_account.remove(&id);
+        self.ownership_changes += 1;
+        let account_hash = env::sha256(account.as_bytes());
+        let mut account_corgis = self.account_corgis.get(&account_hash).unwrap();
+        account_corgis.remove(&id);
+        self.update_top_holders(&account, account_corgis.len());
+        if account_corgis.is_empty() {
+            self.account_corgis.remove(&account_hash);
+        } else {
+            self.account_corgis.insert(&account_hash, &account_corgis);
+        }
+    }
+
+    /// Whether `account` can't receive another corgi under `max_corgis_per_account`.
+    pub(crate) fn at_holding_cap(&self, account: &AccountId) -> bool {
+        let max = match self.max_corgis_per_account {
+            Some(max) => max,
+            None => return false,
+        };
+        let held = self
+            .account_corgis
+            .get(&env::sha256(account.as_bytes()))
+            .map_or(0, |corgis| corgis.len());
+        held >= max && *account != env::current_account_id() && !self.cap_exempt.contains(account)
+    }
+
+    /// Re-ranks `account` in the holder leaderboard after its corgi count changed
+    /// to `count`. Accounts only enter or move when their own count changes.
+    pub(crate) fn update_top_holders(&mut self, account: &AccountId, count: u64) {
+        if *account == env::current_account_id() {
+            return;
+        }
+        self.top_holders.retain(|(holder, _)| holder != account);
+        if count == 0 {
+            return;
+        }
+        let position = self
+            .top_holders
+            .iter()
+            .position(|(_, held)| *held < count)
+            .unwrap_or(self.top_holders.len());
+        if position < MAX_LEADERBOARD_SIZE {
+            self.top_holders.insert(position, (account.clone(), count));
+            self.top_holders.truncate(MAX_LEADERBOARD_SIZE);
+        }
+    }
+
+    pub(crate) fn save_corgi_to_account(&mut self, id: TokenId, account: AccountId) {
+        let account_hash = env::sha256(account.as_bytes());
+
+        self.corgi_to_account.insert(&id, &account);
+        self.ownership_changes += 1;
+        let mut account_corgis = self.account_corgis.get(&account_hash).unwrap_or_else(|| {
+            let mut prefix = Vec::with_capacity(33);
+            prefix.push(b'u');
+            prefix.extend(account_hash.clone());
+            UnorderedSet::new(prefix)
+        });
+        if self.at_holding_cap(&account) {
+            env::panic(b"Account holds the maximum number of corgis");
+        }
+        account_corgis.insert(&id);
+        self.update_top_holders(&account, account_corgis.len());
+        self.account_corgis.insert(&account_hash, &account_corgis);
+        self.update_kennel_stats(&account, id, true);
+        self.update_held_votes(&account, id, true);
+    }
+}
+
+fn storage_balance(balance: Balance) -> StorageBalance {
+    StorageBalance {
+        total: U128(balance),
+        available: U128(balance),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn merge_accounts() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        contract.merge_accounts_into(mike());
+        assert_eq!(contract.get_pending_merge(robert()), Some(mike()));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let report = contract.accept_merge(robert(), 10);
+        assert_eq!(report.moved, 1);
+        assert!(report.complete);
+        assert_eq!(contract.get_corgis_by_owner(mike()).len(), 1);
+        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 0);
+        assert_eq!(contract.get_pending_merge(robert()), None);
+    }
+
+    #[test]
+    fn merge_pages_corgis_and_moves_balances() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mut ids = vec![];
+        for name in &["a", "b", "c"] {
+            ids.push(mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            ).token_id.0);
+        }
+        contract.lock_corgi(ids[0], Some(1));
+        contract.deposit();
+        contract.merge_accounts_into(mike());
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let report = contract.accept_merge(robert(), 1);
+        assert_eq!(report.moved, 1);
+        assert!(!report.complete);
+        assert_eq!(contract.get_deposit(mike()).0, 3 * 10u128.pow(24));
+        assert_eq!(contract.get_deposit(robert()).0, 0);
+        assert_eq!(contract.get_pending_merge(robert()), Some(mike()));
+
+        let report = contract.accept_merge(robert(), 10);
+        assert_eq!(report.moved, 1);
+        assert_eq!(report.skipped, vec![ids[0]]);
+        assert!(report.complete);
+        assert_eq!(contract.get_corgis_by_owner(mike()).len(), 2);
+        assert_eq!(contract.get_token_owner(ids[0]), robert());
+        assert_eq!(contract.get_pending_merge(robert()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = r#"No pending merge into this account"#)]
+    fn accept_merge_without_initiation() {
+        testing_env!(get_context(mike(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.accept_merge(robert(), 10);
+    }
+
+    #[test]
+    fn deposit_and_withdraw() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.deposit();
+        assert_eq!(contract.get_deposit(robert()).0, 3 * 10u128.pow(24));
+        contract.withdraw(U128(10u128.pow(24)));
+        assert_eq!(contract.get_deposit(robert()).0, 2 * 10u128.pow(24));
+    }
+
+    #[test]
+    fn mint_storage_paid_from_storage_balance() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        assert_eq!(contract.storage_balance_of(robert()), None);
+        contract.storage_deposit(None, None);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = MINT_PRICE;
+        testing_env!(context);
+        let storage_before = env::storage_usage();
+        let pending = contract.create_corgi(
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        let pending_cost = (env::storage_usage() - storage_before) as u128 * env::storage_byte_cost();
+        let balance = contract.storage_balance_of(robert()).unwrap();
+        assert_eq!(
+            balance.available.0,
+            3 * 10u128.pow(24) - pending_cost - pending.storage_reserve.0
+        );
+
+        let result = reveal_next_block(&mut contract, pending.id);
+        let storage_cost = result.storage_used.0 as u128 * env::storage_byte_cost();
+        assert_eq!(result.charged.0, MINT_PRICE + storage_cost);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.attached_deposit = 1;
+        testing_env!(context);
+        let balance = contract.storage_withdraw(None);
+        assert_eq!(balance.total, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not enough deposited balance"#)]
+    fn withdraw_more_than_deposited() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.withdraw(U128(1));
+    }
+
+    #[test]
+    fn spend_deposit_with_allowance() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.deposit();
+        contract.approve_spender(mike(), U128(10u128.pow(24)));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        let remaining = contract.spend_deposit(robert(), U128(4 * 10u128.pow(23)));
+        assert_eq!(remaining.0, 6 * 10u128.pow(23));
+        assert_eq!(contract.get_deposit(mike()).0, 4 * 10u128.pow(23));
+        assert_eq!(contract.get_deposit(robert()).0, 26 * 10u128.pow(23));
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Not enough allowance"#)]
+    fn spend_deposit_over_allowance() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.deposit();
+        contract.approve_spender(mike(), U128(1));
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.spend_deposit(robert(), U128(2));
+    }
+
+    #[test]
+    fn storage_report() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        assert_eq!(contract.get_storage_report(robert()).total, 0);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let report = contract.get_storage_report(robert());
+        assert!(report.corgis > 0);
+        assert_eq!(report.access, 0);
+        assert_eq!(report.total, report.corgis);
+        assert_eq!(
+            report.storage_cost.0,
+            report.total as u128 * env::storage_byte_cost()
+        );
+
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        let listed = contract.get_storage_report(robert());
+        assert_eq!(listed.corgis, report.corgis);
+        assert!(listed.listings > 0 && listed.listings < report.corgis);
+        assert_eq!(listed.total, listed.corgis + listed.listings);
+    }
+
+    #[test]
+    fn cleanup_expired_prunes_and_pays() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.set_transfer_cooldown(1);
+        for name in &["a", "b", "c", "d", "e", "f"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        let treasury = contract.get_treasury_balance().0;
+
+        let mut context = get_context(mike(), env::storage_usage());
+        context.block_timestamp = 60 * 60 * 1_000_000_000;
+        testing_env!(context);
+        // too small a batch for the keeper bounty
+        let report = contract.cleanup_expired(0, 1);
+        assert_eq!(report.pruned, 1);
+        assert_eq!(report.next_from, 1);
+        assert_eq!(contract.get_treasury_balance().0, treasury);
+        let report = contract.cleanup_expired(report.next_from, 10);
+        assert_eq!(report.pruned, MIN_KEEPER_BATCH);
+        assert_eq!(report.next_from, 0);
+        assert!(contract.transfer_locks.get(&1).is_none());
+        assert_eq!(
+            contract.get_treasury_balance().0,
+            treasury - MIN_KEEPER_BATCH as u128 * DEFAULT_KEEPER_BOUNTY
+        );
+    }
+
+    #[test]
+    fn cleanup_expired_settles_auctions_and_refunds_stale_offers() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let mut token_ids = vec![];
+        for name in &["a", "b"] {
+            token_ids.push(mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            ).token_id.0);
+        }
+        contract.start_auction(token_ids[0], 1, BidIncrement::Absolute(U128(1)), None, None);
+        let mut context = get_context(mike(), env::storage_usage());
+        context.attached_deposit = 10u128.pow(24);
+        testing_env!(context);
+        contract.make_offer(token_ids[1]);
+        testing_env!(get_context(robert(), env::storage_usage()));
+        contract.delete_corgi(token_ids[1]);
+
+        let mut context = get_context(joe(), env::storage_usage());
+        context.block_timestamp = 60 * 60 * 1_000_000_000;
+        testing_env!(context);
+        let report = contract.cleanup_expired(0, 10);
+        assert_eq!(report.pruned, 2);
+        assert!(contract.get_auction(token_ids[0]).is_none());
+        assert_eq!(contract.get_token_owner(token_ids[0]), robert());
+        assert!(contract.get_offers_by_account(mike()).is_empty());
+        assert_eq!(contract.get_deposit(mike()), U128(10u128.pow(24)));
+    }
+
+    #[test]
+    fn last_corgi_leaving_frees_account_set() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.transfer(mike(), token_id, None);
+        assert!(contract.account_corgis.get(&env::sha256(robert().as_bytes())).is_none());
+        assert!(contract.get_corgis_by_owner_range(robert(), 0, 10).is_empty());
+        assert_eq!(contract.account_corgis.len(), 1);
+    }
+
+    #[test]
+    fn sweep_empty_accounts_removes_leftovers() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        for (i, account) in [mike(), joe()].iter().enumerate() {
+            let hash = env::sha256(account.as_bytes());
+            let mut prefix = vec![b'x', i as u8];
+            prefix.extend(hash.clone());
+            contract.account_corgis.insert(&hash, &UnorderedSet::new(prefix));
+        }
+        let report = contract.sweep_empty_accounts(0, 10);
+        assert_eq!(report.pruned, 2);
+        assert_eq!(report.next_from, 0);
+        assert_eq!(contract.account_corgis.len(), 1);
+    }
+}
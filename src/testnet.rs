@@ -0,0 +1,67 @@
+use crate::*;
+
+/// Minimum time between two faucet mints of the same account.
+const FAUCET_INTERVAL: u64 = 60 * 60 * 1_000_000_000;
+
+/// Testnet-only helpers, compiled only with the `testnet` feature.
+#[near_bindgen]
+impl Corgi3D {
+    /// Mints a random corgi to the caller for free, at most once per
+    /// `FAUCET_INTERVAL`. Does not go through the treasury or the mint gate.
+    pub fn faucet_mint(&mut self) -> TokenId {
+        let predecessor = env::predecessor_account_id();
+        // Kept out of the contract struct so mainnet state has no trace of the faucet
+        let mut claims: LookupMap<AccountId, u64> = LookupMap::new(b"faucet-claims".to_vec());
+        let now = env::block_timestamp();
+        if let Some(claimed_at) = claims.get(&predecessor) {
+            if now < claimed_at + FAUCET_INTERVAL {
+                env::panic(b"Faucet can be used once per hour");
+            }
+        }
+        claims.insert(&predecessor, &now);
+        let spec = CorgiSpec {
+            name: format!("Faucet corgi {}", self.next_corgi_id),
+            color: "#fbbf24".to_string(),
+            background_color: "#ffffff".to_string(),
+            quote: "Woof from the faucet".to_string(),
+        };
+        self.assert_valid_spec(&spec);
+        let (rate, sausage) = self.generate_rate_sausage();
+        let corgi = self.mint_corgi(predecessor.clone(), spec, rate, sausage);
+        emit_mint(&predecessor, corgi.id, "faucet");
+        corgi.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn faucet_mint_once_per_interval() {
+        let mut context = get_context(robert(), 0);
+        context.attached_deposit = 0;
+        testing_env!(context);
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = contract.faucet_mint();
+        assert_eq!(contract.get_token_owner(token_id), robert());
+        assert_eq!(contract.get_treasury_balance().0, 0);
+
+        let mut context = get_context(robert(), env::storage_usage());
+        context.block_timestamp = FAUCET_INTERVAL;
+        testing_env!(context);
+        contract.faucet_mint();
+    }
+
+    #[test]
+    #[should_panic(expected = r#"Faucet can be used once per hour"#)]
+    fn faucet_mint_rate_limited() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        contract.faucet_mint();
+        contract.faucet_mint();
+    }
+}
@@ -0,0 +1,927 @@
+use crate::*;
+
+/// Read-only views of corgis and their ownership.
+#[near_bindgen]
+impl Corgi3D {
+    pub fn get_api_version(&self) -> u32 {
+        API_VERSION
+    }
+
+    /// Deprecated: only the first `MAX_VIEW_LIMIT` corgis, use `get_corgis_by_owner_range`.
+    pub fn get_corgis_by_owner(&self, owner: AccountId) -> Vec<Corgi> {
+        log_deprecated("get_corgis_by_owner", "get_corgis_by_owner_range");
+        let count = self.nft_supply_for_owner(owner.clone()).0 as u64;
+        self.get_corgis_by_owner_range(owner, 0, count)
+    }
+
+    /// Escrows `account_id` gave access to. Grants from before escrow IDs were
+    /// recorded are left out until indexed with `index_access_grants`.
+    pub fn get_access_grantees(&self, account_id: AccountId) -> Vec<AccountId> {
+        self.account_gives_access
+            .get(&env::sha256(account_id.as_bytes()))
+            .map_or(vec![], |access_set| {
+                access_set
+                    .iter()
+                    .filter_map(|escrow_hash| self.hashed_account_ids.get(&escrow_hash))
+                    .collect()
+            })
+    }
+
+    /// Accounts that gave `escrow_id` access to their corgis, with the same caveat
+    /// as `get_access_grantees`.
+    pub fn get_accounts_granting_access_to(&self, escrow_id: AccountId) -> Vec<AccountId> {
+        self.access_grantors
+            .get(&env::sha256(escrow_id.as_bytes()))
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|account_hash| self.hashed_account_ids.get(account_hash))
+            .collect()
+    }
+
+    pub fn get_corgis_by_owner_range(
+        &self,
+        owner: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<Corgi> {
+        let limit = view_limit(limit);
+        let hash = env::sha256(owner.as_bytes());
+        let corgi_ids = match self.account_corgis.get(&hash) {
+            Some(corgi_ids) => corgi_ids,
+            None => return vec![],
+        };
+        let corgi_ids_vec = corgi_ids.as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), corgi_ids.len()))
+            .filter_map(|index| {
+                corgi_ids_vec
+                    .get(index)
+                    .map(|corgi_id| self.corgis.get(&corgi_id).unwrap())
+            })
+            .collect()
+    }
+
+    pub fn get_corgi(&self, id: TokenId) -> Corgi {
+        self.corgis.get(&id).unwrap_or_else(|| ContractError::CorgiNotFound.panic())
+    }
+
+    pub fn get_corgi_level(&self, token_id: TokenId) -> Option<u64> {
+        self.corgis.get(&token_id).map(|corgi| corgi_level(&corgi))
+    }
+
+    /// All hidden trait slots of a corgi, with values only for unlocked ones.
+    /// Empty for unknown corgis.
+    pub fn get_hidden_traits(&self, token_id: TokenId) -> Vec<HiddenTrait> {
+        let corgi = match self.corgis.get(&token_id) {
+            Some(corgi) => corgi,
+            None => return vec![],
+        };
+        HIDDEN_TRAITS
+            .iter()
+            .enumerate()
+            .map(|(slot, (name, _))| HiddenTrait {
+                slot: slot as u8,
+                name: name.to_string(),
+                value: if corgi.unlocked_traits & (1 << slot) != 0 {
+                    Some(hidden_trait_value(token_id, slot).to_string())
+                } else {
+                    None
+                },
+            })
+            .collect()
+    }
+
+    // Cheap existence checks that avoid loading full corgi structs
+
+    pub fn corgi_exists(&self, token_id: TokenId) -> bool {
+        self.corgi_to_account.get(&token_id).is_some()
+    }
+
+    pub fn is_selling(&self, token_id: TokenId) -> bool {
+        self.selling_corgis.contains(&token_id)
+    }
+
+    pub fn owns(&self, account_id: AccountId, token_id: TokenId) -> bool {
+        self.corgi_to_account.get(&token_id) == Some(account_id)
+    }
+
+    /// Raw `(token, owner)` pairs in storage order, for indexers bootstrapping ownership.
+    pub fn get_ownership_pairs(&self, from_index: u64, limit: u64) -> Vec<(U64, AccountId)> {
+        let limit = view_limit(limit);
+        let token_ids = self.corgi_to_account.keys_as_vector();
+        let owners = self.corgi_to_account.values_as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), token_ids.len()))
+            .map(|index| (U64(token_ids.get(index).unwrap()), owners.get(index).unwrap()))
+            .collect()
+    }
+
+    /// The homepage carousel with each corgi's owner, level and rarity score.
+    pub fn get_featured(&self) -> Vec<FeaturedCorgi> {
+        self.featured
+            .iter()
+            .map(|&token_id| {
+                let corgi = self.get_corgi(token_id);
+                FeaturedCorgi {
+                    owner_id: self.get_token_owner(token_id),
+                    level: corgi_level(&corgi),
+                    rarity_score: self.rarity_score(&corgi),
+                    corgi,
+                }
+            })
+            .collect()
+    }
+
+    pub fn get_transfer_payload(&self, token_id: TokenId, receiver: AccountId) -> Option<CallPayload> {
+        if !self.corgi_exists(token_id) {
+            return None;
+        }
+        Some(call_payload(
+            env::current_account_id(),
+            "transfer",
+            json!({ "new_owner_id": receiver, "token_id": token_id, "memo": null }),
+            0,
+            SUGGESTED_CALL_GAS,
+        ))
+    }
+
+    /// Buys a NEAR listing with `buy_corgi`, or a token-priced one through the
+    /// token's `ft_transfer_call`. `None` unless the corgi is for sale.
+    pub fn get_buy_payload(&self, token_id: TokenId) -> Option<CallPayload> {
+        let corgi = self.corgis.get(&token_id).filter(|corgi| corgi.selling)?;
+        let payload = match self.listing_currencies.get(&token_id).unwrap_or(Currency::Near) {
+            Currency::Near => call_payload(
+                env::current_account_id(),
+                "buy_corgi",
+                json!({ "id": token_id, "memo": null }),
+                corgi.selling_price.0,
+                SUGGESTED_BUY_GAS,
+            ),
+            Currency::Ft(ft_id) => call_payload(
+                ft_id,
+                "ft_transfer_call",
+                json!({
+                    "receiver_id": env::current_account_id(),
+                    "amount": corgi.selling_price,
+                    "memo": null,
+                    "msg": token_id.to_string(),
+                }),
+                1,
+                SUGGESTED_FT_BUY_GAS,
+            ),
+        };
+        Some(payload)
+    }
+
+    pub fn get_sell_payload(&self, token_id: TokenId, price: U128) -> Option<CallPayload> {
+        if !self.corgi_exists(token_id) {
+            return None;
+        }
+        Some(call_payload(
+            env::current_account_id(),
+            "sell_corgi",
+            json!({ "id": token_id, "price": price, "memo": null, "currency": null }),
+            0,
+            SUGGESTED_CALL_GAS,
+        ))
+    }
+
+    pub fn get_mint_payload(&self, spec: CorgiSpec) -> CallPayload {
+        let storage = pending_mint_storage_estimate(&spec);
+        call_payload(
+            env::current_account_id(),
+            "create_corgi",
+            json!({
+                "name": spec.name,
+                "color": spec.color,
+                "background_color": spec.background_color,
+                "quote": spec.quote,
+            }),
+            self.public_mint_price() + storage as u128 * env::storage_byte_cost(),
+            SUGGESTED_CALL_GAS,
+        )
+    }
+
+    /// SHA-256 over the same page `get_ownership_pairs` returns, so mirrors can
+    /// compare pages instead of downloading them. Each pair is encoded as the
+    /// little-endian token ID, the owner's length as one byte, then the owner.
+    pub fn state_checksum(&self, from: u64, limit: u64) -> StateChecksum {
+        let pairs = self.get_ownership_pairs(from, limit);
+        StateChecksum {
+            from,
+            count: pairs.len() as u64,
+            total: self.corgi_to_account.len(),
+            checksum: ownership_checksum(&pairs),
+        }
+    }
+
+    /// Display name and localization key of every rarity tier.
+    pub fn get_rarity_names(&self) -> Vec<RarityDisplay> {
+        Rarity::ALL
+            .iter()
+            .map(|&rarity| RarityDisplay {
+                rarity,
+                name: self
+                    .rarity_names
+                    .get(&rarity)
+                    .unwrap_or_else(|| rarity.default_name().to_string()),
+                localization_key: rarity.localization_key(),
+            })
+            .collect()
+    }
+
+    /// Sum over the corgi's traits of `population / corgis with that trait`, in
+    /// hundredths. Rarer trait combinations score higher.
+    pub fn get_rarity_score(&self, token_id: TokenId) -> Option<u64> {
+        self.corgis.get(&token_id).map(|corgi| self.rarity_score(&corgi))
+    }
+
+    /// Corgis ranked by rarity score, highest first. Scores are as of each
+    /// corgi's last mint or trait change; see `refresh_rarity_ranking`.
+    pub fn get_rarity_ranking(&self, from_index: u64, limit: u64) -> Vec<(U64, u64)> {
+        self.rarity_ranking
+            .iter()
+            .skip(from_index as usize)
+            .take(view_limit(limit) as usize)
+            .map(|((inverted_score, token_id), ())| (U64(token_id), u64::MAX - inverted_score))
+            .collect()
+    }
+
+    pub fn get_render_spec(&self, token_id: TokenId) -> Option<RenderSpec> {
+        let corgi = self.corgis.get(&token_id)?;
+        Some(RenderSpec {
+            spec_version: RENDER_SPEC_VERSION,
+            token_id,
+            name: corgi.name,
+            color: normalize_color(&corgi.color),
+            background_color: normalize_color(&corgi.background_color),
+            rarity: corgi.rate,
+            sausage: corgi.sausage.parse().unwrap_or(0),
+            accessories: corgi.accessories,
+            extra: self
+                .extras
+                .get(&token_id)
+                .and_then(|extra| serde_json::from_str(&extra).ok()),
+        })
+    }
+
+    /// Ancestor tree of `token_id` up to `depth` generations (capped).
+    pub fn get_pedigree(&self, token_id: TokenId, depth: u64) -> Option<PedigreeNode> {
+        let corgi = self.corgis.get(&token_id)?;
+        Some(self.pedigree_node(
+            token_id,
+            Some(corgi),
+            std::cmp::min(depth, MAX_PEDIGREE_DEPTH),
+        ))
+    }
+
+    /// Deprecated: only the first `MAX_VIEW_LIMIT` corgis, use `display_global_corgis_range`.
+    pub fn display_global_corgis(&self) -> Vec<Corgi> {
+        log_deprecated("display_global_corgis", "display_global_corgis_range");
+        self.display_global_corgis_range(0, self.corgis.len())
+    }
+
+    /// Dry run of `action` for UIs to pre-validate transactions: the NEAR it
+    /// would cost, or the error message the real call would panic with.
+    /// Nothing is changed.
+    pub fn simulate(&self, action: SimulatedAction) -> Simulation {
+        match self.check_action(action) {
+            Ok(cost) => Simulation {
+                ok: true,
+                cost: U128(cost),
+                error: None,
+            },
+            Err(error) => Simulation {
+                ok: false,
+                cost: U128(0),
+                error: Some(error.to_string()),
+            },
+        }
+    }
+
+    /// Pages over existing corgis only, so burned IDs don't cost gas. Burns move
+    /// the last corgi into the freed index.
+    pub fn display_global_corgis_range(&self, from_index: u64, limit: u64) -> Vec<Corgi> {
+        let limit = view_limit(limit);
+        let corgis = self.corgis.values_as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), corgis.len()))
+            .filter_map(|index| corgis.get(index))
+            .collect()
+    }
+
+    /// Number of corgis in existence, the bound for `display_global_corgis_range`.
+    pub fn get_corgi_count(&self) -> u64 {
+        self.corgis.len()
+    }
+
+    pub fn get_corgis_for_sale(&self, from_index: u64, limit: u64) -> Vec<Corgi> {
+        let limit = view_limit(limit);
+        let token_ids = self.selling_corgis.as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), token_ids.len()))
+            .filter_map(|index| token_ids.get(index))
+            .filter_map(|token_id| self.corgis.get(&token_id))
+            .collect()
+    }
+
+    pub fn get_corgis_by_rarity(&self, rate: Rarity, from_index: u64, limit: u64) -> Vec<Corgi> {
+        let limit = view_limit(limit);
+        let token_ids = match self.rarity_corgis.get(&rate) {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+        let token_ids = token_ids.as_vector();
+        (from_index..std::cmp::min(from_index.saturating_add(limit), token_ids.len()))
+            .filter_map(|index| token_ids.get(index))
+            .filter_map(|token_id| self.corgis.get(&token_id))
+            .collect()
+    }
+
+    /// Owner changes of the corgi with index `from_index` onwards, oldest first.
+    /// Indexes keep increasing; entries older than the last
+    /// `MAX_PROVENANCE_ENTRIES` are gone.
+    pub fn get_corgi_history(&self, token_id: TokenId, from_index: u64, limit: u64) -> Vec<ProvenanceEntry> {
+        let limit = view_limit(limit);
+        let log = match self.provenance.get(&token_id) {
+            Some(log) => log,
+            None => return vec![],
+        };
+        let oldest = log.recorded.saturating_sub(MAX_PROVENANCE_ENTRIES);
+        let from_index = std::cmp::max(from_index, oldest);
+        (from_index..std::cmp::min(from_index.saturating_add(limit), log.recorded))
+            .filter_map(|index| log.entries.get(index % MAX_PROVENANCE_ENTRIES))
+            .collect()
+    }
+
+    pub fn get_global_stats(&self) -> GlobalStats {
+        GlobalStats {
+            total_minted: self.total_minted,
+            total_burned: self.total_burned,
+            total_supply: self.corgis.len(),
+            rarity_counts: Rarity::ALL
+                .iter()
+                .map(|&rate| (rate, self.rarity_corgis.get(&rate).map_or(0, |ids| ids.len())))
+                .collect(),
+            highest_sale_price: U128(self.highest_sale_price),
+        }
+    }
+
+    /// Accounts holding the most corgis, most first. The contract's own custody
+    /// isn't ranked.
+    pub fn get_leaderboard(&self, limit: u64) -> Vec<(AccountId, u64)> {
+        let limit = view_limit(limit);
+        self.top_holders.iter().take(limit as usize).cloned().collect()
+    }
+
+    pub fn get_id_era(&self) -> u32 {
+        self.id_era
+    }
+
+    pub fn get_token_id_parts(&self, token_id: TokenId) -> TokenIdParts {
+        TokenIdParts {
+            token_id,
+            token_id_str: token_id.to_string(),
+            era: (token_id >> TOKEN_SERIAL_BITS) as u32,
+            serial: token_id & ((1 << TOKEN_SERIAL_BITS) - 1),
+        }
+    }
+
+    /// The ID the next corgi minted here will get.
+    pub fn get_next_token_id(&self) -> TokenIdParts {
+        self.get_token_id_parts(self.token_id_for(self.next_corgi_id))
+    }
+}
+
+// Helper methods
+impl Corgi3D {
+    /// The checks of the real call behind `action`, returning the NEAR it costs.
+    fn check_action(&self, action: SimulatedAction) -> Result<Balance, &'static str> {
+        match action {
+            SimulatedAction::Buy { token_id, buyer, deposit } => {
+                let corgi = self.corgis.get(&token_id).ok_or("Corgi not found")?;
+                let seller = self.get_token_owner(token_id);
+                if !corgi.selling {
+                    return Err("Corgi is not for sale");
+                }
+                if self.listing_currency(token_id) != Currency::Near {
+                    return Err("Corgi is priced in a fungible token, buy it with ft_transfer_call");
+                }
+                if self.get_listing_start(token_id).is_some() {
+                    return Err("Listing has not started yet");
+                }
+                let price = self.get_flash_price(Some(token_id), buyer.clone()).0;
+                if deposit.0 < price {
+                    return Err("Don't pay enough money to buy corgi");
+                }
+                self.check_ownership_change(token_id, &seller, &buyer)?;
+                Ok(deposit.0)
+            }
+            SimulatedAction::Transfer { token_id, sender, receiver } => {
+                if self.corgis.get(&token_id).is_none() {
+                    return Err("Corgi not found");
+                }
+                let owner = self.get_token_owner(token_id);
+                if sender != owner {
+                    let access = self
+                        .account_gives_access
+                        .get(&env::sha256(owner.as_bytes()))
+                        .is_some_and(|access| access.contains(&env::sha256(sender.as_bytes())));
+                    if !access && !self.is_approved_for(token_id, &sender) {
+                        return Err("Attempt to transfer a token with no access.");
+                    }
+                }
+                self.check_ownership_change(token_id, &owner, &receiver)?;
+                Ok(0)
+            }
+            SimulatedAction::Mint { account_id, deposit } => {
+                let fee = self.get_flash_price(None, account_id.clone()).0;
+                if deposit.0 < fee {
+                    return Err("Attached deposit must cover the mint fee");
+                }
+                if let Some(reason) = self.drop_blocker(&account_id, 1) {
+                    return Err(reason);
+                }
+                if self.mint_gate.is_some() && !self.can_mint(account_id.clone()) {
+                    return Err("Account is not registered long enough or exceeded mints in this block");
+                }
+                if self.at_holding_cap(&account_id) {
+                    return Err("Account holds the maximum number of corgis");
+                }
+                Ok(fee)
+            }
+        }
+    }
+
+    /// Checks `internal_transfer` makes when moving `token_id` from `owner` to `receiver`.
+    fn check_ownership_change(
+        &self,
+        token_id: TokenId,
+        owner: &AccountId,
+        receiver: &AccountId,
+    ) -> Result<(), &'static str> {
+        if *owner != env::current_account_id() {
+            if let Some(reason) = self.transfer_blocker(token_id) {
+                return Err(reason);
+            }
+        }
+        if self.at_holding_cap(receiver) {
+            return Err("Account holds the maximum number of corgis");
+        }
+        Ok(())
+    }
+
+    /// `get_rarity_score` of an already loaded corgi.
+    pub(crate) fn rarity_score(&self, corgi: &Corgi) -> u64 {
+        let population = self.corgis.len();
+        corgi_traits(corgi)
+            .iter()
+            .map(|key| population * 100 / self.trait_counts.get(key).unwrap_or(1))
+            .sum()
+    }
+
+    fn pedigree_node(&self, token_id: TokenId, corgi: Option<Corgi>, depth: u64) -> PedigreeNode {
+        match corgi {
+            Some(corgi) => PedigreeNode {
+                token_id,
+                parents: if depth == 0 {
+                    vec![]
+                } else {
+                    corgi
+                        .parents
+                        .iter()
+                        .map(|&parent| self.pedigree_node(parent, self.corgis.get(&parent), depth - 1))
+                        .collect()
+                },
+                name: Some(corgi.name),
+                rate: Some(corgi.rate),
+            },
+            None => PedigreeNode {
+                token_id,
+                name: None,
+                rate: None,
+                parents: vec![],
+            },
+        }
+    }
+}
+
+fn ownership_checksum(pairs: &[(U64, AccountId)]) -> String {
+    let mut data = vec![];
+    for (token_id, owner) in pairs {
+        data.extend_from_slice(&token_id.0.to_le_bytes());
+        data.push(owner.len() as u8);
+        data.extend_from_slice(owner.as_bytes());
+    }
+    to_hex(&env::sha256(&data))
+}
+
+fn call_payload(
+    receiver_id: AccountId,
+    method_name: &str,
+    args: serde_json::Value,
+    deposit: Balance,
+    gas: Gas,
+) -> CallPayload {
+    CallPayload {
+        receiver_id,
+        method_name: method_name.to_string(),
+        args: args.to_string(),
+        deposit: U128(deposit),
+        gas: U64(gas),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::*;
+    use near_sdk::testing_env;
+    use near_sdk::MockedBlockchain;
+
+    #[test]
+    fn test_render_spec() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "#ABC".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let spec = contract.get_render_spec(token_id).unwrap();
+        assert_eq!(spec.spec_version, RENDER_SPEC_VERSION);
+        assert_eq!(spec.color, "#0000ff".to_string());
+        assert_eq!(spec.background_color, "#aabbcc".to_string());
+        assert_eq!(spec.rarity, contract.get_corgi(token_id).rate);
+    }
+
+    #[test]
+    fn pedigree_follows_parents() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b", "c"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        let mut child = contract.get_corgi(2);
+        child.parents = vec![0, 1];
+        contract.corgis.insert(&2, &child);
+        let mut parent = contract.get_corgi(1);
+        parent.parents = vec![0];
+        contract.corgis.insert(&1, &parent);
+
+        let pedigree = contract.get_pedigree(2, 1).unwrap();
+        assert_eq!(pedigree.parents.len(), 2);
+        assert_eq!(pedigree.parents[1].name, Some("b".to_string()));
+        assert_eq!(pedigree.parents[1].parents.len(), 0);
+        let pedigree = contract.get_pedigree(2, 2).unwrap();
+        assert_eq!(pedigree.parents[1].parents[0].token_id, 0);
+    }
+
+    #[test]
+    fn exists_checks() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        assert!(!contract.corgi_exists(0));
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert!(contract.corgi_exists(token_id));
+        assert!(contract.owns(robert(), token_id));
+        assert!(!contract.is_selling(token_id));
+        contract.sell_corgi(token_id, U128(1), None, None);
+        assert!(contract.is_selling(token_id));
+        contract.transfer(joe(), token_id, None);
+        assert!(!contract.is_selling(token_id));
+        assert!(contract.owns(joe(), token_id));
+    }
+
+    #[test]
+    fn ownership_pairs_paging() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b", "c"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        assert_eq!(contract.get_ownership_pairs(0, 2), vec![(U64(0), robert()), (U64(1), robert())]);
+        assert_eq!(contract.get_ownership_pairs(2, 10), vec![(U64(2), robert())]);
+        assert_eq!(contract.get_ownership_pairs(5, 10).len(), 0);
+    }
+
+    #[test]
+    fn rarity_score_follows_trait_counts() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for color in &["blue", "blue", "red"] {
+            mint_revealed(&mut contract,
+                "a".to_string(),
+                color.to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        let count = |key: &str| contract.trait_counts.get(&key.to_string());
+        assert_eq!(count("color:#0000ff"), Some(2));
+        assert_eq!(count("color:#ff0000"), Some(1));
+        assert_eq!(count("background_color:#008000"), Some(3));
+        contract.refresh_rarity_ranking(vec![0, 1, 2, 3]);
+        let ranking = contract.get_rarity_ranking(0, 3);
+        assert_eq!(ranking.len(), 3);
+        assert!(ranking[0].1 >= ranking[1].1 && ranking[1].1 >= ranking[2].1);
+        assert_eq!(contract.get_rarity_score(ranking[0].0.into()), Some(ranking[0].1));
+        assert_eq!(contract.get_rarity_ranking(1, 10)[..], ranking[1..]);
+        // color alone contributes 300 to the red corgi and 150 to blue ones
+        assert!(contract.get_rarity_score(2).unwrap() >= 300 + 100 * 3);
+
+        contract.delete_corgi(2);
+        assert_eq!(contract.trait_counts.get(&"color:#ff0000".to_string()), None);
+        let ranked: Vec<U64> =
+            contract.get_rarity_ranking(0, 10).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ranked.len(), 2);
+        assert!(!ranked.contains(&U64(2)));
+    }
+
+    #[test]
+    fn state_checksum_tracks_ownership() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for name in &["a", "b", "c"] {
+            mint_revealed(&mut contract,
+                name.to_string(),
+                "blue".to_string(),
+                "green".to_string(),
+                "haha".to_string(),
+            );
+        }
+        let page = contract.state_checksum(0, 2);
+        assert_eq!((page.count, page.total), (2, 3));
+        assert_eq!(page.checksum, ownership_checksum(&contract.get_ownership_pairs(0, 2)));
+        assert_eq!(page.checksum.len(), 64);
+
+        contract.transfer(mike(), 1, None);
+        assert_ne!(contract.state_checksum(0, 2).checksum, page.checksum);
+    }
+
+    #[test]
+    fn buy_payload_matches_listing() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        );
+        contract.sell_corgi(0, U128(10u128.pow(24)), None, None);
+        let payload = contract.get_buy_payload(0).unwrap();
+        assert_eq!(payload.receiver_id, "alice.testnet");
+        assert_eq!(payload.method_name, "buy_corgi");
+        assert_eq!(payload.deposit.0, 10u128.pow(24));
+        let args: serde_json::Value = serde_json::from_str(&payload.args).unwrap();
+        assert_eq!(args["id"], 0);
+
+        let payload = contract.get_transfer_payload(0, joe()).unwrap();
+        let args: serde_json::Value = serde_json::from_str(&payload.args).unwrap();
+        assert_eq!(args["new_owner_id"], joe());
+        assert_eq!(payload.deposit.0, 0);
+    }
+
+    #[test]
+    fn views_of_unknown_corgis_are_empty() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert_eq!(contract.get_corgi_level(token_id + 1), None);
+        assert_eq!(contract.get_rarity_score(token_id + 1), None);
+        assert!(contract.get_hidden_traits(token_id + 1).is_empty());
+        assert!(contract.get_render_spec(token_id + 1).is_none());
+        assert!(contract.get_pedigree(token_id + 1, 1).is_none());
+        assert!(contract.get_sell_payload(token_id + 1, U128(1)).is_none());
+        // not listed
+        assert!(contract.get_buy_payload(token_id).is_none());
+        assert!(contract.get_corgis_by_owner_range(joe(), 0, 10).is_empty());
+    }
+
+    #[test]
+    fn view_pages_are_capped_and_ids_are_strings() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert_eq!(view_limit(u64::MAX), MAX_VIEW_LIMIT);
+        assert_eq!(contract.get_ownership_pairs(0, u64::MAX).len(), 1);
+        assert_eq!(contract.nft_tokens(None, Some(u64::MAX)).len(), 1);
+
+        let json = serde_json::to_value(contract.get_corgi(token_id)).unwrap();
+        assert_eq!(json["id"], token_id.to_string());
+        assert_eq!(json["parents"], json!([]));
+        let json = serde_json::to_value(contract.get_render_spec(token_id)).unwrap();
+        assert_eq!(json["token_id"], token_id.to_string());
+    }
+
+    #[test]
+    fn simulate_reports_cost_or_error() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        let buy = |deposit| SimulatedAction::Buy {
+            token_id,
+            buyer: mike(),
+            deposit: U128(deposit),
+        };
+        assert_eq!(
+            contract.simulate(buy(10u128.pow(24))).error,
+            Some("Corgi is not for sale".to_string())
+        );
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        assert_eq!(
+            contract.simulate(buy(10u128.pow(23))).error,
+            Some("Don't pay enough money to buy corgi".to_string())
+        );
+        assert_eq!(
+            contract.simulate(buy(10u128.pow(24))),
+            Simulation {
+                ok: true,
+                cost: U128(10u128.pow(24)),
+                error: None,
+            }
+        );
+        assert_eq!(
+            contract.simulate(SimulatedAction::Transfer {
+                token_id,
+                sender: mike(),
+                receiver: joe(),
+            })
+            .error,
+            Some("Attempt to transfer a token with no access.".to_string())
+        );
+        assert_eq!(
+            contract.simulate(SimulatedAction::Mint {
+                account_id: mike(),
+                deposit: U128(3 * 10u128.pow(24)),
+            }),
+            Simulation {
+                ok: true,
+                cost: U128(MINT_PRICE),
+                error: None,
+            }
+        );
+        assert!(contract.get_corgi(token_id).selling);
+    }
+
+    #[test]
+    fn page_market_and_rarity_views() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_ids: Vec<TokenId> = (0..3)
+            .map(|_| {
+                mint_revealed(&mut contract,
+                    "a".to_string(),
+                    "blue".to_string(),
+                    "green".to_string(),
+                    "haha".to_string(),
+                ).token_id.0
+            })
+            .collect();
+        contract.sell_corgi(token_ids[1], U128(10u128.pow(24)), None, None);
+        contract.delete_corgi(token_ids[0]);
+
+        assert_eq!(contract.get_corgi_count(), 2);
+        assert_eq!(contract.display_global_corgis_range(0, 10).len(), 2);
+        assert_eq!(contract.get_corgis_by_owner(robert()).len(), 2);
+        let for_sale = contract.get_corgis_for_sale(0, 10);
+        assert_eq!(for_sale.len(), 1);
+        assert_eq!(for_sale[0].id, token_ids[1]);
+        let by_rarity: usize = Rarity::ALL
+            .iter()
+            .map(|&rate| contract.get_corgis_by_rarity(rate, 0, 10).len())
+            .sum();
+        assert_eq!(by_rarity, 2);
+        let rate = contract.get_corgi(token_ids[2]).rate;
+        assert!(contract
+            .get_corgis_by_rarity(rate, 0, 10)
+            .iter()
+            .any(|corgi| corgi.id == token_ids[2]));
+    }
+
+    #[test]
+    fn global_stats_and_leaderboard() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_ids: Vec<TokenId> = (0..3)
+            .map(|_| {
+                mint_revealed(&mut contract,
+                    "a".to_string(),
+                    "blue".to_string(),
+                    "green".to_string(),
+                    "haha".to_string(),
+                ).token_id.0
+            })
+            .collect();
+        contract.sell_corgi(token_ids[1], U128(10u128.pow(24)), None, None);
+        contract.delete_corgi(token_ids[0]);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.buy_corgi(token_ids[1], None);
+
+        let stats = contract.get_global_stats();
+        assert_eq!((stats.total_minted, stats.total_burned, stats.total_supply), (3, 1, 2));
+        assert_eq!(stats.rarity_counts.iter().map(|(_, count)| count).sum::<u64>(), 2);
+        assert_eq!(stats.highest_sale_price, U128(10u128.pow(24)));
+        assert_eq!(contract.get_leaderboard(10), vec![(robert(), 1), (mike(), 1)]);
+        assert_eq!(contract.get_leaderboard(1), vec![(robert(), 1)]);
+    }
+
+    #[test]
+    fn corgi_history_records_transfers_and_sales() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        contract.transfer(mike(), token_id, None);
+
+        testing_env!(get_context(mike(), env::storage_usage()));
+        contract.sell_corgi(token_id, U128(10u128.pow(24)), None, None);
+        testing_env!(get_context(joe(), env::storage_usage()));
+        contract.buy_corgi(token_id, None);
+
+        let history = contract.get_corgi_history(token_id, 0, 10);
+        assert_eq!(history.len(), 2);
+        assert_eq!((history[0].from.as_str(), history[0].to.as_str()), ("robert.testnet", "mike.testnet"));
+        assert_eq!(history[0].price, None);
+        assert_eq!((history[1].from.as_str(), history[1].to.as_str()), ("mike.testnet", "joe.testnet"));
+        assert_eq!(history[1].price, Some(U128(10u128.pow(24))));
+        assert_eq!(contract.get_corgi_history(token_id, 1, 10), history[1..].to_vec());
+    }
+
+    #[test]
+    fn corgi_history_keeps_latest_entries() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(robert(), None, None);
+        for index in 0..MAX_PROVENANCE_ENTRIES + 5 {
+            contract.record_provenance(0, &robert(), &mike(), Some(U128(index as u128)));
+        }
+        let history = contract.get_corgi_history(0, 0, 100);
+        assert_eq!(history.len() as u64, MAX_PROVENANCE_ENTRIES);
+        assert_eq!(history[0].price, Some(U128(5)));
+        assert_eq!(history.last().unwrap().price, Some(U128(MAX_PROVENANCE_ENTRIES as u128 + 4)));
+    }
+
+    #[test]
+    fn token_ids_carry_era() {
+        testing_env!(get_context(robert(), 0));
+        let mut contract = Corgi3D::new(
+            robert(),
+            Some(InitConfig {
+                id_era: Some(3),
+                ..Default::default()
+            }),
+            None,
+        );
+        let token_id = mint_revealed(&mut contract,
+            "a".to_string(),
+            "blue".to_string(),
+            "green".to_string(),
+            "haha".to_string(),
+        ).token_id.0;
+        assert_eq!(token_id, 3 << TOKEN_SERIAL_BITS);
+        assert_eq!(
+            contract.get_token_id_parts(token_id),
+            TokenIdParts {
+                token_id,
+                token_id_str: token_id.to_string(),
+                era: 3,
+                serial: 0,
+            }
+        );
+        assert_eq!(contract.get_next_token_id().serial, 1);
+        assert_eq!(contract.display_global_corgis_range(0, 10)[0].id, token_id);
+    }
+}